@@ -0,0 +1,56 @@
+//! With the `embed_frontend` feature, lists every file of `../frontend/dist` in
+//! `$OUT_DIR/frontend.rs` so they are compiled into the binary, see `web::frontend`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("Failed to read `{}`: {}", dir.display(), e));
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if env::var_os("CARGO_FEATURE_EMBED_FRONTEND").is_none() {
+        return;
+    }
+
+    let dist = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("../frontend/dist");
+    let dist = dist
+        .canonicalize()
+        .unwrap_or_else(|_| panic!("Build the frontend into `{}` first", dist.display()));
+    println!("cargo:rerun-if-changed={}", dist.display());
+
+    let mut files = vec![];
+    collect(&dist, &mut files);
+    files.sort();
+
+    let mut generated = String::from("pub static FILES: &[(&str, &[u8], &str)] = &[\n");
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+        let name = file.strip_prefix(&dist).unwrap().to_string_lossy().replace('\\', "/");
+        let contents = fs::read(&file).unwrap();
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        let etag = format!("\"{:x}-{:x}\"", contents.len(), hasher.finish());
+        generated.push_str(&format!(
+            "    ({:?}, include_bytes!({:?}), {:?}),\n",
+            name,
+            file.display().to_string(),
+            etag
+        ));
+    }
+    generated.push_str("];\n");
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("frontend.rs");
+    fs::write(out, generated).unwrap();
+}
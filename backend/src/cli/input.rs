@@ -1,5 +1,6 @@
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono::NaiveDate;
+use datatypes::{Category, User};
 
 #[derive(PartialEq)]
 pub enum InputResult<T> {
@@ -10,14 +11,14 @@ pub enum InputResult<T> {
 pub enum Task {
     AddExpense,
     ViewExpenses,
-    MakeCalculations,
+    ViewBalances,
 }
 
 pub fn select_task() -> InputResult<Task> {
     println!("Select task:");
     println!("1> Add expense");
     println!("2> View expenses");
-    println!("3> Make calculations");
+    println!("3> View balances");
     println!("0> Exit");
 
     let selection = parse_int_input(0..=3);
@@ -25,15 +26,15 @@ pub fn select_task() -> InputResult<Task> {
         0 => InputResult::Return,
         1 => InputResult::Value(Task::AddExpense),
         2 => InputResult::Value(Task::ViewExpenses),
-        3 => InputResult::Value(Task::MakeCalculations),
+        3 => InputResult::Value(Task::ViewBalances),
         _ => unreachable!(),
     }
 }
 
-pub fn select_user(users: &Vec<User>) -> InputResult<&User> {
+pub fn select_user(users: &[User]) -> InputResult<&User> {
     println!("Select user:");
     for (i, user) in users.iter().enumerate() {
-        println!("{}. {}", i + 1, user.name);
+        println!("{}. {}", i + 1, user.username());
     }
     println!("0> Return");
 
@@ -44,10 +45,10 @@ pub fn select_user(users: &Vec<User>) -> InputResult<&User> {
     }
 }
 
-pub fn select_category(categories: &Vec<Category>) -> &Category {
+pub fn select_category(categories: &[Category]) -> &Category {
     println!("Select category:");
     for (i, category) in categories.iter().enumerate() {
-        println!("{}. {}", i + 1, category.name);
+        println!("{}. {}", i + 1, category.name());
     }
 
     let selection = parse_int_input(1..=categories.len() as i32);
@@ -71,20 +72,20 @@ pub fn parse_int_input(range: std::ops::RangeInclusive<i32>) -> i32 {
     }
 }
 
-pub fn parse_float_input() -> f32 {
+pub fn parse_float_input() -> f64 {
     loop {
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).unwrap();
         let input = input.trim();
 
-        match input.parse::<f32>() {
+        match input.parse::<f64>() {
             Ok(f) => return f,
             Err(_) => println!("Invalid input! Try again."),
         }
     }
 }
 
-pub fn parse_date_input() -> DateTime<Utc> {
+pub fn parse_date_input() -> NaiveDate {
     println!("Enter date [dd/mm/yyyy] (empty for today's date):");
     loop {
         let mut input = String::new();
@@ -92,15 +93,10 @@ pub fn parse_date_input() -> DateTime<Utc> {
         let input = input.trim();
 
         match NaiveDate::parse_from_str(input, "%d/%m/%Y") {
-            Ok(date) => {
-                let midnight = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
-                let date_time = NaiveDateTime::new(date, midnight);
-                let date_time_utc = DateTime::<Utc>::from_utc(date_time, Utc);
-                return date_time_utc;
-            }
+            Ok(date) => return date,
             Err(_) => {
                 if input.is_empty() {
-                    return Utc::now();
+                    return chrono::Local::now().date_naive();
                 }
                 println!("Invalid input! Try again.");
             }
@@ -129,7 +125,7 @@ pub fn parse_confirmation(default: bool) -> bool {
     }
 }
 
-pub fn parse_shared_amount(total_amount: f32) -> f32 {
+pub fn parse_shared_amount(total_amount: f64) -> f64 {
     println!("Enter shared amount ($<amount> | <ratio>%). INCLUDE CHARS!!");
     loop {
         let mut input = String::new();
@@ -149,14 +145,14 @@ pub fn parse_shared_amount(total_amount: f32) -> f32 {
     }
 }
 
-fn calculate_shared_amount(total_amount: f32, input: &str) -> Result<f32> {
-    if input.starts_with("$") {
-        match input[1..].parse::<f32>() {
+fn calculate_shared_amount(total_amount: f64, input: &str) -> Result<f64> {
+    if let Some(amount) = input.strip_prefix('$') {
+        match amount.parse::<f64>() {
             Ok(amount) => Ok(amount),
-            Err(_) => return Err(anyhow!("Invalid input! Try again.")),
+            Err(_) => Err(anyhow!("Invalid input! Try again.")),
         }
-    } else if input.ends_with("%") {
-        match input[..input.len() - 1].parse::<f32>() {
+    } else if let Some(ratio) = input.strip_suffix('%') {
+        match ratio.parse::<f64>() {
             Ok(ratio) => Ok(total_amount * ratio / 100.0),
             Err(_) => Err(anyhow!("Invalid input! Try again.")),
         }
@@ -170,12 +166,12 @@ mod tests {
     #[test]
     fn test_parse_shared_amount() {
         use super::calculate_shared_amount;
-        let total_amount = 30.0 as f32;
+        let total_amount = 30.0_f64;
         let actual = calculate_shared_amount(total_amount, "$10.0").unwrap();
-        let expected = 10.0 as f32;
+        let expected = 10.0_f64;
         assert_eq!(expected, actual);
 
-        let expected = 15.0 as f32;
+        let expected = 15.0_f64;
         let actual = calculate_shared_amount(total_amount, "50.0%").unwrap();
         assert_eq!(expected, actual);
 
@@ -183,6 +179,6 @@ mod tests {
         assert_eq!(expected, actual);
 
         let actual = calculate_shared_amount(total_amount, "50");
-        assert_eq!(true, actual.is_err());
+        assert!(actual.is_err());
     }
 }
@@ -1,46 +1,85 @@
 mod input;
 
-use crate::categories::Category;
 use crate::cli::input::*;
-use crate::expenses::FullExpense;
-use crate::users::User;
-use crate::utils::{config, into_vec};
-// use crate::{categories, expenses, users, utils};
+use crate::database::repository::Repository;
+use crate::utils::balance;
+use crate::utils::expenses::{category_name, username};
 use anyhow::Result;
+use datatypes::{Category, Expense, User, UserOwes};
 use log::{error, info};
 
-pub async fn run(db_pool: sqlx::PgPool) -> Result<()> {
-    let users = into_vec::<User>(users::list(&db).await)?;
-    let categories = into_vec::<Category>(categories::list(&db).await)?;
+const RECENT_EXPENSES: usize = 10;
+
+pub async fn run(repository: &dyn Repository) -> Result<()> {
     loop {
+        let users = repository.get_users().await?;
+        let categories = repository.get_categories().await?;
         match select_task() {
             InputResult::Return => break,
             InputResult::Value(task) => match task {
-                Task::AddExpense => match add_expense(&users, &categories, db).await {
+                Task::AddExpense => match add_expense(&users, &categories, repository).await {
                     Ok(_) => (),
                     Err(e) => error!("Error adding expense: {}", e),
                 },
-                Task::ViewExpenses => match show_expenses(db).await {
+                Task::ViewExpenses => match show_expenses(&users, &categories, repository).await {
                     Ok(_) => (),
                     Err(e) => error!("Error showing expenses: {}", e),
                 },
-                Task::MakeCalculations => (),
+                Task::ViewBalances => match show_balances(&users, repository).await {
+                    Ok(_) => (),
+                    Err(e) => error!("Error showing balances: {}", e),
+                },
             },
         }
     }
     Ok(())
 }
 
-async fn show_expenses(db: &utils::DB) -> Result<()> {
-    let exps = expenses::list_all(&db).await?;
-    for (i, expense) in exps.iter().enumerate() {
-        println!("Expense #{} ====================================", i + 1);
-        println!("{expense}",);
+fn print_expense(expense: &Expense, users: &[User], categories: &[Category]) {
+    println!(
+        "{}: {:.2} paid by {} [{}] on {}: {}",
+        expense.id(),
+        expense.amount(),
+        username(users, expense.user_id()),
+        category_name(categories, expense.category_id()),
+        expense.purchased_at().format("%d/%m/%Y"),
+        expense.description()
+    );
+    for owes in expense.user_owes() {
+        println!("    {} owes {:.2}", username(users, owes.user_id()), owes.amount());
+    }
+}
+
+async fn show_expenses(users: &[User], categories: &[Category], repository: &dyn Repository) -> Result<()> {
+    let exps = repository.get_expenses(None).await?;
+    println!("=== Last {} expenses:", RECENT_EXPENSES);
+    for expense in exps.iter().take(RECENT_EXPENSES) {
+        print_expense(expense, users, categories);
     }
     Ok(())
 }
 
-async fn add_expense(usrs: &Vec<User>, cats: &Vec<Category>, db: &utils::DB) -> Result<()> {
+async fn show_balances(users: &[User], repository: &dyn Repository) -> Result<()> {
+    let expenses = balance::expenses_since_last_reset(repository).await?;
+    let balances = balance::balances(&expenses);
+    println!("=== Balances since the last reset:");
+    for user in users {
+        let amount = balances.get(&user.id()).copied().unwrap_or(0.0);
+        if amount >= 0.0 {
+            println!("  - {} is owed {:.2}", user.username(), amount);
+        } else {
+            println!("  - {} owes {:.2}", user.username(), -amount);
+        }
+    }
+    Ok(())
+}
+
+async fn add_expense(usrs: &[User], cats: &[Category], repository: &dyn Repository) -> Result<()> {
+    if usrs.is_empty() || cats.is_empty() {
+        println!("Create at least one user and one category first.");
+        return Ok(());
+    }
+
     let user = select_user(usrs);
     let user = if let InputResult::Value(user) = user {
         user
@@ -61,57 +100,61 @@ async fn add_expense(usrs: &Vec<User>, cats: &Vec<Category>, db: &utils::DB) ->
     println!("Is communal? [Y/n]:");
     let is_communal = parse_confirmation(true);
 
-    let mut owings: Vec<(f32, User)> = vec![];
-    let is_equal_split;
-    if is_communal == true {
+    let mut owings: Vec<UserOwes> = vec![];
+    if is_communal {
         println!("Equally split? [Y/n]:");
-        is_equal_split = parse_confirmation(true);
-        if is_equal_split == true {
+        let is_equal_split = parse_confirmation(true);
+        if is_equal_split {
             for u in usrs {
-                if u == user {
-                    continue;
-                }
-                owings.push((total_amount / usrs.len() as f32, u.clone()))
+                owings.push(UserOwes::new(u.id(), -1, total_amount / usrs.len() as f64));
             }
         } else {
-            let mut temp_users = usrs.clone();
-            remove_vec_item(&mut temp_users, &user);
+            let mut temp_users = usrs.to_vec();
+            remove_vec_item(&mut temp_users, user);
+            let mut shared_amount = 0.0;
             for i in 1..usrs.len() {
                 println!("Associated user {i}:");
                 match select_user(&temp_users) {
                     InputResult::Value(u) => {
                         let user_amount = parse_shared_amount(total_amount);
-                        let ind = find_index(usrs, u);
-                        owings.push((user_amount, usrs[ind].clone()));
+                        if shared_amount + user_amount > total_amount {
+                            println!("Shared amounts cannot add up to more than the total amount!");
+                            break;
+                        }
+                        shared_amount += user_amount;
+                        owings.push(UserOwes::new(u.id(), -1, user_amount));
                         let remove_ind = find_index(&temp_users, u);
                         temp_users.remove(remove_ind);
                     }
                     InputResult::Return => break,
                 };
             }
+            if shared_amount < total_amount {
+                owings.push(UserOwes::new(user.id(), -1, total_amount - shared_amount));
+            }
         }
+    } else {
+        owings.push(UserOwes::new(user.id(), -1, total_amount));
     }
 
-    // output details
-    let full_expense = FullExpense::new(
-        user.clone(),
+    let expense = Expense::new(
+        user.id(),
+        category.id(),
         total_amount,
-        category.clone(),
+        description,
         purchase_date,
-        is_communal,
         owings,
-        description,
     );
     println!("RECORD:");
-    println!("{full_expense}");
+    print_expense(&expense, usrs, cats);
     println!("Confirm input [Y/n]");
 
     let confirm_input = parse_confirmation(true);
     match confirm_input {
         true => {
-            expenses::create(full_expense, &db).await?;
+            repository.insert_expense(expense).await?;
             info!("Added expense.");
-        } // TODO
+        }
         false => info!("Cancelled adding expense."),
     }
     Ok(())
@@ -122,6 +165,6 @@ fn remove_vec_item<T: PartialEq>(vec: &mut Vec<T>, obj: &T) {
     vec.remove(index);
 }
 
-fn find_index<T: PartialEq>(vec: &Vec<T>, obj: &T) -> usize {
+fn find_index<T: PartialEq>(vec: &[T], obj: &T) -> usize {
     vec.iter().position(|x| x == obj).unwrap()
 }
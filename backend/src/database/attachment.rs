@@ -0,0 +1,160 @@
+use sqlx::Row;
+use datatypes::Attachment;
+
+pub async fn insert_attachment<'e, E>(executor: E, attachment: &Attachment) -> Result<Attachment, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let sql = r#"
+    INSERT INTO attachments (expense_id, filename, content_type, size, sha256)
+    VALUES ($1, $2, $3, $4, $5)
+    RETURNING id, expense_id, filename, content_type, size, sha256, created_at
+    "#;
+    let row = sqlx::query(sql)
+        .bind(attachment.expense_id())
+        .bind(attachment.filename())
+        .bind(attachment.content_type())
+        .bind(attachment.size())
+        .bind(attachment.sha256())
+        .fetch_one(executor)
+        .await?;
+    Ok(Attachment::from(row))
+}
+
+pub async fn get_attachment(db_pool: &sqlx::PgPool, id: i32) -> Result<Option<Attachment>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, expense_id, filename, content_type, size, sha256, created_at
+    FROM attachments
+    WHERE id = $1
+    "#;
+    let attachment = sqlx::query(sql)
+        .bind(id)
+        .fetch_optional(db_pool)
+        .await?
+        .map(Attachment::from);
+    Ok(attachment)
+}
+
+pub async fn get_attachments(db_pool: &sqlx::PgPool, expense_id: i32) -> Result<Vec<Attachment>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, expense_id, filename, content_type, size, sha256, created_at
+    FROM attachments
+    WHERE expense_id = $1
+    ORDER BY id
+    "#;
+    let attachments = sqlx::query(sql)
+        .bind(expense_id)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(Attachment::from)
+        .collect();
+    Ok(attachments)
+}
+
+pub async fn get_all_attachments(db_pool: &sqlx::PgPool) -> Result<Vec<Attachment>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, expense_id, filename, content_type, size, sha256, created_at
+    FROM attachments
+    ORDER BY id
+    "#;
+    let attachments = sqlx::query(sql)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(Attachment::from)
+        .collect();
+    Ok(attachments)
+}
+
+/// Removes the attachment row and returns it, or `None` if there was no such attachment. The
+/// stored file is left alone since other attachments can share the same contents.
+pub async fn delete_attachment<'e, E>(executor: E, id: i32) -> Result<Option<Attachment>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let sql = r#"
+    DELETE FROM attachments
+    WHERE id = $1
+    RETURNING id, expense_id, filename, content_type, size, sha256, created_at
+    "#;
+    let attachment = sqlx::query(sql)
+        .bind(id)
+        .fetch_optional(executor)
+        .await?
+        .map(Attachment::from);
+    Ok(attachment)
+}
+
+/// Whether any attachment still points at the stored contents with this hash.
+pub async fn is_hash_in_use<'e, E>(executor: E, sha256: &str) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let sql = r#"
+    SELECT EXISTS (SELECT 1 FROM attachments WHERE sha256 = $1) AS in_use
+    "#;
+    let row = sqlx::query(sql).bind(sha256).fetch_one(executor).await?;
+    Ok(row.get("in_use"))
+}
+
+/// Makes other transactions that lock the same hash wait until this one ends. Taken by
+/// everything that stores or deletes contents, so they are never deleted while an attachment
+/// that uses them is being added.
+pub async fn lock_hash(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, sha256: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind(sha256)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{test_pool, unique_name};
+    use crate::database::{category, expense, user};
+    use datatypes::Expense;
+
+    #[tokio::test]
+    async fn test_attachments() {
+        let db_pool = test_pool().await;
+
+        let payer = user::insert_user(&db_pool, unique_name("attachment")).await.unwrap();
+        let category = category::insert_category(&db_pool, unique_name("attachment"), &String::new(), None)
+            .await
+            .unwrap();
+        let expense = expense::insert_expense(
+            &db_pool,
+            Expense::new(
+                payer.id(),
+                category.id(),
+                10.0,
+                "receipt".to_string(),
+                chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                vec![],
+            ),
+        )
+        .await
+        .unwrap();
+
+        let sha256 = unique_name("sha");
+        let attachment = Attachment::new(
+            expense.id(),
+            "receipt.pdf".to_string(),
+            "application/pdf".to_string(),
+            3,
+            sha256.clone(),
+        );
+        let first = insert_attachment(&db_pool, &attachment).await.unwrap();
+        let second = insert_attachment(&db_pool, &attachment).await.unwrap();
+        assert_eq!(get_attachments(&db_pool, expense.id()).await.unwrap(), vec![first.clone(), second.clone()]);
+        assert_eq!(get_attachment(&db_pool, first.id()).await.unwrap(), Some(first.clone()));
+
+        assert_eq!(delete_attachment(&db_pool, first.id()).await.unwrap(), Some(first.clone()));
+        assert_eq!(delete_attachment(&db_pool, first.id()).await.unwrap(), None);
+        assert!(is_hash_in_use(&db_pool, &sha256).await.unwrap());
+        delete_attachment(&db_pool, second.id()).await.unwrap();
+        assert!(!is_hash_in_use(&db_pool, &sha256).await.unwrap());
+    }
+}
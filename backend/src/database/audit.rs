@@ -0,0 +1,132 @@
+use datatypes::{AuditEntry, AuditFilter};
+use rocket::serde::json::serde_json;
+
+/// `NULL` rather than the json `null`, so the column says whether there was anything.
+pub(crate) fn nullable_json(value: &serde_json::Value) -> Option<String> {
+    (!value.is_null()).then(|| value.to_string())
+}
+
+pub async fn insert_audit_entry(db_pool: &sqlx::PgPool, entry: &AuditEntry) -> Result<AuditEntry, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let inserted = insert_audit_entry_tx(&mut tx, entry).await?;
+    tx.commit().await?;
+
+    Ok(inserted)
+}
+
+/// Records the entry as part of a caller-owned transaction, so it is only kept together with the
+/// change it describes.
+pub async fn insert_audit_entry_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    entry: &AuditEntry,
+) -> Result<AuditEntry, sqlx::Error> {
+    let sql = r#"
+    INSERT INTO audit_log (actor, source, address, action, entity_id, before, after)
+    VALUES ($1, $2, $3, $4, $5, $6::jsonb, $7::jsonb)
+    RETURNING id, at, actor, source, address, action, entity_id, before::text AS before, after::text AS after
+    "#;
+    let row = sqlx::query(sql)
+        .bind(entry.actor())
+        .bind(entry.source().as_str())
+        .bind(entry.address())
+        .bind(entry.action().name())
+        .bind(entry.entity_id())
+        .bind(nullable_json(entry.before()))
+        .bind(nullable_json(entry.after()))
+        .fetch_one(&mut *tx)
+        .await?;
+    Ok(AuditEntry::from(row))
+}
+
+/// The latest entries matching the filter, newest first.
+pub async fn get_audit_entries(db_pool: &sqlx::PgPool, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    let actions = match filter.action_names() {
+        Some(names) if names.is_empty() => return Ok(vec![]),
+        names => names.unwrap_or_default(),
+    };
+    let sources = filter.sources.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+
+    let sql = r#"
+    SELECT id, at, actor, source, address, action, entity_id, before::text AS before, after::text AS after
+    FROM audit_log
+    WHERE (cardinality($1::text[]) = 0 OR action = ANY($1))
+    AND (cardinality($2::integer[]) = 0 OR entity_id = ANY($2))
+    AND (cardinality($3::text[]) = 0 OR actor = ANY($3))
+    AND (cardinality($4::text[]) = 0 OR source = ANY($4))
+    AND at >= $5
+    ORDER BY id DESC
+    LIMIT $6
+    "#;
+    let entries = sqlx::query(sql)
+        .bind(actions)
+        .bind(&filter.entity_ids)
+        .bind(&filter.actors)
+        .bind(sources)
+        .bind(filter.since)
+        .bind(filter.limit)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(AuditEntry::from)
+        .collect();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{test_pool, unique_name};
+    use datatypes::{AuditSource, EventKind};
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let db_pool = test_pool().await;
+        let actor = unique_name("auditor");
+        let created = AuditEntry::new(
+            actor.clone(),
+            AuditSource::Cli,
+            EventKind::CategoryCreated,
+            Some(1),
+            serde_json::Value::Null,
+            serde_json::json!({ "name": "groceries" }),
+        );
+        let created = insert_audit_entry(&db_pool, &created).await.unwrap();
+        assert!(created.before().is_null());
+        assert_eq!(created.after()["name"], "groceries");
+        let deleted = AuditEntry::new(
+            actor.clone(),
+            AuditSource::Web,
+            EventKind::CategoryDeleted,
+            Some(1),
+            created.after().clone(),
+            serde_json::Value::Null,
+        );
+        let deleted = insert_audit_entry(&db_pool, &deleted).await.unwrap();
+
+        let mut filter = AuditFilter {
+            actors: vec![actor.clone()],
+            ..AuditFilter::default()
+        };
+        let entries = get_audit_entries(&db_pool, &filter).await.unwrap();
+        assert_eq!(entries, vec![deleted.clone(), created.clone()]);
+
+        filter.actions = vec!["category.deleted".to_string()];
+        assert_eq!(get_audit_entries(&db_pool, &filter).await.unwrap(), vec![deleted.clone()]);
+        filter.actions = vec!["category".to_string()];
+        filter.sources = vec![AuditSource::Cli];
+        assert_eq!(get_audit_entries(&db_pool, &filter).await.unwrap(), vec![created.clone()]);
+        filter.actions = vec!["categories".to_string()];
+        assert!(get_audit_entries(&db_pool, &filter).await.unwrap().is_empty());
+
+        let changed = sqlx::query("UPDATE audit_log SET actor = 'someone else' WHERE id = $1")
+            .bind(created.id())
+            .execute(&db_pool)
+            .await;
+        assert!(changed.is_err());
+        let removed = sqlx::query("DELETE FROM audit_log WHERE id = $1")
+            .bind(created.id())
+            .execute(&db_pool)
+            .await;
+        assert!(removed.is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Whole tables for `backup`. Rows are copied out and back in as json with every column, so ids,
+//! timestamps and the reset history come back exactly as they were.
+
+/// Every table with the columns its rows are ordered by. Tables come after the ones they
+/// reference, so they can be restored in this order.
+pub const TABLES: [(&str, &str); 14] = [
+    ("users", "id"),
+    ("categories", "id"),
+    ("expenses", "id"),
+    ("user_owes", "id"),
+    ("tags", "id"),
+    ("expense_tags", "expense_id, tag_id"),
+    ("attachments", "id"),
+    ("imported_transactions", "fingerprint"),
+    ("import_batches", "id"),
+    ("draft_expenses", "id"),
+    ("webhooks", "id"),
+    ("webhook_deliveries", "id"),
+    ("audit_log", "id"),
+    ("cleared_from", "id"),
+];
+
+/// The rows of one table as a json array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDump {
+    pub name: String,
+    pub rows: i64,
+    pub json: String,
+    /// The id the next row would get, for tables with a SERIAL `id`. Deleted rows may have used
+    /// ids past the last one dumped.
+    pub next_id: Option<i64>,
+}
+
+/// Dumps every table from the same snapshot, so rows never point at rows that are missing.
+pub async fn dump_tables(db_pool: &sqlx::PgPool) -> Result<Vec<TableDump>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut tx)
+        .await?;
+
+    let mut dumps = vec![];
+    for (name, order_by) in TABLES {
+        let sql = format!(
+            "SELECT COUNT(*), COALESCE(json_agg(t ORDER BY {}), '[]')::text FROM {} t",
+            order_by, name
+        );
+        let (rows, json): (i64, String) = sqlx::query_as(&sql).fetch_one(&mut tx).await?;
+
+        let next_id = match order_by {
+            "id" => {
+                let (sequence,): (String,) = sqlx::query_as("SELECT pg_get_serial_sequence($1, 'id')")
+                    .bind(name)
+                    .fetch_one(&mut tx)
+                    .await?;
+                let sql = format!(
+                    "SELECT CASE WHEN is_called THEN last_value + 1 ELSE last_value END FROM {}",
+                    sequence
+                );
+                let (next_id,): (i64,) = sqlx::query_as(&sql).fetch_one(&mut tx).await?;
+                Some(next_id)
+            }
+            _ => None,
+        };
+        dumps.push(TableDump {
+            name: name.to_string(),
+            rows,
+            json,
+            next_id,
+        });
+    }
+    tx.commit().await?;
+
+    Ok(dumps)
+}
+
+/// Inserts the dumped rows and sets the id sequences to where they were. Only restores into an empty
+/// database: returns the tables that already have rows, in which case nothing was written. The
+/// dumps have to be of the `TABLES`, in that order.
+pub async fn restore_tables(db_pool: &sqlx::PgPool, dumps: &[TableDump]) -> Result<Vec<&'static str>, sqlx::Error> {
+    assert!(
+        dumps.iter().map(|d| d.name.as_str()).eq(TABLES.iter().map(|(name, _)| *name)),
+        "the dumps have to be of the known tables"
+    );
+
+    let mut tx = db_pool.begin().await?;
+    // Nothing else may write until the restore is done, or the tables would not stay empty.
+    let names = TABLES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+    sqlx::query(&format!("LOCK TABLE {} IN EXCLUSIVE MODE", names))
+        .execute(&mut tx)
+        .await?;
+
+    let mut non_empty = vec![];
+    for (name, _) in TABLES {
+        let sql = format!("SELECT EXISTS (SELECT 1 FROM {})", name);
+        let (exists,): (bool,) = sqlx::query_as(&sql).fetch_one(&mut tx).await?;
+        if exists {
+            non_empty.push(name);
+        }
+    }
+    if !non_empty.is_empty() {
+        return Ok(non_empty);
+    }
+
+    for ((name, _), dump) in TABLES.iter().zip(dumps) {
+        // One statement per table, so rows may reference rows of the same table in any order.
+        let sql = format!(
+            "INSERT INTO {0} SELECT * FROM json_populate_recordset(NULL::{0}, $1::json)",
+            name
+        );
+        sqlx::query(&sql).bind(&dump.json).execute(&mut tx).await?;
+
+        if let Some(next_id) = dump.next_id {
+            // Never below the restored ids, whatever the dump says.
+            let sql = format!(
+                "SELECT setval(pg_get_serial_sequence('{0}', 'id'), GREATEST($1, COALESCE(MAX(id), 0) + 1), false) FROM {0}",
+                name
+            );
+            sqlx::query(&sql).bind(next_id).execute(&mut tx).await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(vec![])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{drop_database, empty_database};
+    use crate::database::{audit, category, expense, tag, user};
+    use crate::utils::audit::Actor;
+    use datatypes::{AuditEntry, AuditSource, EventKind, Expense, UserOwes};
+    use rocket::serde::json::serde_json;
+
+    #[tokio::test]
+    async fn test_dump_and_restore() {
+        let (source, source_name) = empty_database("backup_source").await;
+        let alice = user::insert_user(&source, "alice".to_string()).await.unwrap();
+        let bob = user::insert_user(&source, "bob".to_string()).await.unwrap();
+        // The gap it leaves has to survive the restore.
+        let carol = user::insert_user(&source, "carol".to_string()).await.unwrap();
+        user::delete_user(&source, carol.id()).await.unwrap();
+        let food = category::insert_category(&source, "food".to_string(), &String::new(), None)
+            .await
+            .unwrap();
+        category::insert_category(&source, "fruit".to_string(), &String::new(), Some(food.id()))
+            .await
+            .unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let shares = vec![UserOwes::new(bob.id(), -1, 5.5)];
+        let apples = Expense::new(alice.id(), food.id(), 11.0, "apples".to_string(), date, shares);
+        let apples = expense::insert_expense(&source, apples).await.unwrap();
+        let actor = Actor::new("alice".to_string(), AuditSource::Cli);
+        tag::set_expense_tags(&source, &actor, apples.id(), &["weekly".to_string()]).await.unwrap();
+        expense::insert_last_reset(&source).await.unwrap();
+        let entry = AuditEntry::new(
+            "alice".to_string(),
+            AuditSource::Cli,
+            EventKind::ExpenseCreated,
+            Some(apples.id()),
+            serde_json::Value::Null,
+            serde_json::to_value(&apples).unwrap(),
+        );
+        audit::insert_audit_entry(&source, &entry).await.unwrap();
+
+        let dumps = dump_tables(&source).await.unwrap();
+        assert_eq!(dumps.len(), TABLES.len());
+        assert_eq!(dumps[0].rows, 2);
+        assert_eq!(dumps.iter().find(|d| d.name == "cleared_from").unwrap().rows, 1);
+
+        let (target, target_name) = empty_database("backup_target").await;
+        assert!(restore_tables(&target, &dumps).await.unwrap().is_empty());
+        assert_eq!(dump_tables(&target).await.unwrap(), dumps);
+        assert_eq!(
+            expense::get_expense(&target, apples.id()).await.unwrap(),
+            expense::get_expense(&source, apples.id()).await.unwrap()
+        );
+
+        // The sequences continue after the restored ids.
+        let dave = user::insert_user(&target, "dave".to_string()).await.unwrap();
+        assert_eq!(dave.id(), carol.id() + 1);
+        let non_empty = restore_tables(&target, &dumps).await.unwrap();
+        assert!(non_empty.contains(&"users") && non_empty.contains(&"audit_log"));
+        assert_eq!(user::get_users(&target).await.unwrap().len(), 3);
+
+        drop_database(source, &source_name).await;
+        drop_database(target, &target_name).await;
+    }
+}
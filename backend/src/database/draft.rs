@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+
+use datatypes::{DraftExpense, DraftStatus, EventKind, Expense, ImportBatch};
+use rocket::serde::json::serde_json;
+
+use crate::database::expense::insert_expense_tx;
+use crate::utils::audit::{json, record_tx, Actor};
+
+pub async fn insert_import_batch_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    source: &str,
+) -> Result<ImportBatch, sqlx::Error> {
+    let sql = r#"
+    INSERT INTO import_batches (source)
+    VALUES ($1)
+    RETURNING id, source, created_at
+    "#;
+    let row = sqlx::query(sql).bind(source).fetch_one(&mut *tx).await?;
+    Ok(ImportBatch::from(row))
+}
+
+pub async fn insert_draft_expense_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    batch_id: i32,
+    draft: &DraftExpense,
+) -> Result<DraftExpense, sqlx::Error> {
+    let sql = r#"
+    INSERT INTO draft_expenses (batch_id, line, raw, user_id, category_id, amount, description, purchased_at, user_owes, errors, warnings)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::jsonb, $10, $11)
+    RETURNING id, batch_id, line, raw, user_id, category_id, amount, description, purchased_at, user_owes::text AS user_owes, errors, warnings, status, expense_id
+    "#;
+    let row = sqlx::query(sql)
+        .bind(batch_id)
+        .bind(draft.line())
+        .bind(draft.raw())
+        .bind(draft.user_id())
+        .bind(draft.category_id())
+        .bind(draft.amount())
+        .bind(draft.description())
+        .bind(draft.purchased_at())
+        .bind(serde_json::to_string(draft.user_owes()).unwrap())
+        .bind(draft.errors())
+        .bind(draft.warnings())
+        .fetch_one(&mut *tx)
+        .await?;
+    Ok(DraftExpense::from(row))
+}
+
+/// Remembers the statement transaction the draft was made from, so it isn't staged again.
+pub async fn set_draft_fingerprint_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: i32,
+    fingerprint: &str,
+) -> Result<(), sqlx::Error> {
+    let sql = r#"
+    UPDATE draft_expenses
+    SET fingerprint = $2
+    WHERE id = $1
+    "#;
+    sqlx::query(sql).bind(id).bind(fingerprint).execute(&mut *tx).await?;
+    Ok(())
+}
+
+/// The fingerprints of the statement transactions staged as drafts, whatever became of them.
+pub async fn get_draft_fingerprints(db_pool: &sqlx::PgPool) -> Result<HashSet<String>, sqlx::Error> {
+    let sql = r#"
+    SELECT fingerprint
+    FROM draft_expenses
+    WHERE fingerprint IS NOT NULL
+    "#;
+    let fingerprints: Vec<(String,)> = sqlx::query_as(sql).fetch_all(db_pool).await?;
+    Ok(fingerprints.into_iter().map(|(fingerprint,)| fingerprint).collect())
+}
+
+pub async fn get_import_batches(db_pool: &sqlx::PgPool) -> Result<Vec<ImportBatch>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, source, created_at
+    FROM import_batches
+    ORDER BY created_at DESC
+    "#;
+    let batches = sqlx::query(sql)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(ImportBatch::from)
+        .collect();
+    Ok(batches)
+}
+
+pub async fn get_draft_expenses(
+    db_pool: &sqlx::PgPool,
+    batch_id: i32,
+) -> Result<Vec<DraftExpense>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, batch_id, line, raw, user_id, category_id, amount, description, purchased_at, user_owes::text AS user_owes, errors, warnings, status, expense_id
+    FROM draft_expenses
+    WHERE batch_id = $1
+    ORDER BY line
+    "#;
+    let drafts = sqlx::query(sql)
+        .bind(batch_id)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(DraftExpense::from)
+        .collect();
+    Ok(drafts)
+}
+
+pub async fn get_draft_expense<'e, E>(executor: E, id: i32) -> Result<Option<DraftExpense>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let sql = r#"
+    SELECT id, batch_id, line, raw, user_id, category_id, amount, description, purchased_at, user_owes::text AS user_owes, errors, warnings, status, expense_id
+    FROM draft_expenses
+    WHERE id = $1
+    "#;
+    let row = sqlx::query(sql).bind(id).fetch_optional(executor).await?;
+    Ok(row.map(DraftExpense::from))
+}
+
+/// Saves the editable fields of `draft` after re-validating it. An edited draft goes back to
+/// `Pending` and has to be approved again; posted drafts can't be edited, in which case `None`
+/// is returned. The change is recorded as done by `actor`.
+pub async fn update_draft_expense(
+    db_pool: &sqlx::PgPool,
+    actor: &Actor,
+    mut draft: DraftExpense,
+) -> Result<Option<DraftExpense>, sqlx::Error> {
+    draft.validate();
+    let mut tx = db_pool.begin().await?;
+    let before = get_draft_expense(&mut tx, draft.id()).await?;
+
+    let sql = r#"
+    UPDATE draft_expenses
+    SET user_id = $2, category_id = $3, amount = $4, description = $5, purchased_at = $6,
+        user_owes = $7::jsonb, errors = $8, warnings = $9, status = 'pending'
+    WHERE id = $1 AND status <> 'posted'
+    RETURNING id, batch_id, line, raw, user_id, category_id, amount, description, purchased_at, user_owes::text AS user_owes, errors, warnings, status, expense_id
+    "#;
+    let row = sqlx::query(sql)
+        .bind(draft.id())
+        .bind(draft.user_id())
+        .bind(draft.category_id())
+        .bind(draft.amount())
+        .bind(draft.description())
+        .bind(draft.purchased_at())
+        .bind(serde_json::to_string(draft.user_owes()).unwrap())
+        .bind(draft.errors())
+        .bind(draft.warnings())
+        .fetch_optional(&mut tx)
+        .await?;
+    let Some(updated) = row.map(DraftExpense::from) else {
+        return Ok(None);
+    };
+    let before = before.as_ref().map(json).unwrap_or_default();
+    record_tx(&mut tx, actor, EventKind::DraftUpdated, Some(updated.id()), before, json(&updated)).await?;
+
+    tx.commit().await?;
+    Ok(Some(updated))
+}
+
+/// Approves or rejects a draft, recorded as done by `actor`. Drafts with errors can't be
+/// approved and posted drafts can't change at all; `false` is returned when nothing was
+/// updated.
+pub async fn set_draft_status(
+    db_pool: &sqlx::PgPool,
+    actor: &Actor,
+    id: i32,
+    status: DraftStatus,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let before = get_draft_expense(&mut tx, id).await?;
+
+    let sql = r#"
+    UPDATE draft_expenses
+    SET status = $2
+    WHERE id = $1
+    AND status <> 'posted'
+    AND ($2 <> 'approved' OR cardinality(errors) = 0)
+    RETURNING id, batch_id, line, raw, user_id, category_id, amount, description, purchased_at, user_owes::text AS user_owes, errors, warnings, status, expense_id
+    "#;
+    let row = sqlx::query(sql)
+        .bind(id)
+        .bind(status.as_str())
+        .fetch_optional(&mut tx)
+        .await?;
+    let Some(updated) = row.map(DraftExpense::from) else {
+        return Ok(false);
+    };
+    let before = before.as_ref().map(json).unwrap_or_default();
+    record_tx(&mut tx, actor, EventKind::DraftUpdated, Some(id), before, json(&updated)).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Creates an expense for every approved draft of the batch in a single transaction and marks
+/// the drafts as posted. The expenses are recorded as created by `actor`.
+pub async fn post_approved_drafts(
+    db_pool: &sqlx::PgPool,
+    actor: &Actor,
+    batch_id: i32,
+) -> Result<Vec<Expense>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let sql = r#"
+    SELECT id, batch_id, line, raw, user_id, category_id, amount, description, purchased_at, user_owes::text AS user_owes, errors, warnings, status, expense_id
+    FROM draft_expenses
+    WHERE batch_id = $1 AND status = 'approved'
+    ORDER BY line
+    FOR UPDATE
+    "#;
+    let drafts = sqlx::query(sql)
+        .bind(batch_id)
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(DraftExpense::from)
+        .collect::<Vec<DraftExpense>>();
+
+    let mut expenses = vec![];
+    for draft in drafts {
+        // Approval requires an error free draft, so every field is set.
+        let expense = Expense::new(
+            draft.user_id().unwrap(),
+            draft.category_id().unwrap(),
+            draft.amount().unwrap(),
+            draft.description().to_string(),
+            *draft.purchased_at().unwrap(),
+            draft.user_owes().clone(),
+        );
+        let expense = insert_expense_tx(&mut tx, expense).await?;
+
+        let sql = r#"
+        UPDATE draft_expenses
+        SET status = 'posted', expense_id = $2
+        WHERE id = $1
+        "#;
+        sqlx::query(sql)
+            .bind(draft.id())
+            .bind(expense.id())
+            .execute(&mut tx)
+            .await?;
+        // Drafts staged from a statement count as imported from now on.
+        let sql = r#"
+        INSERT INTO imported_transactions (fingerprint, expense_id)
+        SELECT fingerprint, $2
+        FROM draft_expenses
+        WHERE id = $1 AND fingerprint IS NOT NULL
+        "#;
+        sqlx::query(sql)
+            .bind(draft.id())
+            .bind(expense.id())
+            .execute(&mut tx)
+            .await?;
+        let after = json(&expense);
+        record_tx(&mut tx, actor, EventKind::ExpenseCreated, Some(expense.id()), serde_json::Value::Null, after).await?;
+        expenses.push(expense);
+    }
+
+    tx.commit().await?;
+
+    Ok(expenses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{test_pool, unique_name};
+    use crate::database::{audit, category, user};
+    use datatypes::{AuditFilter, AuditSource, UserOwes};
+
+    async fn insert_batch(db_pool: &sqlx::PgPool, drafts: Vec<DraftExpense>) -> Vec<DraftExpense> {
+        let mut tx = db_pool.begin().await.unwrap();
+        let batch = insert_import_batch_tx(&mut tx, "test").await.unwrap();
+        let mut inserted = vec![];
+        for draft in drafts {
+            inserted.push(insert_draft_expense_tx(&mut tx, batch.id(), &draft).await.unwrap());
+        }
+        tx.commit().await.unwrap();
+        inserted
+    }
+
+    #[tokio::test]
+    async fn test_review_and_post() {
+        let db_pool = test_pool().await;
+        let actor = Actor::new(unique_name("reviewer"), AuditSource::Cli);
+
+        let payer = user::insert_user(&db_pool, unique_name("payer")).await.unwrap();
+        let category = category::insert_category(&db_pool, unique_name("draft"), &"description".to_string(), None)
+            .await
+            .unwrap();
+
+        let mut complete = DraftExpense::new(2, "complete".to_string());
+        complete.set_user_id(payer.id());
+        complete.set_category_id(category.id());
+        complete.set_amount(12.5);
+        complete.set_description("lunch".to_string());
+        complete.set_purchased_at(chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+        complete.set_user_owes(vec![UserOwes::new(payer.id(), -1, 12.5)]);
+        complete.validate();
+
+        let mut broken = DraftExpense::new(3, "broken".to_string());
+        broken.validate();
+        broken.add_error("Invalid amount `abc`.".to_string());
+
+        let drafts = insert_batch(&db_pool, vec![complete, broken]).await;
+        assert!(drafts[0].errors().is_empty());
+        assert_eq!(drafts[1].errors().len(), 5);
+
+        assert!(set_draft_status(&db_pool, &actor, drafts[0].id(), DraftStatus::Approved).await.unwrap());
+        assert!(!set_draft_status(&db_pool, &actor, drafts[1].id(), DraftStatus::Approved).await.unwrap());
+
+        // Fixing the broken draft clears its errors, but it stays pending.
+        let mut fixed = drafts[1].clone();
+        fixed.set_user_id(payer.id());
+        fixed.set_category_id(category.id());
+        fixed.set_amount(3.0);
+        fixed.set_purchased_at(chrono::NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        fixed.set_user_owes(vec![UserOwes::new(payer.id(), -1, 3.0)]);
+        let fixed = update_draft_expense(&db_pool, &actor, fixed).await.unwrap().unwrap();
+        assert!(fixed.errors().is_empty());
+        assert_eq!(fixed.warnings().len(), 1);
+        assert_eq!(fixed.status(), DraftStatus::Pending);
+
+        let expenses = post_approved_drafts(&db_pool, &actor, drafts[0].batch_id()).await.unwrap();
+        assert_eq!(expenses.len(), 1);
+        assert_eq!(expenses[0].amount(), 12.5);
+        assert_eq!(expenses[0].user_owes().len(), 1);
+        let posted_id = expenses[0].id();
+
+        let posted = get_draft_expense(&db_pool, drafts[0].id()).await.unwrap().unwrap();
+        assert_eq!(posted.status(), DraftStatus::Posted);
+        assert_eq!(posted.expense_id(), Some(expenses[0].id()));
+        assert!(!set_draft_status(&db_pool, &actor, posted.id(), DraftStatus::Rejected).await.unwrap());
+
+        // Posting again doesn't duplicate anything.
+        let expenses = post_approved_drafts(&db_pool, &actor, drafts[0].batch_id()).await.unwrap();
+        assert!(expenses.is_empty());
+
+        let filter = AuditFilter {
+            actors: vec![actor.name().clone()],
+            ..AuditFilter::default()
+        };
+        let entries = audit::get_audit_entries(&db_pool, &filter).await.unwrap();
+        let recorded = entries.iter().rev().map(|e| (e.action(), e.entity_id())).collect::<Vec<(EventKind, Option<i32>)>>();
+        assert_eq!(
+            recorded,
+            vec![
+                (EventKind::DraftUpdated, Some(drafts[0].id())),
+                (EventKind::DraftUpdated, Some(drafts[1].id())),
+                (EventKind::ExpenseCreated, Some(posted_id)),
+            ]
+        );
+        assert_eq!(entries[1].after()["status"], "Pending");
+    }
+}
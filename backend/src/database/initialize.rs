@@ -1,3 +1,7 @@
+/// The version of the tables created below. Raise it with every change to them, so servers that
+/// expect another version report that they are not ready.
+pub const SCHEMA_VERSION: i32 = 3;
+
 pub async fn initialize_db(db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
     let sql = r#"
     CREATE TABLE IF NOT EXISTS users (
@@ -18,6 +22,12 @@ pub async fn initialize_db(db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
     "#;
     sqlx::query(sql).execute(db_pool).await?;
 
+    let sql = r#"
+    ALTER TABLE categories
+    ADD COLUMN IF NOT EXISTS parent_id INTEGER REFERENCES categories(id);
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
     let sql = r#"
     CREATE TABLE IF NOT EXISTS expenses (
         id SERIAL PRIMARY KEY,
@@ -44,6 +54,146 @@ pub async fn initialize_db(db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
     "#;
     sqlx::query(sql).execute(db_pool).await?;
 
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS tags (
+        id SERIAL PRIMARY KEY,
+        name VARCHAR(255) NOT NULL UNIQUE,
+        created_at TIMESTAMP NOT NULL DEFAULT NOW()
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS expense_tags (
+        expense_id INTEGER NOT NULL REFERENCES expenses(id),
+        tag_id INTEGER NOT NULL REFERENCES tags(id),
+        PRIMARY KEY (expense_id, tag_id)
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS attachments (
+        id SERIAL PRIMARY KEY,
+        expense_id INTEGER NOT NULL REFERENCES expenses(id),
+        filename VARCHAR(255) NOT NULL,
+        content_type VARCHAR(255) NOT NULL,
+        size BIGINT NOT NULL,
+        sha256 VARCHAR(64) NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT NOW()
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS imported_transactions (
+        fingerprint VARCHAR(64) PRIMARY KEY,
+        expense_id INTEGER NOT NULL REFERENCES expenses(id),
+        created_at TIMESTAMP NOT NULL DEFAULT NOW()
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS import_batches (
+        id SERIAL PRIMARY KEY,
+        source VARCHAR(255) NOT NULL,
+        created_at TIMESTAMP NOT NULL DEFAULT NOW()
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS draft_expenses (
+        id SERIAL PRIMARY KEY,
+        batch_id INTEGER NOT NULL REFERENCES import_batches(id),
+        line INTEGER NOT NULL,
+        raw TEXT NOT NULL,
+
+        user_id INTEGER REFERENCES users(id),
+        category_id INTEGER REFERENCES categories(id),
+        amount NUMERIC(10, 3),
+        description VARCHAR(255) NOT NULL,
+        purchased_at DATE,
+        user_owes JSONB NOT NULL DEFAULT '[]',
+
+        errors TEXT[] NOT NULL DEFAULT '{}',
+        warnings TEXT[] NOT NULL DEFAULT '{}',
+        status VARCHAR(16) NOT NULL DEFAULT 'pending',
+        expense_id INTEGER REFERENCES expenses(id)
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    // Drafts staged from a bank statement, moved to `imported_transactions` once posted.
+    let sql = r#"
+    ALTER TABLE draft_expenses
+    ADD COLUMN IF NOT EXISTS fingerprint VARCHAR(64) UNIQUE;
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS webhooks (
+        id SERIAL PRIMARY KEY,
+        url VARCHAR(2048) NOT NULL,
+        secret VARCHAR(255) NOT NULL,
+        events TEXT[] NOT NULL DEFAULT '{}',
+        created_at TIMESTAMP NOT NULL DEFAULT NOW()
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS webhook_deliveries (
+        id SERIAL PRIMARY KEY,
+        webhook_id INTEGER NOT NULL REFERENCES webhooks(id),
+        event VARCHAR(64) NOT NULL,
+        payload JSONB NOT NULL,
+        attempt INTEGER NOT NULL,
+        status INTEGER,
+        error TEXT,
+        created_at TIMESTAMP NOT NULL DEFAULT NOW()
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS audit_log (
+        id SERIAL PRIMARY KEY,
+        at TIMESTAMP NOT NULL DEFAULT NOW(),
+        actor VARCHAR(255) NOT NULL,
+        source VARCHAR(16) NOT NULL,
+        action VARCHAR(64) NOT NULL,
+        entity_id INTEGER,
+        before JSONB,
+        after JSONB
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    ALTER TABLE audit_log
+    ADD COLUMN IF NOT EXISTS address VARCHAR(64);
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    // Entries are never changed, not even by hand.
+    let sql = r#"
+    CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS TRIGGER AS $$
+    BEGIN
+        RAISE EXCEPTION 'audit_log is append-only';
+    END;
+    $$ LANGUAGE plpgsql;
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE OR REPLACE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
     let sql = r#"
     CREATE TABLE IF NOT EXISTS cleared_from (
         id SERIAL PRIMARY KEY,
@@ -52,5 +202,42 @@ pub async fn initialize_db(db_pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
     "#;
     sqlx::query(sql).execute(db_pool).await?;
 
+    // A single row, describing the tables rather than the data, so backups leave it out.
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS schema_version (
+        single BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (single),
+        version INTEGER NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    // An older build never lowers the version a newer one set.
+    let sql = r#"
+    INSERT INTO schema_version (version)
+    VALUES ($1)
+    ON CONFLICT (single) DO UPDATE SET version = GREATEST(schema_version.version, EXCLUDED.version)
+    "#;
+    sqlx::query(sql).bind(SCHEMA_VERSION).execute(db_pool).await?;
+
     Ok(())
 }
+
+/// The version the tables were last created or updated to, `None` before `initialize_db` ran.
+pub async fn get_schema_version(db_pool: &sqlx::PgPool) -> Result<Option<i32>, sqlx::Error> {
+    // Without the table there is no version yet, rather than an error.
+    let sql = r#"
+    SELECT to_regclass('schema_version') IS NOT NULL
+    "#;
+    let (exists,): (bool,) = sqlx::query_as(sql).fetch_one(db_pool).await?;
+    if !exists {
+        return Ok(None);
+    }
+
+    let sql = r#"
+    SELECT version
+    FROM schema_version
+    "#;
+    let version: Option<(i32,)> = sqlx::query_as(sql).fetch_optional(db_pool).await?;
+
+    Ok(version.map(|(version,)| version))
+}
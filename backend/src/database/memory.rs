@@ -0,0 +1,592 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use datatypes::{AuditEntry, AuditFilter, Category, Expense, Filter, OrderBy, User};
+
+use crate::database::repository::{
+    AuditRepository, CategoryRepository, Dataset, ExpenseRepository, ImportRepository, Imported, ResetRepository,
+    UserRepository,
+};
+use crate::database::tag::normalize_tags;
+use crate::database::References;
+use crate::utils::categories::descendant_ids;
+
+#[derive(Default, Clone)]
+struct State {
+    next_id: i32,
+    users: Vec<User>,
+    categories: Vec<Category>,
+    expenses: Vec<Expense>,
+    /// The statement transaction fingerprints with the expense made from each.
+    fingerprints: Vec<(String, i32)>,
+    resets: Vec<chrono::NaiveDateTime>,
+    audit_log: Vec<AuditEntry>,
+}
+
+impl State {
+    fn next_id(&mut self) -> i32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn insert_user(&mut self, username: String) -> Result<User, sqlx::Error> {
+        if self.users.iter().any(|u| u.username() == &username) {
+            return Err(constraint(format!("User `{}` already exists.", username)));
+        }
+        let mut user = User::new(username);
+        user.set_id(self.next_id());
+        user.set_created_at(now());
+        self.users.push(user.clone());
+        Ok(user)
+    }
+
+    fn insert_category(&mut self, name: String, description: &str, parent_id: Option<i32>) -> Result<Category, sqlx::Error> {
+        if self.categories.iter().any(|c| c.name() == &name) {
+            return Err(constraint(format!("Category `{}` already exists.", name)));
+        }
+        if let Some(parent_id) = parent_id {
+            if !self.categories.iter().any(|c| c.id() == parent_id) {
+                return Err(constraint(format!("Category `{}` does not exist.", parent_id)));
+            }
+        }
+        let mut category = Category::new(name, description.to_string());
+        category.set_id(self.next_id());
+        category.set_created_at(now());
+        category.set_parent_id(parent_id);
+        self.categories.push(category.clone());
+        Ok(category)
+    }
+
+    fn set_category_parent(&mut self, id: i32, parent_id: Option<i32>) -> bool {
+        if let Some(parent_id) = parent_id {
+            if descendant_ids(&self.categories, id).contains(&parent_id)
+                || !self.categories.iter().any(|c| c.id() == parent_id)
+            {
+                return false;
+            }
+        }
+        match self.categories.iter_mut().find(|c| c.id() == id) {
+            Some(category) => {
+                category.set_parent_id(parent_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert_expenses(&mut self, expenses: Vec<Expense>) -> Result<Vec<Expense>, sqlx::Error> {
+        for expense in &expenses {
+            check_expense(self, expense)?;
+        }
+
+        let mut inserted = vec![];
+        for expense in expenses {
+            let id = self.next_id();
+            let mut stored = store_expense(self, &expense, id);
+            stored.set_created_at(now());
+            self.expenses.push(stored.clone());
+            inserted.push(stored);
+        }
+        Ok(inserted)
+    }
+
+    /// `ImportRepository::import_dataset`, leaving the state half changed if it fails.
+    fn import_dataset(&mut self, dataset: Dataset) -> Result<Imported, sqlx::Error> {
+        if !dataset.attachments.is_empty() {
+            return Err(constraint("Attachments need a Postgres database.".to_string()));
+        }
+        let mut imported = Imported::default();
+        for username in dataset.users {
+            imported.users.push(self.insert_user(username)?);
+        }
+        let user_ids = imported.users.iter().map(|u| u.id()).collect::<Vec<i32>>();
+
+        for new in &dataset.categories {
+            imported.categories.push(self.insert_category(new.name.clone(), &new.description, None)?);
+        }
+        let category_ids = imported.categories.iter().map(|c| c.id()).collect::<Vec<i32>>();
+        for (category, new) in imported.categories.iter_mut().zip(&dataset.categories) {
+            if let Some(parent_id) = new.parent_id {
+                let parent_id = Dataset::resolve(&category_ids, parent_id)?;
+                if self.set_category_parent(category.id(), Some(parent_id)) {
+                    category.set_parent_id(Some(parent_id));
+                }
+            }
+        }
+
+        let mut expenses = vec![];
+        for expense in &dataset.expenses {
+            expenses.push(Dataset::resolve_expense(expense, &user_ids, &category_ids)?);
+        }
+        imported.expenses = self.insert_expenses(expenses)?;
+        for (index, fingerprint) in dataset.fingerprints {
+            if self.fingerprints.iter().any(|(f, _)| *f == fingerprint) {
+                return Err(constraint(format!("Transaction `{}` was already imported.", fingerprint)));
+            }
+            let expense_id = Dataset::indexed_expense(&imported.expenses, index)?.id();
+            self.fingerprints.push((fingerprint, expense_id));
+        }
+        Ok(imported)
+    }
+}
+
+/// Stands in for the database where the schema's constraints would reject a change.
+fn constraint(message: String) -> sqlx::Error {
+    sqlx::Error::Protocol(message)
+}
+
+fn now() -> chrono::NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+/// Keeps everything in memory and enforces the same rules as the schema, so code written
+/// against the repository traits can be tested without a database. Drafts don't exist here, so
+/// they never show up in references.
+#[derive(Default)]
+pub struct MemoryRepository {
+    state: Mutex<State>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryRepository {
+    async fn insert_user(&self, username: String) -> Result<User, sqlx::Error> {
+        self.state.lock().unwrap().insert_user(username)
+    }
+
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        Ok(self.state.lock().unwrap().users.clone())
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>, sqlx::Error> {
+        Ok(self.state.lock().unwrap().users.iter().find(|u| u.id() == id).cloned())
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool, sqlx::Error> {
+        if !self.get_user_references(id).await?.is_empty() {
+            return Err(constraint(format!("User `{}` is still referenced.", id)));
+        }
+        let mut state = self.state.lock().unwrap();
+        let count = state.users.len();
+        state.users.retain(|u| u.id() != id);
+        Ok(state.users.len() != count)
+    }
+
+    async fn rename_user(&self, id: i32, username: String) -> Result<Option<User>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.users.iter().any(|u| u.id() != id && u.username() == &username) {
+            return Err(constraint(format!("User `{}` already exists.", username)));
+        }
+        let user = state.users.iter_mut().find(|u| u.id() == id);
+        Ok(user.map(|user| {
+            user.set_username(username);
+            user.clone()
+        }))
+    }
+
+    async fn get_user_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        let expenses = state.expenses.iter().filter(|e| e.user_id() == id).count();
+        let shares = state
+            .expenses
+            .iter()
+            .flat_map(|e| e.user_owes())
+            .filter(|o| o.user_id() == id)
+            .count();
+        Ok(References {
+            expenses: expenses as i64,
+            shares: shares as i64,
+            categories: 0,
+            drafts: 0,
+        })
+    }
+
+    async fn merge_users(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        let exists = |id: i32| state.users.iter().any(|u| u.id() == id);
+        if from == into || !exists(from) || !exists(into) {
+            return Ok(false);
+        }
+
+        for expense in state.expenses.iter_mut() {
+            let mut user_owes = expense.user_owes().clone();
+            if let Some(from_share) = user_owes.iter().position(|o| o.user_id() == from) {
+                let amount = user_owes[from_share].amount();
+                match user_owes.iter().position(|o| o.user_id() == into) {
+                    Some(into_share) => {
+                        let merged = &user_owes[into_share];
+                        let mut replacement =
+                            datatypes::UserOwes::new(into, expense.id(), merged.amount() + amount);
+                        replacement.set_id(merged.id());
+                        replacement.set_created_at(*merged.created_at());
+                        user_owes[into_share] = replacement;
+                        user_owes.remove(from_share);
+                    }
+                    None => {
+                        let moved = &user_owes[from_share];
+                        let mut replacement = datatypes::UserOwes::new(into, expense.id(), amount);
+                        replacement.set_id(moved.id());
+                        replacement.set_created_at(*moved.created_at());
+                        user_owes[from_share] = replacement;
+                    }
+                }
+            }
+            let user_id = if expense.user_id() == from { into } else { expense.user_id() };
+            *expense = rebuild(expense, user_id, expense.category_id(), user_owes);
+        }
+        state.users.retain(|u| u.id() != from);
+        Ok(true)
+    }
+}
+
+/// A copy of the expense with a different payer, category or split.
+fn rebuild(expense: &Expense, user_id: i32, category_id: i32, user_owes: Vec<datatypes::UserOwes>) -> Expense {
+    let mut rebuilt = Expense::new(
+        user_id,
+        category_id,
+        expense.amount(),
+        expense.description().clone(),
+        *expense.purchased_at(),
+        user_owes,
+    );
+    rebuilt.set_id(expense.id());
+    rebuilt.set_created_at(*expense.created_at());
+    rebuilt.set_tags(expense.tags().clone());
+    rebuilt
+}
+
+#[async_trait]
+impl CategoryRepository for MemoryRepository {
+    async fn insert_category(
+        &self,
+        name: String,
+        description: &str,
+        parent_id: Option<i32>,
+    ) -> Result<Category, sqlx::Error> {
+        self.state.lock().unwrap().insert_category(name, description, parent_id)
+    }
+
+    async fn get_categories(&self) -> Result<Vec<Category>, sqlx::Error> {
+        let mut categories = self.state.lock().unwrap().categories.clone();
+        categories.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(categories)
+    }
+
+    async fn delete_category(&self, id: i32) -> Result<bool, sqlx::Error> {
+        if !self.get_category_references(id).await?.is_empty() {
+            return Err(constraint(format!("Category `{}` is still referenced.", id)));
+        }
+        let mut state = self.state.lock().unwrap();
+        let count = state.categories.len();
+        state.categories.retain(|c| c.id() != id);
+        Ok(state.categories.len() != count)
+    }
+
+    async fn rename_category(&self, id: i32, name: String) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        match state.categories.iter_mut().find(|c| c.id() == id) {
+            Some(category) => {
+                category.set_name(name);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn update_category_description(&self, id: i32, description: &str) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        match state.categories.iter_mut().find(|c| c.id() == id) {
+            Some(category) => {
+                category.set_description(description.to_string());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_category_parent(&self, id: i32, parent_id: Option<i32>) -> Result<bool, sqlx::Error> {
+        Ok(self.state.lock().unwrap().set_category_parent(id, parent_id))
+    }
+
+    async fn get_category_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        let expenses = state.expenses.iter().filter(|e| e.category_id() == id).count();
+        let categories = state.categories.iter().filter(|c| c.parent_id() == Some(id)).count();
+        Ok(References {
+            expenses: expenses as i64,
+            shares: 0,
+            categories: categories as i64,
+            drafts: 0,
+        })
+    }
+
+    async fn merge_categories(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        let Some(from_parent) = state.categories.iter().find(|c| c.id() == from).map(|c| c.parent_id()) else {
+            return Ok(false);
+        };
+        if from == into || !state.categories.iter().any(|c| c.id() == into) {
+            return Ok(false);
+        }
+
+        for expense in state.expenses.iter_mut() {
+            if expense.category_id() == from {
+                *expense = rebuild(expense, expense.user_id(), into, expense.user_owes().clone());
+            }
+        }
+        // Lift `into` out of `from` first if it is one of its descendants.
+        if descendant_ids(&state.categories, from).contains(&into) {
+            let category = state.categories.iter_mut().find(|c| c.id() == into).unwrap();
+            category.set_parent_id(from_parent);
+        }
+        for category in state.categories.iter_mut() {
+            if category.parent_id() == Some(from) {
+                category.set_parent_id(Some(into));
+            }
+        }
+        state.categories.retain(|c| c.id() != from);
+        Ok(true)
+    }
+}
+
+fn matches(filter: &Filter, categories: &[Category], expense: &Expense) -> bool {
+    let category_ids = filter
+        .category_ids()
+        .iter()
+        .flat_map(|id| descendant_ids(categories, *id))
+        .collect::<Vec<i32>>();
+    let has = |tag: &String| expense.tags().contains(tag);
+
+    filter.user_ids().contains(&expense.user_id())
+        && category_ids.contains(&expense.category_id())
+        && expense.amount() >= filter.min_amount()
+        && expense.amount() <= filter.max_amount()
+        && expense.purchased_at() >= filter.min_date()
+        && expense.purchased_at() <= filter.max_date()
+        && (filter.tags_any().is_empty() || filter.tags_any().iter().any(has))
+        && filter.tags_all().iter().all(has)
+        && !filter.tags_none().iter().any(has)
+}
+
+fn check_expense(state: &State, expense: &Expense) -> Result<(), sqlx::Error> {
+    let mut user_ids = expense.user_owes().iter().map(|o| o.user_id()).collect::<Vec<i32>>();
+    user_ids.push(expense.user_id());
+    if let Some(id) = user_ids.iter().find(|id| !state.users.iter().any(|u| u.id() == **id)) {
+        return Err(constraint(format!("User `{}` does not exist.", id)));
+    }
+    if !state.categories.iter().any(|c| c.id() == expense.category_id()) {
+        return Err(constraint(format!("Category `{}` does not exist.", expense.category_id())));
+    }
+    Ok(())
+}
+
+/// The expense as it is stored under `id`, with new shares and normalized tags.
+fn store_expense(state: &mut State, expense: &Expense, id: i32) -> Expense {
+    let mut user_owes = vec![];
+    for owes in expense.user_owes() {
+        let mut owes = owes.clone();
+        owes.set_id(state.next_id());
+        owes.set_expense_id(id);
+        owes.set_created_at(now());
+        user_owes.push(owes);
+    }
+    let mut stored = rebuild(expense, expense.user_id(), expense.category_id(), user_owes);
+    stored.set_id(id);
+    stored.set_tags(normalize_tags(expense.tags()));
+    stored
+}
+
+#[async_trait]
+impl ExpenseRepository for MemoryRepository {
+    async fn insert_expense(&self, expense: Expense) -> Result<Expense, sqlx::Error> {
+        Ok(self.insert_expenses(vec![expense]).await?.remove(0))
+    }
+
+    async fn insert_expenses(&self, expenses: Vec<Expense>) -> Result<Vec<Expense>, sqlx::Error> {
+        self.state.lock().unwrap().insert_expenses(expenses)
+    }
+
+    async fn get_expense(&self, id: i32) -> Result<Option<Expense>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state.expenses.iter().find(|e| e.id() == id).cloned())
+    }
+
+    async fn get_expenses(&self, filter: Option<Filter>) -> Result<Vec<Expense>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        let mut expenses = match &filter {
+            Some(filter) => state
+                .expenses
+                .iter()
+                .filter(|e| matches(filter, &state.categories, e))
+                .cloned()
+                .collect::<Vec<Expense>>(),
+            None => state.expenses.clone(),
+        };
+
+        match &filter {
+            Some(filter) => {
+                match filter.order_by() {
+                    OrderBy::Amount => expenses.sort_by(|a, b| a.amount().total_cmp(&b.amount())),
+                    OrderBy::Created => expenses.sort_by_key(|e| (*e.created_at(), e.id())),
+                    OrderBy::Date => expenses.sort_by_key(|e| *e.purchased_at()),
+                }
+                if !filter.order_asc() {
+                    expenses.reverse();
+                }
+            }
+            None => {
+                expenses.sort_by_key(|e| (*e.created_at(), e.id()));
+                expenses.reverse();
+            }
+        }
+        Ok(expenses)
+    }
+
+    async fn update_expense(&self, expense: Expense) -> Result<Option<Expense>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        let Some(position) = state.expenses.iter().position(|e| e.id() == expense.id()) else {
+            return Ok(None);
+        };
+        check_expense(&state, &expense)?;
+        let mut stored = store_expense(&mut state, &expense, expense.id());
+        stored.set_created_at(*state.expenses[position].created_at());
+        state.expenses[position] = stored.clone();
+        Ok(Some(stored))
+    }
+
+    async fn delete_expense(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        let count = state.expenses.len();
+        state.expenses.retain(|e| e.id() != id);
+        state.fingerprints.retain(|(_, expense_id)| *expense_id != id);
+        Ok(state.expenses.len() != count)
+    }
+}
+
+#[async_trait]
+impl ResetRepository for MemoryRepository {
+    async fn insert_last_reset(&self) -> Result<(), sqlx::Error> {
+        self.state.lock().unwrap().resets.push(now());
+        Ok(())
+    }
+
+    async fn get_last_reset(&self) -> Result<chrono::NaiveDateTime, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        state.resets.iter().max().copied().ok_or(sqlx::Error::RowNotFound)
+    }
+}
+
+#[async_trait]
+impl ImportRepository for MemoryRepository {
+    async fn import_dataset(&self, dataset: Dataset) -> Result<Imported, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        // Imported into a copy, so nothing is kept if any of it fails.
+        let mut staged = state.clone();
+        let imported = staged.import_dataset(dataset)?;
+        *state = staged;
+        Ok(imported)
+    }
+
+    async fn get_import_fingerprints(&self) -> Result<HashSet<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state.fingerprints.iter().map(|(f, _)| f.clone()).collect())
+    }
+}
+
+#[async_trait]
+impl AuditRepository for MemoryRepository {
+    async fn insert_audit_entry(&self, mut entry: AuditEntry) -> Result<AuditEntry, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        entry.set_id(state.next_id());
+        entry.set_at(now());
+        state.audit_log.push(entry.clone());
+        Ok(entry)
+    }
+
+    async fn get_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+        let entries = state
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|e| filter.matches(e))
+            .take(filter.limit.max(0) as usize)
+            .cloned()
+            .collect();
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use datatypes::UserOwes;
+
+    fn date(day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2023, 1, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_merge() {
+        let repository = MemoryRepository::new();
+        let alice = repository.insert_user("alice".to_string()).await.unwrap();
+        assert!(repository.insert_user("alice".to_string()).await.is_err());
+        let food = repository.insert_category("food".to_string(), "", None).await.unwrap();
+        let bakery = repository
+            .insert_category("bakery".to_string(), "", Some(food.id()))
+            .await
+            .unwrap();
+        let fuel = repository.insert_category("fuel".to_string(), "", None).await.unwrap();
+        assert!(!repository.set_category_parent(food.id(), Some(bakery.id())).await.unwrap());
+
+        let mut bread = Expense::new(alice.id(), bakery.id(), 4.0, "bread".to_string(), date(2), vec![]);
+        bread.set_tags(vec!["weekly".to_string(), " weekly ".to_string()]);
+        let petrol = Expense::new(alice.id(), fuel.id(), 50.0, "petrol".to_string(), date(1), vec![
+            UserOwes::new(alice.id(), -1, 50.0),
+        ]);
+        let inserted = repository.insert_expenses(vec![bread, petrol]).await.unwrap();
+        assert_eq!(inserted[0].tags(), &vec!["weekly".to_string()]);
+        assert_eq!(inserted[1].user_owes()[0].expense_id(), inserted[1].id());
+
+        let filter = |category_ids: Vec<i32>, tags_none: Vec<String>| Filter {
+            user_ids: vec![alice.id()],
+            category_ids,
+            min_amount: 0.0,
+            max_amount: 100.0,
+            min_date: date(1),
+            max_date: date(31),
+            order_by: OrderBy::Amount,
+            order_asc: false,
+            tags_any: vec![],
+            tags_all: vec![],
+            tags_none,
+        };
+        let found = repository.get_expenses(Some(filter(vec![food.id()], vec![]))).await.unwrap();
+        assert_eq!(found, vec![inserted[0].clone()]);
+        let found = repository.get_expenses(Some(filter(vec![food.id(), fuel.id()], vec![]))).await.unwrap();
+        assert_eq!(found, vec![inserted[1].clone(), inserted[0].clone()]);
+        let found = repository
+            .get_expenses(Some(filter(vec![food.id(), fuel.id()], vec!["weekly".to_string()])))
+            .await
+            .unwrap();
+        assert_eq!(found, vec![inserted[1].clone()]);
+
+        assert!(repository.delete_category(food.id()).await.is_err());
+        assert!(repository.merge_categories(food.id(), bakery.id()).await.unwrap());
+        let categories = repository.get_categories().await.unwrap();
+        assert_eq!(categories.iter().map(|c| (c.name().as_str(), c.parent_id())).collect::<Vec<_>>(), vec![
+            ("bakery", None),
+            ("fuel", None),
+        ]);
+
+        assert!(repository.get_last_reset().await.is_err());
+        repository.insert_last_reset().await.unwrap();
+        assert!(repository.get_last_reset().await.is_ok());
+    }
+}
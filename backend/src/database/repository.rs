@@ -0,0 +1,659 @@
+//! Storage as seen by the web endpoints, the CLI and the import and export code. `PgRepository`
+//! is backed by the database functions in the sibling modules, `MemoryRepository` keeps
+//! everything in memory for tests that shouldn't need a database.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datatypes::{Attachment, AuditEntry, AuditFilter, Category, EventKind, Expense, Filter, User, UserOwes};
+use rocket::serde::json::serde_json;
+
+use crate::database::{attachment, audit, category, expense, user, References};
+use crate::utils::attachments;
+use crate::utils::audit::{json, merged_into, record_tx, Actor};
+
+pub use crate::database::memory::MemoryRepository;
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn insert_user(&self, username: String) -> Result<User, sqlx::Error>;
+
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error>;
+
+    /// Returns `None` if there is no such user.
+    async fn get_user(&self, id: i32) -> Result<Option<User>, sqlx::Error>;
+
+    /// Returns `false` if there was no such user.
+    async fn delete_user(&self, id: i32) -> Result<bool, sqlx::Error>;
+
+    /// Returns `None` if there was no such user.
+    async fn rename_user(&self, id: i32, username: String) -> Result<Option<User>, sqlx::Error>;
+
+    async fn get_user_references(&self, id: i32) -> Result<References, sqlx::Error>;
+
+    /// Moves everything of `from` over to `into` and deletes `from`. Returns `false` if either
+    /// user does not exist.
+    async fn merge_users(&self, from: i32, into: i32) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait CategoryRepository: Send + Sync {
+    async fn insert_category(
+        &self,
+        name: String,
+        description: &str,
+        parent_id: Option<i32>,
+    ) -> Result<Category, sqlx::Error>;
+
+    /// Every category, ordered by name.
+    async fn get_categories(&self) -> Result<Vec<Category>, sqlx::Error>;
+
+    async fn delete_category(&self, id: i32) -> Result<bool, sqlx::Error>;
+
+    async fn rename_category(&self, id: i32, name: String) -> Result<bool, sqlx::Error>;
+
+    async fn update_category_description(&self, id: i32, description: &str) -> Result<bool, sqlx::Error>;
+
+    /// Returns `false` if the category does not exist or the move would create a cycle.
+    async fn set_category_parent(&self, id: i32, parent_id: Option<i32>) -> Result<bool, sqlx::Error>;
+
+    async fn get_category_references(&self, id: i32) -> Result<References, sqlx::Error>;
+
+    async fn merge_categories(&self, from: i32, into: i32) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ExpenseRepository: Send + Sync {
+    async fn insert_expense(&self, expense: Expense) -> Result<Expense, sqlx::Error>;
+
+    /// Inserts all of the expenses or, if any of them fails, none of them.
+    async fn insert_expenses(&self, expenses: Vec<Expense>) -> Result<Vec<Expense>, sqlx::Error>;
+
+    async fn get_expense(&self, id: i32) -> Result<Option<Expense>, sqlx::Error>;
+
+    /// Every expense, newest first, or the ones matching the filter in its order.
+    async fn get_expenses(&self, filter: Option<Filter>) -> Result<Vec<Expense>, sqlx::Error>;
+
+    /// Replaces the expense with the same id, including its shares and tags. Returns `None` if
+    /// there is no such expense.
+    async fn update_expense(&self, expense: Expense) -> Result<Option<Expense>, sqlx::Error>;
+
+    /// Deletes the expense with its shares and tags. Returns `false` if there was no such
+    /// expense.
+    async fn delete_expense(&self, id: i32) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ResetRepository: Send + Sync {
+    async fn insert_last_reset(&self) -> Result<(), sqlx::Error>;
+
+    /// Fails with `sqlx::Error::RowNotFound` if no reset was ever made.
+    async fn get_last_reset(&self) -> Result<chrono::NaiveDateTime, sqlx::Error>;
+}
+
+/// The audit log can only be added to, there is no way to change or remove an entry.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Returns the entry with its id and the time it was recorded.
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, sqlx::Error>;
+
+    /// The latest entries matching the filter, newest first.
+    async fn get_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error>;
+
+    /// The same storage, recording every change made through the returned repository as done by
+    /// `actor` in the same transaction as the change. `None` if the storage can't, then
+    /// `AuditingRepository` records the changes after they are made.
+    fn with_actor(&self, _actor: &Actor) -> Option<DynRepository> {
+        None
+    }
+}
+
+/// A category an import creates.
+#[derive(Debug, Clone)]
+pub struct NewCategory {
+    pub name: String,
+    pub description: String,
+    /// An existing category or a placeholder id, see `Dataset`.
+    pub parent_id: Option<i32>,
+}
+
+/// What an import creates, written by `ImportRepository::import_dataset` all at once. The new
+/// users and categories are referred to by the placeholder ids `add_user` and `add_category` hand
+/// out: `-1` for the first one, `-2` for the second and so on.
+#[derive(Debug, Default)]
+pub struct Dataset {
+    pub users: Vec<String>,
+    pub categories: Vec<NewCategory>,
+    pub expenses: Vec<Expense>,
+    /// The fingerprint of the statement transaction each expense was made from, by index into
+    /// `expenses`.
+    pub fingerprints: Vec<(usize, String)>,
+    /// The attachments of the expenses with their contents, by index into `expenses`. Only
+    /// Postgres stores attachments, their contents go to `attachments_dir`.
+    pub attachments: Vec<(usize, Attachment, Vec<u8>)>,
+    pub attachments_dir: PathBuf,
+}
+
+impl Dataset {
+    /// The placeholder id of the new user.
+    pub fn add_user(&mut self, username: String) -> i32 {
+        self.users.push(username);
+        -(self.users.len() as i32)
+    }
+
+    /// The placeholder id of the new category.
+    pub fn add_category(&mut self, category: NewCategory) -> i32 {
+        self.categories.push(category);
+        -(self.categories.len() as i32)
+    }
+
+    /// The id `id` stands for, given the ids of the users or categories that were created.
+    pub(crate) fn resolve(created: &[i32], id: i32) -> Result<i32, sqlx::Error> {
+        if id >= 0 {
+            return Ok(id);
+        }
+        created
+            .get((-id - 1) as usize)
+            .copied()
+            .ok_or_else(|| sqlx::Error::Protocol(format!("Unknown placeholder id `{}`.", id)))
+    }
+
+    /// `expense` referring to the users and categories that were created instead of placeholders.
+    pub(crate) fn resolve_expense(expense: &Expense, users: &[i32], categories: &[i32]) -> Result<Expense, sqlx::Error> {
+        let mut user_owes = vec![];
+        for owes in expense.user_owes() {
+            user_owes.push(UserOwes::new(Self::resolve(users, owes.user_id())?, -1, owes.amount()));
+        }
+        let mut resolved = Expense::new(
+            Self::resolve(users, expense.user_id())?,
+            Self::resolve(categories, expense.category_id())?,
+            expense.amount(),
+            expense.description().to_string(),
+            *expense.purchased_at(),
+            user_owes,
+        );
+        resolved.set_tags(expense.tags().clone());
+        Ok(resolved)
+    }
+
+    /// The expense at `index`, for a fingerprint or an attachment.
+    pub(crate) fn indexed_expense(expenses: &[Expense], index: usize) -> Result<&Expense, sqlx::Error> {
+        expenses
+            .get(index)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("Unknown expense index `{}`.", index)))
+    }
+}
+
+/// What `import_dataset` created, in the order of the dataset.
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub users: Vec<User>,
+    pub categories: Vec<Category>,
+    pub expenses: Vec<Expense>,
+    pub attachments: Vec<Attachment>,
+}
+
+impl Imported {
+    /// Every row that was created, for the audit log and the event bus.
+    pub(crate) fn created(&self) -> Vec<(EventKind, i32, serde_json::Value)> {
+        let users = self.users.iter().map(|u| (EventKind::UserCreated, u.id(), json(u)));
+        let categories = self.categories.iter().map(|c| (EventKind::CategoryCreated, c.id(), json(c)));
+        let expenses = self.expenses.iter().map(|e| (EventKind::ExpenseCreated, e.id(), json(e)));
+        let attachments = self.attachments.iter().map(|a| (EventKind::AttachmentCreated, a.id(), json(a)));
+        users.chain(categories).chain(expenses).chain(attachments).collect()
+    }
+}
+
+#[async_trait]
+pub trait ImportRepository: Send + Sync {
+    /// Creates everything in the dataset or, if any of it fails, nothing.
+    async fn import_dataset(&self, dataset: Dataset) -> Result<Imported, sqlx::Error>;
+
+    /// The fingerprints of the statement transactions imported so far.
+    async fn get_import_fingerprints(&self) -> Result<HashSet<String>, sqlx::Error>;
+}
+
+/// Everything the application stores, as one trait object.
+pub trait Repository:
+    UserRepository + CategoryRepository + ExpenseRepository + ResetRepository + AuditRepository + ImportRepository
+{
+}
+
+impl<T> Repository for T where
+    T: UserRepository
+        + CategoryRepository
+        + ExpenseRepository
+        + ResetRepository
+        + AuditRepository
+        + ImportRepository
+{
+}
+
+/// How the repository is shared, for example as Rocket state.
+pub type DynRepository = Arc<dyn Repository>;
+
+pub struct PgRepository {
+    db_pool: sqlx::PgPool,
+    /// Who the changes are recorded for, if they are.
+    actor: Option<Actor>,
+}
+
+impl PgRepository {
+    pub fn new(db_pool: sqlx::PgPool) -> Self {
+        Self { db_pool, actor: None }
+    }
+
+    pub fn db_pool(&self) -> &sqlx::PgPool {
+        &self.db_pool
+    }
+
+    /// Records the change as part of its transaction, if changes are recorded.
+    async fn record(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        action: EventKind,
+        entity_id: Option<i32>,
+        before: serde_json::Value,
+        after: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        if let Some(actor) = &self.actor {
+            record_tx(tx, actor, action, entity_id, before, after).await?;
+        }
+        Ok(())
+    }
+
+    /// Records an update of category `id` if `updated` and commits, `before` is the category
+    /// beforehand.
+    async fn category_updated(
+        &self,
+        mut tx: sqlx::Transaction<'_, sqlx::Postgres>,
+        updated: bool,
+        id: i32,
+        before: serde_json::Value,
+    ) -> Result<bool, sqlx::Error> {
+        if updated {
+            let after = category::get_categories(&mut tx)
+                .await?
+                .iter()
+                .find(|c| c.id() == id)
+                .map(json)
+                .unwrap_or_default();
+            self.record(&mut tx, EventKind::CategoryUpdated, Some(id), before, after).await?;
+        }
+        tx.commit().await?;
+        Ok(updated)
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgRepository {
+    async fn insert_user(&self, username: String) -> Result<User, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let user = user::insert_user(&mut tx, username).await?;
+        self.record(&mut tx, EventKind::UserCreated, Some(user.id()), serde_json::Value::Null, json(&user)).await?;
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        user::get_users(&self.db_pool).await
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>, sqlx::Error> {
+        user::get_user(&self.db_pool, id).await
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&user::lock_user(&mut tx, id).await?);
+        let deleted = user::delete_user(&mut tx, id).await?;
+        if deleted {
+            self.record(&mut tx, EventKind::UserDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn rename_user(&self, id: i32, username: String) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&user::lock_user(&mut tx, id).await?);
+        let user = user::rename_user(&mut tx, id, username).await?;
+        if let Some(user) = &user {
+            self.record(&mut tx, EventKind::UserUpdated, Some(id), before, json(user)).await?;
+        }
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn get_user_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        user::get_user_references(&self.db_pool, id).await
+    }
+
+    async fn merge_users(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&user::lock_user(&mut tx, from).await?);
+        let merged = user::merge_users_tx(&mut tx, from, into).await?;
+        if merged {
+            self.record(&mut tx, EventKind::UserDeleted, Some(from), before, merged_into(into)).await?;
+        }
+        tx.commit().await?;
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for PgRepository {
+    async fn insert_category(
+        &self,
+        name: String,
+        description: &str,
+        parent_id: Option<i32>,
+    ) -> Result<Category, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let category = category::insert_category(&mut tx, name, &description.to_string(), parent_id).await?;
+        let after = json(&category);
+        self.record(&mut tx, EventKind::CategoryCreated, Some(category.id()), serde_json::Value::Null, after).await?;
+        tx.commit().await?;
+        Ok(category)
+    }
+
+    async fn get_categories(&self) -> Result<Vec<Category>, sqlx::Error> {
+        category::get_categories(&self.db_pool).await
+    }
+
+    async fn delete_category(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&category::lock_category(&mut tx, id).await?);
+        let deleted = category::delete_category(&mut tx, id).await?;
+        if deleted {
+            self.record(&mut tx, EventKind::CategoryDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn rename_category(&self, id: i32, name: String) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&category::lock_category(&mut tx, id).await?);
+        let renamed = category::rename_category(&mut tx, id, name).await?;
+        self.category_updated(tx, renamed, id, before).await
+    }
+
+    async fn update_category_description(&self, id: i32, description: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&category::lock_category(&mut tx, id).await?);
+        let updated = category::update_category_description(&mut tx, id, description).await?;
+        self.category_updated(tx, updated, id, before).await
+    }
+
+    async fn set_category_parent(&self, id: i32, parent_id: Option<i32>) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&category::lock_category(&mut tx, id).await?);
+        let moved = category::set_category_parent(&mut tx, id, parent_id).await?;
+        self.category_updated(tx, moved, id, before).await
+    }
+
+    async fn get_category_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        category::get_category_references(&self.db_pool, id).await
+    }
+
+    async fn merge_categories(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&category::lock_category(&mut tx, from).await?);
+        let merged = category::merge_categories_tx(&mut tx, from, into).await?;
+        if merged {
+            self.record(&mut tx, EventKind::CategoryDeleted, Some(from), before, merged_into(into)).await?;
+        }
+        tx.commit().await?;
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl ExpenseRepository for PgRepository {
+    async fn insert_expense(&self, expense: Expense) -> Result<Expense, sqlx::Error> {
+        Ok(self.insert_expenses(vec![expense]).await?.remove(0))
+    }
+
+    async fn insert_expenses(&self, expenses: Vec<Expense>) -> Result<Vec<Expense>, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let mut inserted = vec![];
+        for expense in expenses {
+            let expense = expense::insert_expense_tx(&mut tx, expense).await?;
+            let after = json(&expense);
+            self.record(&mut tx, EventKind::ExpenseCreated, Some(expense.id()), serde_json::Value::Null, after).await?;
+            inserted.push(expense);
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn get_expense(&self, id: i32) -> Result<Option<Expense>, sqlx::Error> {
+        expense::get_expense(&self.db_pool, id).await
+    }
+
+    async fn get_expenses(&self, filter: Option<Filter>) -> Result<Vec<Expense>, sqlx::Error> {
+        expense::get_expenses(&self.db_pool, filter).await
+    }
+
+    async fn update_expense(&self, expense: Expense) -> Result<Option<Expense>, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&expense::lock_expense_tx(&mut tx, expense.id()).await?);
+        let expense = expense::update_expense_tx(&mut tx, expense).await?;
+        if let Some(expense) = &expense {
+            self.record(&mut tx, EventKind::ExpenseUpdated, Some(expense.id()), before, json(expense)).await?;
+        }
+        tx.commit().await?;
+        Ok(expense)
+    }
+
+    async fn delete_expense(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&expense::lock_expense_tx(&mut tx, id).await?);
+        let deleted = expense::delete_expense_tx(&mut tx, id).await?;
+        if deleted {
+            self.record(&mut tx, EventKind::ExpenseDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl ResetRepository for PgRepository {
+    async fn insert_last_reset(&self) -> Result<(), sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = match expense::get_last_reset(&mut tx).await {
+            Ok(date) => serde_json::json!({ "date": date }),
+            Err(sqlx::Error::RowNotFound) => serde_json::Value::Null,
+            Err(e) => return Err(e),
+        };
+        expense::insert_last_reset(&mut tx).await?;
+        let after = serde_json::json!({ "date": expense::get_last_reset(&mut tx).await? });
+        self.record(&mut tx, EventKind::Reset, None, before, after).await?;
+        tx.commit().await
+    }
+
+    async fn get_last_reset(&self) -> Result<chrono::NaiveDateTime, sqlx::Error> {
+        expense::get_last_reset(&self.db_pool).await
+    }
+}
+
+#[async_trait]
+impl ImportRepository for PgRepository {
+    async fn import_dataset(&self, dataset: Dataset) -> Result<Imported, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let mut imported = Imported::default();
+        for username in dataset.users {
+            imported.users.push(user::insert_user(&mut tx, username).await?);
+        }
+        let user_ids = imported.users.iter().map(|u| u.id()).collect::<Vec<i32>>();
+
+        for new in &dataset.categories {
+            let category = category::insert_category(&mut tx, new.name.clone(), &new.description, None).await?;
+            imported.categories.push(category);
+        }
+        let category_ids = imported.categories.iter().map(|c| c.id()).collect::<Vec<i32>>();
+        // Parents can come after their children, so they are linked once every category exists.
+        for (category, new) in imported.categories.iter_mut().zip(&dataset.categories) {
+            if let Some(parent_id) = new.parent_id {
+                let parent_id = Dataset::resolve(&category_ids, parent_id)?;
+                if category::set_category_parent(&mut tx, category.id(), Some(parent_id)).await? {
+                    category.set_parent_id(Some(parent_id));
+                }
+            }
+        }
+
+        for expense in &dataset.expenses {
+            let expense = Dataset::resolve_expense(expense, &user_ids, &category_ids)?;
+            imported.expenses.push(expense::insert_expense_tx(&mut tx, expense).await?);
+        }
+        for (index, fingerprint) in &dataset.fingerprints {
+            let expense = Dataset::indexed_expense(&imported.expenses, *index)?;
+            expense::insert_import_fingerprint_tx(&mut tx, fingerprint, expense.id()).await?;
+        }
+        for (index, new, contents) in &dataset.attachments {
+            let expense = Dataset::indexed_expense(&imported.expenses, *index)?;
+            // Stored before committing so no attachment row ever points at missing contents.
+            attachment::lock_hash(&mut tx, new.sha256()).await?;
+            let sha256 = attachments::store_blob(&dataset.attachments_dir, contents)
+                .map_err(|e| sqlx::Error::Io(std::io::Error::other(e.to_string())))?;
+            let new = Attachment::new(
+                expense.id(),
+                new.filename().clone(),
+                new.content_type().clone(),
+                contents.len() as i64,
+                sha256,
+            );
+            imported.attachments.push(attachment::insert_attachment(&mut tx, &new).await?);
+        }
+
+        for (action, id, after) in imported.created() {
+            self.record(&mut tx, action, Some(id), serde_json::Value::Null, after).await?;
+        }
+        tx.commit().await?;
+        Ok(imported)
+    }
+
+    async fn get_import_fingerprints(&self) -> Result<HashSet<String>, sqlx::Error> {
+        expense::get_import_fingerprints(&self.db_pool).await
+    }
+}
+
+#[async_trait]
+impl AuditRepository for PgRepository {
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, sqlx::Error> {
+        audit::insert_audit_entry(&self.db_pool, &entry).await
+    }
+
+    async fn get_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        audit::get_audit_entries(&self.db_pool, filter).await
+    }
+
+    fn with_actor(&self, actor: &Actor) -> Option<DynRepository> {
+        Some(Arc::new(Self {
+            db_pool: self.db_pool.clone(),
+            actor: Some(actor.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{test_pool, unique_name};
+    use crate::utils::audit::AuditingRepository;
+    use datatypes::AuditSource;
+
+    #[tokio::test]
+    async fn test_records_changes() {
+        let actor = Actor::new(unique_name("auditor"), AuditSource::Web).with_address(Some("192.0.2.1".to_string()));
+        let inner: DynRepository = Arc::new(PgRepository::new(test_pool().await));
+        let repository = AuditingRepository::wrap(inner, actor.clone());
+
+        let payer = repository.insert_user(unique_name("payer")).await.unwrap();
+        let food = repository.insert_category(unique_name("food"), "", None).await.unwrap();
+        let bread = Expense::new(
+            payer.id(),
+            food.id(),
+            3.0,
+            "bread".to_string(),
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            vec![],
+        );
+        let bread = repository.insert_expense(bread).await.unwrap();
+        // Still referenced, so neither the deletion nor its entry are kept.
+        assert!(repository.delete_category(food.id()).await.is_err());
+        assert!(repository.update_category_description(food.id(), "eating out").await.unwrap());
+        assert!(repository.delete_expense(bread.id()).await.unwrap());
+
+        let filter = AuditFilter {
+            actors: vec![actor.name().clone()],
+            ..AuditFilter::default()
+        };
+        let entries = repository.get_audit_entries(&filter).await.unwrap();
+        let actions = entries.iter().map(|e| e.action()).collect::<Vec<EventKind>>();
+        assert_eq!(
+            actions,
+            vec![
+                EventKind::ExpenseDeleted,
+                EventKind::CategoryUpdated,
+                EventKind::ExpenseCreated,
+                EventKind::CategoryCreated,
+                EventKind::UserCreated,
+            ]
+        );
+        assert_eq!(entries[0].before()["description"], "bread");
+        assert_eq!(entries[1].before()["description"], "");
+        assert_eq!(entries[1].after()["description"], "eating out");
+        assert_eq!(entries[0].address(), Some("192.0.2.1"));
+    }
+
+    #[tokio::test]
+    async fn test_import_dataset_with_attachments() {
+        let repository = PgRepository::new(test_pool().await);
+        let description = unique_name("receipt");
+        let dataset = |attachments_dir: PathBuf| {
+            let mut dataset = Dataset::default();
+            let payer = dataset.add_user(unique_name("payer"));
+            let category = dataset.add_category(NewCategory {
+                name: unique_name("food"),
+                description: "".to_string(),
+                parent_id: None,
+            });
+            let date = chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+            dataset.expenses.push(Expense::new(payer, category, 3.0, description.clone(), date, vec![]));
+            let contents = b"3.00".to_vec();
+            let sha256 = attachments::sha256_hex(&contents);
+            let receipt = Attachment::new(0, "receipt.txt".to_string(), "text/plain".to_string(), 4, sha256);
+            dataset.attachments.push((0, receipt, contents));
+            dataset.attachments_dir = attachments_dir;
+            dataset
+        };
+        let imported_expenses = || async {
+            let expenses = repository.get_expenses(None).await.unwrap();
+            expenses.into_iter().filter(|e| *e.description() == description).count()
+        };
+
+        // A file where the directory should be, so storing the contents fails.
+        let not_a_dir = std::env::temp_dir().join(unique_name("attachments"));
+        std::fs::write(&not_a_dir, b"").unwrap();
+        assert!(repository.import_dataset(dataset(not_a_dir.clone())).await.is_err());
+        assert_eq!(imported_expenses().await, 0);
+        std::fs::remove_file(not_a_dir).unwrap();
+
+        let dir = std::env::temp_dir().join(unique_name("attachments"));
+        let imported = repository.import_dataset(dataset(dir.clone())).await.unwrap();
+        assert_eq!(imported_expenses().await, 1);
+        assert_eq!(imported.attachments[0].expense_id(), imported.expenses[0].id());
+        assert!(attachments::blob_path(&dir, imported.attachments[0].sha256()).exists());
+        // Other tests back up every attachment, so this one can't outlive its contents.
+        let actor = Actor::new(unique_name("importer"), AuditSource::Import);
+        attachments::remove_attachment(&repository.db_pool, &actor, &dir, imported.attachments[0].id())
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,1313 @@
+//! The SQLite counterpart of the `user`, `category`, `expense` and `initialize` modules, for
+//! setups where running Postgres is overkill. Amounts are stored as whole thousandths in an
+//! INTEGER column, which keeps them exact like `NUMERIC(10, 3)` does. Postgres arrays are
+//! passed as json arrays and unpacked with `json_each`.
+//!
+//! Drafts and attachments only exist in Postgres, so they never show up in references here.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use datatypes::{AuditEntry, AuditFilter, Category, EventKind, Expense, Filter, OrderBy, User, UserOwes};
+use rocket::serde::json::serde_json;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{ConnectOptions, Row};
+
+use crate::database::audit::nullable_json;
+use crate::database::repository::{
+    AuditRepository, CategoryRepository, Dataset, DynRepository, ExpenseRepository, ImportRepository, Imported,
+    ResetRepository, UserRepository,
+};
+use crate::database::tag::normalize_tags;
+use crate::database::{DbConfig, References};
+use crate::utils::audit::{json, merged_into, Actor};
+
+fn to_thousandths(amount: f64) -> i64 {
+    (amount * 1000.0).round() as i64
+}
+
+fn from_thousandths(amount: i64) -> f64 {
+    amount as f64 / 1000.0
+}
+
+fn now() -> chrono::NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+fn json_array<T: serde::Serialize>(values: &[T]) -> String {
+    serde_json::to_string(values).expect("a slice always serializes")
+}
+
+fn user_from_row(row: &SqliteRow) -> User {
+    let mut user = User::new(row.get("username"));
+    user.set_id(row.get("id"));
+    user.set_created_at(row.get("created_at"));
+    user
+}
+
+fn category_from_row(row: &SqliteRow) -> Category {
+    let mut category = Category::new(row.get("name"), row.get("description"));
+    category.set_id(row.get("id"));
+    category.set_parent_id(row.get("parent_id"));
+    category.set_created_at(row.get("created_at"));
+    category
+}
+
+fn expense_from_row(row: &SqliteRow) -> Expense {
+    let mut expense = Expense::new(
+        row.get("user_id"),
+        row.get("category_id"),
+        from_thousandths(row.get("amount")),
+        row.get("description"),
+        row.get("purchased_at"),
+        vec![],
+    );
+    expense.set_id(row.get("id"));
+    expense.set_created_at(row.get("created_at"));
+    expense
+}
+
+fn user_owes_from_row(row: &SqliteRow) -> UserOwes {
+    let mut user_owes = UserOwes::new(
+        row.get("user_id"),
+        row.get("expense_id"),
+        from_thousandths(row.get("amount")),
+    );
+    user_owes.set_id(row.get("id"));
+    user_owes.set_created_at(row.get("created_at"));
+    user_owes
+}
+
+fn audit_entry_from_row(row: &SqliteRow) -> AuditEntry {
+    let json = |column: &str| {
+        row.get::<Option<String>, _>(column)
+            .map(|value| serde_json::from_str(&value).unwrap())
+            .unwrap_or_default()
+    };
+    let action = row.get::<String, _>("action");
+    let mut entry = AuditEntry::new(
+        row.get("actor"),
+        row.get::<String, _>("source").parse().unwrap(),
+        serde_json::from_value(serde_json::Value::String(action)).unwrap(),
+        row.get("entity_id"),
+        json("before"),
+        json("after"),
+    );
+    entry.set_id(row.get("id"));
+    entry.set_at(row.get("at"));
+    entry.set_address(row.get("address"));
+    entry
+}
+
+fn references_from_row(row: &SqliteRow) -> References {
+    References {
+        expenses: row.get("expenses"),
+        shares: row.get("shares"),
+        categories: row.get("categories"),
+        drafts: 0,
+    }
+}
+
+pub async fn initialize_db(db_pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS users (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL UNIQUE,
+        created_at TEXT NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS categories (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE,
+        description TEXT NOT NULL,
+        parent_id INTEGER REFERENCES categories(id),
+        created_at TEXT NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS expenses (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id INTEGER NOT NULL REFERENCES users(id),
+        category_id INTEGER NOT NULL REFERENCES categories(id),
+
+        amount INTEGER NOT NULL,
+        description TEXT NOT NULL,
+
+        created_at TEXT NOT NULL,
+        purchased_at TEXT NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS user_owes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id INTEGER NOT NULL REFERENCES users(id),
+        expense_id INTEGER NOT NULL REFERENCES expenses(id),
+        amount INTEGER NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS tags (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE,
+        created_at TEXT NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS expense_tags (
+        expense_id INTEGER NOT NULL REFERENCES expenses(id),
+        tag_id INTEGER NOT NULL REFERENCES tags(id),
+        PRIMARY KEY (expense_id, tag_id)
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at TEXT NOT NULL,
+        actor TEXT NOT NULL,
+        source TEXT NOT NULL,
+        action TEXT NOT NULL,
+        entity_id INTEGER,
+        before TEXT,
+        after TEXT
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    // SQLite can't add a column only if it is missing.
+    let sql = r#"
+    SELECT COUNT(*) FROM pragma_table_info('audit_log') WHERE name = 'address'
+    "#;
+    let (has_address,): (i64,) = sqlx::query_as(sql).fetch_one(db_pool).await?;
+    if has_address == 0 {
+        sqlx::query("ALTER TABLE audit_log ADD COLUMN address TEXT").execute(db_pool).await?;
+    }
+
+    for change in ["UPDATE", "DELETE"] {
+        let sql = format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS audit_log_append_only_{0}
+            BEFORE {1} ON audit_log
+            BEGIN
+                SELECT RAISE(ABORT, 'audit_log is append-only');
+            END;
+            "#,
+            change.to_lowercase(),
+            change
+        );
+        sqlx::query(&sql).execute(db_pool).await?;
+    }
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS imported_transactions (
+        fingerprint TEXT PRIMARY KEY,
+        expense_id INTEGER NOT NULL REFERENCES expenses(id),
+        created_at TEXT NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    let sql = r#"
+    CREATE TABLE IF NOT EXISTS cleared_from (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        date TEXT NOT NULL
+    );
+    "#;
+    sqlx::query(sql).execute(db_pool).await?;
+
+    Ok(())
+}
+
+/// Stores everything in a SQLite database, created on first use.
+pub struct SqliteRepository {
+    db_pool: SqlitePool,
+    /// Who the changes are recorded for, if they are.
+    actor: Option<Actor>,
+}
+
+impl SqliteRepository {
+    /// Opens the database at `database_url`, e.g. `sqlite://expenses.db` or `sqlite::memory:`,
+    /// and creates the tables.
+    pub async fn connect(db_conf: &DbConfig) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(db_conf.database_url())?
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .disable_statement_logging()
+            .clone();
+        // Every connection to `:memory:` gets a database of its own, so there must only ever
+        // be the one.
+        let pool_options = if db_conf.database_url().contains(":memory:") {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new().max_connections(db_conf.max_connections())
+        };
+        let db_pool = pool_options.connect_with(options).await?;
+        initialize_db(&db_pool).await?;
+
+        Ok(Self { db_pool, actor: None })
+    }
+
+    pub fn db_pool(&self) -> &SqlitePool {
+        &self.db_pool
+    }
+
+    /// Records the change as part of its transaction, if changes are recorded.
+    async fn record(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        action: EventKind,
+        entity_id: Option<i32>,
+        before: serde_json::Value,
+        after: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        if let Some(actor) = &self.actor {
+            insert_audit_entry(&mut *tx, &actor.entry(action, entity_id, before, after)).await?;
+        }
+        Ok(())
+    }
+
+    /// Records an update of category `id` if `updated` and commits, `before` is the category
+    /// beforehand.
+    async fn category_updated(
+        &self,
+        mut tx: sqlx::Transaction<'_, sqlx::Sqlite>,
+        updated: bool,
+        id: i32,
+        before: serde_json::Value,
+    ) -> Result<bool, sqlx::Error> {
+        if updated {
+            let sql = r#"
+            SELECT id, name, description, parent_id, created_at
+            FROM categories
+            WHERE id = ?1
+            "#;
+            let row = sqlx::query(sql).bind(id).fetch_one(&mut tx).await?;
+            let after = json(&category_from_row(&row));
+            self.record(&mut tx, EventKind::CategoryUpdated, Some(id), before, after).await?;
+        }
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    /// Adds the `user_owes` rows and tags stored separately from the expense.
+    async fn complete_expense(&self, mut expense: Expense) -> Result<Expense, sqlx::Error> {
+        expense.extend_user_owes(get_user_owes(&self.db_pool, expense.id()).await?);
+        expense.set_tags(get_expense_tags(&self.db_pool, expense.id()).await?);
+        Ok(expense)
+    }
+}
+
+async fn get_user_owes<'e, E>(executor: E, expense_id: i32) -> Result<Vec<UserOwes>, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let sql = r#"
+    SELECT id, user_id, expense_id, amount, created_at
+    FROM user_owes
+    WHERE expense_id = ?1
+    ORDER BY id
+    "#;
+    let rows = sqlx::query(sql).bind(expense_id).fetch_all(executor).await?;
+    Ok(rows.iter().map(user_owes_from_row).collect())
+}
+
+async fn get_expense_tags<'e, E>(executor: E, expense_id: i32) -> Result<Vec<String>, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let sql = r#"
+    SELECT t.name
+    FROM expense_tags et
+    JOIN tags t ON t.id = et.tag_id
+    WHERE et.expense_id = ?1
+    ORDER BY t.name
+    "#;
+    let rows = sqlx::query(sql).bind(expense_id).fetch_all(executor).await?;
+    Ok(rows.iter().map(|row| row.get::<String, _>("name")).collect())
+}
+
+// SQLite has no `SELECT ... FOR UPDATE`. The snapshots below are read inside the transaction
+// that changes the row instead, a write that commits in between makes that transaction fail
+// rather than record a stale `before`.
+
+async fn get_user_tx(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, id: i32) -> Result<Option<User>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, username, created_at
+    FROM users
+    WHERE id = ?1
+    "#;
+    let row = sqlx::query(sql).bind(id).fetch_optional(&mut *tx).await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+async fn get_category_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    id: i32,
+) -> Result<Option<Category>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, name, description, parent_id, created_at
+    FROM categories
+    WHERE id = ?1
+    "#;
+    let row = sqlx::query(sql).bind(id).fetch_optional(&mut *tx).await?;
+    Ok(row.as_ref().map(category_from_row))
+}
+
+async fn get_expense_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    id: i32,
+) -> Result<Option<Expense>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, user_id, category_id, amount, description, purchased_at, created_at
+    FROM expenses
+    WHERE id = ?1
+    "#;
+    let Some(row) = sqlx::query(sql).bind(id).fetch_optional(&mut *tx).await? else {
+        return Ok(None);
+    };
+    let mut expense = expense_from_row(&row);
+    expense.extend_user_owes(get_user_owes(&mut *tx, id).await?);
+    expense.set_tags(get_expense_tags(&mut *tx, id).await?);
+    Ok(Some(expense))
+}
+
+async fn insert_expense_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    expense: Expense,
+) -> Result<Expense, sqlx::Error> {
+    let sql = r#"
+    INSERT INTO expenses (user_id, category_id, amount, description, purchased_at, created_at)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+    RETURNING id, user_id, category_id, amount, description, purchased_at, created_at
+    "#;
+    let row = sqlx::query(sql)
+        .bind(expense.user_id())
+        .bind(expense.category_id())
+        .bind(to_thousandths(expense.amount()))
+        .bind(expense.description())
+        .bind(expense.purchased_at())
+        .bind(now())
+        .fetch_one(&mut *tx)
+        .await?;
+    let mut inserted_expense = expense_from_row(&row);
+    insert_shares_and_tags_tx(tx, &mut inserted_expense, &expense).await?;
+
+    Ok(inserted_expense)
+}
+
+/// Stores the `user_owes` rows and tags of `expense` for `stored`, which has its id.
+async fn insert_shares_and_tags_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    stored: &mut Expense,
+    expense: &Expense,
+) -> Result<(), sqlx::Error> {
+    for user in expense.user_owes() {
+        let sql = r#"
+        INSERT INTO user_owes (user_id, expense_id, amount, created_at)
+        VALUES (?1, ?2, ?3, ?4)
+        RETURNING id, user_id, expense_id, amount, created_at
+        "#;
+        let row = sqlx::query(sql)
+            .bind(user.user_id())
+            .bind(stored.id())
+            .bind(to_thousandths(user.amount()))
+            .bind(now())
+            .fetch_one(&mut *tx)
+            .await?;
+        stored.add_user_owes(user_owes_from_row(&row));
+    }
+
+    let mut tags = normalize_tags(expense.tags());
+    for tag in &tags {
+        let sql = r#"
+        INSERT INTO tags (name, created_at)
+        VALUES (?1, ?2)
+        ON CONFLICT (name) DO NOTHING
+        "#;
+        sqlx::query(sql).bind(tag).bind(now()).execute(&mut *tx).await?;
+
+        let sql = r#"
+        INSERT INTO expense_tags (expense_id, tag_id)
+        SELECT ?1, id FROM tags WHERE name = ?2
+        "#;
+        sqlx::query(sql).bind(stored.id()).bind(tag).execute(&mut *tx).await?;
+    }
+    tags.sort();
+    stored.set_tags(tags);
+
+    Ok(())
+}
+
+async fn insert_audit_entry<'e, E>(executor: E, entry: &AuditEntry) -> Result<AuditEntry, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let sql = r#"
+    INSERT INTO audit_log (at, actor, source, address, action, entity_id, before, after)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+    RETURNING id, at, actor, source, address, action, entity_id, before, after
+    "#;
+    let row = sqlx::query(sql)
+        .bind(now())
+        .bind(entry.actor())
+        .bind(entry.source().as_str())
+        .bind(entry.address())
+        .bind(entry.action().name())
+        .bind(entry.entity_id())
+        .bind(nullable_json(entry.before()))
+        .bind(nullable_json(entry.after()))
+        .fetch_one(executor)
+        .await?;
+    Ok(audit_entry_from_row(&row))
+}
+
+async fn insert_user<'e, E>(executor: E, username: String) -> Result<User, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let sql = r#"
+    INSERT INTO users (username, created_at)
+    VALUES (?1, ?2)
+    RETURNING id, username, created_at
+    "#;
+    let row = sqlx::query(sql).bind(username).bind(now()).fetch_one(executor).await?;
+    Ok(user_from_row(&row))
+}
+
+async fn insert_category<'e, E>(
+    executor: E,
+    name: String,
+    description: &str,
+    parent_id: Option<i32>,
+) -> Result<Category, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let sql = r#"
+    INSERT INTO categories (name, description, parent_id, created_at)
+    VALUES (?1, ?2, ?3, ?4)
+    RETURNING id, name, description, parent_id, created_at
+    "#;
+    let row = sqlx::query(sql)
+        .bind(name)
+        .bind(description)
+        .bind(parent_id)
+        .bind(now())
+        .fetch_one(executor)
+        .await?;
+    Ok(category_from_row(&row))
+}
+
+async fn set_category_parent<'e, E>(executor: E, id: i32, parent_id: Option<i32>) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let sql = r#"
+    WITH RECURSIVE descendants(id) AS (
+        SELECT id FROM categories WHERE id = ?1
+        UNION
+        SELECT c.id FROM categories c JOIN descendants d ON c.parent_id = d.id
+    )
+    UPDATE categories
+    SET parent_id = ?2
+    WHERE id = ?1
+    AND (?2 IS NULL OR ?2 NOT IN (SELECT id FROM descendants))
+    "#;
+    let result = sqlx::query(sql).bind(id).bind(parent_id).execute(executor).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[async_trait]
+impl UserRepository for SqliteRepository {
+    async fn insert_user(&self, username: String) -> Result<User, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let user = insert_user(&mut tx, username).await?;
+        self.record(&mut tx, EventKind::UserCreated, Some(user.id()), serde_json::Value::Null, json(&user)).await?;
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        let sql = r#"
+        SELECT id, username, created_at
+        FROM users
+        ORDER BY id
+        "#;
+        let rows = sqlx::query(sql).fetch_all(&self.db_pool).await?;
+        Ok(rows.iter().map(user_from_row).collect())
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>, sqlx::Error> {
+        let sql = r#"
+        SELECT id, username, created_at
+        FROM users
+        WHERE id = ?1
+        "#;
+        let row = sqlx::query(sql).bind(id).fetch_optional(&self.db_pool).await?;
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let sql = r#"
+        DELETE FROM users
+        WHERE id = ?1
+        "#;
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_user_tx(&mut tx, id).await?);
+        let deleted = sqlx::query(sql).bind(id).execute(&mut tx).await?.rows_affected() > 0;
+        if deleted {
+            self.record(&mut tx, EventKind::UserDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn rename_user(&self, id: i32, username: String) -> Result<Option<User>, sqlx::Error> {
+        let sql = r#"
+        UPDATE users
+        SET username = ?2
+        WHERE id = ?1
+        RETURNING id, username, created_at
+        "#;
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_user_tx(&mut tx, id).await?);
+        let row = sqlx::query(sql).bind(id).bind(username).fetch_optional(&mut tx).await?;
+        let user = row.as_ref().map(user_from_row);
+        if let Some(user) = &user {
+            self.record(&mut tx, EventKind::UserUpdated, Some(id), before, json(user)).await?;
+        }
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn get_user_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        let sql = r#"
+        SELECT
+            (SELECT COUNT(*) FROM expenses WHERE user_id = ?1) AS expenses,
+            (SELECT COUNT(*) FROM user_owes WHERE user_id = ?1) AS shares,
+            0 AS categories
+        "#;
+        let row = sqlx::query(sql).bind(id).fetch_one(&self.db_pool).await?;
+        Ok(references_from_row(&row))
+    }
+
+    async fn merge_users(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        if from == into {
+            return Ok(false);
+        }
+
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_user_tx(&mut tx, from).await?);
+
+        let sql = r#"
+        SELECT COUNT(*) AS count
+        FROM users
+        WHERE id = ?1 OR id = ?2
+        "#;
+        let row = sqlx::query(sql).bind(from).bind(into).fetch_one(&mut tx).await?;
+        if row.get::<i64, _>("count") != 2 {
+            return Ok(false);
+        }
+
+        let sql = r#"
+        UPDATE user_owes
+        SET amount = amount + (
+            SELECT source.amount FROM user_owes AS source
+            WHERE source.user_id = ?1 AND source.expense_id = user_owes.expense_id
+        )
+        WHERE user_id = ?2
+        AND expense_id IN (SELECT expense_id FROM user_owes WHERE user_id = ?1)
+        "#;
+        sqlx::query(sql).bind(from).bind(into).execute(&mut tx).await?;
+
+        let sql = r#"
+        DELETE FROM user_owes
+        WHERE user_id = ?1
+        AND expense_id IN (SELECT expense_id FROM user_owes WHERE user_id = ?2)
+        "#;
+        sqlx::query(sql).bind(from).bind(into).execute(&mut tx).await?;
+
+        let sql = r#"
+        UPDATE user_owes
+        SET user_id = ?2
+        WHERE user_id = ?1
+        "#;
+        sqlx::query(sql).bind(from).bind(into).execute(&mut tx).await?;
+
+        let sql = r#"
+        UPDATE expenses
+        SET user_id = ?2
+        WHERE user_id = ?1
+        "#;
+        sqlx::query(sql).bind(from).bind(into).execute(&mut tx).await?;
+
+        let sql = r#"
+        DELETE FROM users
+        WHERE id = ?1
+        "#;
+        sqlx::query(sql).bind(from).execute(&mut tx).await?;
+
+        self.record(&mut tx, EventKind::UserDeleted, Some(from), before, merged_into(into)).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for SqliteRepository {
+    async fn insert_category(
+        &self,
+        name: String,
+        description: &str,
+        parent_id: Option<i32>,
+    ) -> Result<Category, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let category = insert_category(&mut tx, name, description, parent_id).await?;
+        let after = json(&category);
+        self.record(&mut tx, EventKind::CategoryCreated, Some(category.id()), serde_json::Value::Null, after).await?;
+        tx.commit().await?;
+        Ok(category)
+    }
+
+    async fn get_categories(&self) -> Result<Vec<Category>, sqlx::Error> {
+        let sql = r#"
+        SELECT id, name, description, parent_id, created_at
+        FROM categories
+        ORDER BY name
+        "#;
+        let rows = sqlx::query(sql).fetch_all(&self.db_pool).await?;
+        Ok(rows.iter().map(category_from_row).collect())
+    }
+
+    async fn delete_category(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let sql = r#"
+        DELETE FROM categories
+        WHERE id = ?1
+        "#;
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_category_tx(&mut tx, id).await?);
+        let deleted = sqlx::query(sql).bind(id).execute(&mut tx).await?.rows_affected() > 0;
+        if deleted {
+            self.record(&mut tx, EventKind::CategoryDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn rename_category(&self, id: i32, name: String) -> Result<bool, sqlx::Error> {
+        let sql = r#"
+        UPDATE categories
+        SET name = ?2
+        WHERE id = ?1
+        "#;
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_category_tx(&mut tx, id).await?);
+        let renamed = sqlx::query(sql).bind(id).bind(name).execute(&mut tx).await?.rows_affected() > 0;
+        self.category_updated(tx, renamed, id, before).await
+    }
+
+    async fn update_category_description(&self, id: i32, description: &str) -> Result<bool, sqlx::Error> {
+        let sql = r#"
+        UPDATE categories
+        SET description = ?2
+        WHERE id = ?1
+        "#;
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_category_tx(&mut tx, id).await?);
+        let result = sqlx::query(sql).bind(id).bind(description).execute(&mut tx).await?;
+        self.category_updated(tx, result.rows_affected() > 0, id, before).await
+    }
+
+    async fn set_category_parent(&self, id: i32, parent_id: Option<i32>) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_category_tx(&mut tx, id).await?);
+        let moved = set_category_parent(&mut tx, id, parent_id).await?;
+        self.category_updated(tx, moved, id, before).await
+    }
+
+    async fn get_category_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        let sql = r#"
+        SELECT
+            (SELECT COUNT(*) FROM expenses WHERE category_id = ?1) AS expenses,
+            0 AS shares,
+            (SELECT COUNT(*) FROM categories WHERE parent_id = ?1) AS categories
+        "#;
+        let row = sqlx::query(sql).bind(id).fetch_one(&self.db_pool).await?;
+        Ok(references_from_row(&row))
+    }
+
+    async fn merge_categories(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        if from == into {
+            return Ok(false);
+        }
+
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_category_tx(&mut tx, from).await?);
+
+        let sql = r#"
+        SELECT COUNT(*) AS count
+        FROM categories
+        WHERE id = ?1 OR id = ?2
+        "#;
+        let row = sqlx::query(sql).bind(from).bind(into).fetch_one(&mut tx).await?;
+        if row.get::<i64, _>("count") != 2 {
+            return Ok(false);
+        }
+
+        let sql = r#"
+        UPDATE expenses
+        SET category_id = ?2
+        WHERE category_id = ?1
+        "#;
+        sqlx::query(sql).bind(from).bind(into).execute(&mut tx).await?;
+
+        // Same as in Postgres: lift `into` out of `from` first when it is one of its descendants.
+        let sql = r#"
+        WITH RECURSIVE descendants(id) AS (
+            SELECT id FROM categories WHERE parent_id = ?1
+            UNION
+            SELECT c.id FROM categories c JOIN descendants d ON c.parent_id = d.id
+        )
+        UPDATE categories
+        SET parent_id = (SELECT parent_id FROM categories WHERE id = ?1)
+        WHERE id = ?2 AND id IN (SELECT id FROM descendants)
+        "#;
+        sqlx::query(sql).bind(from).bind(into).execute(&mut tx).await?;
+
+        let sql = r#"
+        UPDATE categories
+        SET parent_id = ?2
+        WHERE parent_id = ?1
+        "#;
+        sqlx::query(sql).bind(from).bind(into).execute(&mut tx).await?;
+
+        let sql = r#"
+        DELETE FROM categories
+        WHERE id = ?1
+        "#;
+        sqlx::query(sql).bind(from).execute(&mut tx).await?;
+
+        self.record(&mut tx, EventKind::CategoryDeleted, Some(from), before, merged_into(into)).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ExpenseRepository for SqliteRepository {
+    async fn insert_expense(&self, expense: Expense) -> Result<Expense, sqlx::Error> {
+        Ok(self.insert_expenses(vec![expense]).await?.remove(0))
+    }
+
+    async fn insert_expenses(&self, expenses: Vec<Expense>) -> Result<Vec<Expense>, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let mut inserted = vec![];
+        for expense in expenses {
+            let expense = insert_expense_tx(&mut tx, expense).await?;
+            let after = json(&expense);
+            self.record(&mut tx, EventKind::ExpenseCreated, Some(expense.id()), serde_json::Value::Null, after).await?;
+            inserted.push(expense);
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn get_expense(&self, id: i32) -> Result<Option<Expense>, sqlx::Error> {
+        let sql = r#"
+        SELECT id, user_id, category_id, amount, description, purchased_at, created_at
+        FROM expenses
+        WHERE id = ?1
+        "#;
+        match sqlx::query(sql).bind(id).fetch_optional(&self.db_pool).await? {
+            Some(row) => Ok(Some(self.complete_expense(expense_from_row(&row)).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_expenses(&self, filter: Option<Filter>) -> Result<Vec<Expense>, sqlx::Error> {
+        let rows = match filter {
+            None => {
+                let sql = r#"
+                SELECT id, user_id, category_id, amount, description, purchased_at, created_at
+                FROM expenses
+                ORDER BY created_at DESC, id DESC
+                "#;
+                sqlx::query(sql).fetch_all(&self.db_pool).await?
+            }
+            Some(filter) => {
+                // `?8` is compared against the number of distinct tags in it, like the
+                // `cardinality(ARRAY(SELECT DISTINCT ...))` in Postgres.
+                let sql = format!(
+                    r#"
+                SELECT id, user_id, category_id, amount, description, purchased_at, created_at
+                FROM expenses
+                WHERE user_id IN (SELECT value FROM json_each(?1))
+                AND category_id IN (
+                    WITH RECURSIVE descendants(id) AS (
+                        SELECT id FROM categories WHERE id IN (SELECT value FROM json_each(?2))
+                        UNION
+                        SELECT c.id FROM categories c JOIN descendants d ON c.parent_id = d.id
+                    )
+                    SELECT id FROM descendants
+                )
+                AND amount >= ?3
+                AND amount <= ?4
+                AND purchased_at >= ?5
+                AND purchased_at <= ?6
+                AND (json_array_length(?7) = 0 OR EXISTS (
+                    SELECT 1 FROM expense_tags et JOIN tags t ON t.id = et.tag_id
+                    WHERE et.expense_id = expenses.id AND t.name IN (SELECT value FROM json_each(?7))
+                ))
+                AND (
+                    SELECT COUNT(*) FROM expense_tags et JOIN tags t ON t.id = et.tag_id
+                    WHERE et.expense_id = expenses.id AND t.name IN (SELECT value FROM json_each(?8))
+                ) = (SELECT COUNT(DISTINCT value) FROM json_each(?8))
+                AND NOT EXISTS (
+                    SELECT 1 FROM expense_tags et JOIN tags t ON t.id = et.tag_id
+                    WHERE et.expense_id = expenses.id AND t.name IN (SELECT value FROM json_each(?9))
+                )
+                ORDER BY {0} {1}, id {1}
+                "#,
+                    match &filter.order_by() {
+                        OrderBy::Amount => "amount",
+                        OrderBy::Created => "created_at",
+                        OrderBy::Date => "purchased_at",
+                    },
+                    if filter.order_asc() {
+                        "ASC"
+                    } else {
+                        "DESC"
+                    }
+                );
+
+                sqlx::query(&sql)
+                    .bind(json_array(filter.user_ids()))
+                    .bind(json_array(filter.category_ids()))
+                    .bind(to_thousandths(filter.min_amount()))
+                    .bind(to_thousandths(filter.max_amount()))
+                    .bind(filter.min_date())
+                    .bind(filter.max_date())
+                    .bind(json_array(filter.tags_any()))
+                    .bind(json_array(filter.tags_all()))
+                    .bind(json_array(filter.tags_none()))
+                    .fetch_all(&self.db_pool)
+                    .await?
+            }
+        };
+
+        let mut expenses = vec![];
+        for row in &rows {
+            expenses.push(self.complete_expense(expense_from_row(row)).await?);
+        }
+        Ok(expenses)
+    }
+
+    async fn update_expense(&self, expense: Expense) -> Result<Option<Expense>, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_expense_tx(&mut tx, expense.id()).await?);
+        let sql = r#"
+        UPDATE expenses
+        SET user_id = ?2, category_id = ?3, amount = ?4, description = ?5, purchased_at = ?6
+        WHERE id = ?1
+        RETURNING id, user_id, category_id, amount, description, purchased_at, created_at
+        "#;
+        let row = sqlx::query(sql)
+            .bind(expense.id())
+            .bind(expense.user_id())
+            .bind(expense.category_id())
+            .bind(to_thousandths(expense.amount()))
+            .bind(expense.description())
+            .bind(expense.purchased_at())
+            .fetch_optional(&mut tx)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let mut updated_expense = expense_from_row(&row);
+
+        for sql in [
+            "DELETE FROM user_owes WHERE expense_id = ?1",
+            "DELETE FROM expense_tags WHERE expense_id = ?1",
+        ] {
+            sqlx::query(sql).bind(expense.id()).execute(&mut tx).await?;
+        }
+        insert_shares_and_tags_tx(&mut tx, &mut updated_expense, &expense).await?;
+
+        self.record(&mut tx, EventKind::ExpenseUpdated, Some(expense.id()), before, json(&updated_expense)).await?;
+        tx.commit().await?;
+        Ok(Some(updated_expense))
+    }
+
+    async fn delete_expense(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = json(&get_expense_tx(&mut tx, id).await?);
+        for sql in [
+            "DELETE FROM user_owes WHERE expense_id = ?1",
+            "DELETE FROM expense_tags WHERE expense_id = ?1",
+            "DELETE FROM imported_transactions WHERE expense_id = ?1",
+        ] {
+            sqlx::query(sql).bind(id).execute(&mut tx).await?;
+        }
+        let sql = r#"
+        DELETE FROM expenses
+        WHERE id = ?1
+        "#;
+        let deleted = sqlx::query(sql).bind(id).execute(&mut tx).await?.rows_affected() > 0;
+        if deleted {
+            self.record(&mut tx, EventKind::ExpenseDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+}
+
+async fn get_last_reset<'e, E>(executor: E) -> Result<chrono::NaiveDateTime, sqlx::Error>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let sql = r#"
+    SELECT date
+    FROM cleared_from
+    ORDER BY date DESC
+    LIMIT 1
+    "#;
+    let row = sqlx::query(sql).fetch_one(executor).await?;
+    Ok(row.get("date"))
+}
+
+#[async_trait]
+impl ResetRepository for SqliteRepository {
+    async fn insert_last_reset(&self) -> Result<(), sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+        let before = match get_last_reset(&mut tx).await {
+            Ok(date) => serde_json::json!({ "date": date }),
+            Err(sqlx::Error::RowNotFound) => serde_json::Value::Null,
+            Err(e) => return Err(e),
+        };
+        let sql = r#"
+        INSERT INTO cleared_from (date)
+        VALUES (?1)
+        "#;
+        sqlx::query(sql).bind(now()).execute(&mut tx).await?;
+        let after = serde_json::json!({ "date": get_last_reset(&mut tx).await? });
+        self.record(&mut tx, EventKind::Reset, None, before, after).await?;
+        tx.commit().await
+    }
+
+    async fn get_last_reset(&self) -> Result<chrono::NaiveDateTime, sqlx::Error> {
+        get_last_reset(&self.db_pool).await
+    }
+}
+
+#[async_trait]
+impl ImportRepository for SqliteRepository {
+    async fn import_dataset(&self, dataset: Dataset) -> Result<Imported, sqlx::Error> {
+        if !dataset.attachments.is_empty() {
+            return Err(sqlx::Error::Protocol("Attachments need a Postgres database.".to_string()));
+        }
+        let mut tx = self.db_pool.begin().await?;
+        let mut imported = Imported::default();
+        for username in dataset.users {
+            imported.users.push(insert_user(&mut tx, username).await?);
+        }
+        let user_ids = imported.users.iter().map(|u| u.id()).collect::<Vec<i32>>();
+
+        for new in &dataset.categories {
+            imported.categories.push(insert_category(&mut tx, new.name.clone(), &new.description, None).await?);
+        }
+        let category_ids = imported.categories.iter().map(|c| c.id()).collect::<Vec<i32>>();
+        for (category, new) in imported.categories.iter_mut().zip(&dataset.categories) {
+            if let Some(parent_id) = new.parent_id {
+                let parent_id = Dataset::resolve(&category_ids, parent_id)?;
+                if set_category_parent(&mut tx, category.id(), Some(parent_id)).await? {
+                    category.set_parent_id(Some(parent_id));
+                }
+            }
+        }
+
+        for expense in &dataset.expenses {
+            let expense = Dataset::resolve_expense(expense, &user_ids, &category_ids)?;
+            imported.expenses.push(insert_expense_tx(&mut tx, expense).await?);
+        }
+        for (index, fingerprint) in &dataset.fingerprints {
+            let sql = r#"
+            INSERT INTO imported_transactions (fingerprint, expense_id, created_at)
+            VALUES (?1, ?2, ?3)
+            "#;
+            let expense = Dataset::indexed_expense(&imported.expenses, *index)?;
+            sqlx::query(sql).bind(fingerprint).bind(expense.id()).bind(now()).execute(&mut tx).await?;
+        }
+
+        for (action, id, after) in imported.created() {
+            self.record(&mut tx, action, Some(id), serde_json::Value::Null, after).await?;
+        }
+        tx.commit().await?;
+        Ok(imported)
+    }
+
+    async fn get_import_fingerprints(&self) -> Result<HashSet<String>, sqlx::Error> {
+        let sql = r#"
+        SELECT fingerprint
+        FROM imported_transactions
+        "#;
+        let rows = sqlx::query(sql).fetch_all(&self.db_pool).await?;
+        Ok(rows.iter().map(|row| row.get("fingerprint")).collect())
+    }
+}
+
+#[async_trait]
+impl AuditRepository for SqliteRepository {
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, sqlx::Error> {
+        insert_audit_entry(&self.db_pool, &entry).await
+    }
+
+    async fn get_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let actions = match filter.action_names() {
+            Some(names) if names.is_empty() => return Ok(vec![]),
+            names => names.unwrap_or_default(),
+        };
+        let sources = filter.sources.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+
+        let sql = r#"
+        SELECT id, at, actor, source, address, action, entity_id, before, after
+        FROM audit_log
+        WHERE (json_array_length(?1) = 0 OR action IN (SELECT value FROM json_each(?1)))
+        AND (json_array_length(?2) = 0 OR entity_id IN (SELECT value FROM json_each(?2)))
+        AND (json_array_length(?3) = 0 OR actor IN (SELECT value FROM json_each(?3)))
+        AND (json_array_length(?4) = 0 OR source IN (SELECT value FROM json_each(?4)))
+        AND at >= ?5
+        ORDER BY id DESC
+        LIMIT ?6
+        "#;
+        let rows = sqlx::query(sql)
+            .bind(json_array(&actions))
+            .bind(json_array(&filter.entity_ids))
+            .bind(json_array(&filter.actors))
+            .bind(json_array(&sources))
+            .bind(filter.since)
+            .bind(filter.limit)
+            .fetch_all(&self.db_pool)
+            .await?;
+        Ok(rows.iter().map(audit_entry_from_row).collect())
+    }
+
+    fn with_actor(&self, actor: &Actor) -> Option<DynRepository> {
+        Some(std::sync::Arc::new(Self {
+            db_pool: self.db_pool.clone(),
+            actor: Some(actor.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn test_repository() -> SqliteRepository {
+        let db_config = DbConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 5,
+        };
+        SqliteRepository::connect(&db_config).await.unwrap()
+    }
+
+    fn date(day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2023, 1, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_amounts_are_exact() {
+        let repository = test_repository().await;
+        let payer = repository.insert_user("payer".to_string()).await.unwrap();
+        let category = repository.insert_category("food".to_string(), "", None).await.unwrap();
+
+        let expense = Expense::new(
+            payer.id(),
+            category.id(),
+            100.1235,
+            "rounded like NUMERIC(10, 3)".to_string(),
+            date(1),
+            vec![UserOwes::new(payer.id(), -1, 0.1)],
+        );
+        let inserted = repository.insert_expense(expense).await.unwrap();
+        assert_eq!(inserted.amount(), 100.124);
+        assert_eq!(inserted.user_owes()[0].amount(), 0.1);
+        assert_eq!(repository.get_expense(inserted.id()).await.unwrap(), Some(inserted));
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete() {
+        let repository = test_repository().await;
+        let payer = repository.insert_user("payer".to_string()).await.unwrap();
+        let category = repository.insert_category("food".to_string(), "", None).await.unwrap();
+        let mut expense = Expense::new(payer.id(), category.id(), 1.0, "before".to_string(), date(1), vec![
+            UserOwes::new(payer.id(), -1, 1.0),
+        ]);
+        expense.set_tags(vec!["old".to_string()]);
+        let inserted = repository.insert_expense(expense).await.unwrap();
+
+        let mut changed = Expense::new(payer.id(), category.id(), 2.0, "after".to_string(), date(2), vec![]);
+        changed.set_id(inserted.id());
+        changed.set_tags(vec!["new".to_string()]);
+        let updated = repository.update_expense(changed).await.unwrap().unwrap();
+        assert_eq!(updated.created_at(), inserted.created_at());
+        assert_eq!(updated.tags(), &vec!["new".to_string()]);
+        assert!(updated.user_owes().is_empty());
+        assert_eq!(repository.get_expense(inserted.id()).await.unwrap(), Some(updated));
+
+        assert!(repository.delete_expense(inserted.id()).await.unwrap());
+        assert!(!repository.delete_expense(inserted.id()).await.unwrap());
+        assert!(repository.rename_user(payer.id(), "owner".to_string()).await.unwrap().is_some());
+        assert!(repository.rename_category(category.id(), "groceries".to_string()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_merge() {
+        let repository = test_repository().await;
+        let alice = repository.insert_user("alice".to_string()).await.unwrap();
+        let bob = repository.insert_user("bob".to_string()).await.unwrap();
+        let food = repository.insert_category("food".to_string(), "", None).await.unwrap();
+        let fruit = repository.insert_category("fruit".to_string(), "", Some(food.id())).await.unwrap();
+        let rent = repository.insert_category("rent".to_string(), "", None).await.unwrap();
+
+        let mut apples = Expense::new(alice.id(), fruit.id(), 3.5, "apples".to_string(), date(2), vec![
+            UserOwes::new(bob.id(), -1, 1.75),
+        ]);
+        apples.set_tags(vec!["market".to_string(), "weekly".to_string()]);
+        let mut flat = Expense::new(bob.id(), rent.id(), 500.0, "flat".to_string(), date(1), vec![
+            UserOwes::new(alice.id(), -1, 250.0),
+        ]);
+        flat.set_tags(vec!["weekly".to_string()]);
+        repository.insert_expenses(vec![apples, flat]).await.unwrap();
+
+        // A failing insert leaves nothing behind.
+        let broken = Expense::new(-1, rent.id(), 1.0, "broken".to_string(), date(3), vec![]);
+        assert!(repository.insert_expenses(vec![broken]).await.is_err());
+        assert_eq!(repository.get_expenses(None).await.unwrap().len(), 2);
+
+        let filter = |user_ids: Vec<i32>, category_ids: Vec<i32>, tags_all: Vec<&str>| Filter {
+            user_ids,
+            category_ids,
+            min_amount: 0.0,
+            max_amount: 1000.0,
+            min_date: date(1),
+            max_date: date(31),
+            order_by: OrderBy::Date,
+            order_asc: true,
+            tags_any: vec![],
+            tags_all: tags_all.into_iter().map(String::from).collect(),
+            tags_none: vec![],
+        };
+        let descriptions = |expenses: Vec<Expense>| {
+            expenses.iter().map(|e| e.description().clone()).collect::<Vec<String>>()
+        };
+
+        let everything = filter(vec![alice.id(), bob.id()], vec![food.id(), rent.id()], vec![]);
+        let found = repository.get_expenses(Some(everything)).await.unwrap();
+        assert_eq!(descriptions(found), vec!["flat", "apples"]);
+        let by_parent = filter(vec![alice.id()], vec![food.id()], vec![]);
+        let found = repository.get_expenses(Some(by_parent)).await.unwrap();
+        assert_eq!(descriptions(found), vec!["apples"]);
+        let all_tags = filter(vec![alice.id(), bob.id()], vec![food.id(), rent.id()], vec!["weekly", "market", "weekly"]);
+        let found = repository.get_expenses(Some(all_tags)).await.unwrap();
+        assert_eq!(descriptions(found), vec!["apples"]);
+
+        assert!(repository.delete_user(bob.id()).await.is_err());
+        assert!(!repository.set_category_parent(food.id(), Some(fruit.id())).await.unwrap());
+        assert!(repository.merge_users(bob.id(), alice.id()).await.unwrap());
+        let merged = repository.get_expenses(None).await.unwrap();
+        assert!(merged.iter().all(|e| e.user_id() == alice.id()));
+        assert!(repository.get_user_references(bob.id()).await.unwrap().is_empty());
+
+        assert!(repository.merge_categories(food.id(), fruit.id()).await.unwrap());
+        let categories = repository.get_categories().await.unwrap();
+        assert_eq!(categories.len(), 2);
+        assert!(categories.iter().all(|c| c.parent_id().is_none()));
+
+        assert!(matches!(repository.get_last_reset().await, Err(sqlx::Error::RowNotFound)));
+        repository.insert_last_reset().await.unwrap();
+        assert!(repository.get_last_reset().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        use datatypes::{AuditSource, EventKind};
+
+        let repository = test_repository().await;
+        let entry = |actor: &str, action, after| {
+            AuditEntry::new(actor.to_string(), AuditSource::Cli, action, Some(1), serde_json::Value::Null, after)
+        };
+        let created = entry("alice", EventKind::UserCreated, serde_json::json!({ "username": "bob" }));
+        let created = repository.insert_audit_entry(created).await.unwrap();
+        assert_eq!(created.after()["username"], "bob");
+        let deleted = entry("carol", EventKind::UserDeleted, serde_json::Value::Null);
+        let deleted = repository.insert_audit_entry(deleted).await.unwrap();
+        assert!(deleted.after().is_null());
+
+        let everything = repository.get_audit_entries(&AuditFilter::default()).await.unwrap();
+        assert_eq!(everything, vec![deleted.clone(), created.clone()]);
+        let filter = AuditFilter {
+            actions: vec!["user".to_string()],
+            actors: vec!["alice".to_string()],
+            ..AuditFilter::default()
+        };
+        assert_eq!(repository.get_audit_entries(&filter).await.unwrap(), vec![created]);
+
+        let changed = sqlx::query("UPDATE audit_log SET actor = 'someone else'").execute(repository.db_pool()).await;
+        assert!(changed.is_err());
+        let removed = sqlx::query("DELETE FROM audit_log").execute(repository.db_pool()).await;
+        assert!(removed.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_records_changes() {
+        use crate::utils::audit::AuditingRepository;
+        use datatypes::{AuditSource, EventKind};
+
+        let inner: DynRepository = std::sync::Arc::new(test_repository().await);
+        let repository = AuditingRepository::wrap(inner, Actor::new("alice".to_string(), AuditSource::Cli));
+        let alice = repository.insert_user("alice".to_string()).await.unwrap();
+        let food = repository.insert_category("food".to_string(), "", None).await.unwrap();
+        let bread = Expense::new(alice.id(), food.id(), 3.0, "bread".to_string(), date(1), vec![]);
+        repository.insert_expense(bread).await.unwrap();
+        // Still referenced, so neither the deletion nor its entry are kept.
+        assert!(repository.delete_category(food.id()).await.is_err());
+        assert!(repository.rename_category(food.id(), "groceries".to_string()).await.unwrap());
+        repository.insert_last_reset().await.unwrap();
+
+        let entries = repository.get_audit_entries(&AuditFilter::default()).await.unwrap();
+        let actions = entries.iter().map(|e| e.action()).collect::<Vec<EventKind>>();
+        assert_eq!(
+            actions,
+            vec![
+                EventKind::Reset,
+                EventKind::CategoryUpdated,
+                EventKind::ExpenseCreated,
+                EventKind::CategoryCreated,
+                EventKind::UserCreated,
+            ]
+        );
+        assert!(entries[0].before().is_null());
+        assert_eq!(entries[1].before()["name"], "food");
+        assert_eq!(entries[1].after()["name"], "groceries");
+        assert_eq!(entries[4].actor(), "alice");
+        assert_eq!(entries[4].address(), None);
+    }
+}
@@ -0,0 +1,323 @@
+use datatypes::{EventKind, Expense, Tag};
+use sqlx::Row;
+
+use crate::database::expense;
+use crate::utils::audit::{json, record_tx, Actor};
+
+/// Trims the names, drops empty ones and duplicates, keeping the first occurrence's order.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = vec![];
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !normalized.iter().any(|n| n == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+pub async fn get_tags(db_pool: &sqlx::PgPool) -> Result<Vec<Tag>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, name, created_at
+    FROM tags
+    ORDER BY name
+    "#;
+    let tags = sqlx::query(sql)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(Tag::from)
+        .collect();
+    Ok(tags)
+}
+
+pub async fn get_expense_tags<'e, E>(executor: E, expense_id: i32) -> Result<Vec<String>, sqlx::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let sql = r#"
+    SELECT t.name
+    FROM expense_tags et
+    JOIN tags t ON t.id = et.tag_id
+    WHERE et.expense_id = $1
+    ORDER BY t.name
+    "#;
+    let tags = sqlx::query(sql)
+        .bind(expense_id)
+        .fetch_all(executor)
+        .await?
+        .into_iter()
+        .map(|row| row.get::<String, _>("name"))
+        .collect();
+    Ok(tags)
+}
+
+/// Replaces the tags of the expense, creating tags that don't exist yet. Returns the tags the
+/// expense ends up with.
+pub async fn set_expense_tags_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    expense_id: i32,
+    tags: &[String],
+) -> Result<Vec<String>, sqlx::Error> {
+    let tags = normalize_tags(tags);
+
+    let sql = r#"
+    INSERT INTO tags (name)
+    SELECT UNNEST($1::text[])
+    ON CONFLICT (name) DO NOTHING
+    "#;
+    sqlx::query(sql).bind(&tags).execute(&mut *tx).await?;
+
+    let sql = r#"
+    DELETE FROM expense_tags
+    WHERE expense_id = $1
+    "#;
+    sqlx::query(sql).bind(expense_id).execute(&mut *tx).await?;
+
+    let sql = r#"
+    INSERT INTO expense_tags (expense_id, tag_id)
+    SELECT $1, id FROM tags WHERE name = ANY($2)
+    "#;
+    sqlx::query(sql).bind(expense_id).bind(&tags).execute(&mut *tx).await?;
+
+    get_expense_tags(&mut *tx, expense_id).await
+}
+
+/// Replaces the tags of an existing expense, recorded as an update by `actor`. Returns `None`
+/// if the expense does not exist.
+pub async fn set_expense_tags(
+    db_pool: &sqlx::PgPool,
+    actor: &Actor,
+    expense_id: i32,
+    tags: &[String],
+) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+
+    let sql = r#"
+    SELECT id
+    FROM expenses
+    WHERE id = $1
+    FOR UPDATE
+    "#;
+    if sqlx::query(sql).bind(expense_id).fetch_optional(&mut tx).await?.is_none() {
+        return Ok(None);
+    }
+    let before = expense::get_expense_tx(&mut tx, expense_id).await?;
+    let tags = set_expense_tags_tx(&mut tx, expense_id, tags).await?;
+    record_retagged_tx(&mut tx, actor, before.into_iter().collect()).await?;
+
+    tx.commit().await?;
+    Ok(Some(tags))
+}
+
+/// The expenses tagged `name`, as they are before their tags change.
+async fn tagged_expenses_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    name: &str,
+) -> Result<Vec<Expense>, sqlx::Error> {
+    let sql = r#"
+    SELECT et.expense_id
+    FROM expense_tags et
+    JOIN tags t ON t.id = et.tag_id
+    WHERE t.name = $1
+    ORDER BY et.expense_id
+    "#;
+    let ids = sqlx::query(sql)
+        .bind(name)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|row| row.get::<i32, _>("expense_id"))
+        .collect::<Vec<i32>>();
+
+    let mut expenses = vec![];
+    for id in ids {
+        expenses.extend(expense::get_expense_tx(tx, id).await?);
+    }
+    Ok(expenses)
+}
+
+/// Records the tags of `retagged` changing as done by `actor`, `retagged` being the expenses
+/// as they were before.
+async fn record_retagged_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    actor: &Actor,
+    retagged: Vec<Expense>,
+) -> Result<(), sqlx::Error> {
+    for before in retagged {
+        let after = expense::get_expense_tx(tx, before.id()).await?;
+        let after = after.as_ref().map(json).unwrap_or_default();
+        record_tx(tx, actor, EventKind::ExpenseUpdated, Some(before.id()), json(&before), after).await?;
+    }
+    Ok(())
+}
+
+/// Renames a tag, recording every expense that had it as updated by `actor`. Returns `false`
+/// if the tag does not exist, the new name is blank or it is already taken, in which case the
+/// tags have to be merged instead.
+pub async fn rename_tag(db_pool: &sqlx::PgPool, actor: &Actor, from: &str, to: &str) -> Result<bool, sqlx::Error> {
+    if to.trim().is_empty() {
+        return Ok(false);
+    }
+    let mut tx = db_pool.begin().await?;
+    let retagged = tagged_expenses_tx(&mut tx, from).await?;
+
+    let sql = r#"
+    UPDATE tags
+    SET name = $2
+    WHERE name = $1
+    AND NOT EXISTS (SELECT 1 FROM tags WHERE name = $2)
+    RETURNING id
+    "#;
+    let row = sqlx::query(sql)
+        .bind(from)
+        .bind(to.trim())
+        .fetch_optional(&mut tx)
+        .await?;
+    if row.is_none() {
+        return Ok(false);
+    }
+    record_retagged_tx(&mut tx, actor, retagged).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Moves every expense tagged `from` to `into` and deletes `from` in one transaction, recording
+/// the moved expenses as updated by `actor`. Returns `false` if either tag does not exist.
+pub async fn merge_tags(db_pool: &sqlx::PgPool, actor: &Actor, from: &str, into: &str) -> Result<bool, sqlx::Error> {
+    if from == into {
+        return Ok(false);
+    }
+    let mut tx = db_pool.begin().await?;
+
+    let sql = r#"
+    SELECT
+        (SELECT id FROM tags WHERE name = $1) AS from_id,
+        (SELECT id FROM tags WHERE name = $2) AS into_id
+    "#;
+    let row = sqlx::query(sql).bind(from).bind(into).fetch_one(&mut tx).await?;
+    let (from_id, into_id) = match (row.get::<Option<i32>, _>("from_id"), row.get::<Option<i32>, _>("into_id")) {
+        (Some(from_id), Some(into_id)) => (from_id, into_id),
+        _ => return Ok(false),
+    };
+    let retagged = tagged_expenses_tx(&mut tx, from).await?;
+
+    let sql = r#"
+    INSERT INTO expense_tags (expense_id, tag_id)
+    SELECT expense_id, $2 FROM expense_tags WHERE tag_id = $1
+    ON CONFLICT DO NOTHING
+    "#;
+    sqlx::query(sql).bind(from_id).bind(into_id).execute(&mut tx).await?;
+
+    let sql = r#"
+    DELETE FROM expense_tags
+    WHERE tag_id = $1
+    "#;
+    sqlx::query(sql).bind(from_id).execute(&mut tx).await?;
+
+    let sql = r#"
+    DELETE FROM tags
+    WHERE id = $1
+    "#;
+    sqlx::query(sql).bind(from_id).execute(&mut tx).await?;
+    record_retagged_tx(&mut tx, actor, retagged).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{test_pool, unique_name};
+    use crate::database::{audit, category, user};
+    use datatypes::{AuditFilter, AuditSource, Filter, OrderBy, UserOwes};
+    use rocket::serde::json::serde_json;
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = ["  gift".to_string(), "".to_string(), "gift ".to_string(), "holiday".to_string()];
+        assert_eq!(normalize_tags(&tags), vec!["gift".to_string(), "holiday".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_tags() {
+        let db_pool = test_pool().await;
+        let actor = Actor::new(unique_name("tagger"), AuditSource::Cli);
+
+        let payer = user::insert_user(&db_pool, unique_name("payer")).await.unwrap();
+        let category = category::insert_category(&db_pool, unique_name("tags"), &"description".to_string(), None)
+            .await
+            .unwrap();
+        let (holiday, gift, work) = (unique_name("holiday"), unique_name("gift"), unique_name("work"));
+
+        let mut ids = vec![];
+        for tags in [vec![holiday.clone(), gift.clone()], vec![holiday.clone()], vec![]] {
+            let mut new = Expense::new(
+                payer.id(),
+                category.id(),
+                10.0,
+                "tagged".to_string(),
+                chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                vec![UserOwes::new(payer.id(), -1, 10.0)],
+            );
+            new.set_tags(tags);
+            ids.push(expense::insert_expense(&db_pool, new).await.unwrap().id());
+        }
+        let fetched = expense::get_expense(&db_pool, ids[0]).await.unwrap().unwrap();
+        assert_eq!(fetched.tags().len(), 2);
+
+        let filter = |any: Vec<String>, all: Vec<String>, none: Vec<String>| Filter {
+            user_ids: vec![payer.id()],
+            category_ids: vec![category.id()],
+            min_amount: f64::MIN,
+            max_amount: f64::MAX,
+            min_date: chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap(),
+            max_date: chrono::NaiveDate::from_ymd_opt(9999, 12, 31).unwrap(),
+            order_by: OrderBy::Created,
+            order_asc: true,
+            tags_any: any,
+            tags_all: all,
+            tags_none: none,
+        };
+        let found = |filter: Filter| {
+            let db_pool = db_pool.clone();
+            async move {
+                expense::get_expenses(&db_pool, Some(filter))
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|e| e.id())
+                    .collect::<Vec<i32>>()
+            }
+        };
+        assert_eq!(found(filter(vec![holiday.clone(), work.clone()], vec![], vec![])).await, ids[..2]);
+        assert_eq!(found(filter(vec![], vec![holiday.clone(), gift.clone()], vec![])).await, ids[..1]);
+        assert_eq!(found(filter(vec![], vec![], vec![gift.clone()])).await, ids[1..]);
+
+        assert!(set_expense_tags(&db_pool, &actor, ids[2], std::slice::from_ref(&work)).await.unwrap().is_some());
+        assert!(!rename_tag(&db_pool, &actor, &work, &holiday).await.unwrap());
+        assert!(merge_tags(&db_pool, &actor, &work, &holiday).await.unwrap());
+        assert!(!merge_tags(&db_pool, &actor, &work, &holiday).await.unwrap());
+        assert_eq!(found(filter(vec![holiday.clone()], vec![], vec![])).await, ids);
+
+        let renamed = unique_name("trip");
+        assert!(!rename_tag(&db_pool, &actor, &holiday, "  ").await.unwrap());
+        assert!(rename_tag(&db_pool, &actor, &holiday, &renamed).await.unwrap());
+        let fetched = expense::get_expense(&db_pool, ids[1]).await.unwrap().unwrap();
+        assert_eq!(fetched.tags(), &vec![renamed.clone()]);
+
+        // Tagging and merging changed the third expense, the rename changed the tags of all three.
+        let filter = AuditFilter {
+            actors: vec![actor.name().clone()],
+            ..AuditFilter::default()
+        };
+        let entries = audit::get_audit_entries(&db_pool, &filter).await.unwrap();
+        let recorded = entries.iter().rev().map(|e| (e.action(), e.entity_id())).collect::<Vec<(EventKind, Option<i32>)>>();
+        let updated = |id: i32| (EventKind::ExpenseUpdated, Some(id));
+        assert_eq!(recorded, vec![updated(ids[2]), updated(ids[2]), updated(ids[0]), updated(ids[1]), updated(ids[2])]);
+        assert_eq!(entries[3].before()["tags"], serde_json::json!([work]));
+        assert_eq!(entries[3].after()["tags"], serde_json::json!([holiday]));
+        assert_eq!(entries[0].after()["tags"], serde_json::json!([renamed]));
+    }
+}
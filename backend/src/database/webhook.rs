@@ -0,0 +1,167 @@
+use datatypes::{EventKind, Webhook, WebhookDelivery};
+use rocket::serde::json::serde_json;
+
+use crate::utils::audit::{json, record_tx, Actor};
+
+/// Adds the webhook, recorded as created by `actor`. The secret is left out of the record.
+pub async fn insert_webhook(db_pool: &sqlx::PgPool, actor: &Actor, webhook: &Webhook) -> Result<Webhook, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    let sql = r#"
+    INSERT INTO webhooks (url, secret, events)
+    VALUES ($1, $2, $3)
+    RETURNING id, url, secret, events, created_at
+    "#;
+    let row = sqlx::query(sql)
+        .bind(webhook.url())
+        .bind(webhook.secret())
+        .bind(webhook.events())
+        .fetch_one(&mut tx)
+        .await?;
+    let webhook = Webhook::from(row);
+    record_tx(&mut tx, actor, EventKind::WebhookCreated, Some(webhook.id()), serde_json::Value::Null, json(&webhook)).await?;
+    tx.commit().await?;
+    Ok(webhook)
+}
+
+pub async fn get_webhook(db_pool: &sqlx::PgPool, id: i32) -> Result<Option<Webhook>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, url, secret, events, created_at
+    FROM webhooks
+    WHERE id = $1
+    "#;
+    let webhook = sqlx::query(sql)
+        .bind(id)
+        .fetch_optional(db_pool)
+        .await?
+        .map(Webhook::from);
+    Ok(webhook)
+}
+
+pub async fn get_webhooks(db_pool: &sqlx::PgPool) -> Result<Vec<Webhook>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, url, secret, events, created_at
+    FROM webhooks
+    ORDER BY id
+    "#;
+    let webhooks = sqlx::query(sql)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(Webhook::from)
+        .collect();
+    Ok(webhooks)
+}
+
+/// Removes the webhook together with its delivery log, recorded as done by `actor`. `false` if
+/// there was no such webhook.
+pub async fn delete_webhook(db_pool: &sqlx::PgPool, actor: &Actor, id: i32) -> Result<bool, sqlx::Error> {
+    let mut tx = db_pool.begin().await?;
+    sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = $1")
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    let sql = r#"
+    DELETE FROM webhooks
+    WHERE id = $1
+    RETURNING id, url, secret, events, created_at
+    "#;
+    let Some(row) = sqlx::query(sql).bind(id).fetch_optional(&mut tx).await? else {
+        return Ok(false);
+    };
+    let before = json(&Webhook::from(row));
+    record_tx(&mut tx, actor, EventKind::WebhookDeleted, Some(id), before, serde_json::Value::Null).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn insert_delivery(
+    db_pool: &sqlx::PgPool,
+    delivery: &WebhookDelivery,
+) -> Result<WebhookDelivery, sqlx::Error> {
+    let sql = r#"
+    INSERT INTO webhook_deliveries (webhook_id, event, payload, attempt, status, error)
+    VALUES ($1, $2, $3::jsonb, $4, $5, $6)
+    RETURNING id, webhook_id, event, payload::text AS payload, attempt, status, error, created_at
+    "#;
+    let row = sqlx::query(sql)
+        .bind(delivery.webhook_id())
+        .bind(delivery.event().name())
+        .bind(delivery.payload().to_string())
+        .bind(delivery.attempt())
+        .bind(delivery.status())
+        .bind(delivery.error())
+        .fetch_one(db_pool)
+        .await?;
+    Ok(WebhookDelivery::from(row))
+}
+
+/// The latest `limit` attempts of a webhook, newest first.
+pub async fn get_deliveries(
+    db_pool: &sqlx::PgPool,
+    webhook_id: i32,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    let sql = r#"
+    SELECT id, webhook_id, event, payload::text AS payload, attempt, status, error, created_at
+    FROM webhook_deliveries
+    WHERE webhook_id = $1
+    ORDER BY id DESC
+    LIMIT $2
+    "#;
+    let deliveries = sqlx::query(sql)
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(db_pool)
+        .await?
+        .into_iter()
+        .map(WebhookDelivery::from)
+        .collect();
+    Ok(deliveries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::audit;
+    use crate::database::testing::{test_pool, unique_name};
+    use datatypes::{AuditFilter, AuditSource, Event};
+
+    #[tokio::test]
+    async fn test_webhooks_and_deliveries() {
+        let db_pool = test_pool().await;
+        let actor = Actor::new(unique_name("hooker"), AuditSource::Cli);
+        let url = format!("http://localhost/{}", unique_name("hook"));
+        let webhook = Webhook::new(url.clone(), "secret".to_string(), vec!["expense".to_string()]);
+        let webhook = insert_webhook(&db_pool, &actor, &webhook).await.unwrap();
+        assert_eq!(webhook.url(), &url);
+        assert_eq!(webhook.secret(), "secret");
+        assert_eq!(get_webhook(&db_pool, webhook.id()).await.unwrap(), Some(webhook.clone()));
+
+        let event = Event::new(EventKind::Reset, None, serde_json::Value::Null);
+        let failed = WebhookDelivery::new(webhook.id(), &event, 1, Some(500), Some("receiver answered 500".to_string()));
+        insert_delivery(&db_pool, &failed).await.unwrap();
+        let delivered = WebhookDelivery::new(webhook.id(), &event, 2, Some(200), None);
+        insert_delivery(&db_pool, &delivered).await.unwrap();
+
+        let deliveries = get_deliveries(&db_pool, webhook.id(), 10).await.unwrap();
+        assert_eq!(deliveries.iter().map(|d| d.attempt()).collect::<Vec<i32>>(), vec![2, 1]);
+        assert!(deliveries[0].succeeded());
+        assert_eq!(deliveries[1].event(), EventKind::Reset);
+        assert_eq!(deliveries[1].payload()["type"], "reset");
+
+        assert!(delete_webhook(&db_pool, &actor, webhook.id()).await.unwrap());
+        assert!(!delete_webhook(&db_pool, &actor, webhook.id()).await.unwrap());
+        assert!(get_deliveries(&db_pool, webhook.id(), 10).await.unwrap().is_empty());
+
+        let filter = AuditFilter {
+            actors: vec![actor.name().clone()],
+            ..AuditFilter::default()
+        };
+        let entries = audit::get_audit_entries(&db_pool, &filter).await.unwrap();
+        let actions = entries.iter().map(|e| e.action()).collect::<Vec<EventKind>>();
+        assert_eq!(actions, vec![EventKind::WebhookDeleted, EventKind::WebhookCreated]);
+        assert_eq!(entries[0].before()["url"], url.as_str());
+        // The secret is never recorded.
+        assert!(entries[1].after().get("secret").is_none());
+    }
+}
@@ -1,4 +1,5 @@
-// pub mod cli;
+pub mod cli;
 pub mod database;
+pub mod tui;
 pub mod utils;
 pub mod web;
@@ -1,20 +1,31 @@
+use datatypes::AuditSource;
+use expenses_backend::utils::audit::{Actor, AuditingRepository};
 use expenses_backend::{utils, database};
 
 #[tokio::main]
 async fn main() {
-    let config: utils::config::Config =
-        utils::config::read_config("Config.toml").expect("Failed to read config.");
+    let args = utils::args::parse();
+
+    let config = match utils::config::load_config(args.config()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
     utils::logger::init_logger(config.log_level());
 
-    let db_pool = database::connect_db(config.db_config())
+    let (repository, db_pool) = database::connect(config.db_config())
         .await
         .expect("Failed to connect to database.");
 
+    if args.runs_startup() {
+        let startup_repository = AuditingRepository::wrap(repository.clone(), Actor::cli_user(AuditSource::Cli));
+        utils::startup(&config, startup_repository.as_ref()).await.expect("Failed to run startup tasks.");
+    }
 
-    utils::startup(&config, &db_pool).await.expect("Failed to run startup tasks.");
-
-    utils::args::run(config, &db_pool)
+    utils::args::run(args, config, repository, db_pool)
         .await
         .expect("Failed to run application.");
 }
@@ -0,0 +1,228 @@
+use datatypes::{Category, Expense, User};
+
+use crate::utils::expenses;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Payer,
+    Category,
+    Amount,
+    Date,
+    Description,
+    Split,
+}
+
+impl Field {
+    pub const ALL: [Field; 6] = [
+        Field::Payer,
+        Field::Category,
+        Field::Amount,
+        Field::Date,
+        Field::Description,
+        Field::Split,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Field::Payer => "Payer",
+            Field::Category => "Category",
+            Field::Amount => "Amount",
+            Field::Date => "Date (dd/mm/yyyy)",
+            Field::Description => "Description",
+            Field::Split => "Split (bob,carol=5)",
+        }
+    }
+}
+
+/// The "add expense" form. Payer and category are picked from the loaded lists, everything
+/// else is typed in and only parsed on submit.
+pub struct ExpenseForm {
+    pub focus: usize,
+    pub payer: usize,
+    pub category: usize,
+    pub amount: String,
+    pub date: String,
+    pub description: String,
+    pub split: String,
+    pub error: Option<String>,
+}
+
+impl ExpenseForm {
+    pub fn new() -> Self {
+        Self {
+            focus: 0,
+            payer: 0,
+            category: 0,
+            amount: String::new(),
+            date: chrono::Local::now().date_naive().format("%d/%m/%Y").to_string(),
+            description: String::new(),
+            split: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn field(&self) -> Field {
+        Field::ALL[self.focus]
+    }
+
+    pub fn next_field(&mut self) {
+        self.focus = (self.focus + 1) % Field::ALL.len();
+    }
+
+    pub fn previous_field(&mut self) {
+        self.focus = (self.focus + Field::ALL.len() - 1) % Field::ALL.len();
+    }
+
+    /// Moves the selection of the payer or category field, wrapping around.
+    pub fn cycle(&mut self, forward: bool, users: usize, categories: usize) {
+        let (selected, len) = match self.field() {
+            Field::Payer => (&mut self.payer, users),
+            Field::Category => (&mut self.category, categories),
+            _ => return,
+        };
+        if len == 0 {
+            return;
+        }
+        *selected = if forward {
+            (*selected + 1) % len
+        } else {
+            (*selected + len - 1) % len
+        };
+    }
+
+    fn text_mut(&mut self) -> Option<&mut String> {
+        match self.field() {
+            Field::Amount => Some(&mut self.amount),
+            Field::Date => Some(&mut self.date),
+            Field::Description => Some(&mut self.description),
+            Field::Split => Some(&mut self.split),
+            Field::Payer | Field::Category => None,
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if let Some(text) = self.text_mut() {
+            text.push(c);
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(text) = self.text_mut() {
+            text.pop();
+        }
+    }
+
+    pub fn value(&self, field: Field, users: &[User], categories: &[Category]) -> String {
+        match field {
+            Field::Payer => users
+                .get(self.payer)
+                .map(|u| u.username().to_string())
+                .unwrap_or_default(),
+            Field::Category => categories
+                .get(self.category)
+                .map(|c| c.name().to_string())
+                .unwrap_or_default(),
+            Field::Amount => self.amount.clone(),
+            Field::Date => self.date.clone(),
+            Field::Description => self.description.clone(),
+            Field::Split => self.split.clone(),
+        }
+    }
+
+    pub fn to_expense(&self, users: &[User], categories: &[Category]) -> anyhow::Result<Expense> {
+        let payer = users
+            .get(self.payer)
+            .ok_or_else(|| anyhow::anyhow!("Create a user first."))?;
+        let category = categories
+            .get(self.category)
+            .ok_or_else(|| anyhow::anyhow!("Create a category first."))?;
+        let amount = self
+            .amount
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| anyhow::anyhow!("Invalid amount `{}`.", self.amount))?;
+        if amount <= 0.0 {
+            return Err(anyhow::anyhow!("The amount has to be positive."));
+        }
+        let date = chrono::NaiveDate::parse_from_str(self.date.trim(), "%d/%m/%Y")
+            .map_err(|_| anyhow::anyhow!("Invalid date `{}`.", self.date))?;
+
+        let mut shares = vec![];
+        for entry in self.split.split(',').filter(|e| !e.trim().is_empty()) {
+            let (name, share) = expenses::parse_split_entry(entry)?;
+            let user = users
+                .iter()
+                .find(|u| *u.username() == name)
+                .ok_or_else(|| anyhow::anyhow!("User `{}` does not exist.", name))?;
+            shares.push((user.id(), share));
+        }
+
+        Ok(Expense::new(
+            payer.id(),
+            category.id(),
+            amount,
+            self.description.trim().to_string(),
+            date,
+            expenses::split_amount(amount, payer.id(), &shares)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names() -> (Vec<User>, Vec<Category>) {
+        let users = vec![User::new("alice".to_string()), User::new("bob".to_string())];
+        let categories = vec![Category::new("food".to_string(), "food expenses".to_string())];
+        (users, categories)
+    }
+
+    #[test]
+    fn test_to_expense() {
+        let (users, categories) = names();
+        let mut form = ExpenseForm::new();
+        form.amount = "30".to_string();
+        form.date = "01/02/2023".to_string();
+        form.description = " pizza ".to_string();
+
+        let expense = form.to_expense(&users, &categories).unwrap();
+        assert_eq!(expense.amount(), 30.0);
+        assert_eq!(expense.description(), "pizza");
+        assert_eq!(*expense.purchased_at(), chrono::NaiveDate::from_ymd_opt(2023, 2, 1).unwrap());
+        // Without a split the payer owes the whole amount.
+        assert_eq!(expense.user_owes().len(), 1);
+        assert_eq!(expense.user_owes()[0].amount(), 30.0);
+    }
+
+    #[test]
+    fn test_to_expense_invalid_input() {
+        let (users, categories) = names();
+        let mut form = ExpenseForm::new();
+        form.amount = "abc".to_string();
+        assert!(form.to_expense(&users, &categories).is_err());
+
+        form.amount = "10".to_string();
+        form.split = "carol".to_string();
+        assert!(form.to_expense(&users, &categories).is_err());
+
+        form.split.clear();
+        assert!(form.to_expense(&[], &categories).is_err());
+    }
+
+    #[test]
+    fn test_cycle_wraps() {
+        let mut form = ExpenseForm::new();
+        form.cycle(false, 3, 2);
+        assert_eq!(form.payer, 2);
+        form.next_field();
+        form.cycle(true, 3, 2);
+        form.cycle(true, 3, 2);
+        assert_eq!(form.category, 0);
+
+        // Text fields ignore cycling.
+        form.next_field();
+        form.cycle(true, 3, 2);
+        assert_eq!((form.payer, form.category), (2, 0));
+    }
+}
@@ -0,0 +1,203 @@
+mod form;
+mod ui;
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use datatypes::{Category, Expense, User};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::DefaultTerminal;
+
+use crate::database::repository::Repository;
+use crate::utils::{balance, categories};
+use form::ExpenseForm;
+
+const RECENT_EXPENSES: usize = 50;
+
+/// Everything the dashboard shows. It is reloaded from the database on start, on `r` and after
+/// an expense was added.
+pub struct App {
+    users: Vec<User>,
+    categories: Vec<Category>,
+    expenses: Vec<Expense>,
+    last_reset: Option<chrono::NaiveDate>,
+    balances: HashMap<i32, f64>,
+    /// Includes the totals of sub-categories.
+    category_totals: HashMap<i32, f64>,
+    user_filter: Option<i32>,
+    category_filter: Option<i32>,
+    form: Option<ExpenseForm>,
+    status: Option<String>,
+}
+
+impl App {
+    async fn load(repository: &dyn Repository) -> Result<Self> {
+        let mut app = Self {
+            users: vec![],
+            categories: vec![],
+            expenses: vec![],
+            last_reset: None,
+            balances: HashMap::new(),
+            category_totals: HashMap::new(),
+            user_filter: None,
+            category_filter: None,
+            form: None,
+            status: None,
+        };
+        app.refresh(repository).await?;
+        Ok(app)
+    }
+
+    async fn refresh(&mut self, repository: &dyn Repository) -> Result<()> {
+        self.users = repository.get_users().await?;
+        self.categories = repository.get_categories().await?;
+        self.expenses = repository.get_expenses(None).await?;
+        self.last_reset = match repository.get_last_reset().await {
+            Ok(date) => Some(date.date()),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let period = balance::expenses_since_last_reset(repository).await?;
+        self.balances = balance::balances(&period);
+        self.category_totals =
+            categories::rollup_totals(&self.categories, &balance::category_totals(&period));
+        Ok(())
+    }
+
+    fn period_title(&self, title: &str) -> String {
+        match self.last_reset {
+            Some(date) => format!("{} since {}", title, date.format("%d/%m/%Y")),
+            None => format!("{} (all time)", title),
+        }
+    }
+
+    fn visible_expenses(&self) -> impl Iterator<Item = &Expense> {
+        let category_ids = self
+            .category_filter
+            .map(|id| categories::descendant_ids(&self.categories, id));
+        self.expenses
+            .iter()
+            .filter(|e| self.user_filter.is_none_or(|id| e.user_id() == id))
+            .filter(move |e| {
+                category_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&e.category_id()))
+            })
+            .take(RECENT_EXPENSES)
+    }
+
+    /// Steps the filter through every id and back to "no filter".
+    fn next_filter(current: Option<i32>, ids: &[i32]) -> Option<i32> {
+        match current.and_then(|id| ids.iter().position(|i| *i == id)) {
+            None => ids.first().copied(),
+            Some(index) => ids.get(index + 1).copied(),
+        }
+    }
+
+    /// Handles a key press on the dashboard. Returns `false` once the user wants to quit.
+    async fn handle_key(&mut self, repository: &dyn Repository, key: KeyCode) -> Result<bool> {
+        self.status = None;
+        if self.form.is_some() {
+            self.handle_form_key(repository, key).await?;
+            return Ok(true);
+        }
+
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('r') => {
+                self.refresh(repository).await?;
+                self.status = Some("Refreshed.".to_string());
+            }
+            KeyCode::Char('a') => self.form = Some(ExpenseForm::new()),
+            KeyCode::Char('u') => {
+                let ids = self.users.iter().map(|u| u.id()).collect::<Vec<i32>>();
+                self.user_filter = Self::next_filter(self.user_filter, &ids);
+            }
+            KeyCode::Char('c') => {
+                let ids = self.categories.iter().map(|c| c.id()).collect::<Vec<i32>>();
+                self.category_filter = Self::next_filter(self.category_filter, &ids);
+            }
+            KeyCode::Char('x') => {
+                self.user_filter = None;
+                self.category_filter = None;
+            }
+            _ => (),
+        }
+        Ok(true)
+    }
+
+    async fn handle_form_key(&mut self, repository: &dyn Repository, key: KeyCode) -> Result<()> {
+        let Some(form) = self.form.as_mut() else {
+            return Ok(());
+        };
+
+        match key {
+            KeyCode::Esc => self.form = None,
+            KeyCode::Tab | KeyCode::Down => form.next_field(),
+            KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+            KeyCode::Left => form.cycle(false, self.users.len(), self.categories.len()),
+            KeyCode::Right => form.cycle(true, self.users.len(), self.categories.len()),
+            KeyCode::Backspace => form.pop(),
+            KeyCode::Char(c) => form.push(c),
+            KeyCode::Enter => match form.to_expense(&self.users, &self.categories) {
+                Ok(expense) => {
+                    let expense = repository.insert_expense(expense).await?;
+                    self.form = None;
+                    self.refresh(repository).await?;
+                    self.status = Some(format!("Added expense {}.", expense.id()));
+                }
+                Err(e) => form.error = Some(e.to_string()),
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+async fn event_loop(terminal: &mut DefaultTerminal, repository: &dyn Repository) -> Result<()> {
+    let mut app = App::load(repository).await?;
+    loop {
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+
+        // Reading the terminal blocks, keep the runtime's other tasks going meanwhile.
+        let event = tokio::task::block_in_place(event::read)?;
+        if let Event::Key(key) = event {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match app.handle_key(repository, key.code).await {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => app.status = Some(format!("Error: {}", e)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the full screen dashboard until the user quits, restoring the terminal afterwards.
+/// Logging is muted meanwhile since it would write over the screen.
+pub async fn run(repository: &dyn Repository) -> Result<()> {
+    let log_level = log::max_level();
+    log::set_max_level(log::LevelFilter::Off);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, repository).await;
+    ratatui::restore();
+    log::set_max_level(log_level);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_filter() {
+        let ids = [4, 7];
+        assert_eq!(App::next_filter(None, &ids), Some(4));
+        assert_eq!(App::next_filter(Some(4), &ids), Some(7));
+        assert_eq!(App::next_filter(Some(7), &ids), None);
+        assert_eq!(App::next_filter(None, &[]), None);
+    }
+}
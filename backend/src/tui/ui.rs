@@ -0,0 +1,157 @@
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table};
+use ratatui::Frame;
+
+use super::form::Field;
+use crate::utils::categories;
+use crate::utils::expenses::{category_name, username};
+use super::App;
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [top, expenses, help] = Layout::vertical([
+        Constraint::Length(app.users.len().max(app.categories.len()) as u16 + 3),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [balances, totals] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(top);
+
+    draw_balances(frame, app, balances);
+    draw_category_totals(frame, app, totals);
+    draw_expenses(frame, app, expenses);
+
+    let status = match &app.status {
+        Some(status) => status.clone(),
+        None => "a: add  u: filter user  c: filter category  x: clear filters  r: refresh  q: quit"
+            .to_string(),
+    };
+    frame.render_widget(Paragraph::new(status), help);
+
+    if app.form.is_some() {
+        draw_form(frame, app);
+    }
+}
+
+fn draw_balances(frame: &mut Frame, app: &App, area: Rect) {
+    let rows = app.users.iter().map(|user| {
+        let amount = app.balances.get(&user.id()).copied().unwrap_or(0.0);
+        let (text, color) = if amount >= 0.0 {
+            (format!("is owed {:.2}", amount), Color::Green)
+        } else {
+            (format!("owes {:.2}", -amount), Color::Red)
+        };
+        Row::new(vec![
+            Cell::from(user.username().to_string()),
+            Cell::from(text).style(Style::default().fg(color)),
+        ])
+    });
+    let table = Table::new(rows, [Constraint::Percentage(50), Constraint::Percentage(50)]).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.period_title("Balances")),
+    );
+    frame.render_widget(table, area);
+}
+
+fn draw_category_totals(frame: &mut Frame, app: &App, area: Rect) {
+    let tree = categories::category_tree(&app.categories);
+    let rows = categories::flatten_tree(&tree).into_iter().map(|(depth, category)| {
+        let total = app.category_totals.get(&category.id()).copied().unwrap_or(0.0);
+        Row::new(vec![
+            format!("{}{}", "  ".repeat(depth), category.name()),
+            format!("{:.2}", total),
+        ])
+    });
+    let table = Table::new(rows, [Constraint::Percentage(50), Constraint::Percentage(50)]).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.period_title("Spent per category")),
+    );
+    frame.render_widget(table, area);
+}
+
+fn draw_expenses(frame: &mut Frame, app: &App, area: Rect) {
+    let header = Row::new(vec!["Date", "Payer", "Category", "Amount", "Description"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = app.visible_expenses().map(|expense| {
+        Row::new(vec![
+            expense.purchased_at().format("%d/%m/%Y").to_string(),
+            username(&app.users, expense.user_id()),
+            category_name(&app.categories, expense.category_id()),
+            format!("{:.2}", expense.amount()),
+            expense.description().to_string(),
+        ])
+    });
+
+    let mut title = "Recent expenses".to_string();
+    if let Some(id) = app.user_filter {
+        title.push_str(&format!(" paid by {}", username(&app.users, id)));
+    }
+    if let Some(id) = app.category_filter {
+        title.push_str(&format!(" in {}", category_name(&app.categories, id)));
+    }
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(10),
+            Constraint::Length(14),
+            Constraint::Length(14),
+            Constraint::Length(10),
+            Constraint::Min(10),
+        ],
+    )
+    .header(header)
+    .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(table, area);
+}
+
+fn draw_form(frame: &mut Frame, app: &App) {
+    let Some(form) = &app.form else {
+        return;
+    };
+
+    let area = frame.area();
+    let width = area.width.min(70);
+    let height = (Field::ALL.len() as u16 + 4).min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let mut lines = vec![];
+    for (i, field) in Field::ALL.iter().enumerate() {
+        let style = if i == form.focus {
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let mut value = form.value(*field, &app.users, &app.categories);
+        if matches!(field, Field::Payer | Field::Category) {
+            value = format!("< {} >", value);
+        }
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<22}", field.label()), style),
+            Span::raw(value),
+        ]));
+    }
+    lines.push(Line::from(Span::styled(
+        form.error.clone().unwrap_or_default(),
+        Style::default().fg(Color::Red),
+    )));
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Add expense (tab: next field, </>: select, enter: save, esc: cancel)"),
+        ),
+        popup,
+    );
+}
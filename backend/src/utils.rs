@@ -1,16 +1,31 @@
-use crate::database;
+use crate::database::repository::Repository;
 
+pub mod archive;
 pub mod args;
+pub mod attachments;
+pub mod audit;
+pub mod backup;
+pub mod balance;
+pub mod categories;
 pub mod config;
 pub mod logger;
+pub mod migrate;
 pub mod import;
 pub mod export;
+pub mod events;
+pub mod expenses;
+pub mod statement;
+pub mod sync;
+pub mod webhooks;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct JsonFormat {
     users: Vec<datatypes::User>,
     categories: Vec<datatypes::Category>,
     expenses: Vec<datatypes::Expense>,
+    /// Metadata only, the contents go in a tar archive next to the json file.
+    #[serde(default)]
+    attachments: Vec<datatypes::Attachment>,
 }
 
 impl JsonFormat {
@@ -18,27 +33,41 @@ impl JsonFormat {
         users: Vec<datatypes::User>,
         categories: Vec<datatypes::Category>,
         expenses: Vec<datatypes::Expense>,
+        attachments: Vec<datatypes::Attachment>,
     ) -> Self {
         Self {
             users,
             categories,
             expenses,
+            attachments,
         }
     }
 }
 
-pub async fn startup(_config: &config::Config, db_pool: &sqlx::PgPool) -> anyhow::Result<()> {
-    database::initialize::initialize_db(&db_pool)
-        .await
-        .expect("Failed to initialize database.");
+/// Where `export json` puts the attachment contents for the export at `path`.
+pub fn attachments_archive_path(path: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(format!("{}.attachments.tar", path))
+}
+
+/// Asks a y/n question on stdin. Passing `yes` answers it up front for non-interactive use.
+pub fn confirm(yes: bool, question: &str) -> std::io::Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    println!("{} [y/n]", question);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim() == "y")
+}
 
-    // Insert default categories
-    //let categories = config.categories();
-    //for category in categories {
-        //database::category::insert_category(&db_pool, category.name.clone(), &category.description.clone())
-            //.await
-            //.expect("Failed to insert default category.");
-    //}
+pub async fn startup(config: &config::Config, repository: &dyn Repository) -> anyhow::Result<()> {
+    if config.sync_on_startup() {
+        let plan = sync::get_plan(repository, config).await?;
+        if !plan.is_empty() {
+            log::info!("Syncing users and categories from the config:\n{}", plan);
+            sync::apply(repository, &plan).await?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,98 @@
+//! Just enough of the ustar format to bundle attachments with an export: regular files with
+//! short names, no directories, links or extended headers. Any `tar` can read the result.
+
+use std::io::{Read, Write};
+
+const BLOCK: usize = 512;
+
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+fn header(name: &str, size: u64) -> anyhow::Result<[u8; BLOCK]> {
+    if name.len() > 100 {
+        return Err(anyhow::anyhow!("Archive entry name `{}` is too long.", name));
+    }
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], size);
+    octal(&mut header[136..148], 0);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with its own field filled with spaces.
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+    octal(&mut header[148..155], checksum as u64);
+    header[155] = b' ';
+    Ok(header)
+}
+
+fn padding(size: usize) -> usize {
+    (BLOCK - size % BLOCK) % BLOCK
+}
+
+/// Writes the entries as a tar archive.
+pub fn write_tar<W: Write>(mut writer: W, entries: &[(String, Vec<u8>)]) -> anyhow::Result<()> {
+    for (name, contents) in entries {
+        writer.write_all(&header(name, contents.len() as u64)?)?;
+        writer.write_all(contents)?;
+        writer.write_all(&vec![0u8; padding(contents.len())])?;
+    }
+    writer.write_all(&[0u8; BLOCK * 2])?;
+    Ok(())
+}
+
+/// Reads back the regular files of a tar archive, skipping any other kind of entry.
+pub fn read_tar<R: Read>(mut reader: R) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let mut entries = vec![];
+    let mut header = [0u8; BLOCK];
+    loop {
+        reader.read_exact(&mut header)?;
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+
+        let name_end = header[..100].iter().position(|b| *b == 0).unwrap_or(100);
+        let name = String::from_utf8(header[..name_end].to_vec())?;
+        let size = std::str::from_utf8(&header[124..136])?
+            .trim_matches(|c: char| c == '\0' || c == ' ');
+        let size = usize::from_str_radix(size, 8)
+            .map_err(|_| anyhow::anyhow!("Archive entry `{}` has an invalid size.", name))?;
+
+        let mut contents = vec![0u8; size + padding(size)];
+        reader.read_exact(&mut contents)?;
+        contents.truncate(size);
+        if matches!(header[156], b'0' | 0) {
+            entries.push((name, contents));
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let entries = vec![
+            ("empty".to_string(), vec![]),
+            ("a".repeat(64), vec![7u8; 513]),
+            ("exact".to_string(), vec![1u8; BLOCK]),
+        ];
+        let mut tar = vec![];
+        write_tar(&mut tar, &entries).unwrap();
+        assert_eq!(tar.len() % BLOCK, 0);
+        assert_eq!(&tar[257..262], b"ustar");
+        assert_eq!(read_tar(tar.as_slice()).unwrap(), entries);
+
+        assert!(write_tar(vec![], &[("a".repeat(101), vec![])]).is_err());
+    }
+}
@@ -1,17 +1,43 @@
-use crate::{database, utils::config, web};
+use crate::database::repository::{DynRepository, Repository};
+use crate::utils::audit::{Actor, AuditingRepository};
+use crate::utils::events::{EventBus, PublishingRepository};
+use crate::utils::webhooks::Dispatcher;
+use crate::{cli, database, tui, utils::config, web};
 use anyhow::Result;
 
 use clap::{Parser, Subcommand};
+use datatypes::{AuditFilter, AuditSource, DraftStatus, EventKind, Filter, OrderBy};
+use rocket::serde::json::serde_json;
 use log::info;
 
+use super::categories;
+use super::expenses::{self, OutputFormat};
+
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
-struct EntryPoint {
+pub struct EntryPoint {
+    #[arg(long, global = true, default_value = "Config.toml", help = "Path of the config file")]
+    config: std::path::PathBuf,
     #[command(subcommand)]
     command: Commands,
 }
 
+impl EntryPoint {
+    pub fn config(&self) -> &std::path::Path {
+        &self.config
+    }
+
+    /// Restores go into an empty database, which syncing the config on startup would fill.
+    pub fn runs_startup(&self) -> bool {
+        !matches!(self.command, Commands::Backup(Backup::Restore { .. }))
+    }
+}
+
+pub fn parse() -> EntryPoint {
+    EntryPoint::parse()
+}
+
 #[derive(Debug, Subcommand)]
 enum Commands {
     #[command(subcommand, about = "User commands", long_about = None)]
@@ -24,14 +50,30 @@ enum Commands {
     Import(Import),
     #[command(subcommand, about = "Export a dataset", long_about = None)]
     Export(Export),
+    #[command(subcommand, about = "Back up or restore the whole database", long_about = None)]
+    Backup(Backup),
     #[command(subcommand, about = "Set an expense reset point.", long_about = None)]
     Reset(Reset),
+    #[command(subcommand, about = "Review staged import drafts", long_about = None)]
+    Drafts(Drafts),
     // #[command(subcommand, about = "Program utilities", long_about = None)]
     // Utils(Utils),
-    // #[command(subcommand, about = "Expense commands", long_about = None)]
-    // Expenses(Expenses),
-    // #[command(subcommand, about = "CLI commands", long_about = None)]
-    // Cli(Cli),
+    #[command(subcommand, about = "Expense commands", long_about = None)]
+    Expense(Expense),
+    #[command(subcommand, about = "Tag commands", long_about = None)]
+    Tag(Tag),
+    #[command(subcommand, about = "Attachment commands", long_about = None)]
+    Attachment(Attachment),
+    #[command(subcommand, about = "Apply the users and categories from the config", long_about = None)]
+    Config(Config),
+    #[command(subcommand, about = "Webhook commands", long_about = None)]
+    Webhook(Webhook),
+    #[command(subcommand, about = "Show who changed what", long_about = None)]
+    Audit(Audit),
+    #[command(subcommand, about = "CLI commands", long_about = None)]
+    Cli(Cli),
+    #[command(subcommand, about = "Terminal dashboard commands", long_about = None)]
+    Tui(Tui),
 }
 
 
@@ -48,12 +90,25 @@ enum User {
     Delete {
         #[arg(short, long)]
         name: String,
+        #[arg(long, help = "Move everything referring to the user to this user before deleting it")]
+        reassign_to: Option<String>,
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+    #[command(about = "Merge a user into another one, moving everything over", long_about = None)]
+    Merge {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        into: String,
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        yes: bool,
     },
 }
 
 #[derive(Debug, Subcommand)]
 enum Category {
-    #[command(about = "List all categories", long_about = None)]
+    #[command(about = "List all categories as a tree", long_about = None)]
     List,
     #[command(about = "Create a category", long_about = None)]
     Create {
@@ -61,26 +116,464 @@ enum Category {
         name: String,
         #[arg(short, long)]
         description: String,
+        #[arg(long, help = "Create it as a sub-category of this category")]
+        parent: Option<String>,
     },
+    #[command(about = "Move a category below another one", long_about = None)]
+    Move {
+        #[arg(short, long)]
+        name: String,
+        #[arg(long, help = "New parent category, leave out to make it a top level category")]
+        parent: Option<String>,
+    },
+    #[command(about = "Show the amount spent per category since the last reset", long_about = None)]
+    Totals,
     #[command(about = "Delete a category", long_about = None)]
     Delete {
         #[arg(short, long)]
         name: String,
+        #[arg(long, help = "Move everything referring to the category to this category before deleting it")]
+        reassign_to: Option<String>,
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+    #[command(about = "Merge a category into another one, moving everything over", long_about = None)]
+    Merge {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        into: String,
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Expense {
+    #[command(about = "Create an expense", long_about = None)]
+    Add {
+        #[arg(long, help = "Username of the user who paid")]
+        payer: String,
+        #[arg(short, long)]
+        amount: f64,
+        #[arg(short, long)]
+        category: String,
+        #[arg(long, help = "Purchase date as dd/mm/yyyy, defaults to today")]
+        date: Option<String>,
+        #[arg(long)]
+        desc: String,
+        #[arg(long, value_delimiter = ',', help = "Users sharing the expense with the payer, `name` for an equal share or `name=amount`")]
+        split: Vec<String>,
+        #[arg(long, value_delimiter = ',')]
+        tags: Vec<String>,
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    #[command(about = "List expenses, optionally filtered", long_about = None)]
+    List {
+        #[arg(long, value_delimiter = ',', help = "Only expenses paid by these users")]
+        users: Vec<String>,
+        #[arg(long, value_delimiter = ',', help = "Only expenses in these categories")]
+        categories: Vec<String>,
+        #[arg(long)]
+        min_amount: Option<f64>,
+        #[arg(long)]
+        max_amount: Option<f64>,
+        #[arg(long, help = "Earliest purchase date as dd/mm/yyyy")]
+        from: Option<String>,
+        #[arg(long, help = "Latest purchase date as dd/mm/yyyy")]
+        to: Option<String>,
+        #[arg(long, value_enum, default_value_t = OrderByArg::Created)]
+        order_by: OrderByArg,
+        #[arg(long, help = "Sort ascending instead of descending")]
+        asc: bool,
+        #[arg(long, value_delimiter = ',', help = "Only expenses with any of these tags")]
+        tags_any: Vec<String>,
+        #[arg(long, value_delimiter = ',', help = "Only expenses with all of these tags")]
+        tags_all: Vec<String>,
+        #[arg(long, value_delimiter = ',', help = "Only expenses with none of these tags")]
+        tags_none: Vec<String>,
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    #[command(about = "Show a single expense", long_about = None)]
+    Show {
+        id: i32,
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    #[command(about = "Add or remove tags of an expense", long_about = None)]
+    Tag {
+        id: i32,
+        #[arg(long, value_delimiter = ',')]
+        add: Vec<String>,
+        #[arg(long, value_delimiter = ',')]
+        remove: Vec<String>,
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Tag {
+    #[command(about = "List all tags", long_about = None)]
+    List,
+    #[command(about = "Rename a tag", long_about = None)]
+    Rename {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+    },
+    #[command(about = "Merge a tag into another one, retagging its expenses", long_about = None)]
+    Merge {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        into: String,
+    },
+    #[command(about = "Show the amount spent per tag since the last reset", long_about = None)]
+    Totals,
+}
+
+#[derive(Debug, Subcommand)]
+enum Attachment {
+    #[command(about = "Attach one or more files to an expense", long_about = None)]
+    Add {
+        expense_id: i32,
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+    },
+    #[command(about = "List the attachments of an expense", long_about = None)]
+    List { expense_id: i32 },
+    #[command(about = "Save an attachment to a file", long_about = None)]
+    Get {
+        id: i32,
+        #[arg(short, long, help = "Defaults to the original file name")]
+        out: Option<std::path::PathBuf>,
+        #[arg(short, long, help = "Overwrite an existing file without asking")]
+        yes: bool,
+    },
+    #[command(about = "Delete an attachment", long_about = None)]
+    Delete {
+        id: i32,
+        #[arg(short, long, help = "Do not ask for confirmation")]
+        yes: bool,
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum Config {
+    #[command(about = "Create the configured users and categories that are missing and update descriptions", long_about = None)]
+    Sync {
+        #[arg(long, help = "Also delete users and categories that are not in the config and not in use")]
+        prune: bool,
+        #[arg(long, help = "Only show what would change")]
+        dry_run: bool,
+        #[arg(short, long, help = "Do not ask for confirmation")]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Webhook {
+    #[command(about = "List all webhooks", long_about = None)]
+    List,
+    #[command(about = "Send events to a URL as signed json POSTs", long_about = "Send events to a URL as signed json POSTs. Changes are delivered by the process that makes them: the web server for its own, and every other command for its own before it exits, so the receiver hears about changes from both.")]
+    Add {
+        #[arg(short, long)]
+        url: String,
+        #[arg(short, long, help = "Signs every delivery, the receiver checks the `X-Expenses-Signature` header with it")]
+        secret: String,
+        #[arg(short, long, value_delimiter = ',', help = "Events such as `expense.created` or `reset`, or `expense` for every expense event. Every event when left out")]
+        events: Vec<String>,
+    },
+    #[command(about = "Delete a webhook and its delivery log", long_about = None)]
+    Remove {
+        id: i32,
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        yes: bool,
+    },
+    #[command(about = "Show the latest delivery attempts of a webhook", long_about = None)]
+    Deliveries {
+        id: i32,
+        #[arg(short, long, default_value_t = 20)]
+        limit: i64,
+    },
+    #[command(about = "Send a `ping` event to check the receiver, retrying like any other event", long_about = None)]
+    Ping { id: i32 },
+}
+
+#[derive(Debug, Subcommand)]
+enum Audit {
+    #[command(about = "List the latest changes, newest first", long_about = None)]
+    List {
+        #[arg(long, value_delimiter = ',', help = "Only these actions, e.g. `category.deleted`, or `category` for every category action")]
+        action: Vec<String>,
+        #[arg(long, value_delimiter = ',', help = "Only changes to the users, categories or expenses with these ids")]
+        entity_id: Vec<i32>,
+        #[arg(long, value_delimiter = ',', help = "Only changes made by these actors")]
+        actor: Vec<String>,
+        #[arg(long, value_enum, value_delimiter = ',')]
+        source: Vec<AuditSourceArg>,
+        #[arg(long, help = "Only changes from this day on, as dd/mm/yyyy")]
+        since: Option<String>,
+        #[arg(short, long, default_value_t = 50)]
+        limit: i64,
+        #[arg(long, help = "Print the entries as json, including the entity before and after the change")]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum AuditSourceArg {
+    Web,
+    Cli,
+    Import,
+}
+
+impl From<AuditSourceArg> for AuditSource {
+    fn from(source: AuditSourceArg) -> Self {
+        match source {
+            AuditSourceArg::Web => AuditSource::Web,
+            AuditSourceArg::Cli => AuditSource::Cli,
+            AuditSourceArg::Import => AuditSource::Import,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum OrderByArg {
+    Amount,
+    Date,
+    Created,
+}
+
+impl From<OrderByArg> for OrderBy {
+    fn from(order_by: OrderByArg) -> Self {
+        match order_by {
+            OrderByArg::Amount => OrderBy::Amount,
+            OrderByArg::Date => OrderBy::Date,
+            OrderByArg::Created => OrderBy::Created,
+        }
+    }
+}
+
+fn parse_date(date: &str) -> Result<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date, "%d/%m/%Y")
+        .map_err(|_| anyhow::anyhow!("Invalid date `{}`, expected dd/mm/yyyy.", date))
+}
+
+fn find_user_id(users: &[datatypes::User], name: &str) -> Result<i32> {
+    users
+        .iter()
+        .find(|u| u.username() == name)
+        .map(|u| u.id())
+        .ok_or_else(|| anyhow::anyhow!("User `{}` does not exist.", name))
+}
+
+fn find_category_id(categories: &[datatypes::Category], name: &str) -> Result<i32> {
+    categories
+        .iter()
+        .find(|c| c.name() == name)
+        .map(|c| c.id())
+        .ok_or_else(|| anyhow::anyhow!("Category `{}` does not exist.", name))
+}
+
+async fn merge_users(
+    repository: &dyn Repository,
+    (from, from_id): (&str, i32),
+    (into, into_id): (&str, i32),
+    yes: bool,
+) -> Result<()> {
+    if from_id == into_id {
+        return Err(anyhow::anyhow!("Can't merge user `{}` into itself.", from));
+    }
+    let references = repository.get_user_references(from_id).await?;
+    let question = format!(
+        "{} of user `{}` will be moved to `{}` and `{}` will be deleted.",
+        references, from, into, from
+    );
+    if !super::confirm(yes, &question)? {
+        println!("Aborting.");
+        return Ok(());
+    }
+    repository.merge_users(from_id, into_id).await?;
+    println!("Merged user `{}` into `{}`.", from, into);
+    Ok(())
+}
+
+async fn merge_categories(
+    repository: &dyn Repository,
+    (from, from_id): (&str, i32),
+    (into, into_id): (&str, i32),
+    yes: bool,
+) -> Result<()> {
+    if from_id == into_id {
+        return Err(anyhow::anyhow!("Can't merge category `{}` into itself.", from));
+    }
+    let references = repository.get_category_references(from_id).await?;
+    let question = format!(
+        "{} of category `{}` will be moved to `{}` and `{}` will be deleted.",
+        references, from, into, from
+    );
+    if !super::confirm(yes, &question)? {
+        println!("Aborting.");
+        return Ok(());
+    }
+    repository.merge_categories(from_id, into_id).await?;
+    println!("Merged category `{}` into `{}`.", from, into);
+    Ok(())
+}
+
 #[derive(Debug, Subcommand)]
 enum Web {
     #[command(about = "Start the web server", long_about = None)]
     Start,
 }
 
+#[derive(Debug, Subcommand)]
+enum Cli {
+    #[command(about = "Start the command line interface", long_about = None)]
+    Start,
+}
+
+#[derive(Debug, Subcommand)]
+enum Tui {
+    #[command(about = "Start the terminal dashboard", long_about = None)]
+    Start,
+}
+
 #[derive(Debug, Subcommand)]
 enum Import {
     #[command(about = "Import a csv dataset", long_about = None)]
     OldCsvFormat {
         #[arg(short, long)]
         path: String,
+        #[arg(
+            long,
+            conflicts_with_all = ["report", "dry_run", "yes"],
+            help = "Stage the lines as drafts for review instead of creating expenses"
+        )]
+        stage: bool,
+        #[arg(long, help = "Write a json report with the outcome of every line to this path")]
+        report: Option<String>,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+    #[command(about = "Import a json dataset created with `export json`.", long_about = None)]
+    Json {
+        #[arg(short, long)]
+        path: String,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+    #[command(about = "Copy the users, categories and expenses of another database, Postgres or SQLite.", long_about = None)]
+    Database {
+        #[arg(short, long, help = "`database_url` of the database to copy from")]
+        url: String,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+    #[command(about = "Stage the debits of an OFX bank statement as draft expenses to review.", long_about = None)]
+    Ofx {
+        #[arg(short, long)]
+        path: String,
+        #[command(flatten)]
+        statement: StatementArgs,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+    #[command(about = "Stage the debits of a QIF bank statement as draft expenses to review.", long_about = None)]
+    Qif {
+        #[arg(short, long)]
+        path: String,
+        #[arg(long, default_value = "%d/%m/%Y", help = "chrono format of the `D` lines, `'` is read as `/`")]
+        date_format: String,
+        #[command(flatten)]
+        statement: StatementArgs,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct ImportArgs {
+    #[arg(short, long, help = "Don't ask for confirmation")]
+    yes: bool,
+    #[arg(long, help = "Show what would be imported without writing to the database")]
+    dry_run: bool,
+}
+
+impl ImportArgs {
+    fn options(&self) -> super::import::ImportOptions {
+        super::import::ImportOptions {
+            yes: self.yes,
+            dry_run: self.dry_run,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct StatementArgs {
+    #[arg(long, help = "Username of the user who paid")]
+    payer: String,
+    #[arg(short, long, help = "Category name given to every imported expense")]
+    category: String,
+    #[arg(long, value_delimiter = ',', help = "Usernames splitting each expense equally with the payer")]
+    split: Vec<String>,
+}
+
+impl StatementArgs {
+    fn defaults(&self) -> super::import::StatementDefaults {
+        super::import::StatementDefaults {
+            payer: self.payer.clone(),
+            category: self.category.clone(),
+            split: self.split.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Drafts {
+    #[command(about = "List all import batches", long_about = None)]
+    Batches,
+    #[command(about = "List the drafts of a batch", long_about = None)]
+    List {
+        #[arg(short, long)]
+        batch: i32,
+    },
+    #[command(about = "Edit a draft, it has to be approved again afterwards", long_about = None)]
+    Edit {
+        #[arg(short, long)]
+        id: i32,
+        #[arg(long)]
+        payer: Option<String>,
+        #[arg(short, long)]
+        category: Option<String>,
+        #[arg(short, long)]
+        amount: Option<f64>,
+        #[arg(long, help = "Purchase date as dd/mm/yyyy")]
+        date: Option<String>,
+        #[arg(short, long)]
+        description: Option<String>,
+        #[arg(long, value_delimiter = ',', help = "Usernames splitting the expense equally with the payer")]
+        split: Option<Vec<String>>,
+    },
+    #[command(about = "Approve a draft without errors", long_about = None)]
+    Approve {
+        #[arg(short, long)]
+        id: i32,
+    },
+    #[command(about = "Reject a draft", long_about = None)]
+    Reject {
+        #[arg(short, long)]
+        id: i32,
+    },
+    #[command(about = "Create expenses for all approved drafts of a batch", long_about = None)]
+    Post {
+        #[arg(short, long)]
+        batch: i32,
     },
 }
 
@@ -90,6 +583,43 @@ enum Export {
     Json {
         #[arg(short, long)]
         path: String,
+        #[arg(short, long, help = "Overwrite an existing file without asking")]
+        yes: bool,
+    },
+    #[command(about = "Copy the users, categories and expenses to another database, Postgres or SQLite.", long_about = None)]
+    Database {
+        #[arg(short, long, help = "`database_url` of the database to copy to")]
+        url: String,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Backup {
+    #[command(about = "Write every table and the attachments to a checksummed archive", long_about = None)]
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["path", "dir"])))]
+    Create {
+        #[arg(short, long)]
+        path: Option<std::path::PathBuf>,
+        #[arg(short, long, help = "Write the backup into this directory, named after the time it was taken")]
+        dir: Option<std::path::PathBuf>,
+        #[arg(long, conflicts_with = "path", help = "Delete the backups in the directory but the newest of the last N days")]
+        keep_daily: Option<usize>,
+        #[arg(long, conflicts_with = "path", help = "Delete the backups in the directory but the newest of the last N weeks")]
+        keep_weekly: Option<usize>,
+        #[arg(short, long, help = "Overwrite an existing file without asking")]
+        yes: bool,
+    },
+    #[command(about = "Check a backup against its checksums without restoring it", long_about = None)]
+    Verify {
+        #[arg(short, long)]
+        path: std::path::PathBuf,
+    },
+    #[command(about = "Restore a backup into an empty database, keeping its ids and timestamps", long_about = None)]
+    Restore {
+        #[arg(short, long)]
+        path: std::path::PathBuf,
     },
 }
 
@@ -99,15 +629,93 @@ enum Reset {
     Expenses,
 }
 
-pub async fn run(config: config::Config, db_pool: &sqlx::PgPool) -> Result<()> {
-    let entry_point = EntryPoint::parse();
+/// Drafts, tags, attachments and statement imports are only stored in Postgres.
+fn postgres(db_pool: Option<&sqlx::PgPool>) -> Result<&sqlx::PgPool> {
+    db_pool.ok_or_else(|| anyhow::anyhow!("This command needs a Postgres `database_url`."))
+}
+
+async fn migrate(
+    from: &dyn Repository,
+    to: &dyn Repository,
+    options: &super::import::ImportOptions,
+) -> Result<()> {
+    if let Some(migration) = super::migrate::migrate(from, to, options).await? {
+        if options.dry_run {
+            println!("=== Dry run, nothing was written. Would have copied:");
+        } else {
+            println!("=== Copied:");
+        }
+        print!("{}", migration);
+    }
+    Ok(())
+}
+
+/// Publishes the draft as it is now, after it was edited or its status changed.
+async fn publish_draft(db_pool: &sqlx::PgPool, events: &EventBus, id: i32) -> Result<()> {
+    if let Some(draft) = database::draft::get_draft_expense(db_pool, id).await? {
+        events.publish_entity(EventKind::DraftUpdated, id, &draft);
+    }
+    Ok(())
+}
+
+/// Connects to the other side of `import database` and `export database`.
+async fn connect_other(config: &config::Config, database_url: &str) -> Result<DynRepository> {
+    let db_config = database::DbConfig::new(database_url.to_string(), config.db_config().max_connections());
+    let (repository, _) = database::connect(&db_config).await?;
+    Ok(repository)
+}
+
+/// Runs the command. Its changes are recorded in the audit log as made by the user running it,
+/// and delivered to the webhooks before this returns, retries included, the same as changes made
+/// through the web server. The web server does both itself, per request.
+pub async fn run(
+    entry_point: EntryPoint,
+    config: config::Config,
+    shared_repository: DynRepository,
+    db_pool: Option<sqlx::PgPool>,
+) -> Result<()> {
+    let events = EventBus::new();
+    let (shared_repository, dispatched) = match (&entry_point.command, &db_pool) {
+        (Commands::Web(_), _) | (_, None) => (shared_repository, None),
+        (_, Some(db_pool)) => {
+            let dispatched = Dispatcher::new(db_pool.clone(), config.webhooks().clone()).spawn(&events);
+            (PublishingRepository::wrap(shared_repository, events.clone()), Some(dispatched))
+        }
+    };
+    let actor = Actor::cli_user(match &entry_point.command {
+        Commands::Import(_) | Commands::Drafts(Drafts::Post { .. }) => AuditSource::Import,
+        _ => AuditSource::Cli,
+    });
+    let shared_repository = match &entry_point.command {
+        Commands::Web(_) => shared_repository,
+        _ => AuditingRepository::wrap(shared_repository, actor.clone()),
+    };
+    // Dropping the repository and `events` closes the bus, after which the dispatcher finishes.
+    let result = run_command(entry_point, config, shared_repository, events, db_pool, &actor).await;
+    if let Some(dispatched) = dispatched {
+        dispatched.await?;
+    }
+    result
+}
+
+async fn run_command(
+    entry_point: EntryPoint,
+    config: config::Config,
+    shared_repository: DynRepository,
+    // For the writes that go past the repository, which publish themselves like the web routes do.
+    events: EventBus,
+    db_pool: Option<sqlx::PgPool>,
+    actor: &Actor,
+) -> Result<()> {
+    let repository = shared_repository.as_ref();
+    let db_pool = db_pool.as_ref();
 
     match &entry_point.command {
 
         Commands::User(user) => match user {
             User::List => {
                 info!("Listing users");
-                let users = database::user::get_users(&db_pool).await?;
+                let users = repository.get_users().await?;
                 println!("=== Users:");
                 for u in &users {
                     println!("  - {}", u);
@@ -115,93 +723,586 @@ pub async fn run(config: config::Config, db_pool: &sqlx::PgPool) -> Result<()> {
             }
             User::Create { name } => {
                 info!("Creating user {}", name);
-                database::user::insert_user(db_pool, name.to_string()).await?;
+                repository.insert_user(name.to_string()).await?;
             }
-            User::Delete { name } => {
+            User::Delete { name, reassign_to, yes } => {
                 info!("Deleting user {}", name);
-                let users = database::user::get_users(&db_pool).await?;
-                let count = users.iter().filter(|&n| n.username() == name).count();
-                let mut input = String::new();
-                if count == 0 {
-                    println!("User `{}` does not exist.", name);
+                let users = repository.get_users().await?;
+                let id = find_user_id(&users, name)?;
+                if let Some(target) = reassign_to {
+                    let into = find_user_id(&users, target)?;
+                    merge_users(repository, (name, id), (target, into), *yes).await?;
                     return Ok(());
-                } else if count == 1 {
-                    println!("User `{}` will be deleted. [y/n]", name);
-                    std::io::stdin().read_line(&mut input)?;
-                    if input.trim() != "y" {
-                        println!("Aborting.");
-                        return Ok(());
-                    }
-                    let id = users.iter().find(|&n| n.username() == name).unwrap().id();
-                    database::user::delete_user(db_pool, id).await?;
-                } else {
-                    println!("Multiple users with the name `{}` exist.", name);
-                    println!("Please resolve in database manually.");
-                    return Err(anyhow::anyhow!("Multiple users with the name `{}` exist.", name));
                 }
+
+                let references = repository.get_user_references(id).await?;
+                if !references.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "User `{}` is still referenced by {}. Use `--reassign-to` to move them to another user first.",
+                        name,
+                        references
+                    ));
+                }
+                if !super::confirm(*yes, &format!("User `{}` will be deleted.", name))? {
+                    println!("Aborting.");
+                    return Ok(());
+                }
+                repository.delete_user(id).await?;
+            }
+            User::Merge { from, into, yes } => {
+                info!("Merging user {} into {}", from, into);
+                let users = repository.get_users().await?;
+                let from_id = find_user_id(&users, from)?;
+                let into_id = find_user_id(&users, into)?;
+                merge_users(repository, (from, from_id), (into, into_id), *yes).await?;
             }
         },
         Commands::Category(category) => match category {
             Category::List => {
                 info!("Listing categories");
-                let categories = database::category::get_categories(&db_pool).await?;
+                let categories = repository.get_categories().await?;
+                let tree = categories::category_tree(&categories);
                 println!("=== Categories:");
-                for c in &categories {
-                    println!("  - {}", c);
+                for (depth, c) in categories::flatten_tree(&tree) {
+                    println!("{}  - {}", "    ".repeat(depth), c);
                 }
             }
-            Category::Create { name, description } => {
+            Category::Create { name, description, parent } => {
                 info!("Creating category {}", name);
-                database::category::insert_category(db_pool, name.to_string(), description).await?;
+                let parent_id = match parent {
+                    Some(parent) => {
+                        let categories = repository.get_categories().await?;
+                        Some(find_category_id(&categories, parent)?)
+                    }
+                    None => None,
+                };
+                repository.insert_category(name.to_string(), description, parent_id).await?;
+            }
+            Category::Move { name, parent } => {
+                info!("Moving category {}", name);
+                let categories = repository.get_categories().await?;
+                let id = find_category_id(&categories, name)?;
+                let parent_id = match parent {
+                    Some(parent) => Some(find_category_id(&categories, parent)?),
+                    None => None,
+                };
+                if !repository.set_category_parent(id, parent_id).await? {
+                    return Err(anyhow::anyhow!(
+                        "Category `{}` can't be moved below itself or one of its sub-categories.",
+                        name
+                    ));
+                }
             }
-            Category::Delete { name } => {
+            Category::Totals => {
+                info!("Showing category totals");
+                let categories = repository.get_categories().await?;
+                let expenses = super::balance::expenses_since_last_reset(repository).await?;
+                let totals = super::balance::category_totals(&expenses);
+                let rolled = categories::rollup_totals(&categories, &totals);
+                let tree = categories::category_tree(&categories);
+                println!("=== Spent per category since the last reset:");
+                for (depth, c) in categories::flatten_tree(&tree) {
+                    let own = totals.get(&c.id()).copied().unwrap_or(0.0);
+                    let total = rolled.get(&c.id()).copied().unwrap_or(0.0);
+                    if own == total {
+                        println!("{}  - {}: {:.2}", "    ".repeat(depth), c.name(), total);
+                    } else {
+                        println!("{}  - {}: {:.2} ({:.2} directly)", "    ".repeat(depth), c.name(), total, own);
+                    }
+                }
+            }
+            Category::Delete { name, reassign_to, yes } => {
                 info!("Deleting category {}", name);
-                let categories = database::category::get_categories(&db_pool).await?;
-                let count = categories.iter().filter(|&n| n.name() == name).count();
-                let mut input = String::new();
-                if count == 0 {
-                    println!("Category `{}` does not exist.", name);
+                let categories = repository.get_categories().await?;
+                let id = find_category_id(&categories, name)?;
+                if let Some(target) = reassign_to {
+                    let into = find_category_id(&categories, target)?;
+                    merge_categories(repository, (name, id), (target, into), *yes).await?;
+                    return Ok(());
+                }
+
+                let references = repository.get_category_references(id).await?;
+                if !references.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Category `{}` is still referenced by {}. Use `--reassign-to` to move them to another category first.",
+                        name,
+                        references
+                    ));
+                }
+                if !super::confirm(*yes, &format!("Category `{}` will be deleted.", name))? {
+                    println!("Aborting.");
+                    return Ok(());
+                }
+                repository.delete_category(id).await?;
+            }
+            Category::Merge { from, into, yes } => {
+                info!("Merging category {} into {}", from, into);
+                let categories = repository.get_categories().await?;
+                let from_id = find_category_id(&categories, from)?;
+                let into_id = find_category_id(&categories, into)?;
+                merge_categories(repository, (from, from_id), (into, into_id), *yes).await?;
+            }
+        },
+
+        Commands::Expense(expense) => {
+            let users = repository.get_users().await?;
+            let categories = repository.get_categories().await?;
+            match expense {
+                Expense::Add { payer, amount, category, date, desc, split, tags, format } => {
+                    info!("Creating expense");
+                    let payer_id = find_user_id(&users, payer)?;
+                    let mut shares = vec![];
+                    for entry in split {
+                        let (name, share) = expenses::parse_split_entry(entry)?;
+                        shares.push((find_user_id(&users, &name)?, share));
+                    }
+                    let purchased_at = match date {
+                        Some(date) => parse_date(date)?,
+                        None => chrono::Local::now().date_naive(),
+                    };
+                    let mut expense = datatypes::Expense::new(
+                        payer_id,
+                        find_category_id(&categories, category)?,
+                        *amount,
+                        desc.to_string(),
+                        purchased_at,
+                        expenses::split_amount(*amount, payer_id, &shares)?,
+                    );
+                    expense.set_tags(tags.clone());
+                    let expense = repository.insert_expense(expense).await?;
+                    println!("{}", expenses::format_expenses(&[expense], &users, &categories, *format)?);
+                }
+                Expense::List { users: user_names, categories: category_names, min_amount, max_amount, from, to, order_by, asc, tags_any, tags_all, tags_none, format } => {
+                    info!("Listing expenses");
+                    let user_ids = if user_names.is_empty() {
+                        users.iter().map(|u| u.id()).collect()
+                    } else {
+                        user_names.iter().map(|n| find_user_id(&users, n)).collect::<Result<Vec<i32>>>()?
+                    };
+                    let category_ids = if category_names.is_empty() {
+                        categories.iter().map(|c| c.id()).collect()
+                    } else {
+                        category_names.iter().map(|n| find_category_id(&categories, n)).collect::<Result<Vec<i32>>>()?
+                    };
+                    let filter = Filter {
+                        user_ids,
+                        category_ids,
+                        min_amount: min_amount.unwrap_or(f64::MIN),
+                        max_amount: max_amount.unwrap_or(f64::MAX),
+                        min_date: match from {
+                            Some(from) => parse_date(from)?,
+                            None => chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap(),
+                        },
+                        max_date: match to {
+                            Some(to) => parse_date(to)?,
+                            None => chrono::NaiveDate::from_ymd_opt(9999, 12, 31).unwrap(),
+                        },
+                        order_by: (*order_by).into(),
+                        order_asc: *asc,
+                        tags_any: tags_any.clone(),
+                        tags_all: tags_all.clone(),
+                        tags_none: tags_none.clone(),
+                    };
+                    let found = repository.get_expenses(Some(filter)).await?;
+                    println!("{}", expenses::format_expenses(&found, &users, &categories, *format)?);
+                }
+                Expense::Show { id, format } => {
+                    info!("Showing expense {}", id);
+                    match repository.get_expense(*id).await? {
+                        Some(expense) => {
+                            println!("{}", expenses::format_expenses(&[expense], &users, &categories, *format)?);
+                        }
+                        None => return Err(anyhow::anyhow!("Expense `{}` does not exist.", id)),
+                    }
+                }
+                Expense::Tag { id, add, remove, format } => {
+                    info!("Tagging expense {}", id);
+                    let expense = repository.get_expense(*id)
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("Expense `{}` does not exist.", id))?;
+                    let mut tags = expense.tags().clone();
+                    tags.retain(|t| !remove.iter().any(|r| r.trim() == t));
+                    tags.extend(add.iter().cloned());
+                    database::tag::set_expense_tags(postgres(db_pool)?, actor, *id, &tags).await?;
+                    if let Some(tagged) = repository.get_expense(*id).await? {
+                        events.publish_entity(EventKind::ExpenseUpdated, *id, &tagged);
+                        println!("{}", expenses::format_expenses(&[tagged], &users, &categories, *format)?);
+                    }
+                }
+            }
+        }
+        Commands::Tag(tag) => match tag {
+            Tag::List => {
+                info!("Listing tags");
+                let tags = database::tag::get_tags(postgres(db_pool)?).await?;
+                println!("=== Tags:");
+                for t in &tags {
+                    println!("  - {}", t);
+                }
+            }
+            Tag::Rename { from, to } => {
+                info!("Renaming tag {} to {}", from, to);
+                if to.trim().is_empty() {
+                    return Err(anyhow::anyhow!("The new tag name must not be empty."));
+                }
+                if !database::tag::rename_tag(postgres(db_pool)?, actor, from, to).await? {
+                    return Err(anyhow::anyhow!(
+                        "Tag `{}` does not exist or `{}` is already taken. Use `tag merge` to combine two tags.",
+                        from,
+                        to
+                    ));
+                }
+            }
+            Tag::Merge { from, into } => {
+                info!("Merging tag {} into {}", from, into);
+                if !database::tag::merge_tags(postgres(db_pool)?, actor, from, into).await? {
+                    return Err(anyhow::anyhow!("Tags `{}` and `{}` have to be two existing tags.", from, into));
+                }
+                println!("Merged tag `{}` into `{}`.", from, into);
+            }
+            Tag::Totals => {
+                info!("Showing tag totals");
+                let expenses = super::balance::expenses_since_last_reset(repository).await?;
+                let mut totals = super::balance::tag_totals(&expenses).into_iter().collect::<Vec<(String, f64)>>();
+                totals.sort_by(|a, b| a.0.cmp(&b.0));
+                println!("=== Spent per tag since the last reset:");
+                for (tag, total) in totals {
+                    println!("  - {}: {:.2}", tag, total);
+                }
+            }
+        },
+        Commands::Attachment(attachment) => match attachment {
+            Attachment::Add { expense_id, files } => {
+                info!("Attaching {} files to expense {}", files.len(), expense_id);
+                if repository.get_expense(*expense_id).await?.is_none() {
+                    return Err(anyhow::anyhow!("Expense `{}` does not exist.", expense_id));
+                }
+                let mut new_files = vec![];
+                for file in files {
+                    let filename = file
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .ok_or_else(|| anyhow::anyhow!("`{}` is not a file.", file.display()))?;
+                    let content_type = super::attachments::content_type_for(&filename).to_string();
+                    let contents = std::fs::read(file)?;
+                    new_files.push(super::attachments::NewFile { filename, content_type, contents });
+                }
+                let added = super::attachments::add_attachments(
+                    postgres(db_pool)?,
+                    actor,
+                    config.attachments_dir(),
+                    *expense_id,
+                    &new_files,
+                )
+                .await?;
+                for attachment in &added {
+                    events.publish_entity(EventKind::AttachmentCreated, attachment.id(), attachment);
+                    println!("  - {}", attachment);
+                }
+            }
+            Attachment::List { expense_id } => {
+                info!("Listing attachments of expense {}", expense_id);
+                println!("=== Attachments:");
+                for a in database::attachment::get_attachments(postgres(db_pool)?, *expense_id).await? {
+                    println!("  - {}", a);
+                }
+            }
+            Attachment::Get { id, out, yes } => {
+                info!("Saving attachment {}", id);
+                let attachment = database::attachment::get_attachment(postgres(db_pool)?, *id)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Attachment `{}` does not exist.", id))?;
+                let out = out.clone().unwrap_or_else(|| attachment.filename().into());
+                if out.exists() && !super::confirm(*yes, "File already exists. Overwrite?")? {
+                    return Ok(());
+                }
+                let contents = super::attachments::read_blob(config.attachments_dir(), attachment.sha256())?;
+                std::fs::write(&out, contents)?;
+                println!("Saved `{}` to `{}`.", attachment.filename(), out.display());
+            }
+            Attachment::Delete { id, yes } => {
+                info!("Deleting attachment {}", id);
+                if !super::confirm(*yes, &format!("Delete attachment `{}`?", id))? {
+                    return Ok(());
+                }
+                match super::attachments::remove_attachment(postgres(db_pool)?, actor, config.attachments_dir(), *id).await? {
+                    Some(attachment) => {
+                        events.deleted(EventKind::AttachmentDeleted, *id);
+                        println!("Deleted `{}`.", attachment.filename());
+                    }
+                    None => return Err(anyhow::anyhow!("Attachment `{}` does not exist.", id)),
+                }
+            }
+        },
+        Commands::Config(command) => match command {
+            Config::Sync { prune, dry_run, yes } => {
+                info!("Syncing users and categories from the config");
+                let plan = super::sync::get_plan(repository, &config).await?;
+                print!("{}", plan);
+                let prunable = !plan.unmanaged_users().is_empty() || !plan.unmanaged_categories().is_empty();
+                if *dry_run || (plan.is_empty() && !(*prune && prunable)) {
+                    return Ok(());
+                }
+                if !super::confirm(*yes, "Apply the above changes?")? {
+                    println!("Aborting.");
                     return Ok(());
-                } else if count == 1 {
-                    println!("Category `{}` will be deleted. [y/n]", name);
-                    std::io::stdin().read_line(&mut input)?;
-                    if input.trim() != "y" {
-                        println!("Aborting.");
-                        return Ok(());
+                }
+                super::sync::apply(repository, &plan).await?;
+                if *prune {
+                    for (name, references) in super::sync::prune(repository, &plan).await? {
+                        println!("Kept {}, still used by {}.", name, references);
                     }
-                    let id = categories.iter().find(|&n| n.name() == name).unwrap().id();
-                    database::category::delete_category(db_pool, id).await?;
+                } else if prunable {
+                    println!("Use `--prune` to delete the entries that are not in the config.");
+                }
+            }
+        },
+        Commands::Webhook(webhook) => match webhook {
+            Webhook::List => {
+                info!("Listing webhooks");
+                println!("=== Webhooks:");
+                for w in database::webhook::get_webhooks(postgres(db_pool)?).await? {
+                    println!("  - {}", w);
+                }
+            }
+            Webhook::Add { url, secret, events: subscribed } => {
+                info!("Adding webhook for {}", url);
+                super::webhooks::check_subscription(url, secret, subscribed)?;
+                let webhook = datatypes::Webhook::new(url.to_string(), secret.to_string(), subscribed.clone());
+                let webhook = database::webhook::insert_webhook(postgres(db_pool)?, actor, &webhook).await?;
+                events.publish_entity(EventKind::WebhookCreated, webhook.id(), &webhook);
+                println!("Added webhook {}.", webhook);
+            }
+            Webhook::Remove { id, yes } => {
+                info!("Removing webhook {}", id);
+                if !super::confirm(*yes, &format!("Webhook `{}` and its delivery log will be deleted.", id))? {
+                    println!("Aborting.");
+                    return Ok(());
+                }
+                if !database::webhook::delete_webhook(postgres(db_pool)?, actor, *id).await? {
+                    return Err(anyhow::anyhow!("Webhook `{}` does not exist.", id));
+                }
+                events.deleted(EventKind::WebhookDeleted, *id);
+            }
+            Webhook::Deliveries { id, limit } => {
+                info!("Listing deliveries of webhook {}", id);
+                if database::webhook::get_webhook(postgres(db_pool)?, *id).await?.is_none() {
+                    return Err(anyhow::anyhow!("Webhook `{}` does not exist.", id));
+                }
+                println!("=== Deliveries, newest first:");
+                for d in database::webhook::get_deliveries(postgres(db_pool)?, *id, *limit).await? {
+                    println!("  - {}", d);
+                }
+            }
+            Webhook::Ping { id } => {
+                info!("Pinging webhook {}", id);
+                let webhook = database::webhook::get_webhook(postgres(db_pool)?, *id)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Webhook `{}` does not exist.", id))?;
+                let event = datatypes::Event::new(datatypes::EventKind::Ping, None, rocket::serde::json::serde_json::Value::Null);
+                let delivery = Dispatcher::new(postgres(db_pool)?.clone(), config.webhooks().clone())
+                    .deliver(&webhook, &event)
+                    .await?;
+                println!("{}", delivery);
+            }
+        },
+        Commands::Audit(audit) => match audit {
+            Audit::List { action, entity_id, actor: actors, source, since, limit, json } => {
+                info!("Listing the audit log");
+                if let Some(pattern) = action.iter().find(|p| !EventKind::ALL.iter().any(|k| k.matches(p))) {
+                    return Err(anyhow::anyhow!("`{}` matches no action.", pattern));
+                }
+                let mut filter = AuditFilter {
+                    actions: action.clone(),
+                    entity_ids: entity_id.clone(),
+                    actors: actors.clone(),
+                    sources: source.iter().map(|s| AuditSource::from(*s)).collect(),
+                    limit: *limit,
+                    ..AuditFilter::default()
+                };
+                if let Some(since) = since {
+                    filter.since = parse_date(since)?.and_hms_opt(0, 0, 0).unwrap();
+                }
+                let entries = repository.get_audit_entries(&filter).await?;
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&entries)?);
                 } else {
-                    println!("Multiple categories with the name `{}` exist.", name);
-                    println!("Please resolve in database manually.");
-                    return Err(anyhow::anyhow!("Multiple categories with the name `{}` exist.", name));
+                    println!("=== Changes, newest first:");
+                    for e in &entries {
+                        println!("  - {}", e);
+                    }
                 }
             }
         },
-
-
         Commands::Web(web) => match web {
             Web::Start => {
                 info!("Starting web server");
-                web::run(config, db_pool.clone()).await;
+                web::run(config, shared_repository.clone(), db_pool.cloned()).await;
+            }
+        },
+        Commands::Cli(cli) => match cli {
+            Cli::Start => {
+                info!("Starting CLI");
+                cli::run(repository).await?;
+            }
+        },
+        Commands::Tui(tui) => match tui {
+            Tui::Start => {
+                info!("Starting terminal dashboard");
+                tui::run(repository).await?;
             }
         },
         Commands::Import(import) => match import {
-            Import::OldCsvFormat { path } => {
+            Import::OldCsvFormat { path, stage, report, options } => {
+                info!("Importing dataset from {}", path);
+                if *stage {
+                    super::import::stage_old_csv_format(repository, postgres(db_pool)?, path).await?;
+                } else {
+                    super::import::old_csv_format_import(repository, path, &options.options(), report.as_deref()).await?;
+                }
+            }
+            Import::Json { path, options } => {
                 info!("Importing dataset from {}", path);
-                super::import::old_csv_format_import(db_pool, path).await?;
+                super::import::import_json(repository, db_pool, config.attachments_dir(), path, &options.options())
+                    .await?;
+            }
+            Import::Database { url, options } => {
+                info!("Importing dataset from another database");
+                let other = connect_other(&config, url).await?;
+                migrate(other.as_ref(), repository, &options.options()).await?;
+            }
+            Import::Ofx { path, statement, options } => {
+                info!("Importing OFX statement from {}", path);
+                super::import::statement_import(
+                    repository,
+                    postgres(db_pool)?,
+                    path,
+                    super::import::StatementFormat::Ofx,
+                    &statement.defaults(),
+                    &options.options(),
+                )
+                .await?;
+            }
+            Import::Qif { path, date_format, statement, options } => {
+                info!("Importing QIF statement from {}", path);
+                super::import::statement_import(
+                    repository,
+                    postgres(db_pool)?,
+                    path,
+                    super::import::StatementFormat::Qif { date_format: date_format.to_string() },
+                    &statement.defaults(),
+                    &options.options(),
+                )
+                .await?;
             }
         },
         Commands::Export(export) => match export {
-            Export::Json { path } => {
+            Export::Json { path, yes } => {
                 info!("Exporting dataset to {}", path);
-                super::export::export_json(db_pool, path).await?;
+                super::export::export_json(repository, db_pool, config.attachments_dir(), path, *yes).await?;
+            }
+            Export::Database { url, options } => {
+                info!("Exporting dataset to another database");
+                let other = connect_other(&config, url).await?;
+                migrate(repository, other.as_ref(), &options.options()).await?;
             }
         }
+        Commands::Backup(backup) => match backup {
+            Backup::Create { path: Some(path), yes, .. } => {
+                info!("Backing up the database to {}", path.display());
+                if path.exists() && !super::confirm(*yes, "File already exists. Overwrite?")? {
+                    return Ok(());
+                }
+                let summary = super::backup::create_backup(postgres(db_pool)?, config.attachments_dir(), path).await?;
+                println!("Backed up {}.", summary);
+            }
+            Backup::Create { dir, keep_daily, keep_weekly, .. } => {
+                let dir = dir.as_ref().expect("either a path or a directory is required");
+                info!("Backing up the database into {}", dir.display());
+                let retention = match (keep_daily, keep_weekly) {
+                    (None, None) => None,
+                    _ => Some(super::backup::Retention {
+                        daily: keep_daily.unwrap_or_default(),
+                        weekly: keep_weekly.unwrap_or_default(),
+                    }),
+                };
+                let (path, summary, deleted) =
+                    super::backup::create_in(postgres(db_pool)?, config.attachments_dir(), dir, retention).await?;
+                println!("Backed up {} to {}.", summary, path.display());
+                for path in &deleted {
+                    println!("  - deleted {}", path.display());
+                }
+            }
+            Backup::Verify { path } => {
+                info!("Verifying backup {}", path.display());
+                let summary = super::backup::verify_backup(path)?;
+                println!("The backup is complete: {}.", summary);
+            }
+            Backup::Restore { path } => {
+                info!("Restoring backup {}", path.display());
+                let summary = super::backup::restore_backup(postgres(db_pool)?, config.attachments_dir(), path).await?;
+                println!("Restored {}.", summary);
+            }
+        },
         Commands::Reset(reset) => match reset {
             Reset::Expenses => {
                 info!("Adding reset point");
-                database::expense::insert_last_reset(db_pool).await?;
+                repository.insert_last_reset().await?;
+            }
+        },
+        Commands::Drafts(drafts) => match drafts {
+            Drafts::Batches => {
+                info!("Listing import batches");
+                let batches = database::draft::get_import_batches(postgres(db_pool)?).await?;
+                println!("=== Import batches:");
+                for b in &batches {
+                    println!("  - {}", b);
+                }
+            }
+            Drafts::List { batch } => {
+                info!("Listing drafts of batch {}", batch);
+                let drafts = database::draft::get_draft_expenses(postgres(db_pool)?, *batch).await?;
+                let users = repository.get_users().await?;
+                let categories = repository.get_categories().await?;
+                println!("=== Drafts:");
+                for d in &drafts {
+                    super::import::print_draft(d, &users, &categories);
+                }
+            }
+            Drafts::Edit { id, payer, category, amount, date, description, split } => {
+                info!("Editing draft {}", id);
+                let edit = super::import::DraftEdit {
+                    payer: payer.clone(),
+                    category: category.clone(),
+                    amount: *amount,
+                    date: date.clone(),
+                    description: description.clone(),
+                    split: split.clone(),
+                };
+                super::import::edit_draft(repository, postgres(db_pool)?, actor, *id, edit).await?;
+                publish_draft(postgres(db_pool)?, &events, *id).await?;
+            }
+            Drafts::Approve { id } => {
+                info!("Approving draft {}", id);
+                if !database::draft::set_draft_status(postgres(db_pool)?, actor, *id, DraftStatus::Approved).await? {
+                    return Err(anyhow::anyhow!("Draft `{}` does not exist, has errors or was already posted.", id));
+                }
+                publish_draft(postgres(db_pool)?, &events, *id).await?;
+            }
+            Drafts::Reject { id } => {
+                info!("Rejecting draft {}", id);
+                if !database::draft::set_draft_status(postgres(db_pool)?, actor, *id, DraftStatus::Rejected).await? {
+                    return Err(anyhow::anyhow!("Draft `{}` does not exist or was already posted.", id));
+                }
+                publish_draft(postgres(db_pool)?, &events, *id).await?;
+            }
+            Drafts::Post { batch } => {
+                info!("Posting approved drafts of batch {}", batch);
+                let expenses = database::draft::post_approved_drafts(postgres(db_pool)?, actor, *batch).await?;
+                for expense in &expenses {
+                    events.publish_entity(EventKind::ExpenseCreated, expense.id(), expense);
+                }
+                println!("{} expenses created.", expenses.len());
             }
         },
     }
@@ -214,24 +1315,6 @@ pub async fn run(config: config::Config, db_pool: &sqlx::PgPool) -> Result<()> {
 // _ => unreachable!(),
 // },
 
-// Commands::Expenses(expenses) => match expenses {
-// Expenses::List => {
-// info!("Listing expenses");
-// unimplemented!()
-// // let exps = expenses::list_all(&db).await?;
-// // for (i, expense) in exps.iter().enumerate() {
-// //     println!("Expense #{} ====================================", i + 1);
-// //     println!("{expense}",);
-// // }
-// }
-// },
-// Commands::Cli(cli) => match cli {
-// Cli::Start => {
-// info!("Starting CLI");
-// unimplemented!()
-// // cli::run(&db_pool).await?;
-// }
-// },
 
 
 /*
@@ -242,16 +1325,4 @@ enum Utils {
     // Initialize,
 }
 
-#[derive(Debug, Subcommand)]
-enum Expenses {
-    #[command(about = "List all categories", long_about = None)]
-    List,
-}
-
-#[derive(Debug, Subcommand)]
-enum Cli {
-    #[command(about = "Start the command line interface", long_about = None)]
-    Start,
-}
-
  */
\ No newline at end of file
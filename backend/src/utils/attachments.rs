@@ -0,0 +1,258 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rocket::serde::json::serde_json;
+use sha2::{Digest, Sha256};
+
+use datatypes::{Attachment, EventKind};
+use crate::database;
+use crate::utils::audit::{json, record_tx, Actor};
+
+pub fn sha256_hex(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Stored files are spread over sub-directories named after the first two characters of their
+/// hash so no single directory grows too large.
+pub fn blob_path(dir: &Path, sha256: &str) -> PathBuf {
+    dir.join(&sha256[..2]).join(sha256)
+}
+
+/// Stores the contents under their hash and returns it. Contents that are already stored are not
+/// written again.
+pub fn store_blob(dir: &Path, contents: &[u8]) -> anyhow::Result<String> {
+    let sha256 = sha256_hex(contents);
+    let path = blob_path(dir, &sha256);
+    if path.exists() {
+        return Ok(sha256);
+    }
+
+    let parent = path.parent().expect("blob paths always have a parent");
+    fs::create_dir_all(parent)?;
+    // Write next to the final path and rename, so a crash never leaves a truncated blob behind.
+    let tmp = parent.join(format!("{}.tmp", sha256));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, &path)?;
+
+    Ok(sha256)
+}
+
+pub fn read_blob(dir: &Path, sha256: &str) -> anyhow::Result<Vec<u8>> {
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!("`{}` is not a SHA-256 hash.", sha256));
+    }
+    Ok(fs::read(blob_path(dir, sha256))?)
+}
+
+/// The content types attachments are stored and shown with, by file extension. None of them can
+/// run script in a browser, so they are safe to serve inline.
+const CONTENT_TYPES: &[(&[&str], &str)] = &[
+    (&["pdf"], "application/pdf"),
+    (&["png"], "image/png"),
+    (&["jpg", "jpeg"], "image/jpeg"),
+    (&["gif"], "image/gif"),
+    (&["webp"], "image/webp"),
+    (&["heic"], "image/heic"),
+    (&["tif", "tiff"], "image/tiff"),
+    (&["txt"], "text/plain"),
+];
+
+/// Guesses the content type from the file extension, falling back to a generic binary type.
+pub fn content_type_for(filename: &str) -> &'static str {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    CONTENT_TYPES
+        .iter()
+        .find(|(extensions, _)| extensions.contains(&extension.as_str()))
+        .map_or("application/octet-stream", |(_, content_type)| content_type)
+}
+
+/// Whether the content type is one `content_type_for` knows, ignoring parameters such as the
+/// charset. Only those are served inline.
+pub fn is_known_content_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+    CONTENT_TYPES.iter().any(|(_, known)| *known == essence)
+}
+
+/// The content type to store for an upload: the one the client sent if it is known, the guess
+/// from the file name otherwise. Anything else, say `text/html`, would be served back as is.
+pub fn upload_content_type(sent: Option<&str>, filename: &str) -> &'static str {
+    let essence = sent.map(|sent| sent.split(';').next().unwrap_or_default().trim().to_lowercase());
+    CONTENT_TYPES
+        .iter()
+        .map(|(_, known)| *known)
+        .find(|known| essence.as_deref() == Some(*known))
+        .unwrap_or_else(|| content_type_for(filename))
+}
+
+/// A file to attach with `add_attachments`.
+pub struct NewFile {
+    pub filename: String,
+    pub content_type: String,
+    pub contents: Vec<u8>,
+}
+
+/// Stores the contents and records them as attachments of the expense, added by `actor`. Either
+/// every file is attached or, if any of them fails, none are.
+pub async fn add_attachments(
+    db_pool: &sqlx::PgPool,
+    actor: &Actor,
+    dir: &Path,
+    expense_id: i32,
+    files: &[NewFile],
+) -> anyhow::Result<Vec<Attachment>> {
+    let mut tx = db_pool.begin().await?;
+    // Always taken in the same order, so two uploads sharing files can't deadlock.
+    let mut hashes = files.iter().map(|f| sha256_hex(&f.contents)).collect::<Vec<String>>();
+    hashes.sort();
+    hashes.dedup();
+    for sha256 in &hashes {
+        database::attachment::lock_hash(&mut tx, sha256).await?;
+    }
+
+    let mut added = vec![];
+    for file in files {
+        let sha256 = store_blob(dir, &file.contents)?;
+        let attachment = Attachment::new(
+            expense_id,
+            file.filename.clone(),
+            file.content_type.clone(),
+            file.contents.len() as i64,
+            sha256,
+        );
+        let attachment = database::attachment::insert_attachment(&mut tx, &attachment).await?;
+        let (created, after) = (EventKind::AttachmentCreated, json(&attachment));
+        record_tx(&mut tx, actor, created, Some(attachment.id()), serde_json::Value::Null, after).await?;
+        added.push(attachment);
+    }
+    tx.commit().await?;
+    Ok(added)
+}
+
+/// Deletes the attachment, and its stored contents once no other attachment uses them. The
+/// deletion is recorded as done by `actor`.
+pub async fn remove_attachment(
+    db_pool: &sqlx::PgPool,
+    actor: &Actor,
+    dir: &Path,
+    id: i32,
+) -> anyhow::Result<Option<Attachment>> {
+    let mut tx = db_pool.begin().await?;
+    let Some(attachment) = database::attachment::delete_attachment(&mut tx, id).await? else {
+        return Ok(None);
+    };
+    // Without the lock an upload of the same contents could see the file, and then lose it.
+    database::attachment::lock_hash(&mut tx, attachment.sha256()).await?;
+    let (deleted, before) = (EventKind::AttachmentDeleted, json(&attachment));
+    record_tx(&mut tx, actor, deleted, Some(id), before, serde_json::Value::Null).await?;
+    if !database::attachment::is_hash_in_use(&mut tx, attachment.sha256()).await? {
+        let path = blob_path(dir, attachment.sha256());
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    tx.commit().await?;
+    Ok(Some(attachment))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_store_blob() {
+        let dir = std::env::temp_dir().join(format!("attachments-test-{}", std::process::id()));
+        let sha256 = store_blob(&dir, b"receipt").unwrap();
+        assert_eq!(sha256, sha256_hex(b"receipt"));
+        assert!(blob_path(&dir, &sha256).starts_with(dir.join(&sha256[..2])));
+        // Storing the same contents again is a no-op.
+        assert_eq!(store_blob(&dir, b"receipt").unwrap(), sha256);
+        assert_eq!(read_blob(&dir, &sha256).unwrap(), b"receipt");
+        assert!(read_blob(&dir, "../../etc/passwd").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_attachment() {
+        use crate::database::testing::{test_pool, unique_name};
+        use crate::database::{category, expense, user};
+
+        let db_pool = test_pool().await;
+        let actor = Actor::new(unique_name("attacher"), datatypes::AuditSource::Cli);
+        let dir = std::env::temp_dir().join(unique_name("attachments"));
+        let payer = user::insert_user(&db_pool, unique_name("attachment")).await.unwrap();
+        let category = category::insert_category(&db_pool, unique_name("attachment"), &String::new(), None)
+            .await
+            .unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let receipt = datatypes::Expense::new(payer.id(), category.id(), 1.0, "receipt".to_string(), date, vec![]);
+        let receipt = expense::insert_expense(&db_pool, receipt).await.unwrap();
+
+        let file = |filename: &str, contents: &str| NewFile {
+            filename: filename.to_string(),
+            content_type: "text/plain".to_string(),
+            contents: contents.as_bytes().to_vec(),
+        };
+        let (contents, blocked) = (unique_name("contents"), unique_name("blocked"));
+
+        // A directory where the second file's contents would be written, so none are attached.
+        let blocked_path = blob_path(&dir, &sha256_hex(blocked.as_bytes()));
+        fs::create_dir_all(blocked_path.with_extension("tmp")).unwrap();
+        let files = [file("a.txt", &contents), file("b.txt", &blocked)];
+        assert!(add_attachments(&db_pool, &actor, &dir, receipt.id(), &files).await.is_err());
+        assert!(database::attachment::get_attachments(&db_pool, receipt.id()).await.unwrap().is_empty());
+
+        let files = [file("a.txt", &contents), file("b.txt", &contents)];
+        let added = add_attachments(&db_pool, &actor, &dir, receipt.id(), &files).await.unwrap();
+        let (first, second) = (added[0].clone(), added[1].clone());
+        let path = blob_path(&dir, first.sha256());
+
+        // The contents stay until the last attachment using them is gone.
+        assert_eq!(remove_attachment(&db_pool, &actor, &dir, first.id()).await.unwrap(), Some(first.clone()));
+        assert!(path.exists());
+        assert!(remove_attachment(&db_pool, &actor, &dir, second.id()).await.unwrap().is_some());
+        assert!(!path.exists());
+        assert!(remove_attachment(&db_pool, &actor, &dir, second.id()).await.unwrap().is_none());
+
+        let filter = datatypes::AuditFilter {
+            actors: vec![actor.name().clone()],
+            ..datatypes::AuditFilter::default()
+        };
+        let entries = database::audit::get_audit_entries(&db_pool, &filter).await.unwrap();
+        let recorded = entries.iter().rev().map(|e| (e.action(), e.entity_id())).collect::<Vec<(EventKind, Option<i32>)>>();
+        assert_eq!(
+            recorded,
+            vec![
+                (EventKind::AttachmentCreated, Some(first.id())),
+                (EventKind::AttachmentCreated, Some(second.id())),
+                (EventKind::AttachmentDeleted, Some(first.id())),
+                (EventKind::AttachmentDeleted, Some(second.id())),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_content_type_for() {
+        assert_eq!(content_type_for("scan.PDF"), "application/pdf");
+        assert_eq!(content_type_for("photo.jpeg"), "image/jpeg");
+        assert_eq!(content_type_for("notes"), "application/octet-stream");
+
+        assert!(is_known_content_type("text/plain; charset=utf-8"));
+        assert!(!is_known_content_type("text/html"));
+        assert!(!is_known_content_type("application/octet-stream"));
+        assert_eq!(upload_content_type(Some("Image/PNG"), "scan"), "image/png");
+        assert_eq!(upload_content_type(Some("text/html"), "receipt.pdf"), "application/pdf");
+        assert_eq!(upload_content_type(Some("image/svg+xml"), "logo.svg"), "application/octet-stream");
+        assert_eq!(upload_content_type(None, "receipt.txt"), "text/plain");
+    }
+}
@@ -0,0 +1,404 @@
+//! Records who changed what in the audit log. Writes through the repository are recorded by
+//! wrapping it with `AuditingRepository::wrap`, the few writes that go past it call `record` or
+//! `record_tx` themselves. The web server wraps the repository for every request with the caller as the
+//! actor, the CLI once with the user running it.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datatypes::{AuditEntry, AuditFilter, AuditSource, Category, EventKind, Expense, Filter, User};
+use rocket::serde::json::serde_json;
+
+use crate::database::repository::{
+    AuditRepository, CategoryRepository, Dataset, DynRepository, ExpenseRepository, ImportRepository, Imported,
+    Repository, ResetRepository, UserRepository,
+};
+use crate::database::{audit, References};
+
+/// Who makes the changes and through what.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    name: String,
+    source: AuditSource,
+    /// Where a web request came from, recorded since the name is whatever the caller sent.
+    address: Option<String>,
+}
+
+impl Actor {
+    pub fn new(name: String, source: AuditSource) -> Self {
+        Self { name, source, address: None }
+    }
+
+    pub fn with_address(mut self, address: Option<String>) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// The user running the CLI.
+    pub fn cli_user(source: AuditSource) -> Self {
+        let name = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        Self::new(name, source)
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn source(&self) -> AuditSource {
+        self.source
+    }
+
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+
+    /// The entry for a change this actor made.
+    pub fn entry(
+        &self,
+        action: EventKind,
+        entity_id: Option<i32>,
+        before: serde_json::Value,
+        after: serde_json::Value,
+    ) -> AuditEntry {
+        let mut entry = AuditEntry::new(self.name.clone(), self.source, action, entity_id, before, after);
+        entry.set_address(self.address.clone());
+        entry
+    }
+}
+
+pub(crate) fn json<T: serde::Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+pub(crate) fn merged_into(into: i32) -> serde_json::Value {
+    serde_json::json!({ "merged_into": into })
+}
+
+/// Adds an entry for a change `actor` made, for writes that go past an `AuditingRepository`.
+/// `before` is `null` for creations, `after` for deletions.
+pub async fn record(
+    repository: &dyn Repository,
+    actor: &Actor,
+    action: EventKind,
+    entity_id: Option<i32>,
+    before: serde_json::Value,
+    after: serde_json::Value,
+) -> Result<AuditEntry, sqlx::Error> {
+    repository.insert_audit_entry(actor.entry(action, entity_id, before, after)).await
+}
+
+/// `record` as part of a transaction that writes past the repository, so the entries are only
+/// kept if the change is.
+pub async fn record_tx(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    actor: &Actor,
+    action: EventKind,
+    entity_id: Option<i32>,
+    before: serde_json::Value,
+    after: serde_json::Value,
+) -> Result<AuditEntry, sqlx::Error> {
+    audit::insert_audit_entry_tx(tx, &actor.entry(action, entity_id, before, after)).await
+}
+
+/// Passes everything on to the wrapped repository and records what was changed, together with
+/// the entity as it was before. The change is made before it is recorded, so if recording fails
+/// the error is returned although the change went through. Only used for storage that can't
+/// record in the transaction of the change, like the `MemoryRepository`.
+pub struct AuditingRepository {
+    inner: DynRepository,
+    actor: Actor,
+}
+
+impl AuditingRepository {
+    /// `inner` with every change made through it recorded as done by `actor`, in the same
+    /// transaction as the change where the storage supports it.
+    pub fn wrap(inner: DynRepository, actor: Actor) -> DynRepository {
+        match inner.with_actor(&actor) {
+            Some(repository) => repository,
+            None => Arc::new(Self { inner, actor }),
+        }
+    }
+
+    async fn record(
+        &self,
+        action: EventKind,
+        entity_id: Option<i32>,
+        before: serde_json::Value,
+        after: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        record(self.inner.as_ref(), &self.actor, action, entity_id, before, after).await?;
+        Ok(())
+    }
+
+    async fn user(&self, id: i32) -> Result<serde_json::Value, sqlx::Error> {
+        Ok(self.inner.get_user(id).await?.as_ref().map(json).unwrap_or_default())
+    }
+
+    async fn category(&self, id: i32) -> Result<serde_json::Value, sqlx::Error> {
+        let categories = self.inner.get_categories().await?;
+        Ok(categories.iter().find(|c| c.id() == id).map(json).unwrap_or_default())
+    }
+
+    /// Records an update of category `id` if `updated`, `before` is the category beforehand.
+    async fn category_updated(&self, updated: bool, id: i32, before: serde_json::Value) -> Result<bool, sqlx::Error> {
+        if updated {
+            let after = self.category(id).await?;
+            self.record(EventKind::CategoryUpdated, Some(id), before, after).await?;
+        }
+        Ok(updated)
+    }
+}
+
+#[async_trait]
+impl UserRepository for AuditingRepository {
+    async fn insert_user(&self, username: String) -> Result<User, sqlx::Error> {
+        let user = self.inner.insert_user(username).await?;
+        self.record(EventKind::UserCreated, Some(user.id()), serde_json::Value::Null, json(&user)).await?;
+        Ok(user)
+    }
+
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        self.inner.get_users().await
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>, sqlx::Error> {
+        self.inner.get_user(id).await
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let before = self.user(id).await?;
+        let deleted = self.inner.delete_user(id).await?;
+        if deleted {
+            self.record(EventKind::UserDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn rename_user(&self, id: i32, username: String) -> Result<Option<User>, sqlx::Error> {
+        let before = self.user(id).await?;
+        let user = self.inner.rename_user(id, username).await?;
+        if let Some(user) = &user {
+            self.record(EventKind::UserUpdated, Some(id), before, json(user)).await?;
+        }
+        Ok(user)
+    }
+
+    async fn get_user_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        self.inner.get_user_references(id).await
+    }
+
+    async fn merge_users(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let before = self.user(from).await?;
+        let merged = self.inner.merge_users(from, into).await?;
+        if merged {
+            self.record(EventKind::UserDeleted, Some(from), before, merged_into(into)).await?;
+        }
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for AuditingRepository {
+    async fn insert_category(
+        &self,
+        name: String,
+        description: &str,
+        parent_id: Option<i32>,
+    ) -> Result<Category, sqlx::Error> {
+        let category = self.inner.insert_category(name, description, parent_id).await?;
+        let after = json(&category);
+        self.record(EventKind::CategoryCreated, Some(category.id()), serde_json::Value::Null, after).await?;
+        Ok(category)
+    }
+
+    async fn get_categories(&self) -> Result<Vec<Category>, sqlx::Error> {
+        self.inner.get_categories().await
+    }
+
+    async fn delete_category(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let before = self.category(id).await?;
+        let deleted = self.inner.delete_category(id).await?;
+        if deleted {
+            self.record(EventKind::CategoryDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn rename_category(&self, id: i32, name: String) -> Result<bool, sqlx::Error> {
+        let before = self.category(id).await?;
+        let renamed = self.inner.rename_category(id, name).await?;
+        self.category_updated(renamed, id, before).await
+    }
+
+    async fn update_category_description(&self, id: i32, description: &str) -> Result<bool, sqlx::Error> {
+        let before = self.category(id).await?;
+        let updated = self.inner.update_category_description(id, description).await?;
+        self.category_updated(updated, id, before).await
+    }
+
+    async fn set_category_parent(&self, id: i32, parent_id: Option<i32>) -> Result<bool, sqlx::Error> {
+        let before = self.category(id).await?;
+        let moved = self.inner.set_category_parent(id, parent_id).await?;
+        self.category_updated(moved, id, before).await
+    }
+
+    async fn get_category_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        self.inner.get_category_references(id).await
+    }
+
+    async fn merge_categories(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let before = self.category(from).await?;
+        let merged = self.inner.merge_categories(from, into).await?;
+        if merged {
+            self.record(EventKind::CategoryDeleted, Some(from), before, merged_into(into)).await?;
+        }
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl ExpenseRepository for AuditingRepository {
+    async fn insert_expense(&self, expense: Expense) -> Result<Expense, sqlx::Error> {
+        let expense = self.inner.insert_expense(expense).await?;
+        let after = json(&expense);
+        self.record(EventKind::ExpenseCreated, Some(expense.id()), serde_json::Value::Null, after).await?;
+        Ok(expense)
+    }
+
+    async fn insert_expenses(&self, expenses: Vec<Expense>) -> Result<Vec<Expense>, sqlx::Error> {
+        let expenses = self.inner.insert_expenses(expenses).await?;
+        for expense in &expenses {
+            let after = json(expense);
+            self.record(EventKind::ExpenseCreated, Some(expense.id()), serde_json::Value::Null, after).await?;
+        }
+        Ok(expenses)
+    }
+
+    async fn get_expense(&self, id: i32) -> Result<Option<Expense>, sqlx::Error> {
+        self.inner.get_expense(id).await
+    }
+
+    async fn get_expenses(&self, filter: Option<Filter>) -> Result<Vec<Expense>, sqlx::Error> {
+        self.inner.get_expenses(filter).await
+    }
+
+    async fn update_expense(&self, expense: Expense) -> Result<Option<Expense>, sqlx::Error> {
+        let before = json(&self.inner.get_expense(expense.id()).await?);
+        let expense = self.inner.update_expense(expense).await?;
+        if let Some(expense) = &expense {
+            self.record(EventKind::ExpenseUpdated, Some(expense.id()), before, json(expense)).await?;
+        }
+        Ok(expense)
+    }
+
+    async fn delete_expense(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let before = json(&self.inner.get_expense(id).await?);
+        let deleted = self.inner.delete_expense(id).await?;
+        if deleted {
+            self.record(EventKind::ExpenseDeleted, Some(id), before, serde_json::Value::Null).await?;
+        }
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl ResetRepository for AuditingRepository {
+    async fn insert_last_reset(&self) -> Result<(), sqlx::Error> {
+        let before = match self.inner.get_last_reset().await {
+            Ok(date) => serde_json::json!({ "date": date }),
+            Err(sqlx::Error::RowNotFound) => serde_json::Value::Null,
+            Err(e) => return Err(e),
+        };
+        self.inner.insert_last_reset().await?;
+        let after = serde_json::json!({ "date": self.inner.get_last_reset().await? });
+        self.record(EventKind::Reset, None, before, after).await
+    }
+
+    async fn get_last_reset(&self) -> Result<chrono::NaiveDateTime, sqlx::Error> {
+        self.inner.get_last_reset().await
+    }
+}
+
+#[async_trait]
+impl ImportRepository for AuditingRepository {
+    async fn import_dataset(&self, dataset: Dataset) -> Result<Imported, sqlx::Error> {
+        let imported = self.inner.import_dataset(dataset).await?;
+        for (action, id, after) in imported.created() {
+            self.record(action, Some(id), serde_json::Value::Null, after).await?;
+        }
+        Ok(imported)
+    }
+
+    async fn get_import_fingerprints(&self) -> Result<HashSet<String>, sqlx::Error> {
+        self.inner.get_import_fingerprints().await
+    }
+}
+
+#[async_trait]
+impl AuditRepository for AuditingRepository {
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, sqlx::Error> {
+        self.inner.insert_audit_entry(entry).await
+    }
+
+    async fn get_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        self.inner.get_audit_entries(filter).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::MemoryRepository;
+
+    #[tokio::test]
+    async fn test_records_changes() {
+        let inner: DynRepository = Arc::new(MemoryRepository::new());
+        let repository = AuditingRepository::wrap(inner.clone(), Actor::new("alice".to_string(), AuditSource::Cli));
+
+        let food = repository.insert_category("food".to_string(), "", None).await.unwrap();
+        let groceries = repository.insert_category("groceries".to_string(), "", None).await.unwrap();
+        assert!(repository.update_category_description(food.id(), "eating out").await.unwrap());
+        // Nothing changed, so nothing is recorded.
+        assert!(!repository.delete_category(-1).await.unwrap());
+        assert!(repository.merge_categories(groceries.id(), food.id()).await.unwrap());
+        repository.insert_last_reset().await.unwrap();
+        // Another caller on the same storage.
+        let bob = AuditingRepository::wrap(inner, Actor::new("bob".to_string(), AuditSource::Web));
+        assert!(bob.delete_category(food.id()).await.unwrap());
+
+        let entries = repository.get_audit_entries(&AuditFilter::default()).await.unwrap();
+        let actions = entries.iter().map(|e| e.action()).collect::<Vec<EventKind>>();
+        assert_eq!(
+            actions,
+            vec![
+                EventKind::CategoryDeleted,
+                EventKind::Reset,
+                EventKind::CategoryDeleted,
+                EventKind::CategoryUpdated,
+                EventKind::CategoryCreated,
+                EventKind::CategoryCreated,
+            ]
+        );
+        assert_eq!((entries[0].actor().as_str(), entries[0].source()), ("bob", AuditSource::Web));
+        assert_eq!(entries[0].before()["description"], "eating out");
+        assert!(entries[0].after().is_null());
+        assert!(entries[1].before().is_null());
+        assert!(entries[1].after()["date"].is_string());
+        assert_eq!(entries[2].before()["name"], "groceries");
+        assert_eq!(entries[2].after()["merged_into"], food.id());
+        assert_eq!(entries[3].before()["description"], "");
+        assert_eq!(entries[3].after()["description"], "eating out");
+        assert!(entries[5].before().is_null());
+        assert_eq!(entries[5].after()["name"], "food");
+
+        let filter = AuditFilter {
+            actors: vec!["alice".to_string()],
+            actions: vec!["category".to_string()],
+            ..AuditFilter::default()
+        };
+        assert_eq!(repository.get_audit_entries(&filter).await.unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,433 @@
+//! `backup create` and `backup restore`. A backup is a tar archive of every table as json, see
+//! `database::backup`, and the attachment contents, with a manifest listing the checksum of each.
+//! Unlike `export json` it keeps everything: ids, timestamps, the reset history, drafts, webhooks
+//! and the audit log.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, NaiveDateTime};
+use rocket::serde::json::serde_json;
+
+use crate::database::backup::{self, TableDump, TABLES};
+use crate::utils::{archive, attachments};
+
+/// Raised whenever the layout changes. Restoring a backup of another version is refused.
+pub const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Manifest {
+    version: u32,
+    created_at: NaiveDateTime,
+    tables: Vec<TableEntry>,
+    /// The SHA-256 of each attachment, which is also the checksum of its entry.
+    attachments: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct TableEntry {
+    name: String,
+    rows: i64,
+    next_id: Option<i64>,
+    sha256: String,
+}
+
+fn table_path(name: &str) -> String {
+    format!("tables/{}.json", name)
+}
+
+fn attachment_path(sha256: &str) -> String {
+    format!("attachments/{}", sha256)
+}
+
+/// What a backup holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    created_at: NaiveDateTime,
+    tables: usize,
+    rows: i64,
+    attachments: usize,
+}
+
+impl Summary {
+    pub fn created_at(&self) -> &NaiveDateTime {
+        &self.created_at
+    }
+
+    pub fn rows(&self) -> i64 {
+        self.rows
+    }
+
+    pub fn attachments(&self) -> usize {
+        self.attachments
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} rows in {} tables and {} attachments, taken {}",
+            self.rows,
+            self.tables,
+            self.attachments,
+            self.created_at.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+/// Writes a backup of the whole database to `path`. The archive is written next to it and moved
+/// into place once complete, so an interrupted backup never looks like a finished one.
+pub async fn create_backup(db_pool: &sqlx::PgPool, attachments_dir: &Path, path: &Path) -> anyhow::Result<Summary> {
+    let dumps = backup::dump_tables(db_pool).await?;
+
+    // Taken from the dump rather than queried again, so they match the rows.
+    let attachments_dump = dumps
+        .iter()
+        .find(|d| d.name == "attachments")
+        .expect("the attachments are one of the tables");
+    let mut hashes: Vec<String> = vec![];
+    for row in serde_json::from_str::<Vec<serde_json::Value>>(&attachments_dump.json)? {
+        let sha256 = row["sha256"].as_str().unwrap_or_default().to_string();
+        if !hashes.contains(&sha256) {
+            hashes.push(sha256);
+        }
+    }
+
+    let manifest = Manifest {
+        version: FORMAT_VERSION,
+        created_at: chrono::Local::now().naive_local(),
+        tables: dumps
+            .iter()
+            .map(|d| TableEntry {
+                name: d.name.clone(),
+                rows: d.rows,
+                next_id: d.next_id,
+                sha256: attachments::sha256_hex(d.json.as_bytes()),
+            })
+            .collect(),
+        attachments: hashes,
+    };
+
+    let mut entries = vec![(MANIFEST.to_string(), serde_json::to_vec_pretty(&manifest)?)];
+    for dump in &dumps {
+        entries.push((table_path(&dump.name), dump.json.clone().into_bytes()));
+    }
+    for sha256 in &manifest.attachments {
+        entries.push((attachment_path(sha256), attachments::read_blob(attachments_dir, sha256)?));
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let file = File::create(&tmp)?;
+    archive::write_tar(&file, &entries)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+
+    Ok(summarize(&manifest))
+}
+
+fn summarize(manifest: &Manifest) -> Summary {
+    Summary {
+        created_at: manifest.created_at,
+        tables: manifest.tables.len(),
+        rows: manifest.tables.iter().map(|t| t.rows).sum(),
+        attachments: manifest.attachments.len(),
+    }
+}
+
+/// A backup that was checked against its manifest.
+struct Backup {
+    manifest: Manifest,
+    dumps: Vec<TableDump>,
+    /// The attachment contents by their hash.
+    attachments: Vec<(String, Vec<u8>)>,
+}
+
+/// Reads the backup at `path` and checks that it is complete and unchanged: a version this build
+/// writes, every table once with the checksum and row count of the manifest, and every attachment.
+fn read_backup(path: &Path) -> anyhow::Result<Backup> {
+    let mut entries = archive::read_tar(File::open(path)?)?;
+    let position = entries
+        .iter()
+        .position(|(name, _)| name == MANIFEST)
+        .ok_or_else(|| anyhow::anyhow!("`{}` is not a backup, it has no {}.", path.display(), MANIFEST))?;
+    let manifest: Manifest = serde_json::from_slice(&entries.remove(position).1)?;
+    if manifest.version != FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "The backup has version {}, this build only restores version {}.",
+            manifest.version,
+            FORMAT_VERSION
+        ));
+    }
+    let names = manifest.tables.iter().map(|t| t.name.as_str());
+    if !names.eq(TABLES.iter().map(|(name, _)| *name)) {
+        return Err(anyhow::anyhow!("The backup does not have the tables of this version."));
+    }
+
+    let mut take = |name: &str| -> anyhow::Result<Vec<u8>> {
+        let position = entries
+            .iter()
+            .position(|(entry, _)| entry == name)
+            .ok_or_else(|| anyhow::anyhow!("The backup is missing `{}`.", name))?;
+        Ok(entries.remove(position).1)
+    };
+
+    let mut dumps = vec![];
+    for table in &manifest.tables {
+        let name = table_path(&table.name);
+        let contents = take(&name)?;
+        if attachments::sha256_hex(&contents) != table.sha256 {
+            return Err(anyhow::anyhow!("`{}` does not match its checksum.", name));
+        }
+        let json = String::from_utf8(contents)?;
+        let rows = serde_json::from_str::<Vec<serde_json::Value>>(&json)?.len() as i64;
+        if rows != table.rows {
+            return Err(anyhow::anyhow!("`{}` has {} rows, the manifest says {}.", name, rows, table.rows));
+        }
+        dumps.push(TableDump {
+            name: table.name.clone(),
+            rows,
+            json,
+            next_id: table.next_id,
+        });
+    }
+
+    let mut contents = vec![];
+    for sha256 in &manifest.attachments {
+        let name = attachment_path(sha256);
+        let blob = take(&name)?;
+        if attachments::sha256_hex(&blob) != *sha256 {
+            return Err(anyhow::anyhow!("`{}` does not match its checksum.", name));
+        }
+        contents.push((sha256.clone(), blob));
+    }
+
+    if let Some((name, _)) = entries.first() {
+        return Err(anyhow::anyhow!("The backup has `{}`, which is not in its manifest.", name));
+    }
+
+    Ok(Backup {
+        manifest,
+        dumps,
+        attachments: contents,
+    })
+}
+
+/// Checks the backup at `path` without restoring it.
+pub fn verify_backup(path: &Path) -> anyhow::Result<Summary> {
+    Ok(summarize(&read_backup(path)?.manifest))
+}
+
+/// Restores the backup at `path` into an empty database, exactly as it was, and stores its
+/// attachments. Nothing is written unless the whole backup checks out and every table is empty.
+pub async fn restore_backup(db_pool: &sqlx::PgPool, attachments_dir: &Path, path: &Path) -> anyhow::Result<Summary> {
+    let backup = read_backup(path)?;
+
+    // Stored first: blobs nothing points at are harmless, rows pointing at missing blobs are not.
+    for (_, contents) in &backup.attachments {
+        attachments::store_blob(attachments_dir, contents)?;
+    }
+    let non_empty = backup::restore_tables(db_pool, &backup.dumps).await?;
+    if !non_empty.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Backups are only restored into an empty database, but {} already have rows.",
+            non_empty.join(", ")
+        ));
+    }
+
+    Ok(summarize(&backup.manifest))
+}
+
+/// How many backups `create_in` keeps in its directory: the newest of each of the last `daily`
+/// days and of each of the last `weekly` weeks that have one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retention {
+    pub daily: usize,
+    pub weekly: usize,
+}
+
+const NAME_FORMAT: &str = "expenses-%Y%m%d-%H%M%S.tar";
+
+/// When the backup was taken, for files named by `create_in`. Any other file is not a backup.
+fn taken_at(path: &Path) -> Option<NaiveDateTime> {
+    let name = path.file_name()?.to_str()?;
+    NaiveDateTime::parse_from_str(name, NAME_FORMAT).ok()
+}
+
+/// The backups that `retention` does not keep.
+fn expired(mut backups: Vec<(PathBuf, NaiveDateTime)>, retention: Retention) -> Vec<PathBuf> {
+    backups.sort_by_key(|(_, at)| std::cmp::Reverse(*at));
+
+    let mut keep = HashSet::new();
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    for (path, at) in &backups {
+        if days.len() < retention.daily && days.insert(at.date()) {
+            keep.insert(path.clone());
+        }
+        let week = at.iso_week();
+        if weeks.len() < retention.weekly && weeks.insert((week.year(), week.week())) {
+            keep.insert(path.clone());
+        }
+    }
+
+    backups
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| !keep.contains(path))
+        .collect()
+}
+
+/// Writes a backup into `dir`, named after the time it was taken, for running on a schedule.
+/// With a retention the older backups there it does not keep are deleted, the new one is always
+/// kept. Returns the new backup and the deleted ones.
+pub async fn create_in(
+    db_pool: &sqlx::PgPool,
+    attachments_dir: &Path,
+    dir: &Path,
+    retention: Option<Retention>,
+) -> anyhow::Result<(PathBuf, Summary, Vec<PathBuf>)> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(chrono::Local::now().format(NAME_FORMAT).to_string());
+    if path.exists() {
+        return Err(anyhow::anyhow!("`{}` already exists.", path.display()));
+    }
+    let summary = create_backup(db_pool, attachments_dir, &path).await?;
+
+    let mut deleted = vec![];
+    if let Some(retention) = retention {
+        let mut backups = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let other = entry?.path();
+            if let Some(at) = taken_at(&other) {
+                backups.push((other, at));
+            }
+        }
+        // The new backup counts as the newest of its day and week, so older ones from the same
+        // day or week make way for it.
+        for other in expired(backups, retention).into_iter().filter(|other| *other != path) {
+            std::fs::remove_file(&other)?;
+            deleted.push(other);
+        }
+    }
+
+    Ok((path, summary, deleted))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{drop_database, empty_database};
+    use crate::database::{attachment, category, expense, user};
+    use datatypes::{Attachment, Expense};
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_expired() {
+        // 2024-01-01 is a Monday.
+        let backups = [at(1, 3), at(2, 3), at(8, 3), at(14, 3), at(15, 3), at(15, 9)]
+            .into_iter()
+            .map(|at| (PathBuf::from(at.format(NAME_FORMAT).to_string()), at))
+            .collect::<Vec<_>>();
+        let kept = |retention| {
+            let expired = expired(backups.clone(), retention);
+            backups
+                .iter()
+                .filter(|(path, _)| !expired.contains(path))
+                .map(|(_, at)| *at)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kept(Retention { daily: 2, weekly: 0 }), vec![at(14, 3), at(15, 9)]);
+        assert_eq!(kept(Retention { daily: 0, weekly: 2 }), vec![at(14, 3), at(15, 9)]);
+        assert_eq!(kept(Retention { daily: 1, weekly: 3 }), vec![at(2, 3), at(14, 3), at(15, 9)]);
+        assert_eq!(kept(Retention { daily: 10, weekly: 10 }).len(), 5);
+        assert!(kept(Retention::default()).is_empty());
+
+        assert_eq!(taken_at(&backups[0].0), Some(at(1, 3)));
+        assert_eq!(taken_at(Path::new("notes.txt")), None);
+    }
+
+    #[tokio::test]
+    async fn test_create_in_with_retention() {
+        let dir = std::env::temp_dir().join(crate::database::testing::unique_name("backup_retention"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let today = chrono::Local::now().date_naive();
+        let earlier_today = dir.join(today.and_hms_opt(0, 0, 0).unwrap().format(NAME_FORMAT).to_string());
+        let yesterday = today.pred_opt().unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let yesterday = dir.join(yesterday.format(NAME_FORMAT).to_string());
+        std::fs::write(&earlier_today, b"").unwrap();
+        std::fs::write(&yesterday, b"").unwrap();
+
+        // Two days are kept: the new backup stands in for today, so the earlier one goes.
+        let retention = Retention { daily: 2, weekly: 0 };
+        let db_pool = crate::database::testing::test_pool().await;
+        let (path, _, deleted) = create_in(&db_pool, &dir.join("attachments"), &dir, Some(retention))
+            .await
+            .unwrap();
+        assert_eq!(deleted, vec![earlier_today]);
+        assert!(path.exists() && yesterday.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_and_restore() {
+        let dir = std::env::temp_dir().join(crate::database::testing::unique_name("backup"));
+        let source_attachments = dir.join("source");
+        let target_attachments = dir.join("target");
+
+        let (source, source_name) = empty_database("backup_archive_source").await;
+        let alice = user::insert_user(&source, "alice".to_string()).await.unwrap();
+        let food = category::insert_category(&source, "food".to_string(), &String::new(), None)
+            .await
+            .unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let apples = Expense::new(alice.id(), food.id(), 3.0, "apples".to_string(), date, vec![]);
+        let apples = expense::insert_expense(&source, apples).await.unwrap();
+        let sha256 = attachments::store_blob(&source_attachments, b"receipt").unwrap();
+        let receipt = Attachment::new(apples.id(), "receipt.txt".to_string(), "text/plain".to_string(), 7, sha256.clone());
+        attachment::insert_attachment(&source, &receipt).await.unwrap();
+
+        let (path, summary, deleted) = create_in(&source, &source_attachments, &dir, Some(Retention::default()))
+            .await
+            .unwrap();
+        assert!(deleted.is_empty());
+        assert_eq!((summary.rows(), summary.attachments()), (4, 1));
+        assert_eq!(verify_backup(&path).unwrap(), summary);
+
+        // Changed contents, or entries the manifest does not know, fail the check.
+        let mut entries = archive::read_tar(File::open(&path).unwrap()).unwrap();
+        let tampered = dir.join("tampered.tar");
+        entries.push(("extra".to_string(), vec![]));
+        archive::write_tar(File::create(&tampered).unwrap(), &entries).unwrap();
+        assert!(verify_backup(&tampered).unwrap_err().to_string().contains("`extra`"));
+        entries.pop();
+        let users = entries.iter_mut().find(|(name, _)| name == "tables/users.json").unwrap();
+        users.1 = b"[]".to_vec();
+        archive::write_tar(File::create(&tampered).unwrap(), &entries).unwrap();
+        assert!(verify_backup(&tampered).unwrap_err().to_string().contains("checksum"));
+
+        let (target, target_name) = empty_database("backup_archive_target").await;
+        assert!(restore_backup(&target, &target_attachments, &tampered).await.is_err());
+        assert_eq!(restore_backup(&target, &target_attachments, &path).await.unwrap(), summary);
+        assert_eq!(backup::dump_tables(&target).await.unwrap(), backup::dump_tables(&source).await.unwrap());
+        assert_eq!(attachments::read_blob(&target_attachments, &sha256).unwrap(), b"receipt");
+        assert!(restore_backup(&target, &target_attachments, &path).await.is_err());
+
+        drop_database(source, &source_name).await;
+        drop_database(target, &target_name).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
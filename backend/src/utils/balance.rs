@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use datatypes::Expense;
+
+use crate::database::repository::Repository;
+
+/// Returns the expenses purchased on or after the day of the last reset, or every expense if no
+/// reset was ever made.
+pub async fn expenses_since_last_reset(repository: &dyn Repository) -> Result<Vec<Expense>, sqlx::Error> {
+    let last_reset = match repository.get_last_reset().await {
+        Ok(date) => Some(date.date()),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(e) => return Err(e),
+    };
+    let expenses = repository.get_expenses(None).await?;
+
+    Ok(match last_reset {
+        Some(date) => expenses
+            .into_iter()
+            .filter(|e| *e.purchased_at() >= date)
+            .collect(),
+        None => expenses,
+    })
+}
+
+/// Net balance per user id: positive when the user is owed money, negative when they owe it.
+/// A payer's own share of an expense doesn't count towards either.
+pub fn balances(expenses: &[Expense]) -> HashMap<i32, f64> {
+    let mut balances = HashMap::new();
+    for expense in expenses {
+        for owes in expense.user_owes() {
+            if owes.user_id() == expense.user_id() {
+                continue;
+            }
+            *balances.entry(expense.user_id()).or_insert(0.0) += owes.amount();
+            *balances.entry(owes.user_id()).or_insert(0.0) -= owes.amount();
+        }
+    }
+    balances
+}
+
+/// Total amount spent per category id.
+pub fn category_totals(expenses: &[Expense]) -> HashMap<i32, f64> {
+    let mut totals = HashMap::new();
+    for expense in expenses {
+        *totals.entry(expense.category_id()).or_insert(0.0) += expense.amount();
+    }
+    totals
+}
+
+/// Total amount spent per tag. An expense with several tags counts towards each of them.
+pub fn tag_totals(expenses: &[Expense]) -> HashMap<String, f64> {
+    let mut totals = HashMap::new();
+    for expense in expenses {
+        for tag in expense.tags() {
+            *totals.entry(tag.clone()).or_insert(0.0) += expense.amount();
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use datatypes::UserOwes;
+
+    fn expense(payer: i32, owes: Vec<(i32, f64)>) -> Expense {
+        categorised_expense(payer, 1, owes)
+    }
+
+    fn categorised_expense(payer: i32, category: i32, owes: Vec<(i32, f64)>) -> Expense {
+        let amount = owes.iter().map(|(_, a)| a).sum();
+        Expense::new(
+            payer,
+            category,
+            amount,
+            "test".to_string(),
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            owes.into_iter()
+                .map(|(user, amount)| UserOwes::new(user, -1, amount))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_balances() {
+        let expenses = vec![
+            expense(1, vec![(1, 10.0), (2, 10.0), (3, 10.0)]),
+            expense(2, vec![(1, 4.0), (2, 4.0)]),
+        ];
+        let balances = balances(&expenses);
+        assert_eq!(balances[&1], 16.0);
+        assert_eq!(balances[&2], -6.0);
+        assert_eq!(balances[&3], -10.0);
+        assert_eq!(balances.values().sum::<f64>(), 0.0);
+    }
+
+    #[test]
+    fn test_own_share_is_ignored() {
+        let balances = balances(&[expense(1, vec![(1, 10.0)])]);
+        assert!(balances.is_empty());
+    }
+
+    #[test]
+    fn test_category_totals() {
+        let totals = category_totals(&[
+            categorised_expense(1, 1, vec![(1, 10.0)]),
+            categorised_expense(2, 1, vec![(2, 5.0)]),
+            categorised_expense(1, 2, vec![(1, 3.0)]),
+        ]);
+        assert_eq!(totals[&1], 15.0);
+        assert_eq!(totals[&2], 3.0);
+    }
+
+    #[test]
+    fn test_tag_totals() {
+        let mut trip = expense(1, vec![(1, 10.0)]);
+        trip.set_tags(vec!["holiday".to_string(), "reimbursable".to_string()]);
+        let mut souvenir = expense(1, vec![(1, 4.0)]);
+        souvenir.set_tags(vec!["holiday".to_string()]);
+
+        let totals = tag_totals(&[trip, souvenir, expense(1, vec![(1, 1.0)])]);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["holiday"], 14.0);
+        assert_eq!(totals["reimbursable"], 10.0);
+    }
+}
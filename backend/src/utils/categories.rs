@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use datatypes::{Category, CategoryNode};
+
+fn children_of(categories: &[Category], parent_id: Option<i32>) -> Vec<CategoryNode> {
+    categories
+        .iter()
+        .filter(|c| c.parent_id() == parent_id)
+        .map(|c| CategoryNode::new(c.clone(), children_of(categories, Some(c.id()))))
+        .collect()
+}
+
+/// Arranges the categories as a forest of top level categories. A category whose parent is not
+/// in the list is treated as top level so nothing gets lost.
+pub fn category_tree(categories: &[Category]) -> Vec<CategoryNode> {
+    categories
+        .iter()
+        .filter(|c| match c.parent_id() {
+            None => true,
+            Some(parent_id) => !categories.iter().any(|p| p.id() == parent_id),
+        })
+        .map(|c| CategoryNode::new(c.clone(), children_of(categories, Some(c.id()))))
+        .collect()
+}
+
+/// The category and all of its sub-categories, however deep.
+pub fn descendant_ids(categories: &[Category], id: i32) -> Vec<i32> {
+    let mut ids = vec![id];
+    let mut i = 0;
+    while i < ids.len() {
+        let parent_id = ids[i];
+        for category in categories.iter().filter(|c| c.parent_id() == Some(parent_id)) {
+            if !ids.contains(&category.id()) {
+                ids.push(category.id());
+            }
+        }
+        i += 1;
+    }
+    ids
+}
+
+/// Adds the totals of every sub-category to its ancestors, so each level shows what was spent
+/// in it and below it.
+pub fn rollup_totals(categories: &[Category], totals: &HashMap<i32, f64>) -> HashMap<i32, f64> {
+    categories
+        .iter()
+        .map(|c| {
+            let total = descendant_ids(categories, c.id())
+                .iter()
+                .map(|id| totals.get(id).copied().unwrap_or(0.0))
+                .sum();
+            (c.id(), total)
+        })
+        .collect()
+}
+
+/// Flattens the tree depth first, pairing every category with its depth.
+pub fn flatten_tree(nodes: &[CategoryNode]) -> Vec<(usize, &Category)> {
+    fn walk<'a>(nodes: &'a [CategoryNode], depth: usize, out: &mut Vec<(usize, &'a Category)>) {
+        for node in nodes {
+            out.push((depth, node.category()));
+            walk(node.children(), depth + 1, out);
+        }
+    }
+    let mut out = vec![];
+    walk(nodes, 0, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rocket::serde::json::serde_json;
+
+    /// Builds categories through JSON since ids are only ever assigned by the database.
+    fn category(id: i32, name: &str, parent_id: Option<i32>) -> Category {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "description": "",
+            "parent_id": parent_id,
+            "created_at": "2023-01-01T00:00:00",
+        }))
+        .unwrap()
+    }
+
+    fn categories() -> Vec<Category> {
+        vec![
+            category(1, "food", None),
+            category(2, "groceries", Some(1)),
+            category(3, "restaurants", Some(1)),
+            category(4, "fast food", Some(3)),
+            category(5, "fuel", None),
+        ]
+    }
+
+    #[test]
+    fn test_category_tree() {
+        let tree = category_tree(&categories());
+        let flat = flatten_tree(&tree)
+            .into_iter()
+            .map(|(depth, c)| (depth, c.name().as_str()))
+            .collect::<Vec<(usize, &str)>>();
+        assert_eq!(
+            flat,
+            vec![(0, "food"), (1, "groceries"), (1, "restaurants"), (2, "fast food"), (0, "fuel")]
+        );
+
+        // Orphans show up at the top level.
+        let tree = category_tree(&categories()[1..]);
+        assert_eq!(tree.len(), 3);
+    }
+
+    #[test]
+    fn test_descendant_ids() {
+        assert_eq!(descendant_ids(&categories(), 1), vec![1, 2, 3, 4]);
+        assert_eq!(descendant_ids(&categories(), 3), vec![3, 4]);
+        assert_eq!(descendant_ids(&categories(), 5), vec![5]);
+    }
+
+    #[test]
+    fn test_rollup_totals() {
+        let totals = HashMap::from([(1, 1.0), (2, 10.0), (4, 5.0), (5, 20.0)]);
+        let rolled = rollup_totals(&categories(), &totals);
+        assert_eq!(rolled[&1], 16.0);
+        assert_eq!(rolled[&2], 10.0);
+        assert_eq!(rolled[&3], 5.0);
+        assert_eq!(rolled[&5], 20.0);
+    }
+}
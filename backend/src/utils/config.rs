@@ -1,8 +1,9 @@
 extern crate toml;
 
 use serde::Deserialize;
+use std::ffi::OsString;
 use std::io::Read;
-use std::{fs, path};
+use std::{fmt, fs, net, path};
 
 use crate::database;
 
@@ -12,13 +13,119 @@ pub struct Config {
     log_level: log::LevelFilter,
     db_config: database::DbConfig,
 
-    #[serde(rename = "category")]
+    /// Where uploaded attachments are stored, relative to the working directory unless absolute.
+    #[serde(default = "default_attachments_dir")]
+    attachments_dir: path::PathBuf,
+
+    /// Run `config sync` without pruning every time the program starts.
+    #[serde(default)]
+    sync_on_startup: bool,
+
+    #[serde(default)]
+    web: WebConfig,
+
+    #[serde(default)]
+    webhooks: WebhookConfig,
+
+    #[serde(default, rename = "user")]
+    users: Vec<UserConfig>,
+
+    #[serde(default, rename = "category")]
     categories: Vec<CategoryConfig>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct WebConfig {
+    address: net::IpAddr,
+    port: u16,
+
+    /// Serve the built frontend from this directory, e.g. `frontend/dist`. Takes precedence over
+    /// the copy embedded by the `embed_frontend` feature.
+    frontend_dir: Option<path::PathBuf>,
+}
+
+impl WebConfig {
+    pub fn address(&self) -> net::IpAddr {
+        self.address
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn frontend_dir(&self) -> Option<&path::Path> {
+        self.frontend_dir.as_deref()
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            address: net::Ipv4Addr::UNSPECIFIED.into(),
+            port: 8080,
+            frontend_dir: None,
+        }
+    }
+}
+
+/// How persistent the webhook deliveries are.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Tries per event, including the first one.
+    attempts: u32,
+
+    /// The wait before the first retry, doubled for every retry after it.
+    backoff_secs: u64,
+
+    /// How long the receiver gets to answer.
+    timeout_secs: u64,
+}
+
+impl WebhookConfig {
+    pub fn new(attempts: u32, backoff_secs: u64, timeout_secs: u64) -> Self {
+        Self {
+            attempts,
+            backoff_secs,
+            timeout_secs,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// The wait after the given failed attempt, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> std::time::Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        std::time::Duration::from_secs(self.backoff_secs.saturating_mul(factor))
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout_secs)
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            backoff_secs: 2,
+            timeout_secs: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct UserConfig {
+    pub username: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct CategoryConfig {
     pub name: String,
+    #[serde(default)]
     pub description: String,
 }
 
@@ -31,12 +138,35 @@ impl Config {
         &self.db_config
     }
 
+    pub fn attachments_dir(&self) -> &path::Path {
+        &self.attachments_dir
+    }
+
+    pub fn web(&self) -> &WebConfig {
+        &self.web
+    }
+
+    pub fn webhooks(&self) -> &WebhookConfig {
+        &self.webhooks
+    }
+
+    pub fn sync_on_startup(&self) -> bool {
+        self.sync_on_startup
+    }
+
+    pub fn users(&self) -> &Vec<UserConfig> {
+        &self.users
+    }
 
     pub fn categories(&self) -> &Vec<CategoryConfig> {
         &self.categories
     }
 }
 
+fn default_attachments_dir() -> path::PathBuf {
+    path::PathBuf::from("attachments")
+}
+
 pub fn read_config<P: AsRef<path::Path>, T: for<'de> Deserialize<'de>>(
     path: P
 ) -> Result<T, String> {
@@ -47,6 +177,254 @@ pub fn read_config<P: AsRef<path::Path>, T: for<'de> Deserialize<'de>>(
     toml::from_str(&contents).map_err(|e| e.to_string())
 }
 
+/// Prefix of the environment variables overriding the config file.
+pub const ENV_PREFIX: &str = "EXPENSES_";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    String,
+    Integer,
+    Bool,
+}
+
+/// Every scalar setting: its path in the file, its type, and whether it has to be set. The
+/// environment variable for a setting is its path joined by `_`, uppercased and prefixed with
+/// `ENV_PREFIX`, so `db_config.database_url` becomes `EXPENSES_DB_CONFIG_DATABASE_URL`. Any of the
+/// string settings can also be read from a file by setting `<key>_file` instead.
+const SETTINGS: &[(&[&str], Kind, bool)] = &[
+    (&["log_level"], Kind::String, true),
+    (&["attachments_dir"], Kind::String, false),
+    (&["sync_on_startup"], Kind::Bool, false),
+    (&["db_config", "database_url"], Kind::String, true),
+    (&["db_config", "max_connections"], Kind::Integer, true),
+    (&["web", "address"], Kind::String, false),
+    (&["web", "port"], Kind::Integer, false),
+    (&["web", "frontend_dir"], Kind::String, false),
+    (&["webhooks", "attempts"], Kind::Integer, false),
+    (&["webhooks", "backoff_secs"], Kind::Integer, false),
+    (&["webhooks", "timeout_secs"], Kind::Integer, false),
+];
+
+fn env_name(path: &[&str]) -> String {
+    format!("{}{}", ENV_PREFIX, path.join("_").to_uppercase())
+}
+
+/// Everything wrong with the configuration, reported together so it can be fixed in one go.
+#[derive(Debug)]
+pub struct ConfigError {
+    source: String,
+    problems: Vec<(String, String)>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration from `{}`:", self.source)?;
+        for (key, problem) in &self.problems {
+            writeln!(f, "  - {}: {}", key, problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn table_at<'a>(table: &'a mut toml::Table, path: &[&str]) -> &'a mut toml::Table {
+    let mut table = table;
+    for key in path {
+        let entry = table
+            .entry(key.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        table = entry.as_table_mut().unwrap();
+    }
+    table
+}
+
+fn get<'a>(table: &'a toml::Table, path: &[&str]) -> Option<&'a toml::Value> {
+    let (key, parents) = path.split_last()?;
+    let mut table = table;
+    for parent in parents {
+        table = table.get(*parent)?.as_table()?;
+    }
+    table.get(*key)
+}
+
+fn get_table_mut<'a>(table: &'a mut toml::Table, path: &[&str]) -> Option<&'a mut toml::Table> {
+    let mut table = table;
+    for key in path {
+        table = table.get_mut(*key)?.as_table_mut()?;
+    }
+    Some(table)
+}
+
+/// Overrides settings with the matching `EXPENSES_*` variables. Variables with the prefix that
+/// don't name a setting, or whose value isn't UTF-8, are reported rather than silently ignored.
+fn apply_env<I>(table: &mut toml::Table, vars: I, problems: &mut Vec<(String, String)>)
+where
+    I: IntoIterator<Item = (OsString, OsString)>,
+{
+    for (name, value) in vars {
+        // A name that isn't UTF-8 can't be one of ours.
+        let Some(name) = name.to_str().filter(|name| name.starts_with(ENV_PREFIX)) else {
+            continue;
+        };
+        let name = name.to_string();
+        let setting = SETTINGS.iter().find_map(|(path, kind, _)| {
+            if env_name(path) == name {
+                Some((*path, *kind, false))
+            } else if *kind == Kind::String && format!("{}_FILE", env_name(path)) == name {
+                Some((*path, Kind::String, true))
+            } else {
+                None
+            }
+        });
+        let Some((path, kind, is_file)) = setting else {
+            problems.push((name, "is not a known setting".to_string()));
+            continue;
+        };
+        let Ok(value) = value.into_string() else {
+            problems.push((name, "is not valid UTF-8".to_string()));
+            continue;
+        };
+
+        let value = match kind {
+            Kind::String => toml::Value::String(value),
+            Kind::Integer => match value.parse::<i64>() {
+                Ok(value) => toml::Value::Integer(value),
+                Err(_) => {
+                    problems.push((name, format!("expected an integer, found `{}`", value)));
+                    continue;
+                }
+            },
+            Kind::Bool => match value.parse::<bool>() {
+                Ok(value) => toml::Value::Boolean(value),
+                Err(_) => {
+                    problems.push((name, format!("expected true or false, found `{}`", value)));
+                    continue;
+                }
+            },
+        };
+        // The variable wins over the file whichever of `<key>` and `<key>_file` either of them uses.
+        let (key, parents) = path.split_last().unwrap();
+        let table = table_at(table, parents);
+        if is_file {
+            table.remove(*key);
+            table.insert(format!("{}_file", key), value);
+        } else {
+            table.remove(&format!("{}_file", key));
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Replaces the `<key>_file` of every string setting with the trimmed contents of the file it
+/// names, so secrets such as the database url can live outside the config.
+fn read_secret_files(table: &mut toml::Table, problems: &mut Vec<(String, String)>) {
+    for (path, kind, _) in SETTINGS {
+        if *kind != Kind::String {
+            continue;
+        }
+        let (key, parents) = path.split_last().unwrap();
+        let file_key = format!("{}_file", key);
+        let Some(value) = get_table_mut(table, parents).and_then(|table| table.remove(&file_key)) else {
+            continue;
+        };
+        let full_key = format!("{}_file", path.join("."));
+        let table = get_table_mut(table, parents).unwrap();
+        if table.contains_key(*key) {
+            problems.push((full_key, format!("can't be combined with `{}`", path.join("."))));
+            continue;
+        }
+        let Some(secret_path) = value.as_str() else {
+            problems.push((full_key, "expected a file path".to_string()));
+            continue;
+        };
+        match fs::read_to_string(secret_path) {
+            Ok(secret) => {
+                table.insert(key.to_string(), toml::Value::String(secret.trim_end().to_string()));
+            }
+            Err(e) => problems.push((full_key, format!("failed to read `{}`: {}", secret_path, e))),
+        }
+    }
+}
+
+fn validate(table: &toml::Table, problems: &mut Vec<(String, String)>) {
+    for (path, kind, required) in SETTINGS {
+        let key = path.join(".");
+        let Some(value) = get(table, path) else {
+            if *required {
+                problems.push((key, format!("is missing, set it in the file or with `{}`", env_name(path))));
+            }
+            continue;
+        };
+        let type_ok = match kind {
+            Kind::String => value.is_str(),
+            Kind::Integer => value.is_integer(),
+            Kind::Bool => value.is_bool(),
+        };
+        if !type_ok {
+            problems.push((key, format!("expected {:?}, found `{}`", kind, value).to_lowercase()));
+        }
+    }
+
+    if let Some(level) = get(table, &["log_level"]).and_then(|v| v.as_str()) {
+        if !["trace", "debug", "info", "warn", "error"].contains(&level) {
+            problems.push((
+                "log_level".to_string(),
+                format!("`{}` is not one of trace, debug, info, warn or error", level),
+            ));
+        }
+    }
+    if let Some(n) = get(table, &["db_config", "max_connections"]).and_then(|v| v.as_integer()) {
+        if n < 1 || n > u32::MAX as i64 {
+            problems.push(("db_config.max_connections".to_string(), format!("`{}` is not a positive number", n)));
+        }
+    }
+    if let Some(port) = get(table, &["web", "port"]).and_then(|v| v.as_integer()) {
+        if u16::try_from(port).is_err() {
+            problems.push(("web.port".to_string(), format!("`{}` is not a valid port", port)));
+        }
+    }
+    if let Some(address) = get(table, &["web", "address"]).and_then(|v| v.as_str()) {
+        if address.parse::<net::IpAddr>().is_err() {
+            problems.push(("web.address".to_string(), format!("`{}` is not an IP address", address)));
+        }
+    }
+}
+
+/// Builds the config from the file at `path`, then `EXPENSES_*` environment variables, then
+/// secret files, in that order of precedence from lowest to highest.
+pub fn load_config<P: AsRef<path::Path>>(path: P) -> Result<Config, ConfigError> {
+    load_config_with(path, std::env::vars_os())
+}
+
+fn load_config_with<P, I>(path: P, vars: I) -> Result<Config, ConfigError>
+where
+    P: AsRef<path::Path>,
+    I: IntoIterator<Item = (OsString, OsString)>,
+{
+    let source = path.as_ref().display().to_string();
+    let error = |problems| ConfigError {
+        source: source.clone(),
+        problems,
+    };
+
+    let mut table: toml::Table =
+        read_config(&path).map_err(|e| error(vec![("file".to_string(), e)]))?;
+    let mut problems = vec![];
+    apply_env(&mut table, vars, &mut problems);
+    read_secret_files(&mut table, &mut problems);
+    validate(&table, &mut problems);
+    if !problems.is_empty() {
+        return Err(error(problems));
+    }
+
+    Config::deserialize(toml::Value::Table(table))
+        .map_err(|e| error(vec![("config".to_string(), e.message().to_string())]))
+}
+
 fn parse_log_filter<'de, D>(deserializer: D) -> Result<log::LevelFilter, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -64,3 +442,114 @@ where
         ))),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(name: &str, contents: &str) -> path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        vars.iter().map(|(k, v)| (OsString::from(k), OsString::from(v))).collect()
+    }
+
+    const FILE: &str = r#"
+    log_level = "info"
+
+    [db_config]
+    database_url = "postgres://localhost/file"
+    max_connections = 5
+    "#;
+
+    #[test]
+    fn test_env_overrides() {
+        let path = write("env.toml", FILE);
+        let config = load_config_with(
+            &path,
+            vars(&[
+                ("EXPENSES_DB_CONFIG_MAX_CONNECTIONS", "7"),
+                ("EXPENSES_WEB_PORT", "9000"),
+                ("EXPENSES_SYNC_ON_STARTUP", "true"),
+                ("EXPENSES_WEBHOOKS_ATTEMPTS", "3"),
+                ("PATH", "/bin"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.db_config().max_connections(), 7);
+        assert_eq!(config.webhooks().attempts(), 3);
+        assert_eq!(config.webhooks().backoff(3), std::time::Duration::from_secs(8));
+        assert_eq!(config.db_config().database_url(), "postgres://localhost/file");
+        assert_eq!(config.web().port(), 9000);
+        assert_eq!(config.web().address(), WebConfig::default().address());
+        assert!(config.sync_on_startup());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_secret_file() {
+        let secret = write("secret", "postgres://localhost/secret\n");
+        let path = write("secret.toml", FILE);
+        let config = load_config_with(
+            &path,
+            vars(&[("EXPENSES_DB_CONFIG_DATABASE_URL_FILE", secret.to_str().unwrap())]),
+        )
+        .unwrap();
+        assert_eq!(config.db_config().database_url(), "postgres://localhost/secret");
+
+        // Setting both in the same place is ambiguous.
+        let contents = FILE.replace(
+            "max_connections",
+            &format!("database_url_file = {:?}\n    max_connections", secret),
+        );
+        let path = write("conflict.toml", &contents);
+        let error = load_config_with(&path, vars(&[])).unwrap_err();
+        assert_eq!(error.problems[0].0, "db_config.database_url_file");
+
+        // Only string settings are read from files, anything else named `_file` is left alone.
+        let contents = format!("{}\n[web]\nport_file = \"/nonexistent\"\n", FILE);
+        let path = write("port_file.toml", &contents);
+        assert_eq!(load_config_with(&path, vars(&[])).unwrap().web().port(), WebConfig::default().port());
+        fs::remove_file(path).unwrap();
+        fs::remove_file(secret).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_env() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let path = write("non_utf8.toml", FILE);
+        let mut env = vars(&[]);
+        env.push((OsString::from_vec(b"EXPENSES_\xff".to_vec()), OsString::from("x")));
+        env.push((OsString::from("EXPENSES_WEB_ADDRESS"), OsString::from_vec(b"\xff".to_vec())));
+        let error = load_config_with(&path, env).unwrap_err();
+        assert_eq!(error.problems, vec![("EXPENSES_WEB_ADDRESS".to_string(), "is not valid UTF-8".to_string())]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_problems_are_collected() {
+        let path = write("invalid.toml", "log_level = \"loud\"\n[db_config]\nmax_connections = 0\n");
+        let error = load_config_with(
+            &path,
+            vars(&[("EXPENSES_WEB_PORT", "eighty"), ("EXPENSES_COLOUR", "blue")]),
+        )
+        .unwrap_err();
+        let keys = error.problems.iter().map(|(k, _)| k.as_str()).collect::<Vec<&str>>();
+        assert_eq!(
+            keys,
+            vec![
+                "EXPENSES_WEB_PORT",
+                "EXPENSES_COLOUR",
+                "db_config.database_url",
+                "log_level",
+                "db_config.max_connections",
+            ]
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,322 @@
+//! Tells the open browser tabs, and anything else listening, what changed. Writes made through
+//! the web server are published on an `EventBus` by wrapping its repository in a
+//! `PublishingRepository`, routes that write past the repository publish themselves. A CLI command
+//! publishes on a bus of its own, which delivers to the webhooks before the command exits; the
+//! browser tabs only see those changes after a reload.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datatypes::{AuditEntry, AuditFilter, Category, Event, EventKind, Expense, Filter, User};
+use rocket::serde::json::serde_json;
+use tokio::sync::broadcast;
+
+use crate::database::repository::{
+    AuditRepository, CategoryRepository, Dataset, DynRepository, ExpenseRepository, ImportRepository, Imported,
+    ResetRepository, UserRepository,
+};
+use crate::database::References;
+use crate::utils::audit::Actor;
+
+/// How many events a slow subscriber may fall behind before it misses some.
+const CAPACITY: usize = 256;
+
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    /// Does nothing if no one is subscribed.
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Every event published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Publishes `entity` as it is now, for routes that write past the repository.
+    pub fn publish_entity<T: serde::Serialize>(&self, kind: EventKind, id: i32, entity: &T) {
+        let data = serde_json::to_value(entity).unwrap_or_default();
+        self.publish(Event::new(kind, Some(id), data));
+    }
+
+    /// Publishes the deletion of `id`, for routes that write past the repository as well.
+    pub fn deleted(&self, kind: EventKind, id: i32) {
+        self.publish(Event::new(kind, Some(id), serde_json::Value::Null));
+    }
+
+    fn merged(&self, kind: EventKind, id: i32, into: i32) {
+        self.publish(Event::new(kind, Some(id), serde_json::json!({ "merged_into": into })));
+    }
+}
+
+/// Passes everything on to the wrapped repository and publishes what was changed. Merges only
+/// publish the deletion, not every expense that moved over.
+pub struct PublishingRepository {
+    inner: DynRepository,
+    events: EventBus,
+}
+
+impl PublishingRepository {
+    pub fn wrap(inner: DynRepository, events: EventBus) -> DynRepository {
+        Arc::new(Self { inner, events })
+    }
+
+    async fn category_updated(&self, id: i32) -> Result<(), sqlx::Error> {
+        let categories = self.inner.get_categories().await?;
+        if let Some(category) = categories.iter().find(|c| c.id() == id) {
+            self.events.publish_entity(EventKind::CategoryUpdated, id, category);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for PublishingRepository {
+    async fn insert_user(&self, username: String) -> Result<User, sqlx::Error> {
+        let user = self.inner.insert_user(username).await?;
+        self.events.publish_entity(EventKind::UserCreated, user.id(), &user);
+        Ok(user)
+    }
+
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        self.inner.get_users().await
+    }
+
+    async fn get_user(&self, id: i32) -> Result<Option<User>, sqlx::Error> {
+        self.inner.get_user(id).await
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let deleted = self.inner.delete_user(id).await?;
+        if deleted {
+            self.events.deleted(EventKind::UserDeleted, id);
+        }
+        Ok(deleted)
+    }
+
+    async fn rename_user(&self, id: i32, username: String) -> Result<Option<User>, sqlx::Error> {
+        let user = self.inner.rename_user(id, username).await?;
+        if let Some(user) = &user {
+            self.events.publish_entity(EventKind::UserUpdated, id, user);
+        }
+        Ok(user)
+    }
+
+    async fn get_user_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        self.inner.get_user_references(id).await
+    }
+
+    async fn merge_users(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let merged = self.inner.merge_users(from, into).await?;
+        if merged {
+            self.events.merged(EventKind::UserDeleted, from, into);
+        }
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for PublishingRepository {
+    async fn insert_category(
+        &self,
+        name: String,
+        description: &str,
+        parent_id: Option<i32>,
+    ) -> Result<Category, sqlx::Error> {
+        let category = self.inner.insert_category(name, description, parent_id).await?;
+        self.events.publish_entity(EventKind::CategoryCreated, category.id(), &category);
+        Ok(category)
+    }
+
+    async fn get_categories(&self) -> Result<Vec<Category>, sqlx::Error> {
+        self.inner.get_categories().await
+    }
+
+    async fn delete_category(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let deleted = self.inner.delete_category(id).await?;
+        if deleted {
+            self.events.deleted(EventKind::CategoryDeleted, id);
+        }
+        Ok(deleted)
+    }
+
+    async fn rename_category(&self, id: i32, name: String) -> Result<bool, sqlx::Error> {
+        let renamed = self.inner.rename_category(id, name).await?;
+        if renamed {
+            self.category_updated(id).await?;
+        }
+        Ok(renamed)
+    }
+
+    async fn update_category_description(&self, id: i32, description: &str) -> Result<bool, sqlx::Error> {
+        let updated = self.inner.update_category_description(id, description).await?;
+        if updated {
+            self.category_updated(id).await?;
+        }
+        Ok(updated)
+    }
+
+    async fn set_category_parent(&self, id: i32, parent_id: Option<i32>) -> Result<bool, sqlx::Error> {
+        let moved = self.inner.set_category_parent(id, parent_id).await?;
+        if moved {
+            self.category_updated(id).await?;
+        }
+        Ok(moved)
+    }
+
+    async fn get_category_references(&self, id: i32) -> Result<References, sqlx::Error> {
+        self.inner.get_category_references(id).await
+    }
+
+    async fn merge_categories(&self, from: i32, into: i32) -> Result<bool, sqlx::Error> {
+        let merged = self.inner.merge_categories(from, into).await?;
+        if merged {
+            self.events.merged(EventKind::CategoryDeleted, from, into);
+        }
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl ExpenseRepository for PublishingRepository {
+    async fn insert_expense(&self, expense: Expense) -> Result<Expense, sqlx::Error> {
+        let expense = self.inner.insert_expense(expense).await?;
+        self.events.publish_entity(EventKind::ExpenseCreated, expense.id(), &expense);
+        Ok(expense)
+    }
+
+    async fn insert_expenses(&self, expenses: Vec<Expense>) -> Result<Vec<Expense>, sqlx::Error> {
+        let expenses = self.inner.insert_expenses(expenses).await?;
+        for expense in &expenses {
+            self.events.publish_entity(EventKind::ExpenseCreated, expense.id(), expense);
+        }
+        Ok(expenses)
+    }
+
+    async fn get_expense(&self, id: i32) -> Result<Option<Expense>, sqlx::Error> {
+        self.inner.get_expense(id).await
+    }
+
+    async fn get_expenses(&self, filter: Option<Filter>) -> Result<Vec<Expense>, sqlx::Error> {
+        self.inner.get_expenses(filter).await
+    }
+
+    async fn update_expense(&self, expense: Expense) -> Result<Option<Expense>, sqlx::Error> {
+        let expense = self.inner.update_expense(expense).await?;
+        if let Some(expense) = &expense {
+            self.events.publish_entity(EventKind::ExpenseUpdated, expense.id(), expense);
+        }
+        Ok(expense)
+    }
+
+    async fn delete_expense(&self, id: i32) -> Result<bool, sqlx::Error> {
+        let deleted = self.inner.delete_expense(id).await?;
+        if deleted {
+            self.events.deleted(EventKind::ExpenseDeleted, id);
+        }
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl ResetRepository for PublishingRepository {
+    async fn insert_last_reset(&self) -> Result<(), sqlx::Error> {
+        self.inner.insert_last_reset().await?;
+        self.events.publish(Event::new(EventKind::Reset, None, serde_json::Value::Null));
+        Ok(())
+    }
+
+    async fn get_last_reset(&self) -> Result<chrono::NaiveDateTime, sqlx::Error> {
+        self.inner.get_last_reset().await
+    }
+}
+
+#[async_trait]
+impl ImportRepository for PublishingRepository {
+    async fn import_dataset(&self, dataset: Dataset) -> Result<Imported, sqlx::Error> {
+        let imported = self.inner.import_dataset(dataset).await?;
+        for (kind, id, data) in imported.created() {
+            self.events.publish(Event::new(kind, Some(id), data));
+        }
+        Ok(imported)
+    }
+
+    async fn get_import_fingerprints(&self) -> Result<HashSet<String>, sqlx::Error> {
+        self.inner.get_import_fingerprints().await
+    }
+}
+
+#[async_trait]
+impl AuditRepository for PublishingRepository {
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<AuditEntry, sqlx::Error> {
+        self.inner.insert_audit_entry(entry).await
+    }
+
+    async fn get_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        self.inner.get_audit_entries(filter).await
+    }
+
+    fn with_actor(&self, actor: &Actor) -> Option<DynRepository> {
+        let inner = self.inner.with_actor(actor)?;
+        Some(Self::wrap(inner, self.events.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::MemoryRepository;
+
+    #[tokio::test]
+    async fn test_publishes_changes() {
+        let events = EventBus::new();
+        let mut receiver = events.subscribe();
+        let repository = PublishingRepository::wrap(Arc::new(MemoryRepository::new()), events);
+
+        let alice = repository.insert_user("alice".to_string()).await.unwrap();
+        let bob = repository.insert_user("bob".to_string()).await.unwrap();
+        let food = repository.insert_category("food".to_string(), "", None).await.unwrap();
+        assert!(repository.rename_category(food.id(), "groceries".to_string()).await.unwrap());
+        // Nothing changed, so nothing is published.
+        assert!(!repository.delete_category(-1).await.unwrap());
+        assert!(repository.merge_users(bob.id(), alice.id()).await.unwrap());
+        repository.insert_last_reset().await.unwrap();
+
+        let mut received = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            received.push(event);
+        }
+        let kinds = received.iter().map(|e| e.kind()).collect::<Vec<EventKind>>();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::UserCreated,
+                EventKind::UserCreated,
+                EventKind::CategoryCreated,
+                EventKind::CategoryUpdated,
+                EventKind::UserDeleted,
+                EventKind::Reset,
+            ]
+        );
+        assert_eq!(received[0].data()["username"], "alice");
+        assert_eq!(received[3].data()["name"], "groceries");
+        assert_eq!(received[4].id(), Some(bob.id()));
+        assert_eq!(received[4].data()["merged_into"], alice.id());
+    }
+}
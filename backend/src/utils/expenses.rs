@@ -0,0 +1,254 @@
+use datatypes::{Category, Expense, User, UserOwes};
+use rocket::serde::json::serde_json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct OwesRow {
+    user: String,
+    amount: f64,
+}
+
+/// An expense with user and category names resolved, as it is printed by the expense commands.
+#[derive(Debug, serde::Serialize)]
+struct ExpenseRow {
+    id: i32,
+    purchased_at: chrono::NaiveDate,
+    payer: String,
+    category: String,
+    amount: f64,
+    description: String,
+    user_owes: Vec<OwesRow>,
+    tags: Vec<String>,
+    created_at: chrono::NaiveDateTime,
+}
+
+/// The name of the user with `id`, or the id itself if there is no such user.
+pub(crate) fn username(users: &[User], id: i32) -> String {
+    users
+        .iter()
+        .find(|u| u.id() == id)
+        .map(|u| u.username().to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// The name of the category with `id`, or the id itself if there is no such category.
+pub(crate) fn category_name(categories: &[Category], id: i32) -> String {
+    categories
+        .iter()
+        .find(|c| c.id() == id)
+        .map(|c| c.name().to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn expense_row(expense: &Expense, users: &[User], categories: &[Category]) -> ExpenseRow {
+    ExpenseRow {
+        id: expense.id(),
+        purchased_at: *expense.purchased_at(),
+        payer: username(users, expense.user_id()),
+        category: category_name(categories, expense.category_id()),
+        amount: expense.amount(),
+        description: expense.description().to_string(),
+        user_owes: expense
+            .user_owes()
+            .iter()
+            .map(|o| OwesRow {
+                user: username(users, o.user_id()),
+                amount: o.amount(),
+            })
+            .collect(),
+        tags: expense.tags().clone(),
+        created_at: *expense.created_at(),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+const COLUMNS: [&str; 8] = ["id", "date", "payer", "category", "amount", "description", "split", "tags"];
+
+fn row_fields(row: &ExpenseRow, split_separator: &str) -> Vec<String> {
+    vec![
+        row.id.to_string(),
+        row.purchased_at.format("%d/%m/%Y").to_string(),
+        row.payer.clone(),
+        row.category.clone(),
+        format!("{:.2}", row.amount),
+        row.description.clone(),
+        row.user_owes
+            .iter()
+            .map(|o| format!("{} {:.2}", o.user, o.amount))
+            .collect::<Vec<String>>()
+            .join(split_separator),
+        row.tags.join(split_separator),
+    ]
+}
+
+/// Renders the expenses in the requested format, resolving ids to names.
+pub fn format_expenses(
+    expenses: &[Expense],
+    users: &[User],
+    categories: &[Category],
+    format: OutputFormat,
+) -> anyhow::Result<String> {
+    let rows = expenses
+        .iter()
+        .map(|e| expense_row(e, users, categories))
+        .collect::<Vec<ExpenseRow>>();
+
+    let output = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(&rows)?,
+        OutputFormat::Csv => {
+            let mut lines = vec![COLUMNS.join(",")];
+            for row in &rows {
+                let fields = row_fields(row, ";");
+                lines.push(fields.iter().map(|f| csv_field(f)).collect::<Vec<String>>().join(","));
+            }
+            lines.join("\n")
+        }
+        OutputFormat::Table => {
+            let table = rows.iter().map(|r| row_fields(r, ", ")).collect::<Vec<Vec<String>>>();
+            let mut widths = COLUMNS.map(|c| c.len());
+            for fields in &table {
+                for (width, field) in widths.iter_mut().zip(fields) {
+                    *width = (*width).max(field.chars().count());
+                }
+            }
+            let render = |fields: Vec<String>| {
+                fields
+                    .iter()
+                    .zip(widths)
+                    .map(|(f, w)| format!("{:<w$}", f, w = w))
+                    .collect::<Vec<String>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            };
+            let mut lines = vec![render(COLUMNS.map(String::from).to_vec())];
+            lines.push(render(widths.map(|w| "-".repeat(w)).to_vec()));
+            for fields in table {
+                lines.push(render(fields));
+            }
+            lines.join("\n")
+        }
+    };
+    Ok(output)
+}
+
+/// One `--split` entry: `name` takes an equal share of whatever isn't given out explicitly,
+/// `name=amount` owes exactly that amount.
+pub fn parse_split_entry(entry: &str) -> anyhow::Result<(String, Option<f64>)> {
+    match entry.split_once('=') {
+        Some((name, amount)) => {
+            let amount = amount
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| anyhow::anyhow!("Invalid split amount `{}`.", amount))?;
+            Ok((name.trim().to_string(), Some(amount)))
+        }
+        None => Ok((entry.trim().to_string(), None)),
+    }
+}
+
+/// Works out who owes what. The payer always takes part; unless they are given a fixed amount
+/// they share the remainder equally with everyone else listed without one.
+pub fn split_amount(
+    total: f64,
+    payer_id: i32,
+    split: &[(i32, Option<f64>)],
+) -> anyhow::Result<Vec<UserOwes>> {
+    let mut shares: Vec<(i32, Option<f64>)> = vec![];
+    if !split.iter().any(|(id, _)| *id == payer_id) {
+        shares.push((payer_id, None));
+    }
+    for (id, amount) in split {
+        if shares.iter().any(|(existing, _)| existing == id) {
+            return Err(anyhow::anyhow!("User `{}` is listed twice in the split.", id));
+        }
+        shares.push((*id, *amount));
+    }
+
+    let fixed: f64 = shares.iter().filter_map(|(_, a)| *a).sum();
+    let remainder = total - fixed;
+    let sharing = shares.iter().filter(|(_, a)| a.is_none()).count();
+    if remainder < -0.001 {
+        return Err(anyhow::anyhow!(
+            "Split amounts add up to {} which is more than the total {}.",
+            fixed,
+            total
+        ));
+    }
+    if sharing == 0 && remainder > 0.001 {
+        return Err(anyhow::anyhow!(
+            "Split amounts add up to {} instead of the total {}.",
+            fixed,
+            total
+        ));
+    }
+
+    Ok(shares
+        .into_iter()
+        .map(|(id, amount)| {
+            let amount = amount.unwrap_or(remainder / sharing as f64);
+            UserOwes::new(id, -1, amount)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn amounts(owes: &[UserOwes]) -> Vec<(i32, f64)> {
+        owes.iter().map(|o| (o.user_id(), o.amount())).collect()
+    }
+
+    #[test]
+    fn test_split_amount_equal() {
+        let owes = split_amount(30.0, 1, &[(2, None), (3, None)]).unwrap();
+        assert_eq!(amounts(&owes), vec![(1, 10.0), (2, 10.0), (3, 10.0)]);
+
+        let owes = split_amount(30.0, 1, &[]).unwrap();
+        assert_eq!(amounts(&owes), vec![(1, 30.0)]);
+    }
+
+    #[test]
+    fn test_split_amount_fixed() {
+        let owes = split_amount(30.0, 1, &[(2, Some(20.0)), (3, None)]).unwrap();
+        assert_eq!(amounts(&owes), vec![(1, 5.0), (2, 20.0), (3, 5.0)]);
+
+        let owes = split_amount(30.0, 1, &[(1, Some(10.0)), (2, Some(20.0))]).unwrap();
+        assert_eq!(amounts(&owes), vec![(1, 10.0), (2, 20.0)]);
+    }
+
+    #[test]
+    fn test_split_amount_invalid() {
+        assert!(split_amount(30.0, 1, &[(2, Some(40.0))]).is_err());
+        assert!(split_amount(30.0, 1, &[(1, Some(10.0)), (2, Some(10.0))]).is_err());
+        assert!(split_amount(30.0, 1, &[(2, None), (2, None)]).is_err());
+    }
+
+    #[test]
+    fn test_parse_split_entry() {
+        assert_eq!(parse_split_entry("bob").unwrap(), ("bob".to_string(), None));
+        assert_eq!(parse_split_entry("bob=2.5").unwrap(), ("bob".to_string(), Some(2.5)));
+        assert!(parse_split_entry("bob=abc").is_err());
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("pizza"), "pizza");
+        assert_eq!(csv_field("pizza, beer"), "\"pizza, beer\"");
+        assert_eq!(csv_field("\"large\""), "\"\"\"large\"\"\"");
+    }
+}
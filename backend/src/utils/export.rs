@@ -1,33 +1,51 @@
 use std::fs::File;
-use std::io;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use rocket::serde::json::serde_json;
 use crate::database;
-use crate::utils::JsonFormat;
+use crate::database::repository::Repository;
+use crate::utils::{archive, attachments, attachments_archive_path, confirm, JsonFormat};
 
 
-pub async fn export_json(db_pool: &sqlx::PgPool, path: &str) -> anyhow::Result<()> {
-    let mut input = String::new();
+/// Writes the database to `path` as json. Attachment contents are bundled in a tar archive next
+/// to it, see `attachments_archive_path`. Without a Postgres pool there are no attachments.
+pub async fn export_json(
+    repository: &dyn Repository,
+    db_pool: Option<&sqlx::PgPool>,
+    attachments_dir: &Path,
+    path: &str,
+    yes: bool,
+) -> anyhow::Result<()> {
     let fp = PathBuf::from(path);
-    if fp.exists() {
-        println!("File already exists. Overwrite? [y/n]");
-        io::stdin().read_line(&mut input)?;
-        if input.trim() != "y" {
-            return Ok(());
-        }
+    if fp.exists() && !confirm(yes, "File already exists. Overwrite?")? {
+        return Ok(());
     }
-    input.clear();
 
-    let expenses = database::expense::get_expenses(&db_pool, None).await?;
-    let users = database::user::get_users(&db_pool).await?;
-    let categories = database::category::get_categories(&db_pool).await?;
+    let expenses = repository.get_expenses(None).await?;
+    let users = repository.get_users().await?;
+    let categories = repository.get_categories().await?;
+    let expense_attachments = match db_pool {
+        Some(db_pool) => database::attachment::get_all_attachments(db_pool).await?,
+        None => vec![],
+    };
+
+    if !expense_attachments.is_empty() {
+        let mut entries: Vec<(String, Vec<u8>)> = vec![];
+        for attachment in &expense_attachments {
+            if entries.iter().any(|(sha256, _)| sha256 == attachment.sha256()) {
+                continue;
+            }
+            let contents = attachments::read_blob(attachments_dir, attachment.sha256())?;
+            entries.push((attachment.sha256().clone(), contents));
+        }
+        archive::write_tar(File::create(attachments_archive_path(path))?, &entries)?;
+    }
 
-    let json_format = JsonFormat::new(users, categories, expenses);
+    let json_format = JsonFormat::new(users, categories, expenses, expense_attachments);
 
     let json = serde_json::to_string(&json_format)?;
     let mut file = File::create(fp)?;
     file.write_all(json.as_bytes())?;
 
     Ok(())
-}
\ No newline at end of file
+}
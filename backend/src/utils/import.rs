@@ -1,11 +1,18 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io;
 use std::io::BufRead;
 use std::path::{Path, PathBuf};
-use datatypes::{Category, Expense, User, UserOwes};
-use crate::database::category::get_categories;
-use crate::database::expense::insert_expense;
-use crate::database::user::get_users;
+use rocket::serde::json::serde_json;
+use datatypes::{Attachment, Category, DraftExpense, Expense, ImportBatch, User, UserOwes};
+use crate::database::draft::{
+    get_draft_expense, get_draft_fingerprints, insert_draft_expense_tx, insert_import_batch_tx,
+    set_draft_fingerprint_tx, update_draft_expense,
+};
+use crate::database::repository::{Dataset, NewCategory, Repository};
+use crate::utils::audit::Actor;
+use crate::utils::{archive, attachments, attachments_archive_path, confirm, JsonFormat};
+use crate::utils::statement::{self, StatementTransaction};
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
     where P: AsRef<Path>, {
@@ -19,7 +26,7 @@ fn get_user<'a>(username: &str, users: &'a Vec<User>) -> anyhow::Result<&'a User
             return Ok(user);
         }
     }
-    return Err(anyhow::anyhow!("User not found."));
+    Err(anyhow::anyhow!("User not found."))
 }
 
 fn get_category<'a>(category_name: &str, categories: &'a Vec<Category>) -> anyhow::Result<&'a Category> {
@@ -28,97 +35,956 @@ fn get_category<'a>(category_name: &str, categories: &'a Vec<Category>) -> anyho
             return Ok(category);
         }
     }
-    return Err(anyhow::anyhow!("Category not found."));
+    Err(anyhow::anyhow!("Category not found."))
 }
 
-pub async fn old_csv_format_import(db_pool: &sqlx::PgPool, path: &str) -> anyhow::Result<()> {
-    let mut input = String::new();
+/// How an import should treat the confirmation prompts and the database.
+pub struct ImportOptions {
+    /// Answer every prompt with yes.
+    pub yes: bool,
+    /// Report what would be imported without writing anything.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, serde::Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineStatus {
+    Created,
+    Duplicate,
+    Failed,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct LineReport {
+    line: i32,
+    status: LineStatus,
+    expense_id: Option<i32>,
+    errors: Vec<String>,
+}
+
+/// Outcome of a line based import. `committed` tells whether the `created` lines were actually
+/// written, which is not the case for dry runs or when any line failed.
+#[derive(Debug, serde::Serialize)]
+pub struct ImportReport {
+    path: String,
+    dry_run: bool,
+    committed: bool,
+    created: usize,
+    duplicates: usize,
+    failed: usize,
+    lines: Vec<LineReport>,
+}
+
+/// Identifies expenses that are the same purchase, used to skip lines that were imported before.
+fn duplicate_key(expense: &Expense) -> String {
+    format!(
+        "{}|{}|{:.3}|{}|{}",
+        expense.user_id(),
+        expense.category_id(),
+        expense.amount(),
+        expense.purchased_at(),
+        expense.description()
+    )
+}
+
+/// Imports an old csv format file atomically: either every new line is created in one
+/// transaction or, if any line fails, nothing is. Lines matching an existing expense are skipped.
+pub async fn old_csv_format_import(
+    repository: &dyn Repository,
+    path: &str,
+    options: &ImportOptions,
+    report_path: Option<&str>,
+) -> anyhow::Result<()> {
     let fp = PathBuf::from(path);
     if !fp.exists() {
         return Err(anyhow::anyhow!("File does not exist."));
     }
 
-    let users = get_users(&db_pool).await?;
+    let users = repository.get_users().await?;
     println!("=== Users:");
     for user in &users {
         println!(" -id: `{}`\tusername: `{}`", user.id(), user.username());
     }
-    println!("Use the following literal names to create expenses using the csv file? [y/n]");
-    io::stdin().read_line(&mut input)?;
-    if input.trim() != "y" {
+    if !confirm(options.yes, "Use the following literal names to create expenses using the csv file?")? {
         return Ok(());
     }
 
-    let categories = get_categories(&db_pool).await?;
+    let categories = repository.get_categories().await?;
     println!("=== Categories:");
     for category in &categories {
         println!(" -id: `{}`\tname: `{}`", category.id(), category.name());
     }
-    println!("Use the following literal names to create expenses using the csv file? [y/n]");
-    input.clear();
-    io::stdin().read_line(&mut input)?;
-    if input.trim() != "y" {
+    if !confirm(options.yes, "Use the following literal names to create expenses using the csv file?")? {
         return Ok(());
     }
 
+    // Each existing expense can only absorb one identical line, so repeated purchases in a new
+    // file are still created.
+    let mut existing: HashMap<String, usize> = HashMap::new();
+    for expense in repository.get_expenses(None).await? {
+        *existing.entry(duplicate_key(&expense)).or_default() += 1;
+    }
+
+    let mut lines = vec![];
     let mut expenses = vec![];
-    if let Ok(lines) = read_lines(fp) {
-        for line in lines.skip(1) {
-            if let Ok(l) = line {
-                let l = l.split(',').collect::<Vec<&str>>();
-                let username = l[0].clone();
-                let amount = l[1].clone().parse::<f64>()?;
-                let category = l[2].clone();
-                let date = l[3].clone();
-                let associated_users = l[5].trim().split('-').collect::<Vec<&str>>();
-
-                let mut associated_users = if associated_users.len() == 1 && associated_users[0].trim().is_empty() {
-                    vec![]
-                } else {
-                    associated_users
-                };
-                associated_users.push(username);
-
-                let description = l[6].clone();
-
-                let mut user_owes : Vec<UserOwes> = vec![];
-                let cost_per_user = amount / (associated_users.len() as f64);
-                for user in associated_users {
-                    let user = get_user(user, &users)?;
-                    user_owes.push(UserOwes::new(user.id(), -1, cost_per_user));
-                }
-                let expense = Expense::new(
-                    get_user(username, &users)?.id(),
-                    get_category(category, &categories)?.id(),
-                    amount,
-                    description.to_string(),
-                    chrono::NaiveDate::parse_from_str(date, "%d/%m/%Y")?,
-                    user_owes
-                );
-                expenses.push(expense);
+    for (i, line) in read_lines(fp)?.enumerate().skip(1) {
+        let line_no = i as i32 + 1;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let draft = old_csv_format_draft(line_no, &line, &users, &categories);
+        if !draft.errors().is_empty() {
+            lines.push(LineReport {
+                line: line_no,
+                status: LineStatus::Failed,
+                expense_id: None,
+                errors: draft.errors().clone(),
+            });
+            continue;
+        }
+
+        let expense = Expense::new(
+            draft.user_id().unwrap(),
+            draft.category_id().unwrap(),
+            draft.amount().unwrap(),
+            draft.description().to_string(),
+            *draft.purchased_at().unwrap(),
+            draft.user_owes().clone(),
+        );
+        let status = match existing.get_mut(&duplicate_key(&expense)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                LineStatus::Duplicate
+            }
+            _ => {
+                expenses.push((lines.len(), expense));
+                LineStatus::Created
             }
+        };
+        lines.push(LineReport {
+            line: line_no,
+            status,
+            expense_id: None,
+            errors: vec![],
+        });
+    }
+
+    let mut report = ImportReport {
+        path: path.to_string(),
+        dry_run: options.dry_run,
+        committed: false,
+        created: expenses.len(),
+        duplicates: lines.iter().filter(|l| l.status == LineStatus::Duplicate).count(),
+        failed: lines.iter().filter(|l| l.status == LineStatus::Failed).count(),
+        lines,
+    };
+
+    if report.failed > 0 {
+        for line in report.lines.iter().filter(|l| l.status == LineStatus::Failed) {
+            println!(" -line {}: {}", line.line, line.errors.join(" "));
         }
+        write_report(&report, report_path)?;
+        return Err(anyhow::anyhow!("{} lines failed, nothing was imported.", report.failed));
     }
 
-    input.clear();
     println!("The following expenses will be created:");
-    for expense in &expenses {
-        println!(" -id: `{}`\tamount: `{}`\tcategory: `{}`\tdate: `{}`\tdescription: `{}`", expense.id(), expense.amount(), expense.category_id(), expense.purchased_at(), expense.description());
+    for (_, expense) in &expenses {
+        println!(" -amount: `{}`\tcategory: `{}`\tdate: `{}`\tdescription: `{}`", expense.amount(), expense.category_id(), expense.purchased_at(), expense.description());
         for user_owes in expense.user_owes() {
             println!("   -user_id: `{}`\tamount: `{}`", user_owes.user_id(), user_owes.amount());
         }
     }
-    println!("Create the above expenses? [y/n]");
-    io::stdin().read_line(&mut input)?;
-    if input.trim() != "y" {
+    println!("{} lines were already imported and will be skipped.", report.duplicates);
+
+    if !options.dry_run && confirm(options.yes, "Create the above expenses?")? {
+        let (indices, expenses): (Vec<usize>, Vec<Expense>) = expenses.into_iter().unzip();
+        let inserted = repository.insert_expenses(expenses).await?;
+        for (index, expense) in indices.into_iter().zip(inserted) {
+            report.lines[index].expense_id = Some(expense.id());
+        }
+        report.committed = true;
+        println!("{} expenses created.", report.created);
+    }
+
+    write_report(&report, report_path)?;
+
+    Ok(())
+}
+
+fn write_report(report: &ImportReport, report_path: Option<&str>) -> anyhow::Result<()> {
+    if let Some(report_path) = report_path {
+        let file = File::create(report_path)?;
+        serde_json::to_writer_pretty(file, report)?;
+    }
+    Ok(())
+}
+
+/// Reads the contents of the attachments from the archive next to the file at `path`. Every
+/// attachment has to be there, with contents that hash to what the attachment says.
+fn read_attachment_blobs(path: &str, attachments: &[Attachment]) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+    if attachments.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let archive_path = attachments_archive_path(path);
+    if !archive_path.exists() {
+        return Err(anyhow::anyhow!(
+            "The file has attachments but `{}` does not exist.",
+            archive_path.display()
+        ));
+    }
+    let blobs: HashMap<String, Vec<u8>> = archive::read_tar(io::BufReader::new(File::open(archive_path)?))?
+        .into_iter()
+        .collect();
+    for attachment in attachments {
+        let Some(contents) = blobs.get(attachment.sha256()) else {
+            return Err(anyhow::anyhow!(
+                "The contents of attachment `{}` are missing from the archive.",
+                attachment.filename()
+            ));
+        };
+        if attachments::sha256_hex(contents) != *attachment.sha256() {
+            return Err(anyhow::anyhow!(
+                "The contents of attachment `{}` don't match its checksum.",
+                attachment.filename()
+            ));
+        }
+    }
+    Ok(blobs)
+}
+
+/// Loads a file written by `export json`, matching users and categories by name and giving
+/// every imported row a fresh id. The users, categories, expenses and attachments are created all
+/// at once, or none of them are. Attachment contents are read from the archive next to the file
+/// and stored in `attachments_dir`, which needs Postgres.
+pub async fn import_json(
+    repository: &dyn Repository,
+    db_pool: Option<&sqlx::PgPool>,
+    attachments_dir: &Path,
+    path: &str,
+    options: &ImportOptions,
+) -> anyhow::Result<()> {
+    let fp = PathBuf::from(path);
+    if !fp.exists() {
+        return Err(anyhow::anyhow!("File does not exist."));
+    }
+
+    let json_format: JsonFormat = serde_json::from_reader(io::BufReader::new(File::open(fp)?))?;
+    if !json_format.attachments.is_empty() && db_pool.is_none() {
+        return Err(anyhow::anyhow!("The file has attachments, they need a Postgres `database_url`."));
+    }
+    let blobs = read_attachment_blobs(path, &json_format.attachments)?;
+
+    let question = format!(
+        "The file contains {} users, {} categories, {} expenses and {} attachments. Import them?",
+        json_format.users.len(),
+        json_format.categories.len(),
+        json_format.expenses.len(),
+        json_format.attachments.len()
+    );
+    if !confirm(options.yes, &question)? {
         return Ok(());
     }
 
-    for e in expenses {
-        insert_expense(&db_pool, e).await?;
+    let mut users_by_name: HashMap<String, i32> = repository
+        .get_users()
+        .await?
+        .into_iter()
+        .map(|u| (u.username().to_string(), u.id()))
+        .collect();
+    let mut categories_by_name: HashMap<String, i32> = repository
+        .get_categories()
+        .await?
+        .into_iter()
+        .map(|c| (c.name().to_string(), c.id()))
+        .collect();
+
+    // New users and categories get placeholder ids until the dataset is imported.
+    let mut dataset = Dataset::default();
+
+    let mut user_ids = HashMap::new();
+    for user in &json_format.users {
+        let id = match users_by_name.get(user.username()) {
+            Some(id) => *id,
+            None => {
+                let id = dataset.add_user(user.username().to_string());
+                users_by_name.insert(user.username().to_string(), id);
+                id
+            }
+        };
+        user_ids.insert(user.id(), id);
+    }
+
+    let mut category_ids = HashMap::new();
+    let mut created_categories = vec![];
+    for category in &json_format.categories {
+        let id = match categories_by_name.get(category.name()) {
+            Some(id) => *id,
+            None => {
+                let id = dataset.add_category(NewCategory {
+                    name: category.name().to_string(),
+                    description: category.description().to_string(),
+                    parent_id: None,
+                });
+                categories_by_name.insert(category.name().to_string(), id);
+                created_categories.push(category.parent_id());
+                id
+            }
+        };
+        category_ids.insert(category.id(), id);
+    }
+
+    // Parents can come after their children in the export, so they are looked up once every
+    // category has an id. Categories that were already there keep their place in the tree.
+    for (new, parent_id) in dataset.categories.iter_mut().zip(created_categories) {
+        new.parent_id = parent_id.and_then(|p| category_ids.get(&p)).copied();
+    }
+
+    let remap = |ids: &HashMap<i32, i32>, id: i32, kind: &str| {
+        ids.get(&id)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Unknown {} id `{}`.", kind, id))
+    };
+
+    let mut owes_created = 0;
+    for expense in &json_format.expenses {
+        let mut user_owes = vec![];
+        for owes in expense.user_owes() {
+            user_owes.push(UserOwes::new(remap(&user_ids, owes.user_id(), "user")?, -1, owes.amount()));
+        }
+        owes_created += user_owes.len();
+
+        let mut imported = Expense::new(
+            remap(&user_ids, expense.user_id(), "user")?,
+            remap(&category_ids, expense.category_id(), "category")?,
+            expense.amount(),
+            expense.description().to_string(),
+            *expense.purchased_at(),
+            user_owes,
+        );
+        imported.set_tags(expense.tags().clone());
+        dataset.expenses.push(imported);
+    }
+    dataset.attachments_dir = attachments_dir.to_path_buf();
+    for attachment in &json_format.attachments {
+        let index = json_format
+            .expenses
+            .iter()
+            .position(|e| e.id() == attachment.expense_id())
+            .ok_or_else(|| anyhow::anyhow!("Unknown expense id `{}`.", attachment.expense_id()))?;
+        let contents = blobs[attachment.sha256()].clone();
+        dataset.attachments.push((index, attachment.clone(), contents));
     }
 
-    println!("Expenses created.");
+    let users_created = dataset.users.len();
+    let categories_created = dataset.categories.len();
+    if options.dry_run {
+        println!("=== Dry run, nothing was written. Would have imported:");
+    } else {
+        repository.import_dataset(dataset).await?;
+        println!("=== Imported:");
+    }
+    println!(
+        "  - users: {} created, {} matched by name",
+        users_created,
+        json_format.users.len() - users_created
+    );
+    println!(
+        "  - categories: {} created, {} matched by name",
+        categories_created,
+        json_format.categories.len() - categories_created
+    );
+    println!("  - expenses: {} created", json_format.expenses.len());
+    println!("  - user owes: {} created", owes_created);
+    println!("  - attachments: {} created", json_format.attachments.len());
 
     Ok(())
-}
\ No newline at end of file
+}
+
+pub enum StatementFormat {
+    Ofx,
+    Qif { date_format: String },
+}
+
+/// What every expense of a bank statement gets, since the statement doesn't say.
+pub struct StatementDefaults {
+    /// Username of the user who paid.
+    pub payer: String,
+    pub category: String,
+    /// Usernames splitting each expense equally with the payer.
+    pub split: Vec<String>,
+}
+
+/// Stages the debits of a bank statement as draft expenses with the `defaults`, to be reviewed
+/// and posted like any other import batch. Transactions whose fingerprint was imported or staged
+/// before are skipped. Drafts are kept in Postgres only.
+pub async fn statement_import(
+    repository: &dyn Repository,
+    db_pool: &sqlx::PgPool,
+    path: &str,
+    format: StatementFormat,
+    defaults: &StatementDefaults,
+    options: &ImportOptions,
+) -> anyhow::Result<Option<ImportBatch>> {
+    let fp = PathBuf::from(path);
+    if !fp.exists() {
+        return Err(anyhow::anyhow!("File does not exist."));
+    }
+
+    let contents = std::fs::read_to_string(fp)?;
+    let transactions = match format {
+        StatementFormat::Ofx => statement::parse_ofx(&contents)?,
+        StatementFormat::Qif { date_format } => statement::parse_qif(&contents, &date_format)?,
+    };
+
+    let users = repository.get_users().await?;
+    let categories = repository.get_categories().await?;
+    let payer = get_user(&defaults.payer, &users)?;
+    let category = get_category(&defaults.category, &categories)?;
+
+    let mut associated_users = vec![payer.id()];
+    for username in &defaults.split {
+        let id = get_user(username, &users)?.id();
+        if !associated_users.contains(&id) {
+            associated_users.push(id);
+        }
+    }
+
+    let mut known = repository.get_import_fingerprints().await?;
+    known.extend(get_draft_fingerprints(db_pool).await?);
+    let mut credits = 0;
+    let mut duplicates = 0;
+    let mut staged: Vec<(StatementTransaction, DraftExpense)> = vec![];
+    for (i, transaction) in transactions.into_iter().enumerate() {
+        if transaction.amount >= 0.0 {
+            credits += 1;
+            continue;
+        }
+        if !known.insert(transaction.fingerprint.clone()) {
+            duplicates += 1;
+            continue;
+        }
+
+        let amount = -transaction.amount;
+        let raw = format!("{},{},{}", transaction.date, transaction.amount, transaction.description);
+        let mut draft = DraftExpense::new(i as i32 + 1, raw);
+        draft.set_user_id(payer.id());
+        draft.set_category_id(category.id());
+        draft.set_amount(amount);
+        draft.set_description(transaction.description.clone());
+        draft.set_purchased_at(transaction.date);
+        draft.set_user_owes(
+            equal_shares(associated_users.iter().copied(), amount)
+                .into_iter()
+                .map(|(id, share)| UserOwes::new(id, -1, share))
+                .collect(),
+        );
+        draft.validate();
+        staged.push((transaction, draft));
+    }
+
+    println!(
+        "Skipping {} credits and {} already imported or staged transactions.",
+        credits, duplicates
+    );
+    if staged.is_empty() {
+        println!("Nothing to import.");
+        return Ok(None);
+    }
+
+    println!("The following drafts will be staged for review:");
+    for (_, draft) in &staged {
+        print_draft(draft, &users, &categories);
+    }
+    if options.dry_run {
+        println!("=== Dry run, nothing was written.");
+        return Ok(None);
+    }
+    if !confirm(options.yes, "Stage the above drafts?")? {
+        return Ok(None);
+    }
+
+    let mut tx = db_pool.begin().await?;
+    let batch = insert_import_batch_tx(&mut tx, path).await?;
+    for (transaction, draft) in &staged {
+        let draft = insert_draft_expense_tx(&mut tx, batch.id(), draft).await?;
+        set_draft_fingerprint_tx(&mut tx, draft.id(), &transaction.fingerprint).await?;
+    }
+    tx.commit().await?;
+
+    println!(
+        "Staged {} drafts in batch {}, approve and post them to create the expenses.",
+        staged.len(),
+        batch.id()
+    );
+
+    Ok(Some(batch))
+}
+
+/// Parses one line of the old csv format into a draft, collecting every problem instead of
+/// stopping at the first one.
+fn old_csv_format_draft(line: i32, raw: &str, users: &[User], categories: &[Category]) -> DraftExpense {
+    let mut draft = DraftExpense::new(line, raw.to_string());
+    let mut parse_errors = vec![];
+
+    let l = raw.split(',').collect::<Vec<&str>>();
+    if l.len() < 7 {
+        draft.add_error(format!("Expected 7 columns, found {}.", l.len()));
+        return draft;
+    }
+
+    let username = l[0].trim();
+    match users.iter().find(|u| u.username() == username) {
+        Some(user) => draft.set_user_id(user.id()),
+        None => parse_errors.push(format!("Unknown user `{}`.", username)),
+    }
+    match l[1].trim().parse::<f64>() {
+        Ok(amount) => draft.set_amount(amount),
+        Err(_) => parse_errors.push(format!("Invalid amount `{}`.", l[1].trim())),
+    }
+    match categories.iter().find(|c| c.name() == l[2].trim()) {
+        Some(category) => draft.set_category_id(category.id()),
+        None => parse_errors.push(format!("Unknown category `{}`.", l[2].trim())),
+    }
+    match chrono::NaiveDate::parse_from_str(l[3].trim(), "%d/%m/%Y") {
+        Ok(date) => draft.set_purchased_at(date),
+        Err(_) => parse_errors.push(format!("Invalid date `{}`.", l[3].trim())),
+    }
+    draft.set_description(l[6].to_string());
+
+    let mut associated_users = l[5]
+        .trim()
+        .split('-')
+        .map(|u| u.trim())
+        .filter(|u| !u.is_empty())
+        .collect::<Vec<&str>>();
+    associated_users.push(username);
+
+    let mut user_ids = vec![];
+    for name in associated_users {
+        match users.iter().find(|u| u.username() == name) {
+            Some(user) => user_ids.push(user.id()),
+            None if name != username => parse_errors.push(format!("Unknown user `{}`.", name)),
+            None => {}
+        }
+    }
+    if let Some(amount) = draft.amount() {
+        if !user_ids.is_empty() {
+            let cost_per_user = amount / (user_ids.len() as f64);
+            draft.set_user_owes(
+                user_ids
+                    .iter()
+                    .map(|id| UserOwes::new(*id, -1, cost_per_user))
+                    .collect(),
+            );
+        }
+    }
+
+    draft.validate();
+    for error in parse_errors {
+        draft.add_error(error);
+    }
+    draft
+}
+
+/// Stages every line of an old csv format file as a draft expense for review instead of
+/// creating the expenses directly. Drafts are kept in Postgres only.
+pub async fn stage_old_csv_format(
+    repository: &dyn Repository,
+    db_pool: &sqlx::PgPool,
+    path: &str,
+) -> anyhow::Result<ImportBatch> {
+    let fp = PathBuf::from(path);
+    if !fp.exists() {
+        return Err(anyhow::anyhow!("File does not exist."));
+    }
+
+    let users = repository.get_users().await?;
+    let categories = repository.get_categories().await?;
+
+    let mut tx = db_pool.begin().await?;
+    let batch = insert_import_batch_tx(&mut tx, path).await?;
+    let mut with_errors = 0;
+    let mut count = 0;
+    for (i, line) in read_lines(fp)?.enumerate().skip(1) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let draft = old_csv_format_draft(i as i32 + 1, &line, &users, &categories);
+        if !draft.errors().is_empty() {
+            with_errors += 1;
+        }
+        insert_draft_expense_tx(&mut tx, batch.id(), &draft).await?;
+        count += 1;
+    }
+    tx.commit().await?;
+
+    println!(
+        "Staged {} drafts in batch {}, {} of them have errors to fix before approval.",
+        count,
+        batch.id(),
+        with_errors
+    );
+
+    Ok(batch)
+}
+
+pub fn print_draft(draft: &DraftExpense, users: &[User], categories: &[Category]) {
+    let username = |id: i32| {
+        users
+            .iter()
+            .find(|u| u.id() == id)
+            .map(|u| u.username().to_string())
+            .unwrap_or_else(|| id.to_string())
+    };
+    let category = draft
+        .category_id()
+        .and_then(|id| categories.iter().find(|c| c.id() == id))
+        .map(|c| c.name().to_string());
+    let or_missing = |value: Option<String>| value.unwrap_or_else(|| "?".to_string());
+
+    println!(
+        "  - {}: line {} [{}]\tpayer: `{}`\tamount: `{}`\tcategory: `{}`\tdate: `{}`\tdescription: `{}`",
+        draft.id(),
+        draft.line(),
+        draft.status().as_str(),
+        or_missing(draft.user_id().map(username)),
+        or_missing(draft.amount().map(|a| a.to_string())),
+        or_missing(category),
+        or_missing(draft.purchased_at().map(|d| d.to_string())),
+        draft.description()
+    );
+    for owes in draft.user_owes() {
+        println!("      owes: `{}`\tamount: `{}`", username(owes.user_id()), owes.amount());
+    }
+    for error in draft.errors() {
+        println!("      error: {}", error);
+    }
+    for warning in draft.warnings() {
+        println!("      warning: {}", warning);
+    }
+}
+
+pub struct DraftEdit {
+    pub payer: Option<String>,
+    pub category: Option<String>,
+    pub amount: Option<f64>,
+    pub date: Option<String>,
+    pub description: Option<String>,
+    pub split: Option<Vec<String>>,
+}
+
+/// `amount` shared equally between the users, each of them once.
+fn equal_shares(user_ids: impl Iterator<Item = i32>, amount: f64) -> Vec<(i32, f64)> {
+    let mut user_ids = user_ids.collect::<Vec<i32>>();
+    user_ids.sort();
+    user_ids.dedup();
+    let cost_per_user = amount / (user_ids.len() as f64);
+    user_ids.into_iter().map(|id| (id, cost_per_user)).collect()
+}
+
+/// Updates the shares of a draft whose payer, amount or split was edited. A new split shares
+/// the amount equally between the payer and the split users. Otherwise the new payer takes over
+/// the share of the previous one, and a new amount scales every share by the same factor.
+fn reshare_draft(draft: &mut DraftExpense, previous_payer: Option<i32>, previous_amount: Option<f64>, split: Option<&[i32]>) {
+    let Some(amount) = draft.amount() else {
+        return;
+    };
+    let payer = draft.user_id();
+    let shares = match split {
+        Some(split) => equal_shares(payer.into_iter().chain(split.iter().copied()), amount),
+        None => {
+            let mut shares = draft.user_owes().iter().map(|o| (o.user_id(), o.amount())).collect::<Vec<(i32, f64)>>();
+            if let (Some(previous), Some(payer)) = (previous_payer, payer) {
+                if let Some(index) = shares.iter().position(|(id, _)| *id == previous) {
+                    let (_, taken_over) = shares.remove(index);
+                    // Added to the new payer's own share if they already had one.
+                    match shares.iter_mut().find(|(id, _)| *id == payer) {
+                        Some(share) => share.1 += taken_over,
+                        None => shares.insert(index, (payer, taken_over)),
+                    }
+                }
+            }
+            match previous_amount {
+                Some(previous) if previous != 0.0 && !shares.is_empty() => {
+                    shares.into_iter().map(|(id, share)| (id, share * amount / previous)).collect()
+                }
+                // Nothing to scale, so the amount is shared equally like a new split.
+                _ => equal_shares(payer.into_iter().chain(shares.into_iter().map(|(id, _)| id)), amount),
+            }
+        }
+    };
+    if !shares.is_empty() {
+        draft.set_user_owes(shares.into_iter().map(|(id, share)| UserOwes::new(id, -1, share)).collect());
+    }
+}
+
+/// Applies `edit` to the draft, updating the shares as `reshare_draft` does. Drafts are kept in
+/// Postgres only.
+pub async fn edit_draft(
+    repository: &dyn Repository,
+    db_pool: &sqlx::PgPool,
+    actor: &Actor,
+    id: i32,
+    edit: DraftEdit,
+) -> anyhow::Result<()> {
+    let mut draft = get_draft_expense(db_pool, id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Draft `{}` does not exist.", id))?;
+    let users = repository.get_users().await?;
+    let categories = repository.get_categories().await?;
+    let (previous_payer, previous_amount) = (draft.user_id(), draft.amount());
+
+    if let Some(payer) = &edit.payer {
+        draft.set_user_id(get_user(payer, &users)?.id());
+    }
+    if let Some(category) = &edit.category {
+        draft.set_category_id(get_category(category, &categories)?.id());
+    }
+    if let Some(amount) = edit.amount {
+        draft.set_amount(amount);
+    }
+    if let Some(date) = &edit.date {
+        draft.set_purchased_at(chrono::NaiveDate::parse_from_str(date, "%d/%m/%Y")?);
+    }
+    if let Some(description) = edit.description {
+        draft.set_description(description);
+    }
+
+    if edit.amount.is_some() || edit.payer.is_some() || edit.split.is_some() {
+        let split = match &edit.split {
+            Some(split) => Some(
+                split
+                    .iter()
+                    .map(|username| get_user(username, &users).map(|u| u.id()))
+                    .collect::<anyhow::Result<Vec<i32>>>()?,
+            ),
+            None => None,
+        };
+        reshare_draft(&mut draft, previous_payer, previous_amount, split.as_deref());
+    }
+
+    let draft = update_draft_expense(db_pool, actor, draft)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Draft `{}` has already been posted.", id))?;
+    print_draft(&draft, &users, &categories);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::{
+        CategoryRepository, ExpenseRepository, MemoryRepository, UserRepository,
+    };
+
+    fn names() -> (Vec<User>, Vec<Category>) {
+        let users = vec![User::new("alice".to_string()), User::new("bob".to_string())];
+        let categories = vec![Category::new("food".to_string(), "food expenses".to_string())];
+        (users, categories)
+    }
+
+    #[test]
+    fn test_old_csv_format_draft() {
+        let (users, categories) = names();
+        let draft = old_csv_format_draft(2, "alice,10,food,01/02/2023,,bob,pizza", &users, &categories);
+        assert!(draft.errors().is_empty());
+        assert_eq!(draft.amount(), Some(10.0));
+        assert_eq!(draft.description(), "pizza");
+        assert_eq!(draft.user_owes().len(), 2);
+        assert_eq!(draft.user_owes()[0].amount(), 5.0);
+    }
+
+    #[test]
+    fn test_old_csv_format_draft_collects_every_error() {
+        let (users, categories) = names();
+        let draft = old_csv_format_draft(3, "alice,abc,fod,2023-02-01,,carl,pizza", &users, &categories);
+        assert!(draft.errors().contains(&"Invalid amount `abc`.".to_string()));
+        assert!(draft.errors().contains(&"Unknown category `fod`.".to_string()));
+        assert!(draft.errors().contains(&"Invalid date `2023-02-01`.".to_string()));
+        assert!(draft.errors().contains(&"Unknown user `carl`.".to_string()));
+
+        let draft = old_csv_format_draft(4, "alice,10", &users, &categories);
+        assert_eq!(draft.errors(), &vec!["Expected 7 columns, found 2.".to_string()]);
+    }
+
+    #[test]
+    fn test_reshare_draft() {
+        let shares = |draft: &DraftExpense| {
+            draft.user_owes().iter().map(|o| (o.user_id(), o.amount())).collect::<Vec<(i32, f64)>>()
+        };
+        let mut draft = DraftExpense::new(2, String::new());
+        draft.set_user_id(1);
+        draft.set_amount(30.0);
+        draft.set_user_owes(vec![UserOwes::new(1, -1, 10.0), UserOwes::new(2, -1, 20.0)]);
+
+        // The new payer takes over the share of the old one, the others stay.
+        draft.set_user_id(3);
+        reshare_draft(&mut draft, Some(1), Some(30.0), None);
+        assert_eq!(shares(&draft), vec![(3, 10.0), (2, 20.0)]);
+        draft.set_user_id(2);
+        reshare_draft(&mut draft, Some(3), Some(30.0), None);
+        assert_eq!(shares(&draft), vec![(2, 30.0)]);
+
+        // A new amount keeps the proportions.
+        draft.set_user_owes(vec![UserOwes::new(2, -1, 10.0), UserOwes::new(1, -1, 20.0)]);
+        draft.set_amount(60.0);
+        reshare_draft(&mut draft, Some(2), Some(30.0), None);
+        assert_eq!(shares(&draft), vec![(2, 20.0), (1, 40.0)]);
+
+        // A new split starts over with equal shares.
+        reshare_draft(&mut draft, Some(2), Some(60.0), Some(&[3]));
+        assert_eq!(shares(&draft), vec![(2, 30.0), (3, 30.0)]);
+    }
+
+    fn temp_path(prefix: &str, extension: &str) -> String {
+        let name = format!("{}.{}", crate::database::testing::unique_name(prefix), extension);
+        std::env::temp_dir().join(name).to_str().unwrap().to_string()
+    }
+
+    const YES: ImportOptions = ImportOptions { yes: true, dry_run: false };
+
+    #[tokio::test]
+    async fn test_import_json() {
+        let repository = MemoryRepository::new();
+        let alice = repository.insert_user("alice".to_string()).await.unwrap();
+
+        let mut users = vec![User::new("alice".to_string()), User::new("bob".to_string())];
+        users[0].set_id(7);
+        users[1].set_id(8);
+        let mut categories = vec![
+            Category::new("groceries".to_string(), "".to_string()),
+            Category::new("food".to_string(), "".to_string()),
+        ];
+        categories[0].set_id(3);
+        // The parent comes after its child.
+        categories[0].set_parent_id(Some(4));
+        categories[1].set_id(4);
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let owes = vec![UserOwes::new(7, -1, 5.0), UserOwes::new(8, -1, 5.0)];
+        let mut expense = Expense::new(8, 3, 10.0, "bread".to_string(), date, owes);
+        expense.set_id(1);
+        let path = temp_path("import", "json");
+        let write = |attachments: Vec<Attachment>| {
+            let json_format = JsonFormat::new(users.clone(), categories.clone(), vec![expense.clone()], attachments);
+            serde_json::to_writer(File::create(&path).unwrap(), &json_format).unwrap();
+        };
+        write(vec![]);
+
+        let dry_run = ImportOptions { yes: true, dry_run: true };
+        import_json(&repository, None, Path::new("."), &path, &dry_run).await.unwrap();
+        assert_eq!(repository.get_users().await.unwrap().len(), 1);
+
+        import_json(&repository, None, Path::new("."), &path, &YES).await.unwrap();
+        let users = repository.get_users().await.unwrap();
+        let bob = users.iter().find(|u| u.username() == "bob").unwrap();
+        assert_eq!(users.len(), 2);
+        let categories = repository.get_categories().await.unwrap();
+        let food = categories.iter().find(|c| c.name() == "food").unwrap();
+        let groceries = categories.iter().find(|c| c.name() == "groceries").unwrap();
+        assert_eq!(groceries.parent_id(), Some(food.id()));
+        let expenses = repository.get_expenses(None).await.unwrap();
+        assert_eq!((expenses[0].user_id(), expenses[0].category_id()), (bob.id(), groceries.id()));
+        let shares = expenses[0].user_owes().iter().map(|o| o.user_id()).collect::<Vec<i32>>();
+        assert_eq!(shares, vec![alice.id(), bob.id()]);
+
+        // Attachments are stored next to the Postgres rows only.
+        let receipt = Attachment::new(1, "receipt.txt".to_string(), "text/plain".to_string(), 7, "0".repeat(64));
+        write(vec![receipt]);
+        let error = import_json(&repository, None, Path::new("."), &path, &YES).await.unwrap_err();
+        assert!(error.to_string().contains("Postgres"));
+        assert_eq!(repository.get_expenses(None).await.unwrap().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_statement_import() {
+        use crate::database::draft::{get_draft_expenses, post_approved_drafts, set_draft_status};
+        use crate::database::repository::PgRepository;
+        use crate::database::testing::{test_pool, unique_name};
+        use datatypes::DraftStatus;
+
+        let db_pool = test_pool().await;
+        let repository = PgRepository::new(db_pool.clone());
+        let alice = repository.insert_user(unique_name("alice")).await.unwrap();
+        let bob = repository.insert_user(unique_name("bob")).await.unwrap();
+        let fuel = repository.insert_category(unique_name("fuel"), "", None).await.unwrap();
+        let (coffee, shell) = (unique_name("COFFEE"), unique_name("SHELL"));
+        let path = temp_path("statement", "qif");
+        let qif = format!(
+            "!Type:Bank\nD12/04/2023\nT-4.50\nP{}\n^\nD13/04/2023\nT-60.00\nP{}\n^\nD14/04/2023\nT100.00\nPSALARY\n^\n",
+            coffee, shell
+        );
+        std::fs::write(&path, qif).unwrap();
+        let defaults = StatementDefaults {
+            payer: alice.username().to_string(),
+            category: fuel.name().to_string(),
+            split: vec![bob.username().to_string()],
+        };
+        let import = |options: ImportOptions| {
+            let (repository, db_pool, path, defaults) = (&repository, &db_pool, &path, &defaults);
+            async move {
+                let format = StatementFormat::Qif { date_format: "%d/%m/%Y".to_string() };
+                statement_import(repository, db_pool, path, format, defaults, &options).await.unwrap()
+            }
+        };
+        let created = || async {
+            let expenses = repository.get_expenses(None).await.unwrap();
+            expenses.iter().filter(|e| e.category_id() == fuel.id()).count()
+        };
+
+        assert!(import(ImportOptions { yes: true, dry_run: true }).await.is_none());
+
+        // The debits are staged for review, no expense is created yet. Left to the dry run they
+        // would have been skipped here.
+        let batch = import(YES).await.unwrap();
+        let drafts = get_draft_expenses(&db_pool, batch.id()).await.unwrap();
+        assert_eq!(drafts.len(), 2);
+        assert!(drafts.iter().all(|d| d.errors().is_empty()));
+        let shares = drafts[1].user_owes().iter().map(|o| (o.user_id(), o.amount())).collect::<Vec<(i32, f64)>>();
+        assert_eq!(shares, vec![(alice.id(), 30.0), (bob.id(), 30.0)]);
+        assert_eq!(created().await, 0);
+
+        // Transactions that were staged before are skipped, and so are they once posted.
+        assert!(import(YES).await.is_none());
+        let actor = Actor::new(unique_name("importer"), datatypes::AuditSource::Import);
+        for draft in &drafts {
+            assert!(set_draft_status(&db_pool, &actor, draft.id(), DraftStatus::Approved).await.unwrap());
+        }
+        assert_eq!(post_approved_drafts(&db_pool, &actor, batch.id()).await.unwrap().len(), 2);
+        assert_eq!(created().await, 2);
+        let sql = r#"
+        SELECT COUNT(*)
+        FROM imported_transactions i
+        JOIN draft_expenses d ON d.fingerprint = i.fingerprint AND d.expense_id = i.expense_id
+        WHERE d.batch_id = $1
+        "#;
+        let (recorded,): (i64,) = sqlx::query_as(sql).bind(batch.id()).fetch_one(&db_pool).await.unwrap();
+        assert_eq!(recorded, 2);
+        assert!(import(YES).await.is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_attachment_blobs() {
+        let path = std::env::temp_dir().join(format!("{}.json", crate::database::testing::unique_name("blobs")));
+        let path = path.to_str().unwrap();
+        let sha256 = attachments::sha256_hex(b"receipt");
+        let receipt = Attachment::new(1, "receipt.txt".to_string(), "text/plain".to_string(), 7, sha256.clone());
+        let write = |contents: &[u8]| {
+            let archive = File::create(attachments_archive_path(path)).unwrap();
+            archive::write_tar(archive, &[(sha256.clone(), contents.to_vec())]).unwrap();
+        };
+
+        write(b"receipt");
+        assert_eq!(read_attachment_blobs(path, std::slice::from_ref(&receipt)).unwrap()[&sha256], b"receipt");
+        // Contents that were swapped in the archive are refused.
+        write(b"<script>");
+        let error = read_attachment_blobs(path, &[receipt]).unwrap_err();
+        assert!(error.to_string().contains("checksum"));
+        std::fs::remove_file(attachments_archive_path(path)).unwrap();
+    }
+}
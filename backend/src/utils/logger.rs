@@ -29,7 +29,7 @@ pub fn init_logger(log_level: LevelFilter) {
             ))
         })
         .level(log_level)
-        .chain(std::io::stdout())
+        .chain(std::io::stderr())
         .apply()
         .unwrap();
 }
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use datatypes::{Expense, UserOwes};
+
+use crate::database::repository::Repository;
+use crate::utils::import::ImportOptions;
+use crate::utils::confirm;
+
+/// What `migrate` copied, or would have copied on a dry run.
+#[derive(Debug, Default, PartialEq)]
+pub struct Migration {
+    users_created: usize,
+    users_matched: usize,
+    categories_created: usize,
+    categories_matched: usize,
+    expenses_created: usize,
+}
+
+impl std::fmt::Display for Migration {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "  - users: {} created, {} matched by name",
+            self.users_created, self.users_matched
+        )?;
+        writeln!(
+            f,
+            "  - categories: {} created, {} matched by name",
+            self.categories_created, self.categories_matched
+        )?;
+        writeln!(f, "  - expenses: {} created", self.expenses_created)
+    }
+}
+
+/// Copies the users, categories and expenses of `from` into `to`, for moving between Postgres
+/// and SQLite. Works like `import json`: users and categories are matched by name and the
+/// expenses are inserted all at once, or not at all. Drafts, attachments and reset points stay
+/// behind.
+pub async fn migrate(
+    from: &dyn Repository,
+    to: &dyn Repository,
+    options: &ImportOptions,
+) -> anyhow::Result<Option<Migration>> {
+    let users = from.get_users().await?;
+    let categories = from.get_categories().await?;
+    let mut expenses = from.get_expenses(None).await?;
+    // Oldest first, so the new ids keep the original order.
+    expenses.reverse();
+
+    let existing = to.get_expenses(None).await?.len();
+    let mut question = format!(
+        "Copy {} users, {} categories and {} expenses?",
+        users.len(),
+        categories.len(),
+        expenses.len()
+    );
+    if existing > 0 {
+        question.push_str(&format!(" The target already has {} expenses, they are kept.", existing));
+    }
+    if !confirm(options.yes || options.dry_run, &question)? {
+        return Ok(None);
+    }
+
+    let mut migration = Migration::default();
+    let mut users_by_name: HashMap<String, i32> = to
+        .get_users()
+        .await?
+        .into_iter()
+        .map(|u| (u.username().to_string(), u.id()))
+        .collect();
+    let mut categories_by_name: HashMap<String, i32> = to
+        .get_categories()
+        .await?
+        .into_iter()
+        .map(|c| (c.name().to_string(), c.id()))
+        .collect();
+
+    // A dry run hands out made up ids for whatever it would have created.
+    let mut placeholder = 0;
+
+    let mut user_ids = HashMap::new();
+    for user in &users {
+        let id = match users_by_name.get(user.username()) {
+            Some(id) => {
+                migration.users_matched += 1;
+                *id
+            }
+            None => {
+                migration.users_created += 1;
+                let id = if options.dry_run {
+                    placeholder -= 1;
+                    placeholder
+                } else {
+                    to.insert_user(user.username().to_string()).await?.id()
+                };
+                users_by_name.insert(user.username().to_string(), id);
+                id
+            }
+        };
+        user_ids.insert(user.id(), id);
+    }
+
+    let mut category_ids = HashMap::new();
+    let mut created_categories = vec![];
+    for category in &categories {
+        let id = match categories_by_name.get(category.name()) {
+            Some(id) => {
+                migration.categories_matched += 1;
+                *id
+            }
+            None => {
+                migration.categories_created += 1;
+                let id = if options.dry_run {
+                    placeholder -= 1;
+                    placeholder
+                } else {
+                    to.insert_category(category.name().to_string(), category.description(), None)
+                        .await?
+                        .id()
+                };
+                categories_by_name.insert(category.name().to_string(), id);
+                created_categories.push((id, category.parent_id()));
+                id
+            }
+        };
+        category_ids.insert(category.id(), id);
+    }
+
+    let remap = |ids: &HashMap<i32, i32>, id: i32, kind: &str| {
+        ids.get(&id)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Unknown {} id `{}`.", kind, id))
+    };
+
+    let mut copies = vec![];
+    for expense in &expenses {
+        let mut user_owes = vec![];
+        for owes in expense.user_owes() {
+            user_owes.push(UserOwes::new(remap(&user_ids, owes.user_id(), "user")?, -1, owes.amount()));
+        }
+        let mut copy = Expense::new(
+            remap(&user_ids, expense.user_id(), "user")?,
+            remap(&category_ids, expense.category_id(), "category")?,
+            expense.amount(),
+            expense.description().to_string(),
+            *expense.purchased_at(),
+            user_owes,
+        );
+        copy.set_tags(expense.tags().clone());
+        copies.push(copy);
+    }
+    migration.expenses_created = copies.len();
+
+    if options.dry_run {
+        return Ok(Some(migration));
+    }
+
+    // Categories that were already there keep their place in the tree.
+    for (id, parent_id) in created_categories {
+        if let Some(parent_id) = parent_id.and_then(|p| category_ids.get(&p)) {
+            to.set_category_parent(id, Some(*parent_id)).await?;
+        }
+    }
+    to.insert_expenses(copies).await?;
+
+    Ok(Some(migration))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::{CategoryRepository, ExpenseRepository, MemoryRepository, UserRepository};
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let from = MemoryRepository::new();
+        let alice = from.insert_user("alice".to_string()).await.unwrap();
+        let bob = from.insert_user("bob".to_string()).await.unwrap();
+        let food = from.insert_category("food".to_string(), "groceries", None).await.unwrap();
+        let fruit = from.insert_category("fruit".to_string(), "", Some(food.id())).await.unwrap();
+        let mut apples = Expense::new(
+            alice.id(),
+            fruit.id(),
+            3.5,
+            "apples".to_string(),
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            vec![UserOwes::new(bob.id(), -1, 1.75)],
+        );
+        apples.set_tags(vec!["market".to_string()]);
+        from.insert_expense(apples).await.unwrap();
+
+        let to = MemoryRepository::new();
+        to.insert_user("bob".to_string()).await.unwrap();
+
+        let dry_run = ImportOptions { yes: false, dry_run: true };
+        let planned = migrate(&from, &to, &dry_run).await.unwrap().unwrap();
+        assert!(to.get_expenses(None).await.unwrap().is_empty());
+
+        let options = ImportOptions { yes: true, dry_run: false };
+        let migration = migrate(&from, &to, &options).await.unwrap().unwrap();
+        assert_eq!(migration, planned);
+        assert_eq!((migration.users_created, migration.users_matched), (1, 1));
+        assert_eq!(migration.categories_created, 2);
+
+        let categories = to.get_categories().await.unwrap();
+        let food = categories.iter().find(|c| c.name() == "food").unwrap();
+        let fruit = categories.iter().find(|c| c.name() == "fruit").unwrap();
+        assert_eq!(food.description(), "groceries");
+        assert_eq!(fruit.parent_id(), Some(food.id()));
+
+        let copied = to.get_expenses(None).await.unwrap();
+        assert_eq!(copied.len(), 1);
+        assert_eq!(copied[0].category_id(), fruit.id());
+        assert_eq!(copied[0].user_owes()[0].amount(), 1.75);
+        assert_eq!(copied[0].tags(), &vec!["market".to_string()]);
+    }
+}
@@ -0,0 +1,243 @@
+use sha2::{Digest, Sha256};
+
+/// A single transaction read from a bank statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementTransaction {
+    pub date: chrono::NaiveDate,
+    /// Signed as on the statement, debits are negative.
+    pub amount: f64,
+    pub description: String,
+    /// Stays the same when the transaction shows up again in an overlapping statement.
+    pub fingerprint: String,
+}
+
+fn fingerprint(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn parse_amount(amount: &str) -> anyhow::Result<f64> {
+    amount
+        .trim()
+        .replace(',', "")
+        .parse::<f64>()
+        .map_err(|_| anyhow::anyhow!("Invalid amount `{}`.", amount.trim()))
+}
+
+fn describe(payee: &str, memo: &str) -> String {
+    let payee = payee.trim();
+    let memo = memo.trim();
+    if memo.is_empty() || memo == payee {
+        payee.to_string()
+    } else if payee.is_empty() {
+        memo.to_string()
+    } else {
+        format!("{} - {}", payee, memo)
+    }
+}
+
+/// Returns the value of the first `<TAG>value` element inside `block`. Works for both the SGML
+/// flavour of OFX (no closing tags) and the XML one.
+fn ofx_field<'a>(block: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = block.find(&open)? + open.len();
+    let rest = &block[start..];
+    let end = rest.find(['<', '\n', '\r']).unwrap_or(rest.len());
+    Some(rest[..end].trim())
+}
+
+pub fn parse_ofx(contents: &str) -> anyhow::Result<Vec<StatementTransaction>> {
+    let account = ofx_field(contents, "ACCTID").unwrap_or_default();
+
+    let mut transactions = vec![];
+    for block in contents.split("<STMTTRN>").skip(1) {
+        let block = block.split("</STMTTRN>").next().unwrap_or(block);
+
+        let fitid = ofx_field(block, "FITID")
+            .ok_or_else(|| anyhow::anyhow!("Transaction without a FITID."))?;
+        let posted = ofx_field(block, "DTPOSTED")
+            .ok_or_else(|| anyhow::anyhow!("Transaction `{}` has no DTPOSTED.", fitid))?;
+        let amount = ofx_field(block, "TRNAMT")
+            .ok_or_else(|| anyhow::anyhow!("Transaction `{}` has no TRNAMT.", fitid))?;
+
+        let date = posted
+            .get(..8)
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid DTPOSTED `{}`.", posted))?;
+
+        transactions.push(StatementTransaction {
+            date,
+            amount: parse_amount(amount)?,
+            description: describe(
+                ofx_field(block, "NAME").unwrap_or_default(),
+                ofx_field(block, "MEMO").unwrap_or_default(),
+            ),
+            fingerprint: fingerprint(&["ofx", account, fitid]),
+        });
+    }
+    Ok(transactions)
+}
+
+/// QIF has no transaction ids, so the fingerprint is built from the record itself plus how
+/// many identical records came before it in the file.
+pub fn parse_qif(contents: &str, date_format: &str) -> anyhow::Result<Vec<StatementTransaction>> {
+    let mut transactions = vec![];
+    let mut seen: Vec<String> = vec![];
+
+    let mut date = None;
+    let mut amount = None;
+    let mut payee = String::new();
+    let mut memo = String::new();
+
+    for line in contents.lines() {
+        let line = line.trim_end();
+        let Some(code) = line.chars().next() else {
+            continue;
+        };
+        let value = &line[code.len_utf8()..];
+        match code {
+            '!' => {}
+            'D' => {
+                let normalized = value.trim().replace('\'', "/");
+                date = Some(
+                    chrono::NaiveDate::parse_from_str(&normalized, date_format)
+                        .map_err(|_| anyhow::anyhow!("Invalid date `{}`.", value.trim()))?,
+                );
+            }
+            'T' | 'U' => amount = Some(parse_amount(value)?),
+            'P' => payee = value.to_string(),
+            'M' => memo = value.to_string(),
+            '^' => {
+                let date = date
+                    .take()
+                    .ok_or_else(|| anyhow::anyhow!("Transaction without a date."))?;
+                let amount = amount
+                    .take()
+                    .ok_or_else(|| anyhow::anyhow!("Transaction on {} without an amount.", date))?;
+                let description = describe(&payee, &memo);
+
+                let key = format!("{}|{}|{}|{}", date, amount, payee.trim(), memo.trim());
+                let occurrence = seen.iter().filter(|k| **k == key).count().to_string();
+                seen.push(key.clone());
+
+                transactions.push(StatementTransaction {
+                    date,
+                    amount,
+                    description,
+                    fingerprint: fingerprint(&["qif", &key, &occurrence]),
+                });
+                payee.clear();
+                memo.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OFX_SGML: &str = r#"OFXHEADER:100
+DATA:OFXSGML
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<BANKACCTFROM><BANKID>123<ACCTID>987654<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20230412120000[+10:AEST]
+<TRNAMT>-42.50
+<FITID>2023041201
+<NAME>WOOLWORTHS
+<MEMO>Groceries
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20230413
+<TRNAMT>1,000.00
+<FITID>2023041301
+<NAME>SALARY
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+"#;
+
+    const OFX_XML: &str = r#"<?xml version="1.0"?>
+<OFX><BANKACCTFROM><ACCTID>987654</ACCTID></BANKACCTFROM>
+<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20230412</DTPOSTED><TRNAMT>-42.50</TRNAMT><FITID>2023041201</FITID><NAME>WOOLWORTHS</NAME><MEMO>Groceries</MEMO></STMTTRN>
+</OFX>
+"#;
+
+    const QIF: &str = r#"!Type:Bank
+D12/04/2023
+T-4.50
+PCOFFEE
+^
+D12/04'2023
+T-4.50
+PCOFFEE
+^
+D13/04/2023
+T-60.00
+PSHELL
+MFuel
+^
+"#;
+
+    #[test]
+    fn test_parse_ofx_sgml() {
+        let transactions = parse_ofx(OFX_SGML).unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(
+            transactions[0].date,
+            chrono::NaiveDate::from_ymd_opt(2023, 4, 12).unwrap()
+        );
+        assert_eq!(transactions[0].amount, -42.5);
+        assert_eq!(transactions[0].description, "WOOLWORTHS - Groceries");
+        assert_eq!(transactions[1].amount, 1000.0);
+        assert_ne!(transactions[0].fingerprint, transactions[1].fingerprint);
+    }
+
+    #[test]
+    fn test_parse_ofx_xml_matches_sgml() {
+        let sgml = parse_ofx(OFX_SGML).unwrap();
+        let xml = parse_ofx(OFX_XML).unwrap();
+        assert_eq!(xml.len(), 1);
+        assert_eq!(xml[0], sgml[0]);
+    }
+
+    #[test]
+    fn test_parse_qif() {
+        let transactions = parse_qif(QIF, "%d/%m/%Y").unwrap();
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[2].description, "SHELL - Fuel");
+        assert_eq!(transactions[2].amount, -60.0);
+
+        // Identical records on the same day are distinct transactions.
+        assert_eq!(transactions[0].description, transactions[1].description);
+        assert_ne!(transactions[0].fingerprint, transactions[1].fingerprint);
+    }
+
+    #[test]
+    fn test_qif_fingerprints_are_stable() {
+        let first = parse_qif(QIF, "%d/%m/%Y").unwrap();
+        let second = parse_qif(QIF, "%d/%m/%Y").unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_parse_qif_invalid_date() {
+        assert!(parse_qif("D2023-04-12\nT-1\n^\n", "%d/%m/%Y").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt;
+
+use datatypes::{Category, User};
+
+use crate::database::repository::Repository;
+use crate::database::References;
+use crate::utils::config::{CategoryConfig, Config};
+
+/// What it takes to bring the database in line with the users and categories in the config.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncPlan {
+    create_users: Vec<String>,
+    create_categories: Vec<CategoryConfig>,
+    /// Existing categories whose description differs, with the configured description.
+    update_categories: Vec<(Category, String)>,
+    /// Entries that only exist in the database, candidates for `--prune`.
+    unmanaged_users: Vec<User>,
+    unmanaged_categories: Vec<Category>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.create_users.is_empty()
+            && self.create_categories.is_empty()
+            && self.update_categories.is_empty()
+    }
+
+    pub fn unmanaged_users(&self) -> &Vec<User> {
+        &self.unmanaged_users
+    }
+
+    pub fn unmanaged_categories(&self) -> &Vec<Category> {
+        &self.unmanaged_categories
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            writeln!(f, "Users and categories match the config.")?;
+        }
+        for username in &self.create_users {
+            writeln!(f, "  + user `{}`", username)?;
+        }
+        for category in &self.create_categories {
+            writeln!(f, "  + category `{}`: {}", category.name, category.description)?;
+        }
+        for (category, description) in &self.update_categories {
+            writeln!(
+                f,
+                "  ~ category `{}`: `{}` -> `{}`",
+                category.name(),
+                category.description(),
+                description
+            )?;
+        }
+        for user in &self.unmanaged_users {
+            writeln!(f, "  ? user `{}` is not in the config", user.username())?;
+        }
+        for category in &self.unmanaged_categories {
+            writeln!(f, "  ? category `{}` is not in the config", category.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the configured users and categories with the ones in the database. When the config
+/// names an entry twice the last one wins.
+pub fn plan(config: &Config, users: &[User], categories: &[Category]) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+
+    for user in config.users() {
+        let known = users.iter().any(|u| u.username() == &user.username);
+        if !known && !plan.create_users.contains(&user.username) {
+            plan.create_users.push(user.username.clone());
+        }
+    }
+
+    let mut configured: Vec<&CategoryConfig> = vec![];
+    for category in config.categories() {
+        configured.retain(|c| c.name != category.name);
+        configured.push(category);
+    }
+    for wanted in configured {
+        match categories.iter().find(|c| c.name() == &wanted.name) {
+            Some(existing) if existing.description() != &wanted.description => plan
+                .update_categories
+                .push((existing.clone(), wanted.description.clone())),
+            Some(_) => {}
+            None => plan.create_categories.push(wanted.clone()),
+        }
+    }
+
+    plan.unmanaged_users = users
+        .iter()
+        .filter(|u| !config.users().iter().any(|c| &c.username == u.username()))
+        .cloned()
+        .collect();
+    plan.unmanaged_categories = categories
+        .iter()
+        .filter(|c| !config.categories().iter().any(|cc| &cc.name == c.name()))
+        .cloned()
+        .collect();
+
+    plan
+}
+
+pub async fn get_plan(repository: &dyn Repository, config: &Config) -> anyhow::Result<SyncPlan> {
+    let users = repository.get_users().await?;
+    let categories = repository.get_categories().await?;
+    Ok(plan(config, &users, &categories))
+}
+
+/// Creates and updates everything in the plan. An interrupted sync is finished by running it
+/// again, since a fresh plan only contains what is still missing.
+pub async fn apply(repository: &dyn Repository, plan: &SyncPlan) -> anyhow::Result<()> {
+    for username in &plan.create_users {
+        repository.insert_user(username.clone()).await?;
+    }
+    for category in &plan.create_categories {
+        repository.insert_category(category.name.clone(), &category.description, None).await?;
+    }
+    for (category, description) in &plan.update_categories {
+        repository.update_category_description(category.id(), description).await?;
+    }
+    Ok(())
+}
+
+/// Deletes the unmanaged users and categories nothing refers to anymore. Returns the names of
+/// the ones that had to be kept along with what still refers to them.
+pub async fn prune(repository: &dyn Repository, plan: &SyncPlan) -> anyhow::Result<Vec<(String, References)>> {
+    let mut kept = vec![];
+    for user in &plan.unmanaged_users {
+        let references = repository.get_user_references(user.id()).await?;
+        if references.is_empty() {
+            repository.delete_user(user.id()).await?;
+        } else {
+            kept.push((format!("user `{}`", user.username()), references));
+        }
+    }
+
+    // A category with unmanaged sub-categories can only go once they are gone, so keep going
+    // until a round deletes nothing.
+    let mut remaining = plan.unmanaged_categories.clone();
+    loop {
+        let mut still_referenced = vec![];
+        for category in &remaining {
+            let references = repository.get_category_references(category.id()).await?;
+            if references.is_empty() {
+                repository.delete_category(category.id()).await?;
+            } else {
+                still_referenced.push((category.clone(), references));
+            }
+        }
+        if still_referenced.len() == remaining.len() {
+            for (category, references) in still_referenced {
+                kept.push((format!("category `{}`", category.name()), references));
+            }
+            break;
+        }
+        remaining = still_referenced.into_iter().map(|(c, _)| c).collect();
+    }
+
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::{CategoryRepository, MemoryRepository};
+    use rocket::serde::json::serde_json;
+
+    fn test_config(users: &[&str], categories: &[(&str, &str)]) -> Config {
+        let mut toml = "log_level = \"info\"\n[db_config]\ndatabase_url = \"\"\nmax_connections = 1\n".to_string();
+        for username in users {
+            toml.push_str(&format!("[[user]]\nusername = \"{}\"\n", username));
+        }
+        for (name, description) in categories {
+            toml.push_str(&format!("[[category]]\nname = \"{}\"\ndescription = \"{}\"\n", name, description));
+        }
+        toml::from_str(&toml).unwrap()
+    }
+
+    fn category(id: i32, name: &str, description: &str) -> Category {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "description": description,
+            "created_at": "2023-01-01T00:00:00",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan() {
+        let config = test_config(
+            &["alice", "bob", "bob"],
+            &[("food", "old"), ("food", "groceries"), ("fuel", "fuel"), ("rent", "")],
+        );
+        let users = vec![User::new("alice".to_string()), User::new("carol".to_string())];
+        let categories = vec![category(1, "food", "food"), category(2, "fuel", "fuel"), category(3, "misc", "")];
+
+        let plan = plan(&config, &users, &categories);
+        assert_eq!(plan.create_users, vec!["bob".to_string()]);
+        assert_eq!(plan.create_categories.len(), 1);
+        assert_eq!(plan.create_categories[0].name, "rent");
+        assert_eq!(plan.update_categories, vec![(categories[0].clone(), "groceries".to_string())]);
+        assert_eq!(plan.unmanaged_users, vec![users[1].clone()]);
+        assert_eq!(plan.unmanaged_categories, vec![categories[2].clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_is_idempotent() {
+        let repository = MemoryRepository::new();
+        let username = "synced".to_string();
+        let name = "synced".to_string();
+        let config = test_config(&[&username], &[(&name, "first")]);
+
+        let plan = get_plan(&repository, &config).await.unwrap();
+        assert_eq!(plan.create_users, vec![username.clone()]);
+        apply(&repository, &plan).await.unwrap();
+
+        let plan = get_plan(&repository, &config).await.unwrap();
+        assert!(plan.is_empty());
+        apply(&repository, &plan).await.unwrap();
+
+        let config = test_config(&[&username], &[(&name, "second")]);
+        let plan = get_plan(&repository, &config).await.unwrap();
+        assert_eq!(plan.update_categories.len(), 1);
+        apply(&repository, &plan).await.unwrap();
+        let categories = repository.get_categories().await.unwrap();
+        let synced = categories.iter().find(|c| c.name() == &name).unwrap();
+        assert_eq!(synced.description(), "second");
+    }
+}
@@ -0,0 +1,272 @@
+//! Sends the events published on an `EventBus` to the webhooks subscribed to them. Each delivery is
+//! a JSON `POST` of the `Event`, signed with the webhook's secret so the receiver can check where
+//! it came from. Failed deliveries are retried with a doubling backoff, and every attempt ends up
+//! in the delivery log.
+
+use datatypes::{Event, EventKind, Webhook, WebhookDelivery};
+use hmac::{Hmac, Mac};
+use log::{error, warn};
+use rocket::serde::json::serde_json;
+use sha2::Sha256;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::{JoinHandle, JoinSet};
+
+use super::config::WebhookConfig;
+use super::events::EventBus;
+use crate::database::webhook;
+
+/// `sha256=` followed by the hex HMAC-SHA256 of the body, keyed with the webhook's secret.
+pub const SIGNATURE_HEADER: &str = "X-Expenses-Signature";
+/// The event name, e.g. `expense.created`, so receivers can route before parsing the body.
+pub const EVENT_HEADER: &str = "X-Expenses-Event";
+
+/// The value of `SIGNATURE_HEADER` for `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    let hex = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("sha256={}", hex)
+}
+
+/// Refuses what would never be delivered: URLs other than http(s) and event patterns that match
+/// no event. A blank secret is refused too, as the receivers could not trust the signatures.
+pub fn check_subscription(url: &str, secret: &str, events: &[String]) -> anyhow::Result<()> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| anyhow::anyhow!("Invalid webhook URL `{}`: {}.", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow::anyhow!("Webhook URL `{}` has to be http or https.", url));
+    }
+    if secret.trim().is_empty() {
+        return Err(anyhow::anyhow!("The webhook secret must not be empty."));
+    }
+    if let Some(pattern) = events.iter().find(|p| !EventKind::ALL.iter().any(|k| k.matches(p))) {
+        return Err(anyhow::anyhow!("`{}` matches no event.", pattern));
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct Dispatcher {
+    db_pool: sqlx::PgPool,
+    client: reqwest::Client,
+    config: WebhookConfig,
+}
+
+impl Dispatcher {
+    pub fn new(db_pool: sqlx::PgPool, config: WebhookConfig) -> Self {
+        Self {
+            db_pool,
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+
+    /// Sends `event` until the receiver answers with a 2xx or the attempts run out, and returns
+    /// the last attempt.
+    pub async fn deliver(&self, webhook: &Webhook, event: &Event) -> Result<WebhookDelivery, sqlx::Error> {
+        let body = serde_json::to_vec(event).unwrap();
+        let signature = sign(webhook.secret(), &body);
+        let mut attempt = 1;
+        loop {
+            let (status, error) = self.attempt(webhook, event, &body, &signature).await;
+            let delivery = WebhookDelivery::new(webhook.id(), event, attempt as i32, status, error);
+            let delivery = webhook::insert_delivery(&self.db_pool, &delivery).await?;
+            if delivery.succeeded() || attempt >= self.config.attempts() {
+                return Ok(delivery);
+            }
+            tokio::time::sleep(self.config.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn attempt(&self, webhook: &Webhook, event: &Event, body: &[u8], signature: &str) -> (Option<i32>, Option<String>) {
+        let response = self
+            .client
+            .post(webhook.url())
+            .timeout(self.config.timeout())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.kind().name())
+            .header(SIGNATURE_HEADER, signature)
+            .body(body.to_vec())
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+            Ok(response) => {
+                let status = response.status();
+                (Some(status.as_u16() as i32), Some(format!("receiver answered {}", status)))
+            }
+            Err(e) => (None, Some(e.to_string())),
+        }
+    }
+
+    /// Delivers every event published from now on to the webhooks that want it, each webhook
+    /// retrying on its own. Runs until every publisher of `events` is gone and then waits for the
+    /// deliveries still being retried.
+    pub fn spawn(self, events: &EventBus) -> JoinHandle<()> {
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            let mut deliveries = JoinSet::new();
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Webhooks missed {} events, deliveries are too slow.", missed);
+                        continue;
+                    }
+                };
+                // Read every time, so webhooks added from the CLI are picked up without a restart.
+                let webhooks = match webhook::get_webhooks(&self.db_pool).await {
+                    Ok(webhooks) => webhooks,
+                    Err(e) => {
+                        error!("Failed to get webhooks, `{}` is not delivered: {}", event.kind().name(), e);
+                        continue;
+                    }
+                };
+                for webhook in webhooks.into_iter().filter(|w| w.wants(event.kind())) {
+                    let dispatcher = self.clone();
+                    let event = event.clone();
+                    deliveries.spawn(async move {
+                        if let Err(e) = dispatcher.deliver(&webhook, &event).await {
+                            error!("Failed to log delivery to webhook {}: {}", webhook.id(), e);
+                        }
+                    });
+                }
+                while deliveries.try_join_next().is_some() {}
+            }
+            while deliveries.join_next().await.is_some() {}
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::testing::{test_pool, unique_name};
+    use crate::utils::audit::Actor;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A request as the receiver saw it: the lowercased headers and the body.
+    type Received = (Vec<(String, String)>, String);
+
+    /// Answers one request per status in `statuses`, then returns what it received.
+    async fn receiver(statuses: Vec<u16>) -> (String, JoinHandle<Vec<Received>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut received = vec![];
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 4096];
+                let (head, body) = loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let headers = head
+                            .lines()
+                            .skip(1)
+                            .filter_map(|l| l.split_once(": "))
+                            .map(|(k, v)| (k.to_lowercase(), v.to_string()))
+                            .collect::<Vec<(String, String)>>();
+                        let length = headers
+                            .iter()
+                            .find(|(k, _)| k == "content-length")
+                            .map(|(_, v)| v.parse::<usize>().unwrap())
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break (headers, body.to_string());
+                        }
+                    }
+                };
+                let response = format!("HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+                received.push((head, body));
+            }
+            received
+        });
+        (url, handle)
+    }
+
+    fn header<'a>(received: &'a Received, name: &str) -> &'a str {
+        received.0.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_sign() {
+        // The HMAC-SHA256 test vector from RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_check_subscription() {
+        assert!(check_subscription("http://localhost:9000/hook", "s3cret", &["expense".to_string(), "reset".to_string()]).is_ok());
+        assert!(check_subscription("ftp://localhost/hook", "s3cret", &[]).is_err());
+        assert!(check_subscription("localhost/hook", "s3cret", &[]).is_err());
+        assert!(check_subscription("https://localhost/hook", "s3cret", &["expenses".to_string()]).is_err());
+        assert!(check_subscription("https://localhost/hook", " \t", &[]).is_err());
+    }
+
+    fn actor() -> Actor {
+        Actor::new("webhooks test".to_string(), datatypes::AuditSource::Cli)
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_delivered() {
+        let db_pool = test_pool().await;
+        let (url, received) = receiver(vec![500, 204]).await;
+        // Only pings, so the dispatcher of the other test leaves it alone.
+        let webhook = Webhook::new(url, unique_name("secret"), vec!["ping".to_string()]);
+        let webhook = webhook::insert_webhook(&db_pool, &actor(), &webhook).await.unwrap();
+
+        let dispatcher = Dispatcher::new(db_pool.clone(), WebhookConfig::new(3, 0, 5));
+        let event = Event::new(EventKind::Ping, None, serde_json::Value::Null);
+        let delivery = dispatcher.deliver(&webhook, &event).await.unwrap();
+        assert_eq!((delivery.attempt(), delivery.status()), (2, Some(204)));
+        assert!(delivery.succeeded());
+
+        let received = received.await.unwrap();
+        assert_eq!(received.len(), 2);
+        let (_, body) = &received[1];
+        assert_eq!(header(&received[1], "x-expenses-event"), "ping");
+        assert_eq!(header(&received[1], "x-expenses-signature"), sign(webhook.secret(), body.as_bytes()));
+        assert_eq!(serde_json::from_str::<Event>(body).unwrap(), event);
+
+        let log = webhook::get_deliveries(&db_pool, webhook.id(), 10).await.unwrap();
+        assert_eq!(log.iter().map(|d| d.status()).collect::<Vec<Option<i32>>>(), vec![Some(204), Some(500)]);
+        assert_eq!(log[1].error().map(|e| e.as_str()), Some("receiver answered 500 Internal Server Error"));
+        webhook::delete_webhook(&db_pool, &actor(), webhook.id()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delivers_subscribed_events() {
+        let db_pool = test_pool().await;
+        let (url, received) = receiver(vec![200]).await;
+        let webhook = Webhook::new(url, unique_name("secret"), vec!["reset".to_string()]);
+        let webhook = webhook::insert_webhook(&db_pool, &actor(), &webhook).await.unwrap();
+
+        let events = EventBus::new();
+        let dispatched = Dispatcher::new(db_pool.clone(), WebhookConfig::new(1, 0, 5)).spawn(&events);
+        events.publish(Event::new(EventKind::UserDeleted, Some(1), serde_json::Value::Null));
+        events.publish(Event::new(EventKind::Reset, None, serde_json::Value::Null));
+        drop(events);
+        dispatched.await.unwrap();
+
+        let received = received.await.unwrap();
+        assert_eq!(header(&received[0], "x-expenses-event"), "reset");
+        let log = webhook::get_deliveries(&db_pool, webhook.id(), 10).await.unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].event(), EventKind::Reset);
+        webhook::delete_webhook(&db_pool, &actor(), webhook.id()).await.unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use std::ops::Deref;
+
+use datatypes::AuditSource;
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::database::repository::{DynRepository, Repository};
+use crate::utils::audit::{Actor, AuditingRepository};
+
+/// Who the caller is, recorded as the actor of their changes. Nothing checks it, it is there so
+/// that frontends and scripts can say on whose behalf they act, so the caller's address is
+/// recorded next to it. Left out, the address stands in for the name as well.
+pub const ACTOR_HEADER: &str = "X-Actor";
+
+/// The managed repository, recording the changes made through it as the caller. Routes take this
+/// rather than `&State<DynRepository>`, so no write goes unrecorded.
+#[derive(Clone)]
+pub struct Audited {
+    /// The managed repository.
+    inner: DynRepository,
+    /// `inner` recording the changes as the caller, see `AuditingRepository::wrap`.
+    repository: DynRepository,
+    actor: Actor,
+}
+
+impl Audited {
+    fn new(inner: DynRepository, actor: Actor) -> Self {
+        Self {
+            repository: AuditingRepository::wrap(inner.clone(), actor.clone()),
+            inner,
+            actor,
+        }
+    }
+
+    /// The caller, for routes that record their changes in their own transaction.
+    pub fn actor(&self) -> &Actor {
+        &self.actor
+    }
+
+    /// The same caller, with their changes recorded as coming from `source`.
+    pub fn with_source(&self, source: AuditSource) -> Self {
+        let address = self.actor.address().map(str::to_string);
+        Self::new(self.inner.clone(), Actor::new(self.actor.name().clone(), source).with_address(address))
+    }
+}
+
+impl Deref for Audited {
+    type Target = dyn Repository;
+
+    fn deref(&self) -> &Self::Target {
+        self.repository.as_ref()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Audited {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let repository = request
+            .rocket()
+            .state::<DynRepository>()
+            .expect("the repository routes manage the repository");
+        let address = request.client_ip().map(|ip| ip.to_string());
+        let name = request
+            .headers()
+            .get_one(ACTOR_HEADER)
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            // The column is a VARCHAR(255).
+            .map(|name| name.chars().take(255).collect())
+            .or_else(|| address.clone())
+            .unwrap_or_else(|| "unknown".to_string());
+        let actor = Actor::new(name, AuditSource::Web).with_address(address);
+
+        Outcome::Success(Self::new(repository.clone(), actor))
+    }
+}
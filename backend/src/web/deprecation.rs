@@ -0,0 +1,35 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::{Request, Response};
+
+/// The bases of the routes that were replaced by the ones under `/api/v1`. They are kept for one
+/// more release.
+const DEPRECATED_BASES: &[&str] = &["/users", "/categories", "/expenses"];
+
+/// When the routes were deprecated, as the structured date RFC 9745 asks for: 2026-10-19 UTC,
+/// the release that added `/api/v1`.
+const DEPRECATED_AT: &str = "@1792368000";
+
+/// Marks responses of the deprecated routes, see RFC 9745, and points at the docs for the
+/// replacements.
+pub struct Deprecation;
+
+#[rocket::async_trait]
+impl Fairing for Deprecation {
+    fn info(&self) -> Info {
+        Info {
+            name: "Add deprecation headers to the old routes",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let deprecated = request
+            .route()
+            .is_some_and(|route| DEPRECATED_BASES.contains(&route.uri.base()));
+        if deprecated {
+            response.set_header(Header::new("Deprecation", DEPRECATED_AT));
+            response.set_header(Header::new("Link", "</docs>; rel=\"deprecation\""));
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use rocket::form::Form;
+use rocket::fs::{NamedFile, TempFile};
+use rocket::http::{ContentType, Header};
+use rocket::serde::json::Json;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::{delete, get, post, FromForm, Responder, State};
+
+use crate::database::{attachment, expense};
+use crate::utils::events::EventBus;
+use crate::utils::{attachments, config};
+use crate::web::audited::Audited;
+use crate::web::error::ApiError;
+use datatypes::{Attachment, EventKind};
+
+#[derive(FromForm, utoipa::ToSchema)]
+pub struct Upload<'r> {
+    #[schema(value_type = Vec<String>, format = Binary)]
+    files: Vec<TempFile<'r>>,
+}
+
+#[derive(Responder)]
+pub struct AttachmentFile {
+    file: NamedFile,
+    content_type: ContentType,
+    disposition: Header<'static>,
+    nosniff: Header<'static>,
+}
+
+/// The name the browser sent, without any directories. Rocket's sanitized `name()` would drop
+/// the extension, which is needed to guess the content type.
+fn upload_filename(file: &TempFile<'_>) -> String {
+    file.raw_name()
+        .map(|n| n.dangerous_unsafe_unsanitized_raw().as_str())
+        .and_then(|n| n.rsplit(['/', '\\']).next())
+        .filter(|n| !n.is_empty())
+        .unwrap_or("attachment")
+        .to_string()
+}
+
+#[utoipa::path(
+    context_path = "/attachments",
+    tag = "attachments",
+    request_body(content = Upload, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The stored attachments", body = [Attachment]),
+        (status = 404, description = "The expense does not exist"),
+        (status = 500, description = "Failed to store the attachments, none of them were kept", body = ErrorBody)
+    )
+)]
+#[post("/<expense_id>", data = "<upload>")]
+pub async fn attachments_upload(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    config: &State<config::Config>,
+    events: &State<EventBus>,
+    expense_id: i32,
+    upload: Form<Upload<'_>>,
+) -> Result<Option<Json<Vec<Attachment>>>, ApiError> {
+    let exists = expense::get_expense(db_pool, expense_id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get expense"))?
+        .is_some();
+    if !exists {
+        return Ok(None);
+    }
+
+    let mut files = vec![];
+    for file in &upload.files {
+        let filename = upload_filename(file);
+        let sent = file.content_type().map(|content_type| content_type.to_string());
+        let content_type = attachments::upload_content_type(sent.as_deref(), &filename).to_string();
+        let mut contents = vec![];
+        file.open().await?.read_to_end(&mut contents).await?;
+        files.push(attachments::NewFile { filename, content_type, contents });
+    }
+
+    let added = attachments::add_attachments(db_pool, repository.actor(), config.attachments_dir(), expense_id, &files)
+        .await
+        .map_err(|_e| ApiError::Storage("Failed to store the attachments, none of them were kept".to_string()))?;
+    for attachment in &added {
+        events.publish_entity(EventKind::AttachmentCreated, attachment.id(), attachment);
+    }
+
+    Ok(Some(Json(added)))
+}
+
+#[utoipa::path(
+    context_path = "/attachments",
+    tag = "attachments",
+    responses(
+        (status = 200, description = "The attachments of the expense", body = [Attachment]),
+        (status = 500, description = "Failed to get attachments")
+    )
+)]
+#[get("/expense/<expense_id>")]
+pub async fn attachments_of_expense(
+    db_pool: &State<sqlx::PgPool>,
+    expense_id: i32,
+) -> Result<Json<Vec<Attachment>>, std::io::Error> {
+    let attachments = attachment::get_attachments(db_pool, expense_id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get attachments"))?;
+
+    Ok(Json(attachments))
+}
+
+#[utoipa::path(
+    context_path = "/attachments",
+    tag = "attachments",
+    responses(
+        (status = 200, description = "The contents, inline with the content type they were uploaded with if it is a known one, as a download otherwise", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 404, description = "The attachment does not exist"),
+        (status = 500, description = "Failed to get attachment")
+    )
+)]
+#[get("/<id>")]
+pub async fn attachments_download(
+    db_pool: &State<sqlx::PgPool>,
+    config: &State<config::Config>,
+    id: i32,
+) -> Result<Option<AttachmentFile>, std::io::Error> {
+    let Some(attachment) = attachment::get_attachment(db_pool, id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get attachment"))?
+    else {
+        return Ok(None);
+    };
+
+    let file = NamedFile::open(attachments::blob_path(config.attachments_dir(), attachment.sha256())).await?;
+    // Anything but the known types could be HTML or script, so it is only offered as a download.
+    let (content_type, disposition) = match ContentType::parse_flexible(attachment.content_type()) {
+        Some(content_type) if attachments::is_known_content_type(attachment.content_type()) => (content_type, "inline"),
+        _ => (ContentType::Binary, "attachment"),
+    };
+    let disposition = Header::new(
+        "Content-Disposition",
+        format!("{}; filename=\"{}\"", disposition, attachment.filename().replace(['"', '\\'], "_")),
+    );
+
+    Ok(Some(AttachmentFile {
+        file,
+        content_type,
+        disposition,
+        nosniff: Header::new("X-Content-Type-Options", "nosniff"),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/attachments",
+    tag = "attachments",
+    responses(
+        (status = 200, description = "Whether the attachment existed", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to delete attachment")
+    )
+)]
+#[delete("/<id>")]
+pub async fn attachments_delete(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    config: &State<config::Config>,
+    events: &State<EventBus>,
+    id: i32,
+) -> Result<Json<bool>, std::io::Error> {
+    let deleted = attachments::remove_attachment(db_pool, repository.actor(), config.attachments_dir(), id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete attachment"))?;
+    if deleted.is_some() {
+        events.deleted(EventKind::AttachmentDeleted, id);
+    }
+
+    Ok(Json(deleted.is_some()))
+}
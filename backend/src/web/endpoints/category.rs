@@ -1,44 +1,149 @@
 use rocket::serde::json::Json;
-use rocket::{delete, get, post, State};
+use rocket::{delete, get, post};
 
-use crate::database::category;
-use datatypes::Category;
+use crate::utils::categories;
+use crate::web::audited::Audited;
+use crate::web::error::ApiError;
+use datatypes::{Category, CategoryNode};
 
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "categories",
+    request_body = NameDescription,
+    responses(
+        (status = 200, description = "The created category", body = Category),
+        (status = 500, description = "Failed to create category, e.g. because the name is taken")
+    )
+)]
+#[deprecated(note = "use `POST /api/v1/categories`")]
 #[post("/create", format = "json", data = "<name_description>")]
 pub async fn categories_create(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
     name_description: Json<(String, String)>,
 ) -> Result<Json<Category>, std::io::Error> {
-    let category = category::insert_category(db_pool, name_description.0 .0, &name_description.0 .1)
+    let category = repository.insert_category(name_description.0 .0, &name_description.0 .1, None)
         .await
         .map_err(|_e| {
-            std::io::Error::new(std::io::ErrorKind::Other, "Failed to create category")
+            std::io::Error::other("Failed to create category")
         })?;
 
     Ok(Json(category))
 }
 
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Every category, ordered by name", body = [Category]),
+        (status = 500, description = "Failed to get categories")
+    )
+)]
+#[deprecated(note = "use `GET /api/v1/categories`")]
 #[get("/all")]
 pub async fn categories_all(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
 ) -> Result<Json<Vec<Category>>, std::io::Error> {
-    let categories = category::get_categories(db_pool)
+    let categories = repository.get_categories()
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to get categories"))?;
+        .map_err(|_e| std::io::Error::other("Failed to get categories"))?;
 
     Ok(Json(categories))
 }
 
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The top-level categories with their descendants", body = [CategoryNode]),
+        (status = 500, description = "Failed to get categories")
+    )
+)]
+#[deprecated(note = "use `GET /api/v1/categories/tree`")]
+#[get("/tree")]
+pub async fn categories_tree(
+    repository: Audited,
+) -> Result<Json<Vec<CategoryNode>>, std::io::Error> {
+    let categories = repository.get_categories()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get categories"))?;
+
+    Ok(Json(categories::category_tree(&categories)))
+}
+
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "categories",
+    request_body = IdParent,
+    responses(
+        (status = 200, description = "Whether the category was moved, `false` if it does not exist or the move would create a cycle", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to move category")
+    )
+)]
+#[deprecated(note = "use `PATCH /api/v1/categories/<id>`")]
+#[post("/parent", format = "json", data = "<id_parent>")]
+pub async fn categories_parent(
+    repository: Audited,
+    id_parent: Json<(i32, Option<i32>)>,
+) -> Result<Json<bool>, std::io::Error> {
+    let moved = repository.set_category_parent(id_parent.0 .0, id_parent.0 .1)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to move category"))?;
+
+    Ok(Json(moved))
+}
+
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "categories",
+    request_body(content = i32, description = "Id of the category", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Whether the category existed", body = bool, content_type = "application/json"),
+        (status = 409, description = "The category is still referenced, merge it into another category instead", body = ErrorBody),
+        (status = 500, description = "Failed to delete category")
+    )
+)]
+#[deprecated(note = "use `DELETE /api/v1/categories/<id>`")]
 #[delete("/delete", format = "json", data = "<category_id>")]
 pub async fn categories_delete(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
     category_id: Json<i32>,
-) -> Result<Json<bool>, std::io::Error> {
-    let category = category::delete_category(db_pool, category_id.0)
+) -> Result<Json<bool>, ApiError> {
+    let references = repository.get_category_references(category_id.0)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete category"))?;
+    if !references.is_empty() {
+        return Err(ApiError::Conflict(format!(
+            "Category is still referenced by {}, merge it into another category instead",
+            references
+        )));
+    }
+    let category = repository.delete_category(category_id.0)
         .await
         .map_err(|_e| {
-            std::io::Error::new(std::io::ErrorKind::Other, "Failed to delete category")
+            std::io::Error::other("Failed to delete category")
         })?;
 
     Ok(Json(category))
 }
+
+#[utoipa::path(
+    context_path = "/categories",
+    tag = "categories",
+    request_body = FromInto,
+    responses(
+        (status = 200, description = "Whether both categories existed", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to merge categories")
+    )
+)]
+#[deprecated(note = "use `POST /api/v1/categories/<id>/merge/<into>`")]
+#[post("/merge", format = "json", data = "<from_into>")]
+pub async fn categories_merge(
+    repository: Audited,
+    from_into: Json<(i32, i32)>,
+) -> Result<Json<bool>, std::io::Error> {
+    let merged = repository.merge_categories(from_into.0 .0, from_into.0 .1)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to merge categories"))?;
+
+    Ok(Json(merged))
+}
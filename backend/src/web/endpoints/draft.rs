@@ -0,0 +1,161 @@
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+
+use crate::database::draft;
+use crate::utils::events::EventBus;
+use crate::web::audited::Audited;
+use datatypes::{AuditSource, DraftExpense, DraftStatus, EventKind, Expense, ImportBatch};
+
+/// Publishes the draft as it is now, after its status changed.
+async fn publish_draft(db_pool: &sqlx::PgPool, events: &EventBus, id: i32) -> Result<(), std::io::Error> {
+    let draft = draft::get_draft_expense(db_pool, id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get draft"))?;
+    if let Some(draft) = draft {
+        events.publish_entity(EventKind::DraftUpdated, id, &draft);
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    context_path = "/imports",
+    tag = "imports",
+    responses(
+        (status = 200, description = "Every import batch", body = [ImportBatch]),
+        (status = 500, description = "Failed to get import batches")
+    )
+)]
+#[get("/batches")]
+pub async fn imports_batches(
+    db_pool: &State<sqlx::PgPool>,
+) -> Result<Json<Vec<ImportBatch>>, std::io::Error> {
+    let batches = draft::get_import_batches(db_pool)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get import batches"))?;
+
+    Ok(Json(batches))
+}
+
+#[utoipa::path(
+    context_path = "/imports",
+    tag = "imports",
+    responses(
+        (status = 200, description = "The drafts of the batch", body = [DraftExpense]),
+        (status = 500, description = "Failed to get drafts")
+    )
+)]
+#[get("/drafts/<batch_id>")]
+pub async fn imports_drafts(
+    db_pool: &State<sqlx::PgPool>,
+    batch_id: i32,
+) -> Result<Json<Vec<DraftExpense>>, std::io::Error> {
+    let drafts = draft::get_draft_expenses(db_pool, batch_id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get drafts"))?;
+
+    Ok(Json(drafts))
+}
+
+#[utoipa::path(
+    context_path = "/imports",
+    tag = "imports",
+    request_body = DraftExpense,
+    responses(
+        (status = 200, description = "The draft, checked again", body = DraftExpense),
+        (status = 500, description = "Failed to update draft, or it does not exist or was already posted")
+    )
+)]
+#[post("/drafts/update", format = "json", data = "<draft>")]
+pub async fn imports_drafts_update(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    events: &State<EventBus>,
+    draft: Json<DraftExpense>,
+) -> Result<Json<DraftExpense>, std::io::Error> {
+    let draft = draft::update_draft_expense(db_pool, repository.actor(), draft.0)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to update draft"))?
+        .ok_or_else(|| std::io::Error::other("Draft does not exist or was already posted"))?;
+    events.publish_entity(EventKind::DraftUpdated, draft.id(), &draft);
+
+    Ok(Json(draft))
+}
+
+#[utoipa::path(
+    context_path = "/imports",
+    tag = "imports",
+    request_body(content = i32, description = "Id of the draft", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Whether the draft could be approved", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to approve draft")
+    )
+)]
+#[post("/drafts/approve", format = "json", data = "<draft_id>")]
+pub async fn imports_drafts_approve(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    events: &State<EventBus>,
+    draft_id: Json<i32>,
+) -> Result<Json<bool>, std::io::Error> {
+    let approved = draft::set_draft_status(db_pool, repository.actor(), draft_id.0, DraftStatus::Approved)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to approve draft"))?;
+    if approved {
+        publish_draft(db_pool, events, draft_id.0).await?;
+    }
+
+    Ok(Json(approved))
+}
+
+#[utoipa::path(
+    context_path = "/imports",
+    tag = "imports",
+    request_body(content = i32, description = "Id of the draft", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Whether the draft could be rejected", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to reject draft")
+    )
+)]
+#[post("/drafts/reject", format = "json", data = "<draft_id>")]
+pub async fn imports_drafts_reject(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    events: &State<EventBus>,
+    draft_id: Json<i32>,
+) -> Result<Json<bool>, std::io::Error> {
+    let rejected = draft::set_draft_status(db_pool, repository.actor(), draft_id.0, DraftStatus::Rejected)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to reject draft"))?;
+    if rejected {
+        publish_draft(db_pool, events, draft_id.0).await?;
+    }
+
+    Ok(Json(rejected))
+}
+
+#[utoipa::path(
+    context_path = "/imports",
+    tag = "imports",
+    request_body(content = i32, description = "Id of the batch", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The expenses created from the approved drafts", body = [Expense]),
+        (status = 500, description = "Failed to post drafts")
+    )
+)]
+#[post("/post", format = "json", data = "<batch_id>")]
+pub async fn imports_post(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    events: &State<EventBus>,
+    batch_id: Json<i32>,
+) -> Result<Json<Vec<Expense>>, std::io::Error> {
+    let repository = repository.with_source(AuditSource::Import);
+    let expenses = draft::post_approved_drafts(db_pool, repository.actor(), batch_id.0)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to post drafts"))?;
+    for expense in &expenses {
+        events.publish_entity(EventKind::ExpenseCreated, expense.id(), expense);
+    }
+
+    Ok(Json(expenses))
+}
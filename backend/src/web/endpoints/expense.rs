@@ -1,56 +1,126 @@
 use rocket::serde::json::Json;
 use rocket::{get, post, State};
 
-use crate::database::expense;
+use crate::database::tag;
+use crate::utils::events::EventBus;
+use crate::web::audited::Audited;
+use crate::web::endpoints::v1::expense_tags_set;
 
 use datatypes::{Expense, Filter};
 
+#[utoipa::path(
+    context_path = "/expenses",
+    tag = "expenses",
+    request_body = Expense,
+    responses(
+        (status = 200, description = "The created expense", body = Expense),
+        (status = 500, description = "Failed to create expense")
+    )
+)]
+#[deprecated(note = "use `POST /api/v1/expenses`")]
 #[post("/create", format = "json", data = "<expense>")]
 pub async fn expenses_create(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
     expense: Json<Expense>,
 ) -> Result<Json<Expense>, std::io::Error> {
-    let expense = expense::insert_expense(db_pool, expense.0)
+    let expense = repository.insert_expense(expense.0)
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to create expense"))?;
+        .map_err(|_e| std::io::Error::other("Failed to create expense"))?;
 
-    // let expense = expense::insert_expense(db_pool, expense.0)
+    // let expense = repository.insert_expense(expense.0)
     //     .await
     //     .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("Failed to create expense: {}", e.to_string())))?;
 
     Ok(Json(expense))
 }
 
+#[utoipa::path(
+    context_path = "/expenses",
+    tag = "expenses",
+    responses(
+        (status = 200, description = "Every expense, newest first", body = [Expense]),
+        (status = 500, description = "Failed to get expenses")
+    )
+)]
+#[deprecated(note = "use `GET /api/v1/expenses`")]
 #[get("/all")]
 pub async fn expenses_all(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
 ) -> Result<Json<Vec<Expense>>, std::io::Error> {
-    let expenses = expense::get_expenses(db_pool, None)
+    let expenses = repository.get_expenses(None)
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to get expenses"))?;
+        .map_err(|_e| std::io::Error::other("Failed to get expenses"))?;
 
     Ok(Json(expenses))
 }
 
+#[utoipa::path(
+    context_path = "/expenses",
+    tag = "expenses",
+    request_body = Filter,
+    responses(
+        (status = 200, description = "The matching expenses in the order of the filter", body = [Expense]),
+        (status = 500, description = "Failed to get expenses")
+    )
+)]
+#[deprecated(note = "use `GET /api/v1/expenses`")]
 #[post("/filter", format = "json", data = "<filter>")]
 pub async fn expenses_filter(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
     filter: Json<Filter>,
 ) -> Result<Json<Vec<Expense>>, std::io::Error> {
-    let expenses = expense::get_expenses(db_pool, Some(filter.0))
+    let expenses = repository.get_expenses(Some(filter.0))
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to delete user"))?;
+        .map_err(|_e| std::io::Error::other("Failed to delete user"))?;
 
     Ok(Json(expenses))
 }
 
+#[utoipa::path(
+    context_path = "/expenses",
+    tag = "expenses",
+    request_body = ExpenseTags,
+    responses(
+        (status = 200, description = "The tags the expense has now", body = [String], content_type = "application/json"),
+        (status = 500, description = "Failed to set tags, or the expense does not exist")
+    )
+)]
+#[deprecated(note = "use `PUT /api/v1/expenses/<id>/tags`")]
+#[post("/tags", format = "json", data = "<id_tags>")]
+pub async fn expenses_tags(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    events: &State<EventBus>,
+    id_tags: Json<(i32, Vec<String>)>,
+) -> Result<Json<Vec<String>>, std::io::Error> {
+    let (id, tags) = id_tags.0;
+    let tags = tag::set_expense_tags(db_pool, repository.actor(), id, &tags)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to set tags"))?
+        .ok_or_else(|| std::io::Error::other("Expense does not exist"))?;
+    expense_tags_set(db_pool, events, id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to set tags"))?;
+
+    Ok(Json(tags))
+}
+
+#[utoipa::path(
+    context_path = "/expenses",
+    tag = "expenses",
+    responses(
+        (status = 200, description = "When the expenses were last reset", body = chrono::NaiveDateTime, content_type = "application/json"),
+        (status = 500, description = "Failed to get last reset, or there was none yet")
+    )
+)]
+#[deprecated(note = "use `GET /api/v1/expenses/last-reset`")]
 #[get("/last-reset")]
 pub async fn expenses_last_reset(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
 ) -> Result<Json<chrono::NaiveDateTime>, std::io::Error> {
-    let date = expense::get_last_reset(db_pool)
+    let date = repository.get_last_reset()
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to get last reset"))?;
+        .map_err(|_e| std::io::Error::other("Failed to get last reset"))?;
 
     Ok(Json(date))
 }
\ No newline at end of file
@@ -1,7 +1,14 @@
+mod attachment;
 mod category;
+mod draft;
 mod expense;
+mod tag;
 mod user;
+pub(super) mod v1;
 
+pub(super) use attachment::*;
 pub(super) use category::*;
+pub(super) use draft::*;
 pub(super) use expense::*;
+pub(super) use tag::*;
 pub(super) use user::*;
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+
+use crate::database::tag;
+use crate::utils::balance;
+use crate::web::audited::Audited;
+use datatypes::Tag;
+
+#[utoipa::path(
+    context_path = "/tags",
+    tag = "tags",
+    responses(
+        (status = 200, description = "Every tag, ordered by name", body = [Tag]),
+        (status = 500, description = "Failed to get tags")
+    )
+)]
+#[get("/all")]
+pub async fn tags_all(db_pool: &State<sqlx::PgPool>) -> Result<Json<Vec<Tag>>, std::io::Error> {
+    let tags = tag::get_tags(db_pool)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get tags"))?;
+
+    Ok(Json(tags))
+}
+
+#[utoipa::path(
+    context_path = "/tags",
+    tag = "tags",
+    responses(
+        (status = 200, description = "The amount spent per tag since the last reset", body = HashMap<String, f64>),
+        (status = 500, description = "Failed to get expenses")
+    )
+)]
+#[get("/totals")]
+pub async fn tags_totals(
+    repository: Audited,
+) -> Result<Json<HashMap<String, f64>>, std::io::Error> {
+    let expenses = balance::expenses_since_last_reset(&*repository)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get expenses"))?;
+
+    Ok(Json(balance::tag_totals(&expenses)))
+}
+
+#[utoipa::path(
+    context_path = "/tags",
+    tag = "tags",
+    request_body = FromTo,
+    responses(
+        (status = 200, description = "Whether the tag existed and the new name was free", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to rename tag")
+    )
+)]
+#[post("/rename", format = "json", data = "<from_to>")]
+pub async fn tags_rename(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    from_to: Json<(String, String)>,
+) -> Result<Json<bool>, std::io::Error> {
+    let renamed = tag::rename_tag(db_pool, repository.actor(), &from_to.0 .0, &from_to.0 .1)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to rename tag"))?;
+
+    Ok(Json(renamed))
+}
+
+#[utoipa::path(
+    context_path = "/tags",
+    tag = "tags",
+    request_body = FromTo,
+    responses(
+        (status = 200, description = "Whether the tag existed", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to merge tags")
+    )
+)]
+#[post("/merge", format = "json", data = "<from_into>")]
+pub async fn tags_merge(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    from_into: Json<(String, String)>,
+) -> Result<Json<bool>, std::io::Error> {
+    let merged = tag::merge_tags(db_pool, repository.actor(), &from_into.0 .0, &from_into.0 .1)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to merge tags"))?;
+
+    Ok(Json(merged))
+}
@@ -1,40 +1,102 @@
 use rocket::serde::json::Json;
-use rocket::{delete, get, post, State};
+use rocket::{delete, get, post};
 
-use crate::database::user;
+use crate::web::audited::Audited;
+use crate::web::error::ApiError;
 use datatypes::User;
 
+#[utoipa::path(
+    context_path = "/users",
+    tag = "users",
+    request_body(content = String, content_type = "application/json"),
+    responses(
+        (status = 200, description = "The created user", body = User),
+        (status = 500, description = "Failed to create user, e.g. because the name is taken")
+    )
+)]
+#[deprecated(note = "use `POST /api/v1/users`")]
 #[post("/create", format = "json", data = "<name>")]
 pub async fn users_create(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
     name: Json<String>,
 ) -> Result<Json<User>, std::io::Error> {
-    let user = user::insert_user(db_pool, name.0)
+    let user = repository.insert_user(name.0)
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to create user"))?;
+        .map_err(|_e| std::io::Error::other("Failed to create user"))?;
 
     Ok(Json(user))
 }
 
+#[utoipa::path(
+    context_path = "/users",
+    tag = "users",
+    responses(
+        (status = 200, description = "Every user", body = [User]),
+        (status = 500, description = "Failed to get users")
+    )
+)]
+#[deprecated(note = "use `GET /api/v1/users`")]
 #[get("/all")]
 pub async fn users_all(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
 ) -> Result<Json<Vec<User>>, std::io::Error> {
-    let users = user::get_users(db_pool)
+    let users = repository.get_users()
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to get users"))?;
+        .map_err(|_e| std::io::Error::other("Failed to get users"))?;
 
     Ok(Json(users))
 }
 
+#[utoipa::path(
+    context_path = "/users",
+    tag = "users",
+    request_body(content = i32, description = "Id of the user", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Whether the user existed", body = bool, content_type = "application/json"),
+        (status = 409, description = "The user is still referenced, merge it into another user instead", body = ErrorBody),
+        (status = 500, description = "Failed to delete user")
+    )
+)]
+#[deprecated(note = "use `DELETE /api/v1/users/<id>`")]
 #[delete("/delete", format = "json", data = "<user_id>")]
 pub async fn users_delete(
-    db_pool: &State<sqlx::PgPool>,
+    repository: Audited,
     user_id: Json<i32>,
-) -> Result<Json<bool>, std::io::Error> {
-    let user = user::delete_user(db_pool, user_id.0)
+) -> Result<Json<bool>, ApiError> {
+    let references = repository.get_user_references(user_id.0)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete user"))?;
+    if !references.is_empty() {
+        return Err(ApiError::Conflict(format!(
+            "User is still referenced by {}, merge it into another user instead",
+            references
+        )));
+    }
+    let user = repository.delete_user(user_id.0)
         .await
-        .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Failed to delete user"))?;
+        .map_err(|_e| std::io::Error::other("Failed to delete user"))?;
 
     Ok(Json(user))
 }
+
+#[utoipa::path(
+    context_path = "/users",
+    tag = "users",
+    request_body = FromInto,
+    responses(
+        (status = 200, description = "Whether both users existed", body = bool, content_type = "application/json"),
+        (status = 500, description = "Failed to merge users")
+    )
+)]
+#[deprecated(note = "use `POST /api/v1/users/<id>/merge/<into>`")]
+#[post("/merge", format = "json", data = "<from_into>")]
+pub async fn users_merge(
+    repository: Audited,
+    from_into: Json<(i32, i32)>,
+) -> Result<Json<bool>, std::io::Error> {
+    let merged = repository.merge_users(from_into.0 .0, from_into.0 .1)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to merge users"))?;
+
+    Ok(Json(merged))
+}
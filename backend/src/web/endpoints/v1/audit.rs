@@ -0,0 +1,83 @@
+use rocket::form::{FromForm, FromFormField};
+use rocket::get;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use utoipa::{IntoParams, ToSchema};
+
+use super::Date;
+use crate::web::audited::Audited;
+use datatypes::{AuditEntry, AuditFilter, AuditSource, EventKind};
+
+#[derive(FromFormField, ToSchema)]
+pub enum Source {
+    Web,
+    Cli,
+    Import,
+}
+
+impl From<Source> for AuditSource {
+    fn from(source: Source) -> Self {
+        match source {
+            Source::Web => AuditSource::Web,
+            Source::Cli => AuditSource::Cli,
+            Source::Import => AuditSource::Import,
+        }
+    }
+}
+
+/// The `AuditFilter` as query parameters, where everything may be left out. Repeat a parameter
+/// for several values.
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// Entries of these actions, e.g. `category.deleted`, or `category` for every `category.*`
+    /// action.
+    action: Vec<String>,
+    /// Entries about these users, categories or expenses.
+    entity_id: Vec<i32>,
+    /// Entries of changes made by these actors.
+    actor: Vec<String>,
+    #[param(inline)]
+    source: Vec<Source>,
+    /// Entries from this day on.
+    #[field(default = Date(chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap()))]
+    #[param(value_type = Option<String>, format = Date)]
+    since: Date,
+    /// 50 when left out.
+    #[field(default = 50)]
+    #[param(value_type = Option<i64>)]
+    limit: i64,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "The latest matching entries of the audit log, newest first", body = [AuditEntry]),
+        (status = 422, description = "An action matches no event"),
+        (status = 500, description = "Failed to get the audit log")
+    )
+)]
+#[get("/audit?<query..>")]
+pub async fn audit_log(
+    repository: Audited,
+    query: AuditQuery,
+) -> Result<Json<Vec<AuditEntry>>, Status> {
+    if query.action.iter().any(|a| !EventKind::ALL.iter().any(|e| e.matches(a))) {
+        return Err(Status::UnprocessableEntity);
+    }
+    let filter = AuditFilter {
+        actions: query.action,
+        entity_ids: query.entity_id,
+        actors: query.actor,
+        sources: query.source.into_iter().map(AuditSource::from).collect(),
+        since: query.since.0.and_hms_opt(0, 0, 0).unwrap(),
+        limit: query.limit,
+    };
+    let entries = repository.get_audit_entries(&filter)
+        .await
+        .map_err(|_e| Status::InternalServerError)?;
+
+    Ok(Json(entries))
+}
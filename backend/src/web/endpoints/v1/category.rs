@@ -0,0 +1,230 @@
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::{delete, get, patch, post};
+
+use crate::utils::categories;
+use crate::web::audited::Audited;
+use crate::web::error::ApiError;
+use datatypes::{Category, CategoryNode, CategoryPatch, NewCategory};
+
+/// Fails with a conflict if a category other than `id` already has the name.
+async fn check_name_free(repository: &Audited, id: Option<i32>, name: &str) -> Result<(), ApiError> {
+    let categories = repository.get_categories()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get categories"))?;
+    if categories.iter().any(|c| Some(c.id()) != id && c.name() == name) {
+        return Err(ApiError::Conflict(format!("Category `{}` already exists", name)));
+    }
+    Ok(())
+}
+
+/// Fails as unprocessable if there is no category `parent_id` to file a category under, or if
+/// it is category `id` itself or one of its descendants.
+async fn check_parent(repository: &Audited, id: Option<i32>, parent_id: i32) -> Result<(), ApiError> {
+    let categories = repository.get_categories()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get categories"))?;
+    if !categories.iter().any(|c| c.id() == parent_id) {
+        return Err(ApiError::Unprocessable(format!("Parent category {} does not exist", parent_id)));
+    }
+    if id.is_some_and(|id| categories::descendant_ids(&categories, id).contains(&parent_id)) {
+        return Err(ApiError::Unprocessable("Category cannot be moved there".to_string()));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Every category, ordered by name", body = [Category]),
+        (status = 500, description = "Failed to get categories")
+    )
+)]
+#[get("/categories")]
+pub async fn list_categories(
+    repository: Audited,
+) -> Result<Json<Vec<Category>>, std::io::Error> {
+    let categories = repository.get_categories()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get categories"))?;
+
+    Ok(Json(categories))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The top-level categories with their descendants", body = [CategoryNode]),
+        (status = 500, description = "Failed to get categories")
+    )
+)]
+#[get("/categories/tree")]
+pub async fn category_tree(
+    repository: Audited,
+) -> Result<Json<Vec<CategoryNode>>, std::io::Error> {
+    let categories = repository.get_categories()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get categories"))?;
+
+    Ok(Json(categories::category_tree(&categories)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "categories",
+    request_body = NewCategory,
+    responses(
+        (status = 201, description = "The created category", body = Category),
+        (status = 409, description = "The name is taken", body = ErrorBody),
+        (status = 422, description = "The parent does not exist", body = ErrorBody),
+        (status = 500, description = "Failed to create category")
+    )
+)]
+#[post("/categories", format = "json", data = "<category>")]
+pub async fn create_category(
+    repository: Audited,
+    category: Json<NewCategory>,
+) -> Result<Created<Json<Category>>, ApiError> {
+    let NewCategory { name, description, parent_id } = category.0;
+    check_name_free(&repository, None, &name).await?;
+    if let Some(parent_id) = parent_id {
+        check_parent(&repository, None, parent_id).await?;
+    }
+    let category = repository.insert_category(name, &description, parent_id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to create category"))?;
+
+    Ok(Created::new(format!("/api/v1/categories/{}", category.id())).body(Json(category)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "categories",
+    responses(
+        (status = 200, description = "The category", body = Category),
+        (status = 404, description = "The category does not exist"),
+        (status = 500, description = "Failed to get category")
+    )
+)]
+#[get("/categories/<id>")]
+pub async fn get_category(
+    repository: Audited,
+    id: i32,
+) -> Result<Option<Json<Category>>, std::io::Error> {
+    let categories = repository.get_categories()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get category"))?;
+
+    Ok(categories.into_iter().find(|c| c.id() == id).map(Json))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "categories",
+    request_body = CategoryPatch,
+    responses(
+        (status = 200, description = "The updated category", body = Category),
+        (status = 404, description = "The category does not exist"),
+        (status = 409, description = "The name is taken by another category", body = ErrorBody),
+        (status = 422, description = "The new parent does not exist, or the move would create a cycle", body = ErrorBody),
+        (status = 500, description = "Failed to update category")
+    )
+)]
+#[patch("/categories/<id>", format = "json", data = "<patch>")]
+pub async fn update_category(
+    repository: Audited,
+    id: i32,
+    patch: Json<CategoryPatch>,
+) -> Result<Option<Json<Category>>, ApiError> {
+    if get_category(repository.clone(), id).await?.is_none() {
+        return Ok(None);
+    }
+
+    // Everything is checked before the first write, so a refused patch changes nothing.
+    let CategoryPatch { name, description, parent_id } = patch.0;
+    if let Some(name) = &name {
+        check_name_free(&repository, Some(id), name).await?;
+    }
+    if let Some(Some(parent_id)) = parent_id {
+        check_parent(&repository, Some(id), parent_id).await?;
+    }
+    if let Some(parent_id) = parent_id {
+        let moved = repository.set_category_parent(id, parent_id)
+            .await
+            .map_err(|_e| std::io::Error::other("Failed to update category"))?;
+        if !moved {
+            return Err(ApiError::Unprocessable("Category cannot be moved there".to_string()));
+        }
+    }
+    if let Some(name) = name {
+        repository.rename_category(id, name)
+            .await
+            .map_err(|_e| std::io::Error::other("Failed to update category"))?;
+    }
+    if let Some(description) = description {
+        repository.update_category_description(id, &description)
+            .await
+            .map_err(|_e| std::io::Error::other("Failed to update category"))?;
+    }
+
+    Ok(get_category(repository, id).await?)
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "categories",
+    responses(
+        (status = 204, description = "The category was deleted"),
+        (status = 404, description = "The category does not exist"),
+        (status = 409, description = "The category is still referenced, merge it into another category instead", body = ErrorBody),
+        (status = 500, description = "Failed to delete category")
+    )
+)]
+#[delete("/categories/<id>")]
+pub async fn delete_category(
+    repository: Audited,
+    id: i32,
+) -> Result<Option<NoContent>, ApiError> {
+    let references = repository.get_category_references(id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete category"))?;
+    if !references.is_empty() {
+        return Err(ApiError::Conflict(format!(
+            "Category is still referenced by {}, merge it into another category instead",
+            references
+        )));
+    }
+    let deleted = repository.delete_category(id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete category"))?;
+
+    Ok(deleted.then_some(NoContent))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "categories",
+    params(
+        ("id" = i32, Path, description = "The category that is merged and removed"),
+        ("into" = i32, Path, description = "The category that takes over its expenses and sub-categories")
+    ),
+    responses(
+        (status = 204, description = "The categories were merged"),
+        (status = 404, description = "Either category does not exist, or both are the same"),
+        (status = 500, description = "Failed to merge categories")
+    )
+)]
+#[post("/categories/<id>/merge/<into>")]
+pub async fn merge_category(
+    repository: Audited,
+    id: i32,
+    into: i32,
+) -> Result<Option<NoContent>, std::io::Error> {
+    let merged = repository.merge_categories(id, into)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to merge categories"))?;
+
+    Ok(merged.then_some(NoContent))
+}
@@ -0,0 +1,48 @@
+use rocket::http::Status;
+use rocket::response::stream::{Event as SseEvent, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{get, Shutdown, State};
+
+use crate::utils::events::EventBus;
+use datatypes::EventKind;
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "events",
+    params(
+        ("kind" = Option<Vec<String>>, Query, description = "Only these events, e.g. `expense.created`, or `expense` for every `expense.*` event. Repeat for several, every event when left out.")
+    ),
+    responses(
+        (status = 200, description = "A Server-Sent Events stream, named by the event type with the `Event` as data", body = Event, content_type = "text/event-stream"),
+        (status = 422, description = "A kind matches no event")
+    )
+)]
+#[get("/events?<kind>")]
+pub async fn events(
+    bus: &State<EventBus>,
+    kind: Vec<String>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    if kind.iter().any(|k| !EventKind::ALL.iter().any(|e| e.matches(k))) {
+        return Err(Status::UnprocessableEntity);
+    }
+    let mut receiver = bus.subscribe();
+
+    Ok(EventStream! {
+        loop {
+            let event = select! {
+                received = receiver.recv() => match received {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => break,
+                    // The subscriber was too slow, it reloads anyway on the next event.
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut shutdown => break,
+            };
+            if kind.is_empty() || kind.iter().any(|k| event.kind().matches(k)) {
+                yield SseEvent::json(&event).event(event.kind().name());
+            }
+        }
+    })
+}
@@ -0,0 +1,296 @@
+use rocket::form::{self, FromForm, FromFormField, ValueField};
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::{delete, get, patch, post, put, State};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::database::{expense, tag};
+use crate::utils::events::EventBus;
+use crate::web::audited::Audited;
+use datatypes::{EventKind, Expense, ExpensePatch, Filter, OrderBy};
+
+/// A `YYYY-MM-DD` query parameter.
+pub struct Date(pub(super) chrono::NaiveDate);
+
+impl<'v> FromFormField<'v> for Date {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        chrono::NaiveDate::parse_from_str(field.value, "%Y-%m-%d")
+            .map(Date)
+            .map_err(|e| form::Error::validation(e.to_string()).into())
+    }
+}
+
+#[derive(FromFormField, ToSchema)]
+pub enum Order {
+    Amount,
+    Date,
+    Created,
+}
+
+impl From<Order> for OrderBy {
+    fn from(order: Order) -> Self {
+        match order {
+            Order::Amount => OrderBy::Amount,
+            Order::Date => OrderBy::Date,
+            Order::Created => OrderBy::Created,
+        }
+    }
+}
+
+/// The `Filter` as query parameters, where everything may be left out. Repeat `user_id`,
+/// `category_id` and the tag parameters for several values. The defaults are set with `field`
+/// rather than with `Option`, which would take a value that doesn't parse as left out.
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExpenseQuery {
+    /// Expenses paid by these users, every user when left out.
+    user_id: Vec<i32>,
+    /// Expenses in these categories or their sub-categories, every category when left out.
+    category_id: Vec<i32>,
+    #[field(default = f64::MIN)]
+    #[param(value_type = Option<f64>)]
+    min_amount: f64,
+    #[field(default = f64::MAX)]
+    #[param(value_type = Option<f64>)]
+    max_amount: f64,
+    // The widest range every storage can compare against, `NaiveDate::MIN` is too early for
+    // Postgres.
+    #[field(default = Date(chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap()))]
+    #[param(value_type = Option<String>, format = Date)]
+    min_date: Date,
+    #[field(default = Date(chrono::NaiveDate::from_ymd_opt(9999, 12, 31).unwrap()))]
+    #[param(value_type = Option<String>, format = Date)]
+    max_date: Date,
+    /// `created` when left out.
+    #[field(default = Order::Created)]
+    #[param(value_type = Option<Order>, inline)]
+    order_by: Order,
+    /// Newest or largest first when left out.
+    #[field(default = false)]
+    #[param(value_type = Option<bool>)]
+    order_asc: bool,
+    /// Expenses with at least one of these tags.
+    tag_any: Vec<String>,
+    /// Expenses with every one of these tags.
+    tag_all: Vec<String>,
+    /// Expenses with none of these tags.
+    tag_none: Vec<String>,
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    params(ExpenseQuery),
+    responses(
+        (status = 200, description = "The matching expenses, newest first unless ordered otherwise", body = [Expense]),
+        (status = 500, description = "Failed to get expenses")
+    )
+)]
+#[get("/expenses?<query..>")]
+pub async fn list_expenses(
+    repository: Audited,
+    query: ExpenseQuery,
+) -> Result<Json<Vec<Expense>>, std::io::Error> {
+    let mut user_ids = query.user_id;
+    if user_ids.is_empty() {
+        let users = repository.get_users()
+            .await
+            .map_err(|_e| std::io::Error::other("Failed to get expenses"))?;
+        user_ids = users.iter().map(|u| u.id()).collect();
+    }
+    let mut category_ids = query.category_id;
+    if category_ids.is_empty() {
+        let categories = repository.get_categories()
+            .await
+            .map_err(|_e| std::io::Error::other("Failed to get expenses"))?;
+        category_ids = categories.iter().map(|c| c.id()).collect();
+    }
+
+    let filter = Filter {
+        user_ids,
+        category_ids,
+        min_amount: query.min_amount,
+        max_amount: query.max_amount,
+        min_date: query.min_date.0,
+        max_date: query.max_date.0,
+        order_by: query.order_by.into(),
+        order_asc: query.order_asc,
+        tags_any: query.tag_any,
+        tags_all: query.tag_all,
+        tags_none: query.tag_none,
+    };
+    let expenses = repository.get_expenses(Some(filter))
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get expenses"))?;
+
+    Ok(Json(expenses))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    request_body = Expense,
+    responses(
+        (status = 201, description = "The created expense", body = Expense),
+        (status = 500, description = "Failed to create expense")
+    )
+)]
+#[post("/expenses", format = "json", data = "<expense>")]
+pub async fn create_expense(
+    repository: Audited,
+    expense: Json<Expense>,
+) -> Result<Created<Json<Expense>>, std::io::Error> {
+    let expense = repository.insert_expense(expense.0)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to create expense"))?;
+
+    Ok(Created::new(format!("/api/v1/expenses/{}", expense.id())).body(Json(expense)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    responses(
+        (status = 200, description = "The expense", body = Expense),
+        (status = 404, description = "The expense does not exist"),
+        (status = 500, description = "Failed to get expense")
+    )
+)]
+#[get("/expenses/<id>")]
+pub async fn get_expense(
+    repository: Audited,
+    id: i32,
+) -> Result<Option<Json<Expense>>, std::io::Error> {
+    let expense = repository.get_expense(id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get expense"))?;
+
+    Ok(expense.map(Json))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    request_body = ExpensePatch,
+    responses(
+        (status = 200, description = "The updated expense", body = Expense),
+        (status = 404, description = "The expense does not exist"),
+        (status = 500, description = "Failed to update expense, e.g. because the user or category does not exist")
+    )
+)]
+#[patch("/expenses/<id>", format = "json", data = "<patch>")]
+pub async fn update_expense(
+    repository: Audited,
+    id: i32,
+    patch: Json<ExpensePatch>,
+) -> Result<Option<Json<Expense>>, std::io::Error> {
+    let Some(expense) = get_expense(repository.clone(), id).await? else {
+        return Ok(None);
+    };
+    let expense = repository.update_expense(patch.apply(&expense.0))
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to update expense"))?;
+
+    Ok(expense.map(Json))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    responses(
+        (status = 204, description = "The expense was deleted"),
+        (status = 404, description = "The expense does not exist"),
+        (status = 500, description = "Failed to delete expense, e.g. because it still has attachments")
+    )
+)]
+#[delete("/expenses/<id>")]
+pub async fn delete_expense(
+    repository: Audited,
+    id: i32,
+) -> Result<Option<NoContent>, std::io::Error> {
+    let deleted = repository.delete_expense(id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete expense"))?;
+
+    Ok(deleted.then_some(NoContent))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    request_body(content = [String], description = "Replace the tags the expense had", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The tags the expense has now", body = [String], content_type = "application/json"),
+        (status = 404, description = "The expense does not exist"),
+        (status = 500, description = "Failed to set tags")
+    )
+)]
+#[put("/expenses/<id>/tags", format = "json", data = "<tags>")]
+pub async fn set_expense_tags(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    events: &State<EventBus>,
+    id: i32,
+    tags: Json<Vec<String>>,
+) -> Result<Option<Json<Vec<String>>>, std::io::Error> {
+    let tags = tag::set_expense_tags(db_pool, repository.actor(), id, &tags.0)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to set tags"))?;
+    if tags.is_some() {
+        expense_tags_set(db_pool, events, id)
+            .await
+            .map_err(|_e| std::io::Error::other("Failed to set tags"))?;
+    }
+
+    Ok(tags.map(Json))
+}
+
+/// Tags are set past the repository, so the change is published here.
+pub(in crate::web) async fn expense_tags_set(db_pool: &sqlx::PgPool, events: &EventBus, id: i32) -> Result<(), sqlx::Error> {
+    if let Some(after) = expense::get_expense(db_pool, id).await? {
+        events.publish_entity(EventKind::ExpenseUpdated, id, &after);
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    responses(
+        (status = 200, description = "When the expenses were last reset", body = chrono::NaiveDateTime, content_type = "application/json"),
+        (status = 404, description = "There was no reset yet"),
+        (status = 500, description = "Failed to get last reset")
+    )
+)]
+#[get("/expenses/last-reset")]
+pub async fn last_reset(
+    repository: Audited,
+) -> Result<Option<Json<chrono::NaiveDateTime>>, std::io::Error> {
+    match repository.get_last_reset().await {
+        Ok(date) => Ok(Some(Json(date))),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(_e) => Err(std::io::Error::other("Failed to get last reset")),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "expenses",
+    responses(
+        (status = 200, description = "The reset that was just made, balances are computed from here on", body = chrono::NaiveDateTime, content_type = "application/json"),
+        (status = 500, description = "Failed to reset")
+    )
+)]
+#[post("/expenses/last-reset")]
+pub async fn reset(
+    repository: Audited,
+) -> Result<Json<chrono::NaiveDateTime>, std::io::Error> {
+    repository.insert_last_reset()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to reset"))?;
+    let date = repository.get_last_reset()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to reset"))?;
+
+    Ok(Json(date))
+}
@@ -0,0 +1,16 @@
+//! The resource-oriented routes under `/api/v1`. A missing id is a 404 here, where the old routes
+//! answered `false` or failed.
+
+mod audit;
+mod category;
+mod event;
+mod expense;
+mod user;
+mod webhook;
+
+pub(in crate::web) use audit::*;
+pub(in crate::web) use category::*;
+pub(in crate::web) use event::*;
+pub(in crate::web) use expense::*;
+pub(in crate::web) use user::*;
+pub(in crate::web) use webhook::*;
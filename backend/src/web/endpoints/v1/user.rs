@@ -0,0 +1,166 @@
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::{delete, get, patch, post};
+
+use crate::web::audited::Audited;
+use crate::web::error::ApiError;
+use datatypes::{NewUser, User, UserPatch};
+
+/// Fails with a conflict if a user other than `id` already has the name.
+async fn check_name_free(repository: &Audited, id: Option<i32>, username: &str) -> Result<(), ApiError> {
+    let users = repository.get_users()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get users"))?;
+    if users.iter().any(|u| Some(u.id()) != id && u.username() == username) {
+        return Err(ApiError::Conflict(format!("User `{}` already exists", username)));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "users",
+    responses(
+        (status = 200, description = "Every user", body = [User]),
+        (status = 500, description = "Failed to get users")
+    )
+)]
+#[get("/users")]
+pub async fn list_users(
+    repository: Audited,
+) -> Result<Json<Vec<User>>, std::io::Error> {
+    let users = repository.get_users()
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get users"))?;
+
+    Ok(Json(users))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "users",
+    request_body = NewUser,
+    responses(
+        (status = 201, description = "The created user", body = User),
+        (status = 409, description = "The name is taken", body = ErrorBody),
+        (status = 500, description = "Failed to create user")
+    )
+)]
+#[post("/users", format = "json", data = "<user>")]
+pub async fn create_user(
+    repository: Audited,
+    user: Json<NewUser>,
+) -> Result<Created<Json<User>>, ApiError> {
+    check_name_free(&repository, None, &user.username).await?;
+    let user = repository.insert_user(user.0.username)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to create user"))?;
+
+    Ok(Created::new(format!("/api/v1/users/{}", user.id())).body(Json(user)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "users",
+    responses(
+        (status = 200, description = "The user", body = User),
+        (status = 404, description = "The user does not exist"),
+        (status = 500, description = "Failed to get user")
+    )
+)]
+#[get("/users/<id>")]
+pub async fn get_user(
+    repository: Audited,
+    id: i32,
+) -> Result<Option<Json<User>>, std::io::Error> {
+    let user = repository.get_user(id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get user"))?;
+
+    Ok(user.map(Json))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "users",
+    request_body = UserPatch,
+    responses(
+        (status = 200, description = "The updated user", body = User),
+        (status = 404, description = "The user does not exist"),
+        (status = 409, description = "The name is taken by another user", body = ErrorBody),
+        (status = 500, description = "Failed to update user")
+    )
+)]
+#[patch("/users/<id>", format = "json", data = "<patch>")]
+pub async fn update_user(
+    repository: Audited,
+    id: i32,
+    patch: Json<UserPatch>,
+) -> Result<Option<Json<User>>, ApiError> {
+    let Some(username) = patch.0.username else {
+        return Ok(get_user(repository, id).await?);
+    };
+    check_name_free(&repository, Some(id), &username).await?;
+    let user = repository.rename_user(id, username)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to update user"))?;
+
+    Ok(user.map(Json))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "users",
+    responses(
+        (status = 204, description = "The user was deleted"),
+        (status = 404, description = "The user does not exist"),
+        (status = 409, description = "The user is still referenced, merge it into another user instead", body = ErrorBody),
+        (status = 500, description = "Failed to delete user")
+    )
+)]
+#[delete("/users/<id>")]
+pub async fn delete_user(
+    repository: Audited,
+    id: i32,
+) -> Result<Option<NoContent>, ApiError> {
+    let references = repository.get_user_references(id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete user"))?;
+    if !references.is_empty() {
+        return Err(ApiError::Conflict(format!(
+            "User is still referenced by {}, merge it into another user instead",
+            references
+        )));
+    }
+    let deleted = repository.delete_user(id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete user"))?;
+
+    Ok(deleted.then_some(NoContent))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "users",
+    params(
+        ("id" = i32, Path, description = "The user that is merged and removed"),
+        ("into" = i32, Path, description = "The user that takes over its expenses and shares")
+    ),
+    responses(
+        (status = 204, description = "The users were merged"),
+        (status = 404, description = "Either user does not exist, or both are the same"),
+        (status = 500, description = "Failed to merge users")
+    )
+)]
+#[post("/users/<id>/merge/<into>")]
+pub async fn merge_user(
+    repository: Audited,
+    id: i32,
+    into: i32,
+) -> Result<Option<NoContent>, std::io::Error> {
+    let merged = repository.merge_users(id, into)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to merge users"))?;
+
+    Ok(merged.then_some(NoContent))
+}
@@ -0,0 +1,149 @@
+use rocket::response::status::{Accepted, Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, State};
+
+use crate::database::webhook;
+use crate::utils::events::EventBus;
+use crate::utils::webhooks::{self, Dispatcher};
+use crate::web::audited::Audited;
+use crate::web::error::ApiError;
+use datatypes::{Event, EventKind, NewWebhook, Webhook, WebhookDelivery};
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Every webhook, without their secrets", body = [Webhook]),
+        (status = 500, description = "Failed to get webhooks")
+    )
+)]
+#[get("/webhooks")]
+pub async fn list_webhooks(
+    db_pool: &State<sqlx::PgPool>,
+) -> Result<Json<Vec<Webhook>>, std::io::Error> {
+    let webhooks = webhook::get_webhooks(db_pool)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get webhooks"))?;
+
+    Ok(Json(webhooks))
+}
+
+/// Changes are delivered by the process that makes them: the web server for its routes, and each
+/// CLI command for its own changes until it exits.
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "webhooks",
+    request_body = NewWebhook,
+    responses(
+        (status = 201, description = "The created webhook", body = Webhook),
+        (status = 422, description = "The URL is not http(s), the secret is blank or an event matches nothing", body = ErrorBody),
+        (status = 500, description = "Failed to create webhook")
+    )
+)]
+#[post("/webhooks", format = "json", data = "<new>")]
+pub async fn create_webhook(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    bus: &State<EventBus>,
+    new: Json<NewWebhook>,
+) -> Result<Created<Json<Webhook>>, ApiError> {
+    let NewWebhook { url, secret, events } = new.0;
+    webhooks::check_subscription(&url, &secret, &events).map_err(|e| ApiError::Unprocessable(e.to_string()))?;
+    let webhook = webhook::insert_webhook(db_pool, repository.actor(), &Webhook::new(url, secret, events))
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to create webhook"))?;
+    bus.publish_entity(EventKind::WebhookCreated, webhook.id(), &webhook);
+
+    Ok(Created::new(format!("/api/v1/webhooks/{}", webhook.id())).body(Json(webhook)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "webhooks",
+    responses(
+        (status = 204, description = "The webhook and its delivery log were deleted"),
+        (status = 404, description = "The webhook does not exist"),
+        (status = 500, description = "Failed to delete webhook")
+    )
+)]
+#[delete("/webhooks/<id>")]
+pub async fn delete_webhook(
+    repository: Audited,
+    db_pool: &State<sqlx::PgPool>,
+    events: &State<EventBus>,
+    id: i32,
+) -> Result<Option<NoContent>, std::io::Error> {
+    let deleted = webhook::delete_webhook(db_pool, repository.actor(), id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to delete webhook"))?;
+    if deleted {
+        events.deleted(EventKind::WebhookDeleted, id);
+    }
+
+    Ok(deleted.then_some(NoContent))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "The webhook"),
+        ("limit" = Option<i64>, Query, description = "How many attempts, 50 when left out")
+    ),
+    responses(
+        (status = 200, description = "The latest delivery attempts, newest first", body = [WebhookDelivery]),
+        (status = 404, description = "The webhook does not exist"),
+        (status = 500, description = "Failed to get deliveries")
+    )
+)]
+#[get("/webhooks/<id>/deliveries?<limit>")]
+pub async fn webhook_deliveries(
+    db_pool: &State<sqlx::PgPool>,
+    id: i32,
+    limit: Option<i64>,
+) -> Result<Option<Json<Vec<WebhookDelivery>>>, std::io::Error> {
+    let found = webhook::get_webhook(db_pool, id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get deliveries"))?;
+    if found.is_none() {
+        return Ok(None);
+    }
+    let deliveries = webhook::get_deliveries(db_pool, id, limit.unwrap_or(50))
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to get deliveries"))?;
+
+    Ok(Some(Json(deliveries)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "webhooks",
+    responses(
+        (status = 202, description = "A `ping` event is on its way, retrying like any other event. Its attempts show up in the deliveries of the webhook"),
+        (status = 404, description = "The webhook does not exist"),
+        (status = 500, description = "Failed to ping webhook")
+    )
+)]
+#[post("/webhooks/<id>/ping")]
+pub async fn ping_webhook(
+    db_pool: &State<sqlx::PgPool>,
+    dispatcher: &State<Dispatcher>,
+    id: i32,
+) -> Result<Option<Accepted<()>>, std::io::Error> {
+    let Some(webhook) = webhook::get_webhook(db_pool, id)
+        .await
+        .map_err(|_e| std::io::Error::other("Failed to ping webhook"))?
+    else {
+        return Ok(None);
+    };
+    let event = Event::new(EventKind::Ping, None, rocket::serde::json::serde_json::Value::Null);
+    // The retries take minutes, far longer than anyone should wait for an answer.
+    let dispatcher = dispatcher.inner().clone();
+    tokio::spawn(async move {
+        if let Err(e) = dispatcher.deliver(&webhook, &event).await {
+            log::warn!("Failed to record the ping of webhook {}: {}", webhook.id(), e);
+        }
+    });
+
+    Ok(Some(Accepted(())))
+}
@@ -1,11 +1,45 @@
-// #[derive(thiserror::Error, Debug)]
-// pub enum Error {
-//     #[error("Value not of type '{0}'")]
-//     XValueNotOfType(&'static str),
+//! Failures the caller can do something about are answered with a fitting status and a json
+//! body saying what went wrong, and so are failures to store an upload. Everything else stays a
+//! bare 500.
 
-//     #[error(transparent)]
-//     Surreal(#[from] surrealdb::Error),
+use rocket::http::Status;
+use rocket::response::{self, status, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
 
-//     #[error(transparent)]
-//     IO(#[from] std::io::Error),
-// }
+/// The body of a 409 or 422 response, or of a 500 for an upload that wasn't stored.
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request clashes with what is stored, e.g. a taken name or a row that is still
+    /// referenced.
+    Conflict(String),
+    /// The request can't be carried out as asked, e.g. a move that would create a cycle.
+    Unprocessable(String),
+    /// An upload could not be stored, e.g. because the disk is full. Nothing of it was kept, so
+    /// it can be sent again as a whole.
+    Storage(String),
+    Internal(std::io::Error),
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let (status, error) = match self {
+            ApiError::Conflict(error) => (Status::Conflict, error),
+            ApiError::Unprocessable(error) => (Status::UnprocessableEntity, error),
+            ApiError::Storage(error) => (Status::InternalServerError, error),
+            ApiError::Internal(e) => return e.respond_to(request),
+        };
+        status::Custom(status, Json(ErrorBody { error })).respond_to(request)
+    }
+}
@@ -0,0 +1,192 @@
+//! Serves the built frontend next to the API, so a deployment is the binary and `Config.toml`.
+//! The files come from `web.frontend_dir` when it is set, else from the copy compiled in with the
+//! `embed_frontend` feature. Paths a browser navigates to that have no file get `index.html`, so
+//! the page can handle its own routes.
+
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use rocket::http::{Accept, ContentType, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::{get, routes, Build, Request, Rocket, State};
+
+use crate::utils::config::WebConfig;
+
+#[cfg(feature = "embed_frontend")]
+mod embedded {
+    // `FILES`, the path, contents and ETag of every file in `frontend/dist`, see `build.rs`.
+    include!(concat!(env!("OUT_DIR"), "/frontend.rs"));
+}
+
+pub enum Frontend {
+    Directory(PathBuf),
+    #[cfg(feature = "embed_frontend")]
+    Embedded,
+}
+
+impl Frontend {
+    /// The configured directory, else the embedded copy if the binary has one.
+    pub fn from_config(web: &WebConfig) -> Option<Self> {
+        if let Some(dir) = web.frontend_dir() {
+            return Some(Frontend::Directory(dir.to_path_buf()));
+        }
+        #[cfg(feature = "embed_frontend")]
+        return Some(Frontend::Embedded);
+        #[cfg(not(feature = "embed_frontend"))]
+        None
+    }
+
+    async fn file(&self, path: &Path) -> Option<Asset> {
+        match self {
+            Frontend::Directory(dir) => {
+                let path = dir.join(path);
+                let metadata = tokio::fs::metadata(&path).await.ok()?;
+                if !metadata.is_file() {
+                    return None;
+                }
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_secs());
+                let contents = tokio::fs::read(&path).await.ok()?;
+                Some(Asset {
+                    content_type: content_type_of(&path),
+                    etag: format!("\"{:x}-{:x}\"", metadata.len(), modified),
+                    contents: Cow::Owned(contents),
+                })
+            }
+            #[cfg(feature = "embed_frontend")]
+            Frontend::Embedded => {
+                let name = path.to_str()?.replace('\\', "/");
+                let (_, contents, etag) = embedded::FILES.iter().find(|(n, _, _)| *n == name)?;
+                Some(Asset {
+                    content_type: content_type_of(path),
+                    etag: etag.to_string(),
+                    contents: Cow::Borrowed(contents),
+                })
+            }
+        }
+    }
+}
+
+fn content_type_of(path: &Path) -> ContentType {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .and_then(ContentType::from_extension)
+        .unwrap_or(ContentType::Binary)
+}
+
+pub struct Asset {
+    content_type: ContentType,
+    etag: String,
+    contents: Cow<'static, [u8]>,
+}
+
+impl<'r> Responder<'r, 'static> for Asset {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        // The bundle names aren't hashed, so everything is revalidated with the ETag. HTML right
+        // away since it decides which files are loaded, the rest after an hour.
+        let cache_control = if self.content_type == ContentType::HTML {
+            "no-cache"
+        } else {
+            "public, max-age=3600"
+        };
+        let unchanged = request
+            .headers()
+            .get("If-None-Match")
+            .flat_map(|tags| tags.split(','))
+            .any(|tag| tag.trim() == self.etag || tag.trim() == "*");
+
+        let mut response = Response::build();
+        response
+            .header(self.content_type)
+            .raw_header("ETag", self.etag)
+            .raw_header("Cache-Control", cache_control);
+        if unchanged {
+            response.status(Status::NotModified);
+        } else {
+            response.sized_body(self.contents.len(), Cursor::new(self.contents));
+        }
+        response.ok()
+    }
+}
+
+/// Ranked after every API route, so it only answers what they don't.
+#[get("/<path..>", rank = 20)]
+pub async fn frontend_file(frontend: &State<Frontend>, path: PathBuf, accept: Option<&Accept>) -> Option<Asset> {
+    let path = if path.as_os_str().is_empty() {
+        PathBuf::from("index.html")
+    } else {
+        path
+    };
+    if let Some(asset) = frontend.file(&path).await {
+        return Some(asset);
+    }
+
+    // Missing assets stay a 404, as do API calls, which don't ask for HTML first.
+    let navigation = path.extension().is_none() && accept.is_some_and(|a| a.preferred().media_type().is_html());
+    if navigation {
+        frontend.file(Path::new("index.html")).await
+    } else {
+        None
+    }
+}
+
+pub fn mount_frontend(rocket: Rocket<Build>, frontend: Frontend) -> Rocket<Build> {
+    rocket.mount("/", routes![frontend_file]).manage(frontend)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::MemoryRepository;
+    use crate::web::mount_repository_routes;
+    use rocket::http::Header;
+    use rocket::local::asynchronous::Client;
+    use std::sync::Arc;
+
+    #[rocket::async_test]
+    async fn test_frontend_dir() {
+        let dir = std::env::temp_dir().join(format!("{}-frontend", std::process::id()));
+        std::fs::create_dir_all(dir.join("public")).unwrap();
+        std::fs::write(dir.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.join("style.css"), "main {}").unwrap();
+        std::fs::write(dir.join("public/add.svg"), "<svg></svg>").unwrap();
+
+        let rocket = mount_repository_routes(rocket::build(), Arc::new(MemoryRepository::new()));
+        let rocket = mount_frontend(rocket, Frontend::Directory(dir.clone()));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let index = client.get("/").dispatch().await;
+        assert_eq!(index.content_type(), Some(ContentType::HTML));
+        assert_eq!(index.headers().get_one("Cache-Control"), Some("no-cache"));
+        let etag = index.headers().get_one("ETag").unwrap().to_string();
+        assert_eq!(index.into_string().await.unwrap(), "<html></html>");
+
+        let unchanged = client.get("/").header(Header::new("If-None-Match", etag)).dispatch().await;
+        assert_eq!(unchanged.status(), Status::NotModified);
+
+        let style = client.get("/style.css").dispatch().await;
+        assert_eq!(style.content_type(), Some(ContentType::CSS));
+        assert_eq!(style.headers().get_one("Cache-Control"), Some("public, max-age=3600"));
+        let icon = client.get("/public/add.svg").dispatch().await;
+        assert_eq!(icon.content_type(), Some(ContentType::SVG));
+
+        // A browser opening a page of the frontend gets the index, anything else a 404.
+        let page = client.get("/expenses/3").header(Accept::HTML).dispatch().await;
+        assert_eq!(page.into_string().await.unwrap(), "<html></html>");
+        let missing = client.get("/bundle.js").header(Accept::HTML).dispatch().await;
+        assert_eq!(missing.status(), Status::NotFound);
+        let api = client.get("/users/missing").dispatch().await;
+        assert_eq!(api.status(), Status::NotFound);
+        let hidden = client.get("/../Cargo.toml").dispatch().await;
+        assert_eq!(hidden.status(), Status::NotFound);
+
+        let users = client.get("/users/all").dispatch().await;
+        assert_eq!(users.content_type(), Some(ContentType::JSON));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
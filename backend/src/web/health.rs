@@ -0,0 +1,205 @@
+//! Probes for running the server under a supervisor or orchestrator, and metrics for Prometheus.
+//! They are operational rather than part of the API, so they are left out of the OpenAPI
+//! document and served on every storage.
+
+use std::fmt::Write;
+
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Json;
+use rocket::{get, routes, Build, Rocket, State};
+
+use super::metrics::RequestMetrics;
+use crate::database::initialize::{self, SCHEMA_VERSION};
+use crate::database::repository::{DynRepository, Repository};
+use crate::utils::balance;
+
+/// What the probes look at besides the repository.
+pub struct Probes {
+    /// The Postgres pool with its configured size, `None` on other storages.
+    db_pool: Option<(sqlx::PgPool, u32)>,
+    metrics: RequestMetrics,
+}
+
+/// Whether the server can take requests, as answered by `/readyz`.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Readiness {
+    ready: bool,
+    /// Why the storage could not be reached.
+    error: Option<String>,
+    /// The version of the Postgres tables, `None` on other storages.
+    schema_version: Option<i32>,
+    expected_schema_version: i32,
+}
+
+/// The process is up and serving requests.
+#[get("/healthz")]
+pub fn healthz() -> &'static str {
+    "ok"
+}
+
+/// The storage is reachable and, on Postgres, the tables are at the version this build expects.
+/// Answers 503 otherwise.
+#[get("/readyz")]
+pub async fn readyz(probes: &State<Probes>, repository: &State<DynRepository>) -> (Status, Json<Readiness>) {
+    let mut readiness = Readiness {
+        ready: false,
+        error: None,
+        schema_version: None,
+        expected_schema_version: SCHEMA_VERSION,
+    };
+    let reached = match &probes.db_pool {
+        Some((db_pool, _)) => initialize::get_schema_version(db_pool)
+            .await
+            .map(|version| readiness.schema_version = version),
+        None => repository.get_users().await.map(|_| ()),
+    };
+    match reached {
+        Ok(()) => {
+            readiness.ready = probes.db_pool.is_none() || readiness.schema_version == Some(SCHEMA_VERSION);
+        }
+        Err(e) => readiness.error = Some(e.to_string()),
+    }
+
+    let status = if readiness.ready { Status::Ok } else { Status::ServiceUnavailable };
+    (status, Json(readiness))
+}
+
+/// Request counts and latency per route, the usage of the Postgres pool and a few figures about
+/// the stored data, in the Prometheus text format.
+#[get("/metrics")]
+pub async fn metrics(probes: &State<Probes>, repository: &State<DynRepository>) -> (ContentType, String) {
+    let mut out = String::new();
+    probes.metrics.render(&mut out);
+
+    if let Some((db_pool, max_connections)) = &probes.db_pool {
+        let idle = db_pool.num_idle() as u32;
+        let open = db_pool.size();
+        out.push_str("# HELP expenses_db_pool_connections Open connections of the Postgres pool, by whether they are in use.\n");
+        out.push_str("# TYPE expenses_db_pool_connections gauge\n");
+        writeln!(out, "expenses_db_pool_connections{{state=\"idle\"}} {}", idle).unwrap();
+        writeln!(out, "expenses_db_pool_connections{{state=\"in_use\"}} {}", open.saturating_sub(idle)).unwrap();
+        out.push_str("# HELP expenses_db_pool_max_connections The most connections the pool opens.\n");
+        out.push_str("# TYPE expenses_db_pool_max_connections gauge\n");
+        writeln!(out, "expenses_db_pool_max_connections {}", max_connections).unwrap();
+    }
+
+    // Left out when the storage can't be read, the request metrics are still worth scraping.
+    match domain_gauges(repository.inner().as_ref()).await {
+        Ok(gauges) => {
+            for (name, help, value) in gauges {
+                writeln!(out, "# HELP {} {}", name, help).unwrap();
+                writeln!(out, "# TYPE {} gauge", name).unwrap();
+                writeln!(out, "{} {}", name, value).unwrap();
+            }
+        }
+        Err(e) => log::warn!("Failed to read the stored data for the metrics: {}", e),
+    }
+
+    let content_type = ContentType::new("text", "plain")
+        .with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    (content_type, out)
+}
+
+async fn domain_gauges(repository: &dyn Repository) -> Result<Vec<(&'static str, &'static str, f64)>, sqlx::Error> {
+    let expenses = balance::expenses_since_last_reset(repository).await?;
+    let users = repository.get_users().await?;
+    let categories = repository.get_categories().await?;
+    let last_reset = match repository.get_last_reset().await {
+        Ok(date) => date.and_utc().timestamp() as f64,
+        Err(sqlx::Error::RowNotFound) => 0.0,
+        Err(e) => return Err(e),
+    };
+
+    Ok(vec![
+        (
+            "expenses_since_last_reset",
+            "Expenses purchased since the last reset.",
+            expenses.len() as f64,
+        ),
+        (
+            "expenses_amount_since_last_reset",
+            "The amount of the expenses purchased since the last reset.",
+            expenses.iter().map(|e| e.amount()).sum(),
+        ),
+        (
+            "expenses_last_reset_timestamp_seconds",
+            "When the expenses were last reset, 0 if they never were.",
+            last_reset,
+        ),
+        ("expenses_users", "Stored users.", users.len() as f64),
+        ("expenses_categories", "Stored categories.", categories.len() as f64),
+    ])
+}
+
+/// Mounts the probes at the root and starts counting requests. Needs the repository routes.
+pub fn mount_probes(rocket: Rocket<Build>, db_pool: Option<(sqlx::PgPool, u32)>) -> Rocket<Build> {
+    let metrics = RequestMetrics::default();
+    rocket
+        .mount("/", routes![healthz, readyz, metrics])
+        .attach(metrics.clone())
+        .manage(Probes { db_pool, metrics })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::{MemoryRepository, PgRepository};
+    use crate::database::testing::test_pool;
+    use crate::web::mount_repository_routes;
+    use rocket::local::asynchronous::Client;
+    use rocket::serde::json::serde_json;
+    use std::sync::Arc;
+
+    #[rocket::async_test]
+    async fn test_probes_without_database() {
+        let rocket = mount_repository_routes(rocket::build(), Arc::new(MemoryRepository::new()));
+        let client = Client::tracked(mount_probes(rocket, None)).await.unwrap();
+
+        let response = client.get("/healthz").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().await.unwrap(), "ok");
+
+        let response = client.get("/readyz").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let readiness: Readiness = response.into_json().await.unwrap();
+        assert!(readiness.ready && readiness.schema_version.is_none());
+
+        client.post("/api/v1/users").json(&serde_json::json!({ "username": "alice" })).dispatch().await;
+        client.get("/api/v1/users/7").dispatch().await;
+        client.get("/nowhere").dispatch().await;
+
+        let response = client.get("/metrics").dispatch().await;
+        assert_eq!(response.content_type().unwrap().to_string(), "text/plain; version=0.0.4; charset=utf-8");
+        let metrics = response.into_string().await.unwrap();
+        for line in [
+            "expenses_http_requests_total{method=\"POST\",route=\"/api/v1/users\",status=\"201\"} 1",
+            "expenses_http_requests_total{method=\"GET\",route=\"/api/v1/users/<id>\",status=\"404\"} 1",
+            "expenses_http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1",
+            "expenses_http_request_duration_seconds_count{method=\"GET\",route=\"/readyz\"} 1",
+            "expenses_since_last_reset 0",
+            "expenses_last_reset_timestamp_seconds 0",
+            "expenses_users 1",
+        ] {
+            assert!(metrics.lines().any(|l| l == line), "`{}` is missing from:\n{}", line, metrics);
+        }
+        assert!(!metrics.contains("expenses_db_pool"));
+    }
+
+    #[rocket::async_test]
+    async fn test_probes_with_postgres() {
+        let db_pool = test_pool().await;
+        let repository = Arc::new(PgRepository::new(db_pool.clone()));
+        let rocket = mount_repository_routes(rocket::build(), repository);
+        let client = Client::tracked(mount_probes(rocket, Some((db_pool, 5)))).await.unwrap();
+
+        let response = client.get("/readyz").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let readiness: Readiness = response.into_json().await.unwrap();
+        assert_eq!(readiness.schema_version, Some(SCHEMA_VERSION));
+
+        let metrics = client.get("/metrics").dispatch().await.into_string().await.unwrap();
+        assert!(metrics.lines().any(|l| l == "expenses_db_pool_max_connections 5"));
+        assert!(metrics.lines().any(|l| l.starts_with("expenses_db_pool_connections{state=\"idle\"} ")));
+        assert!(metrics.lines().any(|l| l.starts_with("expenses_users ")));
+    }
+}
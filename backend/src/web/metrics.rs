@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+
+/// The upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Requests that matched no route share one label, so unknown paths can't add series.
+const UNMATCHED: &str = "unmatched";
+
+/// When the request came in, kept in the request's local cache.
+struct Started(Instant);
+
+#[derive(Default)]
+struct RouteStats {
+    /// Requests by response status.
+    statuses: BTreeMap<u16, u64>,
+    /// Requests that took at most the bound of the bucket with the same index.
+    buckets: [u64; BUCKETS.len()],
+    seconds: f64,
+    count: u64,
+}
+
+/// Counts the requests and measures their latency per route, for `/metrics`. The route is the
+/// one the request matched, e.g. `/api/v1/users/<id>`, rather than the path.
+#[derive(Clone, Default)]
+pub struct RequestMetrics {
+    routes: Arc<Mutex<BTreeMap<(String, String), RouteStats>>>,
+}
+
+impl RequestMetrics {
+    fn record(&self, method: String, route: String, status: u16, seconds: f64) {
+        let mut routes = self.routes.lock().unwrap();
+        let stats = routes.entry((method, route)).or_default();
+        *stats.statuses.entry(status).or_default() += 1;
+        for (bucket, bound) in stats.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        stats.seconds += seconds;
+        stats.count += 1;
+    }
+
+    /// Appends the request counts and the latency histograms in the Prometheus text format.
+    pub fn render(&self, out: &mut String) {
+        let routes = self.routes.lock().unwrap();
+
+        out.push_str("# HELP expenses_http_requests_total Requests handled, by route and response status.\n");
+        out.push_str("# TYPE expenses_http_requests_total counter\n");
+        for ((method, route), stats) in routes.iter() {
+            for (status, count) in &stats.statuses {
+                let labels = format!("method=\"{}\",route=\"{}\",status=\"{}\"", method, escape(route), status);
+                writeln!(out, "expenses_http_requests_total{{{}}} {}", labels, count).unwrap();
+            }
+        }
+
+        out.push_str("# HELP expenses_http_request_duration_seconds Time from receiving a request to sending the response headers.\n");
+        out.push_str("# TYPE expenses_http_request_duration_seconds histogram\n");
+        for ((method, route), stats) in routes.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+            for (count, bound) in stats.buckets.iter().zip(BUCKETS) {
+                writeln!(out, "expenses_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, count).unwrap();
+            }
+            writeln!(out, "expenses_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, stats.count).unwrap();
+            writeln!(out, "expenses_http_request_duration_seconds_sum{{{}}} {}", labels, stats.seconds).unwrap();
+            writeln!(out, "expenses_http_request_duration_seconds_count{{{}}} {}", labels, stats.count).unwrap();
+        }
+    }
+}
+
+/// Escapes a label value as the text format requires.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Count requests and measure their latency",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| Started(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let started = request.local_cache(|| Started(Instant::now()));
+        let route = match request.route() {
+            Some(route) => route.uri.as_str().to_string(),
+            None => UNMATCHED.to_string(),
+        };
+        let seconds = started.0.elapsed().as_secs_f64();
+        self.record(request.method().as_str().to_string(), route, response.status().code, seconds);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = RequestMetrics::default();
+        metrics.record("GET".to_string(), "/api/v1/users/<id>".to_string(), 200, 0.02);
+        metrics.record("GET".to_string(), "/api/v1/users/<id>".to_string(), 404, 3.0);
+        metrics.record("GET".to_string(), "/a\"b".to_string(), 200, 0.001);
+
+        let mut out = String::new();
+        metrics.render(&mut out);
+        let users = "method=\"GET\",route=\"/api/v1/users/<id>\"";
+        for line in [
+            format!("expenses_http_requests_total{{{},status=\"200\"}} 1", users),
+            format!("expenses_http_requests_total{{{},status=\"404\"}} 1", users),
+            format!("expenses_http_request_duration_seconds_bucket{{{},le=\"0.01\"}} 0", users),
+            format!("expenses_http_request_duration_seconds_bucket{{{},le=\"0.025\"}} 1", users),
+            format!("expenses_http_request_duration_seconds_bucket{{{},le=\"5\"}} 2", users),
+            format!("expenses_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2", users),
+            format!("expenses_http_request_duration_seconds_count{{{}}} 2", users),
+            "expenses_http_requests_total{method=\"GET\",route=\"/a\\\"b\",status=\"200\"} 1".to_string(),
+        ] {
+            assert!(out.lines().any(|l| l == line), "`{}` is missing from:\n{}", line, out);
+        }
+    }
+}
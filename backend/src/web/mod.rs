@@ -1,28 +1,545 @@
+mod audited;
 mod cors;
+mod deprecation;
 mod endpoints;
 mod error;
+mod frontend;
+mod health;
+mod metrics;
+mod openapi;
 
 use endpoints::*;
 
+use crate::database::repository::DynRepository;
 use crate::utils::config;
+use crate::utils::events::{EventBus, PublishingRepository};
+use crate::utils::webhooks::Dispatcher;
 use cors::*;
-use rocket::routes;
+use deprecation::Deprecation;
+use rocket::data::{Limits, ToByteUnit};
+use rocket::fairing::AdHoc;
+use rocket::{routes, Build, Rocket};
 
-pub async fn run(config: config::Config, db_pool: sqlx::PgPool) {
-    rocket::build()
+/// Mounts the routes that only need the repository, so they can be served from any storage,
+/// including a `MemoryRepository` in tests. Changes made through them are published on the
+/// `EventBus` this manages.
+pub fn mount_repository_routes(rocket: Rocket<Build>, repository: DynRepository) -> Rocket<Build> {
+    let events = EventBus::new();
+    let repository = PublishingRepository::wrap(repository, events.clone());
+    mount_deprecated_routes(rocket)
+        .mount(
+            "/api/v1",
+            routes![
+                v1::list_users,
+                v1::create_user,
+                v1::get_user,
+                v1::update_user,
+                v1::delete_user,
+                v1::merge_user,
+                v1::list_categories,
+                v1::category_tree,
+                v1::create_category,
+                v1::get_category,
+                v1::update_category,
+                v1::delete_category,
+                v1::merge_category,
+                v1::list_expenses,
+                v1::create_expense,
+                v1::get_expense,
+                v1::update_expense,
+                v1::delete_expense,
+                v1::last_reset,
+                v1::reset,
+                v1::events,
+                v1::audit_log
+            ],
+        )
+        .mount("/tags", routes![tags_totals])
+        .manage(repository)
+        .manage(events)
+}
+
+/// The routes from before `/api/v1`, see `Deprecation`.
+#[allow(deprecated)]
+fn mount_deprecated_routes(rocket: Rocket<Build>) -> Rocket<Build> {
+    rocket
         .mount(
             "/categories",
-            routes![categories_create, categories_all, categories_delete],
+            routes![
+                categories_create,
+                categories_all,
+                categories_tree,
+                categories_parent,
+                categories_delete,
+                categories_merge
+            ],
         )
         .mount(
             "/expenses",
             routes![expenses_create, expenses_all, expenses_filter, expenses_last_reset]
         )
-        .mount("/users", routes![users_create, users_all, users_delete])
-        .attach(CORS)
+        .mount("/users", routes![users_create, users_all, users_delete, users_merge])
+        .attach(Deprecation)
+}
+
+/// Drafts, tags, attachments, imports and webhooks, which are only stored in Postgres. The webhooks
+/// are sent the events of the `EventBus` from `mount_repository_routes` once the server is up.
+fn mount_postgres_routes(
+    rocket: Rocket<Build>,
+    db_pool: sqlx::PgPool,
+    webhooks: config::WebhookConfig,
+) -> Rocket<Build> {
+    let dispatcher = Dispatcher::new(db_pool.clone(), webhooks);
+    let events = rocket.state::<EventBus>().expect("the repository routes are mounted first").clone();
+    let background = dispatcher.clone();
+    #[allow(deprecated)]
+    let deprecated = routes![expenses_tags];
+    rocket
+        .mount("/expenses", deprecated)
+        .mount(
+            "/api/v1",
+            routes![
+                v1::set_expense_tags,
+                v1::list_webhooks,
+                v1::create_webhook,
+                v1::delete_webhook,
+                v1::webhook_deliveries,
+                v1::ping_webhook
+            ],
+        )
+        .mount(
+            "/attachments",
+            routes![
+                attachments_upload,
+                attachments_of_expense,
+                attachments_download,
+                attachments_delete
+            ],
+        )
+        .mount("/tags", routes![tags_all, tags_rename, tags_merge])
+        .mount(
+            "/imports",
+            routes![
+                imports_batches,
+                imports_drafts,
+                imports_drafts_update,
+                imports_drafts_approve,
+                imports_drafts_reject,
+                imports_post
+            ],
+        )
+        .attach(AdHoc::on_liftoff("Webhooks", move |_| {
+            Box::pin(async move {
+                background.spawn(&events);
+            })
+        }))
         .manage(db_pool)
-        .manage(config)
+        .manage(dispatcher)
+}
+
+/// Mounts everything the storage supports, the Postgres-only routes are left out on SQLite, and
+/// the frontend if there is one to serve.
+pub fn build(config: config::Config, repository: DynRepository, db_pool: Option<sqlx::PgPool>) -> Rocket<Build> {
+    let rocket_config = rocket::Config {
+        address: config.web().address(),
+        port: config.web().port(),
+        limits: Limits::default()
+            .limit("file", 20.mebibytes())
+            .limit("data-form", 50.mebibytes()),
+        ..rocket::Config::default()
+    };
+
+    let mut rocket = mount_repository_routes(rocket::custom(rocket_config), repository);
+    let max_connections = config.db_config().max_connections();
+    rocket = health::mount_probes(rocket, db_pool.clone().map(|db_pool| (db_pool, max_connections)));
+    rocket = openapi::mount_docs(rocket, openapi::document(db_pool.is_some()));
+    if let Some(db_pool) = db_pool {
+        rocket = mount_postgres_routes(rocket, db_pool, config.webhooks().clone());
+    }
+
+    rocket = match frontend::Frontend::from_config(config.web()) {
+        Some(frontend) => frontend::mount_frontend(rocket, frontend),
+        // The frontend is hosted elsewhere and calls the API from another origin.
+        None => rocket.attach(Cors),
+    };
+
+    rocket.manage(config)
+}
+
+pub async fn run(config: config::Config, repository: DynRepository, db_pool: Option<sqlx::PgPool>) {
+    build(config, repository, db_pool)
         .launch()
         .await
         .expect("Bye bye server...");
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::database::repository::MemoryRepository;
+    use std::sync::Arc;
+    use datatypes::{AuditEntry, AuditSource, Category, EventKind, Expense, User, UserOwes};
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+    use rocket::serde::json::serde_json;
+
+    #[rocket::async_test]
+    async fn test_routes_without_database() {
+        let rocket = mount_repository_routes(rocket::build(), Arc::new(MemoryRepository::new()));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let alice: User = client.post("/users/create").json(&"alice").dispatch().await.into_json().await.unwrap();
+        let bob: User = client.post("/users/create").json(&"bob").dispatch().await.into_json().await.unwrap();
+        let food: Category = client
+            .post("/categories/create")
+            .json(&("food", "food expenses"))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+
+        let expense = Expense::new(
+            alice.id(),
+            food.id(),
+            20.0,
+            "dinner".to_string(),
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            vec![UserOwes::new(alice.id(), -1, 10.0), UserOwes::new(bob.id(), -1, 10.0)],
+        );
+        let response = client.post("/expenses/create").json(&expense).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // Bob's share keeps him from being deleted, but he can be merged into alice.
+        let response = client.delete("/users/delete").json(&bob.id()).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let error: serde_json::Value = response.into_json().await.unwrap();
+        assert!(error["error"].as_str().unwrap().starts_with("User is still referenced by"));
+        let merged: bool = client
+            .post("/users/merge")
+            .json(&(bob.id(), alice.id()))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert!(merged);
+
+        let users: Vec<User> = client.get("/users/all").dispatch().await.into_json().await.unwrap();
+        assert_eq!(users, vec![alice.clone()]);
+        let expenses: Vec<Expense> = client.get("/expenses/all").dispatch().await.into_json().await.unwrap();
+        assert_eq!(expenses[0].user_owes().len(), 1);
+        assert_eq!(expenses[0].user_owes()[0].amount(), 20.0);
+
+        let response = client.get("/expenses/last-reset").dispatch().await;
+        assert_eq!(response.status(), Status::InternalServerError);
+        let totals = client.get("/tags/totals").dispatch().await.into_string().await.unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&totals).unwrap(), serde_json::json!({}));
+    }
+
+    #[rocket::async_test]
+    async fn test_v1_routes() {
+        let rocket = mount_repository_routes(rocket::build(), Arc::new(MemoryRepository::new()));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client.post("/api/v1/users").json(&serde_json::json!({ "username": "alice" })).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+        let alice: User = response.into_json().await.unwrap();
+        let bob: User = client
+            .post("/api/v1/users")
+            .json(&serde_json::json!({ "username": "bob" }))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let food: Category = client
+            .post("/api/v1/categories")
+            .json(&serde_json::json!({ "name": "food" }))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let snacks: Category = client
+            .post("/api/v1/categories")
+            .json(&serde_json::json!({ "name": "snacks", "parent_id": food.id() }))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(snacks.parent_id(), Some(food.id()));
+
+        for (user, category, amount, day) in [(&alice, &food, 20.0, 1), (&bob, &snacks, 5.0, 2), (&alice, &snacks, 8.0, 3)] {
+            let expense = Expense::new(
+                user.id(),
+                category.id(),
+                amount,
+                "groceries".to_string(),
+                chrono::NaiveDate::from_ymd_opt(2023, 1, day).unwrap(),
+                vec![UserOwes::new(user.id(), -1, amount)],
+            );
+            let response = client.post("/api/v1/expenses").json(&expense).dispatch().await;
+            assert_eq!(response.status(), Status::Created);
+        }
+
+        let all: Vec<Expense> = client.get("/api/v1/expenses").dispatch().await.into_json().await.unwrap();
+        assert_eq!(all.iter().map(|e| e.amount()).collect::<Vec<f64>>(), vec![8.0, 5.0, 20.0]);
+        let uri = format!("/api/v1/expenses?user_id={}&category_id={}&order_by=amount&order_asc=true", alice.id(), food.id());
+        let filtered: Vec<Expense> = client.get(uri).dispatch().await.into_json().await.unwrap();
+        assert_eq!(filtered.iter().map(|e| e.amount()).collect::<Vec<f64>>(), vec![8.0, 20.0]);
+        let dated: Vec<Expense> = client
+            .get("/api/v1/expenses?min_date=2023-01-02&max_date=2023-01-02")
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(dated.len(), 1);
+        let response = client.get("/api/v1/expenses?min_date=yesterday").dispatch().await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        // Only the fields in the patch change.
+        let uri = format!("/api/v1/expenses/{}", all[0].id());
+        let patched: Expense = client
+            .patch(uri.as_str())
+            .json(&serde_json::json!({ "description": "chips", "tags": [" snack"] }))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(patched.description(), "chips");
+        assert_eq!(patched.tags(), &vec!["snack".to_string()]);
+        assert_eq!(patched.amount(), 8.0);
+        assert_eq!(patched.created_at(), all[0].created_at());
+        let fetched: Expense = client.get(uri.as_str()).dispatch().await.into_json().await.unwrap();
+        assert_eq!(fetched, patched);
+
+        // Without new shares, a new amount scales the old ones and a new payer takes over theirs.
+        let shared = Expense::new(
+            alice.id(),
+            food.id(),
+            20.0,
+            "dinner".to_string(),
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 4).unwrap(),
+            vec![UserOwes::new(alice.id(), -1, 12.0), UserOwes::new(bob.id(), -1, 8.0)],
+        );
+        let shared: Expense = client.post("/api/v1/expenses").json(&shared).dispatch().await.into_json().await.unwrap();
+        let shared_uri = format!("/api/v1/expenses/{}", shared.id());
+        let shares = |expense: &Expense| expense.user_owes().iter().map(|o| (o.user_id(), o.amount())).collect::<Vec<_>>();
+        let patch = |body: serde_json::Value| {
+            let (client, uri) = (&client, shared_uri.as_str());
+            async move { client.patch(uri).json(&body).dispatch().await.into_json::<Expense>().await.unwrap() }
+        };
+        let scaled = patch(serde_json::json!({ "amount": 30.0 })).await;
+        assert_eq!(shares(&scaled), vec![(alice.id(), 18.0), (bob.id(), 12.0)]);
+        let moved = patch(serde_json::json!({ "user_id": bob.id(), "amount": 15.0 })).await;
+        assert_eq!(moved.user_id(), bob.id());
+        assert_eq!(shares(&moved), vec![(bob.id(), 15.0)]);
+        // Shares in the patch are taken as they are.
+        let owes = vec![UserOwes::new(alice.id(), -1, 5.0), UserOwes::new(bob.id(), -1, 5.0)];
+        let replaced = patch(serde_json::json!({ "amount": 10.0, "user_owes": owes })).await;
+        assert_eq!(shares(&replaced), vec![(alice.id(), 5.0), (bob.id(), 5.0)]);
+        assert_eq!(client.delete(shared_uri.as_str()).dispatch().await.status(), Status::NoContent);
+
+        assert_eq!(client.delete(uri.as_str()).dispatch().await.status(), Status::NoContent);
+        assert_eq!(client.get(uri.as_str()).dispatch().await.status(), Status::NotFound);
+        assert_eq!(client.delete(uri.as_str()).dispatch().await.status(), Status::NotFound);
+
+        let uri = format!("/api/v1/users/{}", bob.id());
+        let renamed: User = client
+            .patch(uri.as_str())
+            .json(&serde_json::json!({ "username": "robert" }))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(renamed.username(), "robert");
+        let response = client.patch(uri.as_str()).json(&serde_json::json!({ "username": "alice" })).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let response = client.post("/api/v1/users").json(&serde_json::json!({ "username": "alice" })).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let response = client.delete(uri.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let error: serde_json::Value = response.into_json().await.unwrap();
+        assert!(error["error"].as_str().unwrap().contains("merge it into another user"));
+        let uri = format!("/api/v1/users/{}/merge/{}", bob.id(), alice.id());
+        assert_eq!(client.post(uri).dispatch().await.status(), Status::NoContent);
+        assert_eq!(client.get(format!("/api/v1/users/{}", bob.id())).dispatch().await.status(), Status::NotFound);
+
+        // `null` moves the category to the top level, a cycle is refused.
+        let uri = format!("/api/v1/categories/{}", food.id());
+        let response = client
+            .patch(uri.as_str())
+            .json(&serde_json::json!({ "parent_id": snacks.id() }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let response = client.patch(uri.as_str()).json(&serde_json::json!({ "parent_id": 999 })).dispatch().await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        // Nothing of a refused patch is kept.
+        let response = client
+            .patch(uri.as_str())
+            .json(&serde_json::json!({ "name": "meals", "parent_id": snacks.id() }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let unchanged: Category = client.get(uri.as_str()).dispatch().await.into_json().await.unwrap();
+        assert_eq!(unchanged.name(), food.name());
+        let response = client.patch(uri.as_str()).json(&serde_json::json!({ "name": "snacks" })).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let response = client.delete(uri.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let uri = format!("/api/v1/categories/{}", snacks.id());
+        let moved: Category = client
+            .patch(uri.as_str())
+            .json(&serde_json::json!({ "name": "treats", "parent_id": null }))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!((moved.name().as_str(), moved.parent_id()), ("treats", None));
+        assert_eq!(moved.description(), snacks.description());
+
+        let response = client.get("/api/v1/expenses/last-reset").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        // The old routes still answer, flagged as deprecated.
+        let response = client.get("/users/all").dispatch().await;
+        assert_eq!(response.headers().get_one("Deprecation"), Some("@1792368000"));
+        let users: Vec<User> = response.into_json().await.unwrap();
+        assert_eq!(users.len(), 1);
+        let response = client.get("/api/v1/users").dispatch().await;
+        assert_eq!(response.headers().get_one("Deprecation"), None);
+    }
+
+    #[rocket::async_test]
+    async fn test_events() {
+        use rocket::tokio::io::AsyncReadExt;
+
+        let rocket = mount_repository_routes(rocket::build(), Arc::new(MemoryRepository::new()));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client.get("/api/v1/events?kind=expenses").dispatch().await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        // Subscribed once dispatched, so only the category is left out of the stream.
+        let mut stream = client.get("/api/v1/events?kind=user").dispatch().await;
+        client.post("/api/v1/categories").json(&serde_json::json!({ "name": "food" })).dispatch().await;
+        client.post("/api/v1/users").json(&serde_json::json!({ "username": "alice" })).dispatch().await;
+
+        let mut received = String::new();
+        let mut buffer = [0; 1024];
+        while !received.ends_with("\n\n") {
+            let read = rocket::tokio::time::timeout(std::time::Duration::from_secs(5), stream.read(&mut buffer))
+                .await
+                .expect("no event was sent")
+                .unwrap();
+            received.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+        }
+        assert!(received.starts_with("event:user.created\n"), "{}", received);
+        let data = received.lines().find_map(|l| l.strip_prefix("data:")).unwrap();
+        let event: serde_json::Value = serde_json::from_str(data).unwrap();
+        assert_eq!(event["type"], "user.created");
+        assert_eq!(event["data"]["username"], "alice");
+    }
+
+    #[rocket::async_test]
+    async fn test_audit() {
+        let rocket = mount_repository_routes(rocket::build(), Arc::new(MemoryRepository::new()));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let groceries: Category = client
+            .post("/api/v1/categories")
+            .header(rocket::http::Header::new(audited::ACTOR_HEADER, "alice"))
+            .json(&serde_json::json!({ "name": "groceries" }))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let uri = format!("/api/v1/categories/{}", groceries.id());
+        let response = client
+            .delete(uri)
+            .header(rocket::http::Header::new(audited::ACTOR_HEADER, "bob"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NoContent);
+        // Without the header the caller is who the connection came from.
+        client.post("/api/v1/users").json(&serde_json::json!({ "username": "carol" })).dispatch().await;
+
+        let entries: Vec<AuditEntry> = client.get("/api/v1/audit").dispatch().await.into_json().await.unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].action(), EventKind::UserCreated);
+        assert_eq!(entries[0].source(), AuditSource::Web);
+        let deleted: Vec<AuditEntry> = client
+            .get(format!("/api/v1/audit?action=category.deleted&entity_id={}", groceries.id()))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].actor(), "bob");
+        assert_eq!(deleted[0].before()["name"], "groceries");
+        assert!(deleted[0].after().is_null());
+        let by_alice: Vec<AuditEntry> = client
+            .get("/api/v1/audit?actor=alice&source=web")
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(by_alice.iter().map(|e| e.action()).collect::<Vec<EventKind>>(), vec![EventKind::CategoryCreated]);
+
+        let response = client.get("/api/v1/audit?action=categories").dispatch().await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let response = client.get("/api/v1/audit?source=somewhere").dispatch().await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    /// Every mounted route must be in the document, and nothing else.
+    #[rocket::async_test]
+    async fn test_openapi_matches_routes() {
+        let db_pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let rocket = mount_repository_routes(rocket::build(), Arc::new(MemoryRepository::new()));
+        let rocket = mount_postgres_routes(rocket, db_pool, config::WebhookConfig::default());
+
+        let mut mounted = rocket
+            .routes()
+            .map(|route| {
+                let path = route.uri.path().to_string().replace('<', "{").replace('>', "}");
+                (route.method.as_str().to_lowercase(), path)
+            })
+            .collect::<Vec<(String, String)>>();
+        mounted.sort();
+
+        let document = serde_json::to_value(openapi::document(true)).unwrap();
+        let mut documented = vec![];
+        for (path, operations) in document["paths"].as_object().unwrap() {
+            for method in operations.as_object().unwrap().keys() {
+                documented.push((method.clone(), path.clone()));
+            }
+        }
+        documented.sort();
+        assert_eq!(mounted, documented);
+
+        let client = Client::tracked(openapi::mount_docs(rocket::build(), openapi::document(false)))
+            .await
+            .unwrap();
+        let served: serde_json::Value = client.get("/openapi.json").dispatch().await.into_json().await.unwrap();
+        assert!(served["paths"]["/api/v1/users"]["post"].is_object());
+        assert_eq!(served["paths"]["/users/create"]["post"]["deprecated"], true);
+        assert!(served["paths"]["/api/v1/users"]["get"]["deprecated"].is_null());
+        let parameters = served["paths"]["/api/v1/expenses"]["get"]["parameters"].as_array().unwrap();
+        assert!(parameters.iter().any(|p| p["name"] == "min_date" && p["in"] == "query"));
+        assert!(served["paths"]["/imports/batches"].is_null());
+        assert!(served["components"]["schemas"]["Expense"].is_object());
+        let docs = client.get("/docs").dispatch().await;
+        assert_eq!(docs.content_type(), Some(rocket::http::ContentType::HTML));
+    }
+}
@@ -0,0 +1,182 @@
+//! The OpenAPI document, generated from the `#[utoipa::path]` attributes next to the routes and
+//! the `ToSchema` derives in `datatypes`. It is served at `/openapi.json` and browsable at
+//! `/docs`. The routes from before `/api/v1` are marked deprecated.
+
+use rocket::response::content::RawHtml;
+use rocket::serde::json::Json;
+use rocket::{get, routes, Build, Rocket, State};
+use utoipa::openapi::OpenApi as Document;
+use utoipa::{OpenApi, ToSchema};
+
+use super::endpoints::*;
+use super::error::ErrorBody;
+use datatypes::{
+    Attachment, AuditEntry, AuditSource, Category, CategoryNode, CategoryPatch, DraftExpense, DraftStatus, Event, EventKind, Expense,
+    ExpensePatch, Filter, ImportBatch, NewCategory, NewUser, NewWebhook, OrderBy, Tag, User, UserOwes, UserPatch,
+    Webhook, WebhookDelivery,
+};
+
+// Several routes take a tuple as their body, which serde sends as a json array. These only
+// exist to describe those arrays.
+
+/// `[name, description]`
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct NameDescription(String, String);
+
+/// `[from, into]`, the ids of the entry that is merged and the one it is merged into.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct FromInto(i32, i32);
+
+/// `[from, to]`, the old and the new tag name.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct FromTo(String, String);
+
+/// `[id, parent_id]`, where a `null` parent moves the category to the top level.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct IdParent(i32, Option<i32>);
+
+/// `[expense_id, tags]`, the tags replace the ones the expense had.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct ExpenseTags(i32, Vec<String>);
+
+/// The routes every storage serves.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Expense tracker",
+        description = "Changes are recorded in the audit log under the name sent in the `X-Actor` header, or the caller's address without one. The header is taken as sent, nothing verifies it, so the address the request came from is recorded next to it."
+    ),
+    paths(
+        v1::list_users,
+        v1::create_user,
+        v1::get_user,
+        v1::update_user,
+        v1::delete_user,
+        v1::merge_user,
+        v1::list_categories,
+        v1::category_tree,
+        v1::create_category,
+        v1::get_category,
+        v1::update_category,
+        v1::delete_category,
+        v1::merge_category,
+        v1::list_expenses,
+        v1::create_expense,
+        v1::get_expense,
+        v1::update_expense,
+        v1::delete_expense,
+        v1::last_reset,
+        v1::reset,
+        v1::events,
+        v1::audit_log,
+        users_create,
+        users_all,
+        users_delete,
+        users_merge,
+        categories_create,
+        categories_all,
+        categories_tree,
+        categories_parent,
+        categories_delete,
+        categories_merge,
+        expenses_create,
+        expenses_all,
+        expenses_filter,
+        expenses_last_reset,
+        tags_totals,
+    ),
+    components(schemas(
+        ErrorBody,
+        User,
+        Category,
+        CategoryNode,
+        Expense,
+        UserOwes,
+        Filter,
+        OrderBy,
+        NewUser,
+        NewCategory,
+        UserPatch,
+        CategoryPatch,
+        ExpensePatch,
+        Event,
+        EventKind,
+        AuditEntry,
+        AuditSource,
+        NameDescription,
+        FromInto,
+        IdParent,
+    ))
+)]
+pub struct RepositoryApi;
+
+/// The routes only mounted with Postgres.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        v1::set_expense_tags,
+        v1::list_webhooks,
+        v1::create_webhook,
+        v1::delete_webhook,
+        v1::webhook_deliveries,
+        v1::ping_webhook,
+        expenses_tags,
+        tags_all,
+        tags_rename,
+        tags_merge,
+        attachments_upload,
+        attachments_of_expense,
+        attachments_download,
+        attachments_delete,
+        imports_batches,
+        imports_drafts,
+        imports_drafts_update,
+        imports_drafts_approve,
+        imports_drafts_reject,
+        imports_post,
+    ),
+    components(schemas(
+        Tag,
+        Attachment,
+        Upload,
+        ImportBatch,
+        DraftExpense,
+        DraftStatus,
+        Webhook,
+        WebhookDelivery,
+        NewWebhook,
+        FromTo,
+        ExpenseTags,
+    ))
+)]
+pub struct PostgresApi;
+
+/// The document for the routes that are mounted, see `web::run`.
+pub fn document(postgres: bool) -> Document {
+    let mut document = RepositoryApi::openapi();
+    // Filled in from the manifest, which has no license.
+    document.info.license = None;
+    if postgres {
+        document.merge(PostgresApi::openapi());
+    }
+    document
+}
+
+#[get("/openapi.json")]
+pub async fn openapi_json(document: &State<Document>) -> Json<Document> {
+    Json(document.inner().clone())
+}
+
+#[get("/docs")]
+pub async fn openapi_docs() -> RawHtml<&'static str> {
+    RawHtml(include_str!("docs.html"))
+}
+
+pub fn mount_docs(rocket: Rocket<Build>, document: Document) -> Rocket<Build> {
+    rocket.mount("/", routes![openapi_json, openapi_docs]).manage(document)
+}
@@ -0,0 +1,82 @@
+#[cfg(feature = "postgres")]
+use sqlx::Row;
+
+/// A file such as a scanned receipt stored with an expense. The contents live in the attachment
+/// directory under their SHA-256 hash, so identical files are only stored once.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct Attachment {
+    id: i32,
+    expense_id: i32,
+    filename: String,
+    content_type: String,
+    size: i64,
+    sha256: String,
+    created_at: chrono::NaiveDateTime,
+}
+
+impl Attachment {
+    pub fn new(expense_id: i32, filename: String, content_type: String, size: i64, sha256: String) -> Self {
+        Self {
+            id: -1,
+            expense_id,
+            filename,
+            content_type,
+            size,
+            sha256,
+            created_at: chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn expense_id(&self) -> i32 {
+        self.expense_id
+    }
+
+    pub fn filename(&self) -> &String {
+        &self.filename
+    }
+
+    pub fn content_type(&self) -> &String {
+        &self.content_type
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    pub fn sha256(&self) -> &String {
+        &self.sha256
+    }
+
+    pub fn created_at(&self) -> &chrono::NaiveDateTime {
+        &self.created_at
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::postgres::PgRow> for Attachment {
+    fn from(row: sqlx::postgres::PgRow) -> Self {
+        Self {
+            id: row.get("id"),
+            expense_id: row.get("expense_id"),
+            filename: row.get("filename"),
+            content_type: row.get("content_type"),
+            size: row.get("size"),
+            sha256: row.get("sha256"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+impl std::fmt::Display for Attachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({}, {} bytes) [expense {}]",
+            self.id, self.filename, self.content_type, self.size, self.expense_id
+        )
+    }
+}
@@ -0,0 +1,225 @@
+#[cfg(feature = "postgres")]
+use sqlx::Row;
+
+use crate::EventKind;
+
+/// Where a change was made.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditSource {
+    Web,
+    Cli,
+    /// Imports and posted drafts, from the web server or the CLI.
+    Import,
+}
+
+impl AuditSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditSource::Web => "web",
+            AuditSource::Cli => "cli",
+            AuditSource::Import => "import",
+        }
+    }
+}
+
+impl std::str::FromStr for AuditSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "web" => Ok(AuditSource::Web),
+            "cli" => Ok(AuditSource::Cli),
+            "import" => Ok(AuditSource::Import),
+            _ => Err(format!("Invalid audit source: {}", s)),
+        }
+    }
+}
+
+/// One change to the stored data, who made it and what it changed. Entries are only ever added.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
+pub struct AuditEntry {
+    id: i32,
+    at: chrono::NaiveDateTime,
+
+    /// The user running the CLI, or whoever the API caller said they are.
+    actor: String,
+    source: AuditSource,
+    /// The address an API call came from, which unlike `actor` the caller can't choose. `null`
+    /// for the CLI.
+    address: Option<String>,
+
+    /// Named like the events, a merge is recorded as the deletion of the merged entity.
+    action: EventKind,
+
+    /// The entity that changed, `null` for resets.
+    entity_id: Option<i32>,
+
+    /// The entity before the change, `null` when it was created.
+    #[schema(value_type = Option<Object>)]
+    before: serde_json::Value,
+
+    /// The entity after the change. `null` when it was deleted, or `{"merged_into": id}` when it
+    /// was merged into another.
+    #[schema(value_type = Option<Object>)]
+    after: serde_json::Value,
+}
+
+impl AuditEntry {
+    pub fn new(
+        actor: String,
+        source: AuditSource,
+        action: EventKind,
+        entity_id: Option<i32>,
+        before: serde_json::Value,
+        after: serde_json::Value,
+    ) -> Self {
+        Self {
+            id: -1,
+            at: chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            actor,
+            source,
+            address: None,
+            action,
+            entity_id,
+            before,
+            after,
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn at(&self) -> &chrono::NaiveDateTime {
+        &self.at
+    }
+
+    pub fn actor(&self) -> &String {
+        &self.actor
+    }
+
+    pub fn source(&self) -> AuditSource {
+        self.source
+    }
+
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+
+    pub fn action(&self) -> EventKind {
+        self.action
+    }
+
+    /// `user`, `category`, `expense` or `reset`.
+    pub fn entity(&self) -> &'static str {
+        self.action.name().split('.').next().unwrap()
+    }
+
+    pub fn entity_id(&self) -> Option<i32> {
+        self.entity_id
+    }
+
+    pub fn before(&self) -> &serde_json::Value {
+        &self.before
+    }
+
+    pub fn after(&self) -> &serde_json::Value {
+        &self.after
+    }
+
+    pub fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+
+    pub fn set_at(&mut self, at: chrono::NaiveDateTime) {
+        self.at = at;
+    }
+
+    pub fn set_address(&mut self, address: Option<String>) {
+        self.address = address;
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::postgres::PgRow> for AuditEntry {
+    fn from(row: sqlx::postgres::PgRow) -> Self {
+        let source = row.get::<String, _>("source");
+        let action = row.get::<String, _>("action");
+        let before = row.get::<Option<String>, _>("before");
+        let after = row.get::<Option<String>, _>("after");
+        Self {
+            id: row.get("id"),
+            at: row.get("at"),
+            actor: row.get("actor"),
+            source: source.parse().unwrap(),
+            address: row.get("address"),
+            action: serde_json::from_value(serde_json::Value::String(action)).unwrap(),
+            entity_id: row.get("entity_id"),
+            before: before.map(|b| serde_json::from_str(&b).unwrap()).unwrap_or_default(),
+            after: after.map(|a| serde_json::from_str(&a).unwrap()).unwrap_or_default(),
+        }
+    }
+}
+
+impl std::fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let at = self.at.format("%Y-%m-%d %H:%M:%S");
+        write!(f, "{}: {} {} ({}) {}", self.id, at, self.actor, self.source.as_str(), self.action.name())?;
+        if let Some(entity_id) = self.entity_id {
+            write!(f, " {}", entity_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which audit entries to get, newest first. Every list matches everything when empty.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
+pub struct AuditFilter {
+    /// Event names or entities as in `EventKind::matches`.
+    pub actions: Vec<String>,
+    pub entity_ids: Vec<i32>,
+    pub actors: Vec<String>,
+    pub sources: Vec<AuditSource>,
+    pub since: chrono::NaiveDateTime,
+    pub limit: i64,
+}
+
+impl Default for AuditFilter {
+    fn default() -> Self {
+        Self {
+            actions: vec![],
+            entity_ids: vec![],
+            actors: vec![],
+            sources: vec![],
+            // The earliest time every storage can compare against.
+            since: chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            limit: 50,
+        }
+    }
+}
+
+impl AuditFilter {
+    /// The names of the actions `actions` matches, for storages that compare names. `None` if
+    /// every action matches.
+    pub fn action_names(&self) -> Option<Vec<&'static str>> {
+        if self.actions.is_empty() {
+            return None;
+        }
+        let names = EventKind::ALL
+            .iter()
+            .filter(|k| self.actions.iter().any(|pattern| k.matches(pattern)))
+            .map(|k| k.name())
+            .collect();
+        Some(names)
+    }
+
+    /// Everything but the limit.
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        (self.actions.is_empty() || self.actions.iter().any(|pattern| entry.action.matches(pattern)))
+            && (self.entity_ids.is_empty() || entry.entity_id.is_some_and(|id| self.entity_ids.contains(&id)))
+            && (self.actors.is_empty() || self.actors.contains(&entry.actor))
+            && (self.sources.is_empty() || self.sources.contains(&entry.source))
+            && entry.at >= self.since
+    }
+}
@@ -0,0 +1,10 @@
+//! Writes the TypeScript definitions of the datatypes into the frontend source tree.
+
+use std::path::Path;
+
+fn main() -> std::io::Result<()> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(datatypes::typescript::BINDINGS_PATH);
+    std::fs::write(&path, datatypes::typescript::generate())?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
@@ -1,16 +1,26 @@
 use std::fmt::{Display, Formatter};
 use serde::{Serialize, Deserialize};
+#[cfg(feature = "postgres")]
 use sqlx::Row;
 use chrono::NaiveDateTime;
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
 pub struct Category {
     id: i32,
     name: String,
     description: String,
+    #[serde(default)]
+    parent_id: Option<i32>,
     created_at: chrono::NaiveDateTime,
 }
 
+/// A category together with its sub-categories, as returned by the category tree listings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct CategoryNode {
+    category: Category,
+    children: Vec<CategoryNode>,
+}
+
 
 
 impl Category {
@@ -19,6 +29,7 @@ impl Category {
             id: -1,
             name,
             description,
+            parent_id: None,
             created_at: NaiveDateTime::from_timestamp_millis(0).unwrap(),
         }
     }
@@ -35,17 +46,57 @@ impl Category {
         &self.description
     }
 
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn set_description(&mut self, description: String) {
+        self.description = description;
+    }
+
+    pub fn set_parent_id(&mut self, parent_id: Option<i32>) {
+        self.parent_id = parent_id;
+    }
+
     pub fn created_at(&self) -> &chrono::NaiveDateTime {
         &self.created_at
     }
+
+    pub fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+
+    pub fn set_created_at(&mut self, created_at: chrono::NaiveDateTime) {
+        self.created_at = created_at;
+    }
+}
+
+impl CategoryNode {
+    pub fn new(category: Category, children: Vec<CategoryNode>) -> Self {
+        Self { category, children }
+    }
+
+    pub fn category(&self) -> &Category {
+        &self.category
+    }
+
+    pub fn children(&self) -> &Vec<CategoryNode> {
+        &self.children
+    }
 }
 
+#[cfg(feature = "postgres")]
 impl From<sqlx::postgres::PgRow> for Category {
     fn from(row: sqlx::postgres::PgRow) -> Self {
         Self {
             id: row.get("id"),
             name: row.get("name"),
             description: row.get("description"),
+            parent_id: row.get("parent_id"),
             created_at: row.get("created_at"),
         }
     }
@@ -0,0 +1,269 @@
+#[cfg(feature = "postgres")]
+use sqlx::Row;
+#[cfg(feature = "postgres")]
+use rust_decimal::prelude::ToPrimitive;
+use crate::UserOwes;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
+pub struct ImportBatch {
+    id: i32,
+    source: String,
+    created_at: chrono::NaiveDateTime,
+}
+
+impl ImportBatch {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn source(&self) -> &String {
+        &self.source
+    }
+
+    pub fn created_at(&self) -> &chrono::NaiveDateTime {
+        &self.created_at
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::postgres::PgRow> for ImportBatch {
+    fn from(row: sqlx::postgres::PgRow) -> Self {
+        Self {
+            id: row.get("id"),
+            source: row.get("source"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+impl std::fmt::Display for ImportBatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let created_at = self.created_at().format("%Y-%m-%d %H:%M:%S");
+        write!(f, "{}: {} ({})", self.id, self.source, created_at)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+pub enum DraftStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Posted,
+}
+
+impl DraftStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DraftStatus::Pending => "pending",
+            DraftStatus::Approved => "approved",
+            DraftStatus::Rejected => "rejected",
+            DraftStatus::Posted => "posted",
+        }
+    }
+}
+
+impl std::str::FromStr for DraftStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DraftStatus::Pending),
+            "approved" => Ok(DraftStatus::Approved),
+            "rejected" => Ok(DraftStatus::Rejected),
+            "posted" => Ok(DraftStatus::Posted),
+            _ => Err(format!("Invalid draft status: {}", s)),
+        }
+    }
+}
+
+/// An imported row waiting for review. Every field of the future expense is optional because the
+/// source line may not have parsed; `errors` must be empty before the draft can be approved.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
+pub struct DraftExpense {
+    id: i32,
+    batch_id: i32,
+    line: i32,
+    raw: String,
+
+    user_id: Option<i32>,
+    category_id: Option<i32>,
+    amount: Option<f64>,
+    description: String,
+    purchased_at: Option<chrono::NaiveDate>,
+    user_owes: Vec<UserOwes>,
+
+    errors: Vec<String>,
+    warnings: Vec<String>,
+    status: DraftStatus,
+    expense_id: Option<i32>,
+}
+
+impl DraftExpense {
+    pub fn new(line: i32, raw: String) -> Self {
+        Self {
+            id: -1,
+            batch_id: -1,
+            line,
+            raw,
+            user_id: None,
+            category_id: None,
+            amount: None,
+            description: String::new(),
+            purchased_at: None,
+            user_owes: vec![],
+            errors: vec![],
+            warnings: vec![],
+            status: DraftStatus::Pending,
+            expense_id: None,
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn batch_id(&self) -> i32 {
+        self.batch_id
+    }
+
+    pub fn line(&self) -> i32 {
+        self.line
+    }
+
+    pub fn raw(&self) -> &String {
+        &self.raw
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        self.user_id
+    }
+
+    pub fn category_id(&self) -> Option<i32> {
+        self.category_id
+    }
+
+    pub fn amount(&self) -> Option<f64> {
+        self.amount
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn purchased_at(&self) -> Option<&chrono::NaiveDate> {
+        self.purchased_at.as_ref()
+    }
+
+    pub fn user_owes(&self) -> &Vec<UserOwes> {
+        &self.user_owes
+    }
+
+    pub fn errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+
+    pub fn warnings(&self) -> &Vec<String> {
+        &self.warnings
+    }
+
+    pub fn status(&self) -> DraftStatus {
+        self.status
+    }
+
+    pub fn expense_id(&self) -> Option<i32> {
+        self.expense_id
+    }
+
+    pub fn set_user_id(&mut self, user_id: i32) {
+        self.user_id = Some(user_id);
+    }
+
+    pub fn set_category_id(&mut self, category_id: i32) {
+        self.category_id = Some(category_id);
+    }
+
+    pub fn set_amount(&mut self, amount: f64) {
+        self.amount = Some(amount);
+    }
+
+    pub fn set_description(&mut self, description: String) {
+        self.description = description;
+    }
+
+    pub fn set_purchased_at(&mut self, purchased_at: chrono::NaiveDate) {
+        self.purchased_at = Some(purchased_at);
+    }
+
+    pub fn set_user_owes(&mut self, user_owes: Vec<UserOwes>) {
+        self.user_owes = user_owes;
+    }
+
+    pub fn add_error(&mut self, error: String) {
+        self.errors.push(error);
+    }
+
+    pub fn add_warning(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    /// Replaces `errors` and `warnings` with the problems found in the current field values.
+    pub fn validate(&mut self) {
+        self.errors.clear();
+        self.warnings.clear();
+
+        if self.user_id.is_none() {
+            self.errors.push("No payer.".to_string());
+        }
+        if self.category_id.is_none() {
+            self.errors.push("No category.".to_string());
+        }
+        if self.purchased_at.is_none() {
+            self.errors.push("No purchase date.".to_string());
+        }
+        match self.amount {
+            None => self.errors.push("No amount.".to_string()),
+            Some(amount) => {
+                if amount <= 0.0 {
+                    self.errors.push(format!("Amount `{}` is not positive.", amount));
+                }
+                if self.user_owes.is_empty() {
+                    self.errors.push("Nobody owes anything.".to_string());
+                } else {
+                    let owed: f64 = self.user_owes.iter().map(|o| o.amount()).sum();
+                    if (owed - amount).abs() > 0.01 {
+                        self.warnings
+                            .push(format!("Owed amounts add up to {} instead of {}.", owed, amount));
+                    }
+                }
+            }
+        }
+        if self.description.trim().is_empty() {
+            self.warnings.push("No description.".to_string());
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::postgres::PgRow> for DraftExpense {
+    fn from(row: sqlx::postgres::PgRow) -> Self {
+        let amount = row.get::<Option<rust_decimal::Decimal>, _>("amount");
+        let user_owes = row.get::<String, _>("user_owes");
+        let status = row.get::<String, _>("status");
+        Self {
+            id: row.get("id"),
+            batch_id: row.get("batch_id"),
+            line: row.get("line"),
+            raw: row.get("raw"),
+            user_id: row.get("user_id"),
+            category_id: row.get("category_id"),
+            amount: amount.map(|a| a.to_f64().unwrap()),
+            description: row.get("description"),
+            purchased_at: row.get("purchased_at"),
+            user_owes: serde_json::from_str(&user_owes).unwrap(),
+            errors: row.get("errors"),
+            warnings: row.get("warnings"),
+            status: status.parse().unwrap(),
+            expense_id: row.get("expense_id"),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+/// What happened, named `<entity>.<change>` on the wire.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, utoipa::ToSchema)]
+pub enum EventKind {
+    #[serde(rename = "user.created")]
+    UserCreated,
+    #[serde(rename = "user.updated")]
+    UserUpdated,
+    #[serde(rename = "user.deleted")]
+    UserDeleted,
+    #[serde(rename = "category.created")]
+    CategoryCreated,
+    #[serde(rename = "category.updated")]
+    CategoryUpdated,
+    #[serde(rename = "category.deleted")]
+    CategoryDeleted,
+    #[serde(rename = "expense.created")]
+    ExpenseCreated,
+    #[serde(rename = "expense.updated")]
+    ExpenseUpdated,
+    #[serde(rename = "expense.deleted")]
+    ExpenseDeleted,
+    #[serde(rename = "attachment.created")]
+    AttachmentCreated,
+    #[serde(rename = "attachment.deleted")]
+    AttachmentDeleted,
+    #[serde(rename = "draft.updated")]
+    DraftUpdated,
+    #[serde(rename = "webhook.created")]
+    WebhookCreated,
+    #[serde(rename = "webhook.deleted")]
+    WebhookDeleted,
+    #[serde(rename = "reset")]
+    Reset,
+    /// Only sent to a webhook on request, to check that its receiver is reachable.
+    #[serde(rename = "ping")]
+    Ping,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 16] = [
+        EventKind::UserCreated,
+        EventKind::UserUpdated,
+        EventKind::UserDeleted,
+        EventKind::CategoryCreated,
+        EventKind::CategoryUpdated,
+        EventKind::CategoryDeleted,
+        EventKind::ExpenseCreated,
+        EventKind::ExpenseUpdated,
+        EventKind::ExpenseDeleted,
+        EventKind::AttachmentCreated,
+        EventKind::AttachmentDeleted,
+        EventKind::DraftUpdated,
+        EventKind::WebhookCreated,
+        EventKind::WebhookDeleted,
+        EventKind::Reset,
+        EventKind::Ping,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::UserCreated => "user.created",
+            EventKind::UserUpdated => "user.updated",
+            EventKind::UserDeleted => "user.deleted",
+            EventKind::CategoryCreated => "category.created",
+            EventKind::CategoryUpdated => "category.updated",
+            EventKind::CategoryDeleted => "category.deleted",
+            EventKind::ExpenseCreated => "expense.created",
+            EventKind::ExpenseUpdated => "expense.updated",
+            EventKind::ExpenseDeleted => "expense.deleted",
+            EventKind::AttachmentCreated => "attachment.created",
+            EventKind::AttachmentDeleted => "attachment.deleted",
+            EventKind::DraftUpdated => "draft.updated",
+            EventKind::WebhookCreated => "webhook.created",
+            EventKind::WebhookDeleted => "webhook.deleted",
+            EventKind::Reset => "reset",
+            EventKind::Ping => "ping",
+        }
+    }
+
+    /// Whether a subscription to `pattern` gets this kind of event. A pattern is either a full
+    /// name or just the entity, so `expense` matches every `expense.*` event.
+    pub fn matches(&self, pattern: &str) -> bool {
+        let name = self.name();
+        name == pattern || name.split('.').next() == Some(pattern)
+    }
+}
+
+/// A change to the stored data, as it is sent to subscribers.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
+pub struct Event {
+    #[serde(rename = "type")]
+    kind: EventKind,
+
+    /// The entity that changed, `null` for resets and pings.
+    id: Option<i32>,
+
+    /// The entity after the change. `null` for deletions, resets and pings, or
+    /// `{"merged_into": id}` when the entity was merged into another.
+    #[schema(value_type = Option<Object>)]
+    data: serde_json::Value,
+
+    at: chrono::NaiveDateTime,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, id: Option<i32>, data: serde_json::Value) -> Self {
+        Self {
+            kind,
+            id,
+            data,
+            at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    pub fn id(&self) -> Option<i32> {
+        self.id
+    }
+
+    pub fn data(&self) -> &serde_json::Value {
+        &self.data
+    }
+
+    pub fn at(&self) -> &chrono::NaiveDateTime {
+        &self.at
+    }
+}
@@ -1,7 +1,9 @@
+#[cfg(feature = "postgres")]
 use sqlx::Row;
+#[cfg(feature = "postgres")]
 use rust_decimal::prelude::ToPrimitive;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
 pub struct Expense {
     id: i32,
     user_id: i32,
@@ -14,6 +16,9 @@ pub struct Expense {
     purchased_at: chrono::NaiveDate,
 
     user_owes: Vec<UserOwes>,
+
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 impl Expense {
@@ -34,6 +39,7 @@ impl Expense {
             purchased_at,
             created_at: chrono::NaiveDateTime::from_timestamp_millis(0).unwrap(),
             user_owes,
+            tags: vec![],
         }
     }
 
@@ -76,8 +82,25 @@ impl Expense {
     pub fn extend_user_owes(&mut self, user_owes: Vec<UserOwes>) {
         self.user_owes.extend(user_owes);
     }
+
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags;
+    }
+
+    pub fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+
+    pub fn set_created_at(&mut self, created_at: chrono::NaiveDateTime) {
+        self.created_at = created_at;
+    }
 }
 
+#[cfg(feature = "postgres")]
 impl From<sqlx::postgres::PgRow> for Expense {
     fn from(row: sqlx::postgres::PgRow) -> Self {
         let amount = row.get::<rust_decimal::Decimal, _>("amount");
@@ -90,11 +113,12 @@ impl From<sqlx::postgres::PgRow> for Expense {
             created_at: row.get("created_at"),
             purchased_at: row.get("purchased_at"),
             user_owes: vec![],
+            tags: vec![],
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
 pub struct UserOwes {
     id: i32,
     user_id: i32,
@@ -133,8 +157,21 @@ impl UserOwes {
     pub fn created_at(&self) -> &chrono::NaiveDateTime {
         &self.created_at
     }
+
+    pub fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+
+    pub fn set_created_at(&mut self, created_at: chrono::NaiveDateTime) {
+        self.created_at = created_at;
+    }
+
+    pub fn set_expense_id(&mut self, expense_id: i32) {
+        self.expense_id = expense_id;
+    }
 }
 
+#[cfg(feature = "postgres")]
 impl From<sqlx::postgres::PgRow> for UserOwes {
     fn from(row: sqlx::postgres::PgRow) -> Self {
         let amount = row.get::<rust_decimal::Decimal, _>("amount");
@@ -1,14 +1,15 @@
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
 pub enum OrderBy {
     Amount,
     Date,
     Created,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
 pub struct Filter {
     pub user_ids: Vec<i32>,
 
+    /// Sub-categories of these are matched as well.
     pub category_ids: Vec<i32>,
 
     pub min_amount: f64,
@@ -19,6 +20,16 @@ pub struct Filter {
 
     pub order_by: OrderBy,
     pub order_asc: bool,
+
+    /// Expenses with at least one of these tags. Ignored when empty.
+    #[serde(default)]
+    pub tags_any: Vec<String>,
+    /// Expenses with every one of these tags.
+    #[serde(default)]
+    pub tags_all: Vec<String>,
+    /// Expenses with none of these tags.
+    #[serde(default)]
+    pub tags_none: Vec<String>,
 }
 
 impl Filter {
@@ -53,4 +64,16 @@ impl Filter {
     pub fn order_asc(&self) -> bool {
         self.order_asc
     }
+
+    pub fn tags_any(&self) -> &Vec<String> {
+        &self.tags_any
+    }
+
+    pub fn tags_all(&self) -> &Vec<String> {
+        &self.tags_all
+    }
+
+    pub fn tags_none(&self) -> &Vec<String> {
+        &self.tags_none
+    }
 }
@@ -2,9 +2,24 @@ mod category;
 mod user;
 mod expense;
 mod filter;
+mod draft;
+mod tag;
+mod attachment;
+mod event;
+mod webhook;
+mod audit;
+mod request;
+pub mod typescript;
 
 // pub use expense::Expense;
 pub use user::User;
-pub use category::Category;
+pub use category::{Category, CategoryNode};
 pub use expense::{Expense, UserOwes};
 pub use filter::{Filter, OrderBy};
+pub use draft::{DraftExpense, DraftStatus, ImportBatch};
+pub use tag::Tag;
+pub use attachment::Attachment;
+pub use event::{Event, EventKind};
+pub use webhook::{Webhook, WebhookDelivery};
+pub use audit::{AuditEntry, AuditFilter, AuditSource};
+pub use request::{CategoryPatch, ExpensePatch, NewCategory, NewUser, NewWebhook, UserPatch};
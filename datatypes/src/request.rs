@@ -0,0 +1,142 @@
+//! The bodies the `/api/v1` routes take besides the types they return.
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{Expense, UserOwes};
+
+/// A user to create.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewUser {
+    pub username: String,
+}
+
+/// A category to create.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewCategory {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    /// Left out or `null` for a top-level category.
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+/// A webhook to create.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewWebhook {
+    pub url: String,
+
+    /// Signs every delivery, see the `X-Expenses-Signature` header.
+    pub secret: String,
+
+    /// Event names such as `expense.created`, or entities such as `expense` for every
+    /// `expense.*` event. Left out for every event.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// Tells a field that was sent as `null` apart from one that was left out.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// The changes to a user, fields that are left out stay as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, utoipa::ToSchema)]
+pub struct UserPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+}
+
+/// The changes to a category, fields that are left out stay as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, utoipa::ToSchema)]
+pub struct CategoryPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// `null` moves the category to the top level.
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<i32>)]
+    pub parent_id: Option<Option<i32>>,
+}
+
+/// The changes to an expense, fields that are left out stay as they are. `user_owes` and `tags`
+/// replace the ones the expense had. Without `user_owes`, a new payer takes over the share of the
+/// previous one and a new amount scales every share by the same factor.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, utoipa::ToSchema)]
+pub struct ExpensePatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purchased_at: Option<chrono::NaiveDate>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_owes: Option<Vec<UserOwes>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+impl ExpensePatch {
+    /// `expense` with the fields of the patch replaced, and the shares updated to match.
+    pub fn apply(&self, expense: &Expense) -> Expense {
+        let user_owes = match &self.user_owes {
+            Some(user_owes) => user_owes.clone(),
+            None => reshare(expense, self.user_id, self.amount),
+        };
+        let mut patched = Expense::new(
+            self.user_id.unwrap_or(expense.user_id()),
+            self.category_id.unwrap_or(expense.category_id()),
+            self.amount.unwrap_or(expense.amount()),
+            self.description.clone().unwrap_or_else(|| expense.description().clone()),
+            self.purchased_at.unwrap_or(*expense.purchased_at()),
+            user_owes,
+        );
+        patched.set_id(expense.id());
+        patched.set_created_at(*expense.created_at());
+        patched.set_tags(self.tags.clone().unwrap_or_else(|| expense.tags().clone()));
+        patched
+    }
+}
+
+/// The shares of `expense` once `payer` took over the share of the previous payer and every share
+/// was scaled to `amount`. An expense that cost nothing is shared equally instead.
+fn reshare(expense: &Expense, payer: Option<i32>, amount: Option<f64>) -> Vec<UserOwes> {
+    let mut shares = expense.user_owes().iter().map(|o| (o.user_id(), o.amount())).collect::<Vec<(i32, f64)>>();
+    if let Some(payer) = payer.filter(|p| *p != expense.user_id()) {
+        if let Some(index) = shares.iter().position(|(id, _)| *id == expense.user_id()) {
+            let (_, taken_over) = shares.remove(index);
+            // Added to the new payer's own share if they already had one.
+            match shares.iter_mut().find(|(id, _)| *id == payer) {
+                Some(share) => share.1 += taken_over,
+                None => shares.insert(index, (payer, taken_over)),
+            }
+        }
+    }
+    if let Some(amount) = amount {
+        let previous = expense.amount();
+        let count = shares.len() as f64;
+        for (_, share) in shares.iter_mut() {
+            *share = if previous != 0.0 { *share * amount / previous } else { amount / count };
+        }
+    }
+    shares.into_iter().map(|(id, share)| UserOwes::new(id, expense.id(), share)).collect()
+}
@@ -0,0 +1,42 @@
+use std::fmt::Display;
+#[cfg(feature = "postgres")]
+use sqlx::Row;
+
+/// A free-form label. Expenses can carry any number of them, see `Expense::tags`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct Tag {
+    id: i32,
+    name: String,
+    created_at: chrono::NaiveDateTime,
+}
+
+impl Tag {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn created_at(&self) -> &chrono::NaiveDateTime {
+        &self.created_at
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::postgres::PgRow> for Tag {
+    fn from(row: sqlx::postgres::PgRow) -> Self {
+        Self {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.id, self.name)
+    }
+}
@@ -0,0 +1,181 @@
+//! TypeScript definitions for the frontend, rendered from the same `ToSchema` derives the
+//! OpenAPI document is built from, so serde renames, defaults and `Option`s come out the way the
+//! server sends them. After changing a type run `cargo run --bin typescript` in `datatypes/`,
+//! which writes `frontend/src/bindings.ts`.
+
+use utoipa::openapi::schema::{AdditionalProperties, Schema, SchemaType};
+use utoipa::openapi::RefOr;
+use utoipa::ToSchema;
+
+use crate::*;
+
+/// The generated file, relative to this crate.
+pub const BINDINGS_PATH: &str = "../frontend/src/bindings.ts";
+
+fn schemas() -> Vec<(&'static str, RefOr<Schema>)> {
+    vec![
+        User::schema(),
+        Category::schema(),
+        CategoryNode::schema(),
+        Expense::schema(),
+        UserOwes::schema(),
+        Filter::schema(),
+        OrderBy::schema(),
+        Tag::schema(),
+        Attachment::schema(),
+        ImportBatch::schema(),
+        DraftExpense::schema(),
+        DraftStatus::schema(),
+        NewUser::schema(),
+        NewCategory::schema(),
+        UserPatch::schema(),
+        CategoryPatch::schema(),
+        ExpensePatch::schema(),
+        Event::schema(),
+        EventKind::schema(),
+        Webhook::schema(),
+        WebhookDelivery::schema(),
+        NewWebhook::schema(),
+        AuditEntry::schema(),
+        AuditSource::schema(),
+        AuditFilter::schema(),
+    ]
+}
+
+/// The contents of `bindings.ts`.
+pub fn generate() -> String {
+    let mut bindings = String::from("// Generated by `cargo run --bin typescript` in `datatypes/`, do not edit.\n");
+    for (name, schema) in schemas() {
+        bindings.push('\n');
+        bindings.push_str(&declaration(name, &schema));
+    }
+    bindings
+}
+
+fn declaration(name: &str, schema: &RefOr<Schema>) -> String {
+    let object = match schema {
+        RefOr::T(Schema::Object(object))
+            if object.schema_type == SchemaType::Object && !object.properties.is_empty() =>
+        {
+            object
+        }
+        _ => return format!("export type {} = {};\n", name, type_of(schema)),
+    };
+
+    let mut declaration = doc_comment(object.description.as_deref(), "");
+    declaration.push_str(&format!("export interface {} {{\n", name));
+    for (field, schema) in &object.properties {
+        let description = match schema {
+            RefOr::T(Schema::Object(property)) => property.description.as_deref(),
+            RefOr::T(Schema::Array(property)) => property.description.as_deref(),
+            _ => None,
+        };
+        declaration.push_str(&doc_comment(description, "\t"));
+        // Fields with `#[serde(default)]` or of type `Option` may be left out when sending.
+        let optional = if object.required.contains(field) { "" } else { "?" };
+        declaration.push_str(&format!("\t{}{}: {};\n", field, optional, type_of(schema)));
+    }
+    declaration.push_str("}\n");
+    declaration
+}
+
+fn doc_comment(description: Option<&str>, indent: &str) -> String {
+    match description {
+        Some(description) if !description.is_empty() => {
+            let mut comment = format!("{}/**\n", indent);
+            for line in description.lines() {
+                comment.push_str(&format!("{} * {}\n", indent, line).replace(" * \n", " *\n"));
+            }
+            comment.push_str(&format!("{} */\n", indent));
+            comment
+        }
+        _ => String::new(),
+    }
+}
+
+fn type_of(schema: &RefOr<Schema>) -> String {
+    let schema = match schema {
+        RefOr::Ref(reference) => {
+            return reference
+                .ref_location
+                .rsplit('/')
+                .next()
+                .unwrap_or("unknown")
+                .to_string()
+        }
+        RefOr::T(schema) => schema,
+    };
+
+    match schema {
+        Schema::Object(object) => {
+            let ty = match (&object.enum_values, &object.schema_type) {
+                (Some(values), _) => values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" | "),
+                (None, SchemaType::String) => "string".to_string(),
+                (None, SchemaType::Integer | SchemaType::Number) => "number".to_string(),
+                (None, SchemaType::Boolean) => "boolean".to_string(),
+                (None, SchemaType::Object) => match object.additional_properties.as_deref() {
+                    Some(AdditionalProperties::RefOr(values)) => format!("Record<string, {}>", type_of(values)),
+                    _ if object.properties.is_empty() => "Record<string, unknown>".to_string(),
+                    _ => {
+                        let fields = object
+                            .properties
+                            .iter()
+                            .map(|(field, schema)| format!("{}: {}", field, type_of(schema)))
+                            .collect::<Vec<_>>();
+                        format!("{{ {} }}", fields.join("; "))
+                    }
+                },
+                (None, _) => "unknown".to_string(),
+            };
+            nullable(ty, object.nullable)
+        }
+        Schema::Array(array) => {
+            let item = type_of(&array.items);
+            if item.contains(' ') {
+                format!("({})[]", item)
+            } else {
+                format!("{}[]", item)
+            }
+        }
+        Schema::OneOf(one_of) => nullable(join(&one_of.items, " | "), one_of.nullable),
+        Schema::AllOf(all_of) => nullable(join(&all_of.items, " & "), all_of.nullable),
+        Schema::AnyOf(any_of) => join(&any_of.items, " | "),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn join(items: &[RefOr<Schema>], separator: &str) -> String {
+    items.iter().map(type_of).collect::<Vec<_>>().join(separator)
+}
+
+fn nullable(ty: String, nullable: bool) -> String {
+    if nullable {
+        format!("{} | null", ty)
+    } else {
+        ty
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Fails when a type changed without regenerating the bindings.
+    #[test]
+    fn test_bindings_are_up_to_date() {
+        assert_eq!(
+            generate(),
+            include_str!("../../frontend/src/bindings.ts"),
+            "run `cargo run --bin typescript` in `datatypes/`"
+        );
+    }
+
+    #[test]
+    fn test_renders_options_and_enums() {
+        let bindings = generate();
+        assert!(bindings.contains("\tparent_id?: number | null;\n"));
+        assert!(bindings.contains("\ttags?: string[];\n"));
+        assert!(bindings.contains("\tuser_owes: UserOwes[];\n"));
+        assert!(bindings.contains("export type OrderBy = \"Amount\" | \"Date\" | \"Created\";\n"));
+    }
+}
@@ -1,7 +1,8 @@
 use std::fmt::Display;
+#[cfg(feature = "postgres")]
 use sqlx::Row;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
 pub struct User {
     id: i32,
     username: String,
@@ -28,8 +29,21 @@ impl User {
     pub fn created_at(&self) -> &chrono::NaiveDateTime {
         &self.created_at
     }
+
+    pub fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+
+    pub fn set_username(&mut self, username: String) {
+        self.username = username;
+    }
+
+    pub fn set_created_at(&mut self, created_at: chrono::NaiveDateTime) {
+        self.created_at = created_at;
+    }
 }
 
+#[cfg(feature = "postgres")]
 impl From<sqlx::postgres::PgRow> for User {
     fn from(row: sqlx::postgres::PgRow) -> Self {
         Self {
@@ -0,0 +1,190 @@
+#[cfg(feature = "postgres")]
+use sqlx::Row;
+
+use crate::{Event, EventKind};
+
+/// A URL that is sent every event matching one of `events`, signed with the secret.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct Webhook {
+    id: i32,
+    url: String,
+
+    /// Never sent back out, the receiver already knows it.
+    #[serde(skip)]
+    secret: String,
+
+    /// Event names or entities as in `EventKind::matches`, every event when empty.
+    events: Vec<String>,
+
+    created_at: chrono::NaiveDateTime,
+}
+
+impl Webhook {
+    pub fn new(url: String, secret: String, events: Vec<String>) -> Self {
+        Self {
+            id: -1,
+            url,
+            secret,
+            events,
+            created_at: chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn url(&self) -> &String {
+        &self.url
+    }
+
+    pub fn secret(&self) -> &String {
+        &self.secret
+    }
+
+    pub fn events(&self) -> &Vec<String> {
+        &self.events
+    }
+
+    pub fn created_at(&self) -> &chrono::NaiveDateTime {
+        &self.created_at
+    }
+
+    /// Whether the webhook is subscribed to this kind of event.
+    pub fn wants(&self, kind: EventKind) -> bool {
+        self.events.is_empty() || self.events.iter().any(|pattern| kind.matches(pattern))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::postgres::PgRow> for Webhook {
+    fn from(row: sqlx::postgres::PgRow) -> Self {
+        Self {
+            id: row.get("id"),
+            url: row.get("url"),
+            secret: row.get("secret"),
+            events: row.get("events"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+impl std::fmt::Display for Webhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let events = if self.events.is_empty() {
+            "every event".to_string()
+        } else {
+            self.events.join(", ")
+        };
+        write!(f, "{}: {} ({})", self.id, self.url, events)
+    }
+}
+
+/// One attempt at sending an event to a webhook.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
+pub struct WebhookDelivery {
+    id: i32,
+    webhook_id: i32,
+    event: EventKind,
+
+    /// The body that was sent, the `Event`.
+    #[schema(value_type = Object)]
+    payload: serde_json::Value,
+
+    /// Counts from 1, retries of the same event go up from there.
+    attempt: i32,
+
+    /// The status the receiver answered with, `null` if it could not be reached.
+    status: Option<i32>,
+
+    /// Why the attempt failed, `null` if it succeeded.
+    error: Option<String>,
+
+    created_at: chrono::NaiveDateTime,
+}
+
+impl WebhookDelivery {
+    pub fn new(
+        webhook_id: i32,
+        event: &Event,
+        attempt: i32,
+        status: Option<i32>,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            id: -1,
+            webhook_id,
+            event: event.kind(),
+            payload: serde_json::to_value(event).unwrap(),
+            attempt,
+            status,
+            error,
+            created_at: chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn webhook_id(&self) -> i32 {
+        self.webhook_id
+    }
+
+    pub fn event(&self) -> EventKind {
+        self.event
+    }
+
+    pub fn payload(&self) -> &serde_json::Value {
+        &self.payload
+    }
+
+    pub fn attempt(&self) -> i32 {
+        self.attempt
+    }
+
+    pub fn status(&self) -> Option<i32> {
+        self.status
+    }
+
+    pub fn error(&self) -> Option<&String> {
+        self.error.as_ref()
+    }
+
+    pub fn created_at(&self) -> &chrono::NaiveDateTime {
+        &self.created_at
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<sqlx::postgres::PgRow> for WebhookDelivery {
+    fn from(row: sqlx::postgres::PgRow) -> Self {
+        let event = row.get::<String, _>("event");
+        let payload = row.get::<String, _>("payload");
+        Self {
+            id: row.get("id"),
+            webhook_id: row.get("webhook_id"),
+            event: serde_json::from_value(serde_json::Value::String(event)).unwrap(),
+            payload: serde_json::from_str(&payload).unwrap(),
+            attempt: row.get("attempt"),
+            status: row.get("status"),
+            error: row.get("error"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookDelivery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} attempt {}: ", self.created_at, self.event.name(), self.attempt)?;
+        match (&self.error, self.status) {
+            (None, Some(status)) => write!(f, "delivered ({})", status),
+            (None, None) => write!(f, "delivered"),
+            (Some(error), _) => write!(f, "failed, {}", error),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+// Generated by `cargo run --bin typescript` in `datatypes/`, do not edit.
+
+export interface User {
+	id: number;
+	username: string;
+	created_at: string;
+}
+
+export interface Category {
+	id: number;
+	name: string;
+	description: string;
+	parent_id?: number | null;
+	created_at: string;
+}
+
+/**
+ * A category together with its sub-categories, as returned by the category tree listings.
+ */
+export interface CategoryNode {
+	category: Category;
+	children: CategoryNode[];
+}
+
+export interface Expense {
+	id: number;
+	user_id: number;
+	category_id: number;
+	amount: number;
+	description: string;
+	created_at: string;
+	purchased_at: string;
+	user_owes: UserOwes[];
+	tags?: string[];
+}
+
+export interface UserOwes {
+	id: number;
+	user_id: number;
+	expense_id: number;
+	amount: number;
+	created_at: string;
+}
+
+export interface Filter {
+	user_ids: number[];
+	/**
+	 * Sub-categories of these are matched as well.
+	 */
+	category_ids: number[];
+	min_amount: number;
+	max_amount: number;
+	min_date: string;
+	max_date: string;
+	order_by: OrderBy;
+	order_asc: boolean;
+	/**
+	 * Expenses with at least one of these tags. Ignored when empty.
+	 */
+	tags_any?: string[];
+	/**
+	 * Expenses with every one of these tags.
+	 */
+	tags_all?: string[];
+	/**
+	 * Expenses with none of these tags.
+	 */
+	tags_none?: string[];
+}
+
+export type OrderBy = "Amount" | "Date" | "Created";
+
+/**
+ * A free-form label. Expenses can carry any number of them, see `Expense::tags`.
+ */
+export interface Tag {
+	id: number;
+	name: string;
+	created_at: string;
+}
+
+/**
+ * A file such as a scanned receipt stored with an expense. The contents live in the attachment
+ * directory under their SHA-256 hash, so identical files are only stored once.
+ */
+export interface Attachment {
+	id: number;
+	expense_id: number;
+	filename: string;
+	content_type: string;
+	size: number;
+	sha256: string;
+	created_at: string;
+}
+
+export interface ImportBatch {
+	id: number;
+	source: string;
+	created_at: string;
+}
+
+/**
+ * An imported row waiting for review. Every field of the future expense is optional because the
+ * source line may not have parsed; `errors` must be empty before the draft can be approved.
+ */
+export interface DraftExpense {
+	id: number;
+	batch_id: number;
+	line: number;
+	raw: string;
+	user_id?: number | null;
+	category_id?: number | null;
+	amount?: number | null;
+	description: string;
+	purchased_at?: string | null;
+	user_owes: UserOwes[];
+	errors: string[];
+	warnings: string[];
+	status: DraftStatus;
+	expense_id?: number | null;
+}
+
+export type DraftStatus = "Pending" | "Approved" | "Rejected" | "Posted";
+
+/**
+ * A user to create.
+ */
+export interface NewUser {
+	username: string;
+}
+
+/**
+ * A category to create.
+ */
+export interface NewCategory {
+	name: string;
+	description?: string;
+	/**
+	 * Left out or `null` for a top-level category.
+	 */
+	parent_id?: number | null;
+}
+
+/**
+ * The changes to a user, fields that are left out stay as they are.
+ */
+export interface UserPatch {
+	username?: string | null;
+}
+
+/**
+ * The changes to a category, fields that are left out stay as they are.
+ */
+export interface CategoryPatch {
+	name?: string | null;
+	description?: string | null;
+	/**
+	 * `null` moves the category to the top level.
+	 */
+	parent_id?: number | null;
+}
+
+/**
+ * The changes to an expense, fields that are left out stay as they are. `user_owes` and `tags`
+ * replace the ones the expense had. Without `user_owes`, a new payer takes over the share of the
+ * previous one and a new amount scales every share by the same factor.
+ */
+export interface ExpensePatch {
+	user_id?: number | null;
+	category_id?: number | null;
+	amount?: number | null;
+	description?: string | null;
+	purchased_at?: string | null;
+	user_owes?: UserOwes[];
+	tags?: string[];
+}
+
+/**
+ * A change to the stored data, as it is sent to subscribers.
+ */
+export interface Event {
+	type: EventKind;
+	/**
+	 * The entity that changed, `null` for resets and pings.
+	 */
+	id?: number | null;
+	/**
+	 * The entity after the change. `null` for deletions, resets and pings, or
+	 * `{"merged_into": id}` when the entity was merged into another.
+	 */
+	data?: Record<string, unknown> | null;
+	at: string;
+}
+
+export type EventKind = "user.created" | "user.updated" | "user.deleted" | "category.created" | "category.updated" | "category.deleted" | "expense.created" | "expense.updated" | "expense.deleted" | "attachment.created" | "attachment.deleted" | "draft.updated" | "webhook.created" | "webhook.deleted" | "reset" | "ping";
+
+/**
+ * A URL that is sent every event matching one of `events`, signed with the secret.
+ */
+export interface Webhook {
+	id: number;
+	url: string;
+	/**
+	 * Event names or entities as in `EventKind::matches`, every event when empty.
+	 */
+	events: string[];
+	created_at: string;
+}
+
+/**
+ * One attempt at sending an event to a webhook.
+ */
+export interface WebhookDelivery {
+	id: number;
+	webhook_id: number;
+	event: EventKind;
+	/**
+	 * The body that was sent, the `Event`.
+	 */
+	payload: Record<string, unknown>;
+	/**
+	 * Counts from 1, retries of the same event go up from there.
+	 */
+	attempt: number;
+	/**
+	 * The status the receiver answered with, `null` if it could not be reached.
+	 */
+	status?: number | null;
+	/**
+	 * Why the attempt failed, `null` if it succeeded.
+	 */
+	error?: string | null;
+	created_at: string;
+}
+
+/**
+ * A webhook to create.
+ */
+export interface NewWebhook {
+	url: string;
+	/**
+	 * Signs every delivery, see the `X-Expenses-Signature` header.
+	 */
+	secret: string;
+	/**
+	 * Event names such as `expense.created`, or entities such as `expense` for every
+	 * `expense.*` event. Left out for every event.
+	 */
+	events?: string[];
+}
+
+/**
+ * One change to the stored data, who made it and what it changed. Entries are only ever added.
+ */
+export interface AuditEntry {
+	id: number;
+	at: string;
+	/**
+	 * The user running the CLI, or whoever the API caller said they are.
+	 */
+	actor: string;
+	source: AuditSource;
+	/**
+	 * The address an API call came from, which unlike `actor` the caller can't choose. `null`
+	 * for the CLI.
+	 */
+	address?: string | null;
+	action: EventKind;
+	/**
+	 * The entity that changed, `null` for resets.
+	 */
+	entity_id?: number | null;
+	/**
+	 * The entity before the change, `null` when it was created.
+	 */
+	before?: Record<string, unknown> | null;
+	/**
+	 * The entity after the change. `null` when it was deleted, or `{"merged_into": id}` when it
+	 * was merged into another.
+	 */
+	after?: Record<string, unknown> | null;
+}
+
+export type AuditSource = "web" | "cli" | "import";
+
+/**
+ * Which audit entries to get, newest first. Every list matches everything when empty.
+ */
+export interface AuditFilter {
+	/**
+	 * Event names or entities as in `EventKind::matches`.
+	 */
+	actions: string[];
+	entity_ids: number[];
+	actors: string[];
+	sources: AuditSource[];
+	since: string;
+	limit: number;
+}
@@ -1,53 +1,25 @@
 import { castFieldToDate, castNestedFieldToDate, http } from "./utils";
 import { API_URL } from "./vars";
+import * as bindings from "./bindings";
 
-export interface User {
-	id: number;
-	username: string;
-	created_at: Date;
-}
+// The wire types are generated from the Rust structs, see `datatypes/src/typescript.rs`.
+// Timestamps arrive as strings and are turned into dates once fetched, see `castFieldToDate`.
 
-export interface Category {
-	id: number;
-	name: string;
-	description: string;
-	created_at: Date;
-}
+export type { Filter, OrderBy, Tag, CategoryNode } from "./bindings";
 
-export interface UserOwes {
-	id: number;
-	user_id: number;
-	expense_id: number;
-	amount: number;
-	created_at: Date;
-}
+type WithDates<T, K extends keyof T> = Omit<T, K> & { [P in K]: Date };
 
-export interface Expense {
-	id: number;
-	user_id: number;
-	category_id: number;
-	amount: number;
-	description: string;
-	created_at: Date;
-	purchased_at: Date;
-	user_owes: UserOwes[];
-}
+export type User = WithDates<bindings.User, "created_at">;
 
-export enum OrderBy {
-	Amount = "amount",
-	Date = "date",
-}
+export type Category = WithDates<bindings.Category, "created_at">;
 
-export interface Filter {
-	user_ids: number[];
-	category_ids: number[];
-	min_amount: number;
-	max_amount: number;
-	min_date: string;
-	max_date: string;
-	order_by: string;
-	order_asc: boolean;
-}
+export type UserOwes = WithDates<bindings.UserOwes, "created_at">;
+
+export type Expense = WithDates<Omit<bindings.Expense, "user_owes">, "created_at" | "purchased_at"> & {
+	user_owes: UserOwes[];
+};
+
+export type Attachment = WithDates<bindings.Attachment, "created_at">;
 
 // Program data
 export class UserData {
@@ -57,7 +29,7 @@ export class UserData {
 	constructor() { }
 
 	public async fetchAllUsers() {
-		this.users = await http<User[]>(API_URL + "users/all")
+		this.users = await http<User[]>(API_URL + "api/v1/users")
 		castFieldToDate(this.users, ["created_at"])
 	}
 
@@ -91,7 +63,7 @@ export class CategoryData {
 	}
 
 	public async fetchAllCategories() {
-		this.categories = await http<Category[]>(API_URL + "categories/all");
+		this.categories = await http<Category[]>(API_URL + "api/v1/categories");
 		castFieldToDate(this.categories, ["created_at"]);
 	}
 
@@ -113,7 +85,7 @@ export class ExpenseData {
 	}
 
 	public async fetchAllExpenses() {
-		this.expenses = await http<Expense[]>(API_URL + "expenses/all")
+		this.expenses = await http<Expense[]>(API_URL + "api/v1/expenses")
 		castNestedFieldToDate(this.expenses, ["user_owes"], ["created_at", "purchased_at"], ["created_at"])
 	}
 
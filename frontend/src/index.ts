@@ -1,7 +1,8 @@
 import * as _ from 'lodash';
 
 import { populateUsers, addExpense, populateFilterDropdown, applyFilterDropdown } from './render';
-import { DATA } from './vars';
+import { API_URL, DATA } from './vars';
+import { EventKind } from './bindings';
 
 
 window.onload = pageLoad;
@@ -18,5 +19,28 @@ export async function pageLoad() {
 	// Button event listeners
 	const add_expense_btn = document.getElementById("add-expense")
 	add_expense_btn?.addEventListener("click", addExpense)
+
+	subscribeToChanges();
+}
+
+// Reload whatever another tab or user changed. The browser reconnects on its own if the server
+// goes away.
+function subscribeToChanges() {
+	const events = new EventSource(API_URL + "api/v1/events");
+
+	const reloadAll = async () => {
+		await DATA.init();
+		populateUsers(DATA.users);
+		await populateFilterDropdown();
+		applyFilterDropdown();
+	};
+	const reloadsAll: EventKind[] = ["user.created", "user.updated", "user.deleted", "category.created", "category.updated", "category.deleted"];
+	for (const name of reloadsAll) {
+		events.addEventListener(name, reloadAll);
+	}
+	const reloadsExpenses: EventKind[] = ["expense.created", "expense.updated", "expense.deleted", "reset"];
+	for (const name of reloadsExpenses) {
+		events.addEventListener(name, () => applyFilterDropdown());
+	}
 }
 
@@ -239,7 +239,8 @@ function submitExpense(_: Event) {
 		amount: null,
 		description: null,
 		purchased_at: null,
-		user_owes: []
+		user_owes: [],
+		tags: []
 	}
 
 	{
@@ -324,7 +325,7 @@ function submitExpense(_: Event) {
 		};
 
 		http<Expense>(
-			API_URL + "expenses/create",
+			API_URL + "api/v1/expenses",
 			{
 				method: "POST",
 				headers: { "Content-Type": "application/json" },
@@ -376,7 +377,7 @@ export async function populateFilterDropdown() {
 		todays_date: string
 	};
 
-	const last_reset = await http<string>(API_URL + "expenses/last-reset");
+	const last_reset = await http<string>(API_URL + "api/v1/expenses/last-reset");
 
 
 	const filter_data: FilterData = {
@@ -400,6 +401,24 @@ export async function populateFilterDropdown() {
 //filter_err.innerHTML = message;
 //}
 
+// The query parameters of `GET /api/v1/expenses`, lists are sent as repeated parameters.
+function filterQuery(filter: Filter): string {
+	const query = new URLSearchParams({
+		min_amount: String(filter.min_amount),
+		max_amount: String(filter.max_amount),
+		min_date: filter.min_date,
+		max_date: filter.max_date,
+		order_by: filter.order_by.toLowerCase(),
+		order_asc: String(filter.order_asc),
+	});
+	filter.user_ids.forEach((id) => query.append("user_id", String(id)));
+	filter.category_ids.forEach((id) => query.append("category_id", String(id)));
+	filter.tags_any?.forEach((tag) => query.append("tag_any", tag));
+	filter.tags_all?.forEach((tag) => query.append("tag_all", tag));
+	filter.tags_none?.forEach((tag) => query.append("tag_none", tag));
+	return query.toString();
+}
+
 export async function applyFilterDropdown() {
 	const form = {
 		from_date: getInpVal("filter-from-date"),
@@ -424,7 +443,7 @@ export async function applyFilterDropdown() {
 		max_amount: parseFloat(form.max_amount),
 		min_date: new Date(form.from_date).toISOString().slice(0, 10),
 		max_date: new Date(form.to_date).toISOString().slice(0, 10),
-		order_by: "",
+		order_by: "Date",
 		order_asc: form.order_asc
 	}
 
@@ -456,14 +475,7 @@ export async function applyFilterDropdown() {
 	}
 
 
-	DATA.expenses.expenses = await http<Expense[]>(
-		API_URL + "expenses/filter",
-		{
-			method: "POST",
-			headers: { "Content-Type": "application/json" },
-			body: JSON.stringify(filter)
-		}
-	);
+	DATA.expenses.expenses = await http<Expense[]>(API_URL + "api/v1/expenses?" + filterQuery(filter));
 	castNestedFieldToDate(DATA.expenses.expenses, ["user_owes"], ["created_at", "purchased_at"], ["created_at"])
 	populateExpenses(DATA.expenses);
 }
@@ -1,5 +1,7 @@
 import { Data } from "./datatypes"
 
-export const API_URL = "http://192.168.1.92:8080/"
+// Same origin when `web start` serves the frontend. Point it at the server when the frontend is
+// hosted separately, e.g. with `npx serve`.
+export const API_URL = "/"
 
 export const DATA = new Data();